@@ -503,7 +503,7 @@ async fn set_config_gen_params(
         BitcoinRpcConfig::from_env_vars()?,
         &mut server_gen_params,
         Network::Regtest,
-        10,
+        None,
     );
     // Since we are not actually calling `fedimintd` binary, parse and handle
     // `FM_EXTRA_META_DATA` like it would do.
@@ -3,7 +3,7 @@ use std::io::Cursor;
 use std::path::PathBuf;
 
 use anyhow::{anyhow as format_err, bail};
-use bitcoin::{BlockHash, Network, Script, Transaction, Txid};
+use bitcoin::{Block, BlockHash, Network, Script, Transaction, Txid};
 use bitcoincore_rpc::bitcoincore_rpc_json::EstimateMode;
 use bitcoincore_rpc::{Auth, RpcApi};
 use fedimint_core::bitcoinrpc::FM_BITCOIND_COOKIE_FILE_VAR_NAME;
@@ -64,6 +64,10 @@ impl IBitcoindRpc for BitcoinClient {
         block_in_place(|| self.0.get_block_hash(height)).map_err(anyhow::Error::from)
     }
 
+    async fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block> {
+        block_in_place(|| self.0.get_block(hash)).map_err(anyhow::Error::from)
+    }
+
     async fn get_fee_rate(&self, confirmation_target: u16) -> anyhow::Result<Option<Feerate>> {
         let fee = block_in_place(|| {
             self.0
@@ -1,7 +1,7 @@
 use std::fmt;
 
 use anyhow::anyhow as format_err;
-use bitcoin::{BlockHash, Network, Script, Transaction, Txid};
+use bitcoin::{Block, BlockHash, Network, Script, Transaction, Txid};
 use bitcoin_hashes::hex::ToHex;
 use electrum_client::ElectrumApi;
 use fedimint_core::task::{block_in_place, TaskHandle};
@@ -10,7 +10,7 @@ use fedimint_core::util::SafeUrl;
 use fedimint_core::{apply, async_trait_maybe_send, Feerate};
 use tracing::{info, warn};
 
-use crate::{DynBitcoindRpc, IBitcoindRpc, IBitcoindRpcFactory, RetryClient};
+use crate::{DynBitcoindRpc, IBitcoindRpc, IBitcoindRpcFactory, RetryClient, UnsupportedCall};
 
 #[derive(Debug)]
 pub struct ElectrumFactory;
@@ -67,6 +67,13 @@ impl IBitcoindRpc for ElectrumClient {
             .block_hash())
     }
 
+    async fn get_block(&self, _hash: &BlockHash) -> anyhow::Result<Block> {
+        // Electrum servers don't serve full blocks, only their headers
+        Err(format_err!(UnsupportedCall(
+            "get_block not supported by electrum backend"
+        )))
+    }
+
     async fn get_fee_rate(&self, confirmation_target: u16) -> anyhow::Result<Option<Feerate>> {
         let estimate = block_in_place(|| self.0.estimate_fee(confirmation_target as usize))?;
         let min_fee = block_in_place(|| self.0.relay_fee())?;
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use anyhow::format_err;
-use bitcoin::{BlockHash, Network, Script, Transaction, Txid};
+use bitcoin::{Block, BlockHash, Network, Script, Transaction, Txid};
 use bitcoin_hashes::hex::ToHex;
 use fedimint_core::task::TaskHandle;
 use fedimint_core::txoproof::TxOutProof;
@@ -68,6 +68,13 @@ impl IBitcoindRpc for EsploraClient {
         Ok(self.0.get_block_hash(height as u32).await?)
     }
 
+    async fn get_block(&self, hash: &BlockHash) -> anyhow::Result<Block> {
+        self.0
+            .get_block_by_hash(hash)
+            .await?
+            .ok_or(format_err!("No block found"))
+    }
+
     async fn get_fee_rate(&self, confirmation_target: u16) -> anyhow::Result<Option<Feerate>> {
         let fee_estimates: HashMap<String, f64> = self.0.get_fee_estimates().await?;
 
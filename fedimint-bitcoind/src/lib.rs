@@ -7,7 +7,7 @@ use std::time::Duration;
 
 use anyhow::Context;
 pub use anyhow::Result;
-use bitcoin::{BlockHash, Network, Script, Transaction, Txid};
+use bitcoin::{Block, BlockHash, Network, Script, Transaction, Txid};
 use fedimint_core::bitcoinrpc::BitcoinRpcConfig;
 use fedimint_core::task::TaskHandle;
 use fedimint_core::txoproof::TxOutProof;
@@ -102,6 +102,9 @@ pub trait IBitcoindRpc: Debug {
     /// by a certain number of blocks.
     async fn get_block_hash(&self, height: u64) -> Result<BlockHash>;
 
+    /// Returns the block with the given hash
+    async fn get_block(&self, hash: &BlockHash) -> Result<Block>;
+
     /// Estimates the fee rate for a given confirmation target. Make sure that
     /// all federation members use the same algorithm to avoid widely
     /// diverging results. If the node is not ready yet to return a fee rate
@@ -142,6 +145,19 @@ dyn_newtype_define! {
     pub DynBitcoindRpc(Arc<IBitcoindRpc>)
 }
 
+/// Error of a call the backend can't serve at all, [`RetryClient`] returns it
+/// right away instead of retrying
+#[derive(Debug)]
+pub struct UnsupportedCall(pub &'static str);
+
+impl std::fmt::Display for UnsupportedCall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedCall {}
+
 const RETRY_SLEEP_MIN_MS: Duration = Duration::from_millis(10);
 const RETRY_SLEEP_MAX_MS: Duration = Duration::from_millis(1000);
 
@@ -171,7 +187,7 @@ impl<C> RetryClient<C> {
                     break ret;
                 }
                 Err(e) => {
-                    if self.task_handle.is_shutting_down() {
+                    if self.task_handle.is_shutting_down() || e.is::<UnsupportedCall>() {
                         return Err(e);
                     }
 
@@ -205,6 +221,11 @@ where
             .await
     }
 
+    async fn get_block(&self, hash: &BlockHash) -> Result<Block> {
+        self.retry_call(|| async { self.inner.get_block(hash).await })
+            .await
+    }
+
     async fn get_fee_rate(&self, confirmation_target: u16) -> Result<Option<Feerate>> {
         self.retry_call(|| async { self.inner.get_fee_rate(confirmation_target).await })
             .await
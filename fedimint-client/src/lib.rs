@@ -67,7 +67,7 @@
 //! For a hacky instantiation of a complete client see the [`ng` subcommand of `fedimint-cli`](https://github.com/fedimint/fedimint/blob/55f9d88e17d914b92a7018de677d16e57ed42bf6/fedimint-cli/src/ng.rs#L56-L73).
 
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::io::{Error, Read, Write};
 use std::sync::atomic::AtomicUsize;
@@ -78,8 +78,8 @@ use anyhow::{anyhow, bail, ensure};
 use async_stream::stream;
 use db::{CachedApiVersionSet, CachedApiVersionSetKey, ClientConfigKey, ClientConfigKeyPrefix};
 use fedimint_core::api::{
-    ApiVersionSet, DynGlobalApi, DynModuleApi, GlobalFederationApi, IGlobalFederationApi,
-    InviteCode, WsFederationApi,
+    ApiVersionSet, DynGlobalApi, DynModuleApi, EventType, FederationEvent, GlobalFederationApi,
+    IGlobalFederationApi, InviteCode, WsFederationApi,
 };
 use fedimint_core::config::{
     ClientConfig, ClientModuleConfig, FederationId, JsonClientConfig, JsonWithKind,
@@ -88,6 +88,7 @@ use fedimint_core::config::{
 use fedimint_core::core::{DynInput, DynOutput, IInput, IOutput, ModuleInstanceId, ModuleKind};
 use fedimint_core::db::{AutocommitError, Database, DatabaseTransaction, IDatabase};
 use fedimint_core::encoding::{Decodable, DecodeError, Encodable};
+use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{
     ApiVersion, MultiApiVersion, SupportedApiVersionsSummary, SupportedCoreApiVersions,
@@ -151,6 +152,9 @@ pub mod transaction;
 
 const EXTERNAL_SECRET_CHILD_ID: ChildId = ChildId((ModuleInstanceId::MAX as u64) + 1);
 
+/// How many blocks [`Client::get_federation_events`] requests at once
+const FEDERATION_EVENTS_CONCURRENT_BLOCKS: usize = 16;
+
 pub type InstancelessDynClientInput = ClientInput<
     Box<maybe_add_send_sync!(dyn IInput + 'static)>,
     Box<maybe_add_send_sync!(dyn IState<DynGlobalClientContext> + 'static)>,
@@ -598,6 +602,33 @@ impl Client {
         operation_meta: F,
         tx_builder: TransactionBuilder,
     ) -> anyhow::Result<TransactionId>
+    where
+        F: Fn(TransactionId, Option<OutPoint>) -> M + Clone + MaybeSend + MaybeSync,
+        M: serde::Serialize + MaybeSend,
+    {
+        self.finalize_and_submit_transaction_if_new(
+            operation_id,
+            operation_type,
+            operation_meta,
+            tx_builder,
+        )
+        .await?
+        .ok_or_else(|| anyhow!("There already exists an operation with id {operation_id:?}"))
+    }
+
+    /// Like [`Client::finalize_and_submit_transaction`], but returns `None`
+    /// instead of an error if the operation with given id already exists.
+    ///
+    /// The check and the submission happen in the same database transaction,
+    /// so of several concurrent calls with the same id exactly one submits the
+    /// transaction.
+    pub async fn finalize_and_submit_transaction_if_new<F, M>(
+        &self,
+        operation_id: OperationId,
+        operation_type: &str,
+        operation_meta: F,
+        tx_builder: TransactionBuilder,
+    ) -> anyhow::Result<Option<TransactionId>>
     where
         F: Fn(TransactionId, Option<OutPoint>) -> M + Clone + MaybeSend + MaybeSync,
         M: serde::Serialize + MaybeSend,
@@ -614,7 +645,7 @@ impl Client {
                     let operation_meta = operation_meta.clone();
                     Box::pin(async move {
                         if ClientInner::operation_exists(dbtx, operation_id).await {
-                            bail!("There already exists an operation with id {operation_id:?}")
+                            return Ok(None);
                         }
 
                         let (txid, change_outpoint) = self
@@ -631,7 +662,7 @@ impl Client {
                             )
                             .await;
 
-                        Ok(txid)
+                        Ok(Some(txid))
                     })
                 },
                 Some(100), // TODO: handle what happens after 100 retries
@@ -706,6 +737,68 @@ impl Client {
         &self.inner.db
     }
 
+    /// Collects the events of the given `event_types` from all completed
+    /// epochs starting at `from_epoch`, in the order they were accepted.
+    ///
+    /// Every module client classifies the inputs and outputs of its own
+    /// module, see [`ClientModule::input_event_type`]. Items of modules this
+    /// client doesn't know are skipped.
+    pub async fn get_federation_events(
+        &self,
+        from_epoch: u64,
+        event_types: &[EventType],
+    ) -> anyhow::Result<Vec<FederationEvent>> {
+        let block_count = self.api().fetch_block_count().await?;
+
+        let mut blocks = futures::stream::iter(from_epoch..block_count)
+            .map(|epoch| async move {
+                let block = self.api().await_block(epoch, self.decoders()).await?;
+                anyhow::Ok((epoch, block))
+            })
+            .buffered(FEDERATION_EVENTS_CONCURRENT_BLOCKS);
+
+        let mut events = vec![];
+        while let Some(result) = blocks.next().await {
+            let (epoch, block) = result?;
+            for accepted in block.items {
+                let ConsensusItem::Transaction(transaction) = accepted.item else {
+                    continue;
+                };
+
+                let txid = transaction.tx_hash();
+                let input_events = transaction.inputs.iter().filter_map(|input| {
+                    let module = self
+                        .get_module_client_dyn(input.module_instance_id())
+                        .ok()?;
+                    Some((input.module_instance_id(), module.input_event_type(input)?))
+                });
+                let output_events = transaction.outputs.iter().filter_map(|output| {
+                    let module = self
+                        .get_module_client_dyn(output.module_instance_id())
+                        .ok()?;
+                    Some((
+                        output.module_instance_id(),
+                        module.output_event_type(output)?,
+                    ))
+                });
+
+                events.extend(
+                    input_events
+                        .chain(output_events)
+                        .filter(|(_, event_type)| event_types.contains(event_type))
+                        .map(|(module_instance_id, event_type)| FederationEvent {
+                            epoch,
+                            event_type,
+                            txid,
+                            module_instance_id,
+                        }),
+                );
+            }
+        }
+
+        Ok(events)
+    }
+
     /// Returns a stream of transaction updates for the given operation id that
     /// can later be used to watch for a specific transaction being accepted.
     pub async fn transaction_updates(&self, operation_id: OperationId) -> TransactionUpdates {
@@ -1039,6 +1132,39 @@ impl ClientInner {
         Some(self.modules.get(instance)?.as_ref())
     }
 
+    /// Lets every module with items in `tx` validate them before submission
+    fn pre_submit_hook(&self, tx: &Transaction) -> anyhow::Result<()> {
+        let module_instances = tx
+            .inputs
+            .iter()
+            .map(|input| input.module_instance_id())
+            .chain(tx.outputs.iter().map(|output| output.module_instance_id()))
+            .collect::<BTreeSet<_>>();
+
+        let errors = module_instances
+            .into_iter()
+            .filter_map(|instance| {
+                self.get_module(instance)
+                    .pre_submit_hook(instance, tx)
+                    .err()
+                    .map(|errors| (instance, errors))
+            })
+            .flat_map(|(instance, errors)| {
+                errors
+                    .into_iter()
+                    .map(move |error| format!("module {instance}: {error}"))
+            })
+            .collect::<Vec<_>>();
+
+        ensure!(
+            errors.is_empty(),
+            "Transaction rejected before submission: {}",
+            errors.join(", ")
+        );
+
+        Ok(())
+    }
+
     /// Determines if a transaction is underfunded, overfunded or balanced
     fn transaction_builder_balance(
         &self,
@@ -1139,6 +1265,7 @@ impl ClientInner {
         let (transaction, mut states, change_idx) = self
             .finalize_transaction(dbtx, operation_id, tx_builder)
             .await?;
+        self.pre_submit_hook(&transaction)?;
         let txid = transaction.tx_hash();
         let change_outpoint = change_idx.map(|out_idx| OutPoint { txid, out_idx });
 
@@ -3,12 +3,13 @@ use std::ffi;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-use fedimint_core::api::DynGlobalApi;
+use fedimint_core::api::{DynGlobalApi, EventType};
 use fedimint_core::core::{Decoder, DynInput, DynOutput, IntoDynInstance, ModuleInstanceId};
 use fedimint_core::db::{DatabaseTransaction, ModuleDatabaseTransaction};
 use fedimint_core::module::registry::ModuleRegistry;
-use fedimint_core::module::{ModuleCommon, TransactionItemAmount};
+use fedimint_core::module::{ModuleCommon, ModuleError, TransactionItemAmount};
 use fedimint_core::task::{MaybeSend, MaybeSync};
+use fedimint_core::transaction::Transaction;
 use fedimint_core::util::BoxStream;
 use fedimint_core::{
     apply, async_trait_maybe_send, dyn_newtype_define, maybe_add_send_sync, Amount, OutPoint,
@@ -68,6 +69,35 @@ pub trait ClientModule: Debug + MaybeSend + MaybeSync + 'static {
         output: &<Self::Common as ModuleCommon>::Output,
     ) -> TransactionItemAmount;
 
+    /// Validates the inputs and outputs of this module in a transaction right
+    /// before it is submitted, so that transactions the federation would
+    /// reject fail without a round trip
+    fn pre_submit_hook(
+        &self,
+        _inputs: &[&<Self::Common as ModuleCommon>::Input],
+        _outputs: &[&<Self::Common as ModuleCommon>::Output],
+    ) -> Result<(), Vec<ModuleError>> {
+        Ok(())
+    }
+
+    /// Returns the event an accepted input of this module represents, if any,
+    /// see [`Client::get_federation_events`]
+    fn input_event_type(
+        &self,
+        _input: &<Self::Common as ModuleCommon>::Input,
+    ) -> Option<EventType> {
+        None
+    }
+
+    /// Returns the event an accepted output of this module represents, if
+    /// any, see [`Client::get_federation_events`]
+    fn output_event_type(
+        &self,
+        _output: &<Self::Common as ModuleCommon>::Output,
+    ) -> Option<EventType> {
+        None
+    }
+
     fn supports_backup(&self) -> bool {
         false
     }
@@ -204,6 +234,18 @@ pub trait IClientModule: Debug {
 
     fn output_amount(&self, output: &DynOutput) -> TransactionItemAmount;
 
+    fn input_event_type(&self, input: &DynInput) -> Option<EventType>;
+
+    fn output_event_type(&self, output: &DynOutput) -> Option<EventType>;
+
+    /// Runs [`ClientModule::pre_submit_hook`] on the items of `tx` that belong
+    /// to `module_instance`
+    fn pre_submit_hook(
+        &self,
+        module_instance: ModuleInstanceId,
+        tx: &Transaction,
+    ) -> Result<(), Vec<ModuleError>>;
+
     fn supports_backup(&self) -> bool;
 
     async fn backup(
@@ -309,6 +351,57 @@ where
         )
     }
 
+    fn input_event_type(&self, input: &DynInput) -> Option<EventType> {
+        <T as ClientModule>::input_event_type(
+            self,
+            input
+                .as_any()
+                .downcast_ref()
+                .expect("Dispatched to correct module"),
+        )
+    }
+
+    fn output_event_type(&self, output: &DynOutput) -> Option<EventType> {
+        <T as ClientModule>::output_event_type(
+            self,
+            output
+                .as_any()
+                .downcast_ref()
+                .expect("Dispatched to correct module"),
+        )
+    }
+
+    fn pre_submit_hook(
+        &self,
+        module_instance: ModuleInstanceId,
+        tx: &Transaction,
+    ) -> Result<(), Vec<ModuleError>> {
+        let inputs = tx
+            .inputs
+            .iter()
+            .filter(|input| input.module_instance_id() == module_instance)
+            .map(|input| {
+                input
+                    .as_any()
+                    .downcast_ref()
+                    .expect("Dispatched to correct module")
+            })
+            .collect::<Vec<_>>();
+        let outputs = tx
+            .outputs
+            .iter()
+            .filter(|output| output.module_instance_id() == module_instance)
+            .map(|output| {
+                output
+                    .as_any()
+                    .downcast_ref()
+                    .expect("Dispatched to correct module")
+            })
+            .collect::<Vec<_>>();
+
+        <T as ClientModule>::pre_submit_hook(self, &inputs, &outputs)
+    }
+
     fn supports_backup(&self) -> bool {
         <T as ClientModule>::supports_backup(self)
     }
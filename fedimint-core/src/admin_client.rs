@@ -9,13 +9,15 @@ use serde::{Deserialize, Serialize};
 use tokio_rustls::rustls;
 
 use crate::api::{
-    DynGlobalApi, FederationApiExt, FederationResult, ServerStatus, StatusResponse, WsFederationApi,
+    ConsensusMeasurement, DynGlobalApi, FederationApiExt, FederationResult, NodeInfo, ServerStatus,
+    StatusResponse, WsFederationApi,
 };
 use crate::config::ServerModuleConfigGenParamsRegistry;
 use crate::endpoint_constants::{
-    ADD_CONFIG_GEN_PEER_ENDPOINT, AUDIT_ENDPOINT, AUTH_ENDPOINT, GET_CONFIG_GEN_PEERS_ENDPOINT,
-    GET_CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT, GET_DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT,
-    GET_VERIFY_CONFIG_HASH_ENDPOINT, RUN_DKG_ENDPOINT, SET_CONFIG_GEN_CONNECTIONS_ENDPOINT,
+    ADD_CONFIG_GEN_PEER_ENDPOINT, AUDIT_ENDPOINT, AUTH_ENDPOINT, CONSENSUS_ROUND_TRIP_ENDPOINT,
+    GET_CONFIG_GEN_PEERS_ENDPOINT, GET_CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT,
+    GET_DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT, GET_VERIFY_CONFIG_HASH_ENDPOINT, NODE_INFO_ENDPOINT,
+    POST_EXCHANGE_RATE_ENDPOINT, RUN_DKG_ENDPOINT, SET_CONFIG_GEN_CONNECTIONS_ENDPOINT,
     SET_CONFIG_GEN_PARAMS_ENDPOINT, SET_PASSWORD_ENDPOINT, START_CONSENSUS_ENDPOINT,
     STATUS_ENDPOINT,
 };
@@ -178,6 +180,35 @@ impl WsAdminClient {
             .await
     }
 
+    /// Summarizes how long the recent consensus sessions of the guardian took
+    pub async fn measure_consensus_round_trip(
+        &self,
+    ) -> FederationResult<Option<ConsensusMeasurement>> {
+        self.request(CONSENSUS_ROUND_TRIP_ENDPOINT, ApiRequestErased::default())
+            .await
+    }
+
+    /// Returns the software and module versions the guardian is running
+    pub async fn get_node_info(&self) -> FederationResult<NodeInfo> {
+        self.request(NODE_INFO_ENDPOINT, ApiRequestErased::default())
+            .await
+    }
+
+    /// Posts the price of one bitcoin in `currency` in hundredths of its unit,
+    /// clients can fetch it signed by the guardian
+    pub async fn post_exchange_rate(
+        &self,
+        currency: &str,
+        price_cents: u64,
+        auth: ApiAuth,
+    ) -> FederationResult<()> {
+        self.request(
+            POST_EXCHANGE_RATE_ENDPOINT,
+            ApiRequestErased::new((currency, price_cents)).with_auth(auth),
+        )
+        .await
+    }
+
     /// Check auth credentials
     pub async fn auth(&self, auth: ApiAuth) -> FederationResult<()> {
         self.request(AUTH_ENDPOINT, ApiRequestErased::default().with_auth(auth))
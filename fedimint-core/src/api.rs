@@ -6,7 +6,7 @@ use std::ops::Add;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use std::{cmp, result};
 
 use anyhow::{anyhow, ensure};
@@ -20,11 +20,11 @@ use fedimint_core::encoding::Encodable;
 use fedimint_core::endpoint_constants::AWAIT_BLOCK_ENDPOINT;
 use fedimint_core::fmt_utils::AbbreviateDebug;
 use fedimint_core::module::SerdeModuleEncoding;
-use fedimint_core::task::{MaybeSend, MaybeSync, RwLock, RwLockWriteGuard};
+use fedimint_core::task::{sleep, MaybeSend, MaybeSync, RwLock, RwLockWriteGuard};
 use fedimint_core::time::now;
 use fedimint_core::{
-    apply, async_trait_maybe_send, dyn_newtype_define, ModuleDecoderRegistry, NumPeers, OutPoint,
-    PeerId, TransactionId,
+    apply, async_trait_maybe_send, dyn_newtype_define, Amount, ModuleDecoderRegistry, NumPeers,
+    OutPoint, PeerId, TransactionId,
 };
 use fedimint_derive::Decodable;
 use fedimint_logging::{LOG_CLIENT_NET_API, LOG_NET_API};
@@ -43,21 +43,30 @@ use threshold_crypto::{PublicKey, PK_SIZE};
 use tracing::{debug, error, instrument, trace, warn};
 
 use crate::backup::ClientBackupSnapshot;
-use crate::block::Block;
+use crate::block::{Block, EpochCommitment};
 use crate::core::backup::SignedBackupRequest;
 use crate::core::{Decoder, OutputOutcome};
 use crate::endpoint_constants::{
-    AWAIT_OUTPUT_OUTCOME_ENDPOINT, BACKUP_ENDPOINT, CONFIG_ENDPOINT, CONFIG_HASH_ENDPOINT,
-    FETCH_BLOCK_COUNT_ENDPOINT, RECOVER_ENDPOINT, TRANSACTION_ENDPOINT, VERSION_ENDPOINT,
+    AVERAGE_SESSION_DURATION_ENDPOINT, AWAIT_OUTPUT_OUTCOME_ENDPOINT,
+    AWAIT_TRANSACTION_STATUS_ENDPOINT, BACKUP_ENDPOINT, BALANCE_SHEET_ENDPOINT,
+    CONFIG_DIFF_ENDPOINT, CONFIG_ENDPOINT, CONFIG_HASH_ENDPOINT, CONSTITUTION_ENDPOINT,
+    EPOCH_COMMITMENT_ENDPOINT, EPOCH_METRICS_ENDPOINT, EXCHANGE_RATE_ENDPOINT,
+    FEDERATION_STATS_ENDPOINT, FETCH_BLOCK_COUNT_ENDPOINT, MODULE_AUDIT_ENDPOINT, RECOVER_ENDPOINT,
+    TRANSACTION_ENDPOINT, VALIDATE_TRANSACTION_ENDPOINT, VERSION_ENDPOINT,
     WAIT_TRANSACTION_ENDPOINT,
 };
+use crate::epoch::{combine_sigs, SerdeSignature, SerdeSignatureShare};
+use crate::module::audit::{
+    BalanceSheet, BalanceSheetShare, ModuleAudit, ModuleAuditProof, ModuleAuditShare,
+    SignedBalanceSheet,
+};
 use crate::module::{ApiRequestErased, ApiVersion, SupportedApiVersionsSummary};
 use crate::query::{
-    DiscoverApiVersionSet, FilterMap, QueryStep, QueryStrategy, ThresholdConsensus,
+    AllOrDeadline, DiscoverApiVersionSet, FilterMap, QueryStep, QueryStrategy, ThresholdConsensus,
     UnionResponsesSingle,
 };
 use crate::transaction::{SerdeTransaction, Transaction};
-use crate::util::SafeUrl;
+use crate::util::{BoxStream, SafeUrl};
 use crate::{serde_as_encodable_hex, task};
 
 pub type PeerResult<T> = Result<T, PeerError>;
@@ -125,6 +134,13 @@ impl Display for FederationError {
 }
 
 impl FederationError {
+    pub fn general(error: anyhow::Error) -> Self {
+        FederationError {
+            general: Some(error),
+            peers: BTreeMap::new(),
+        }
+    }
+
     pub fn is_retryable(&self) -> bool {
         self.peers.iter().any(|(_, e)| e.is_retryable())
     }
@@ -335,21 +351,120 @@ impl AsRef<dyn IGlobalFederationApi + 'static> for DynGlobalApi {
     }
 }
 
+/// Number of recent sessions [`GlobalFederationApi::estimate_note_issuance_time`]
+/// averages over
+pub const NOTE_ISSUANCE_ESTIMATE_SESSIONS: u64 = 10;
+
+/// How often [`GlobalFederationApi::fetch_balance_sheet`],
+/// [`GlobalFederationApi::fetch_federation_stats`] and
+/// [`GlobalFederationApi::get_module_audit_proof`] ask the guardians again if
+/// not enough of them signed the same response
+const SIGNED_RESPONSE_ATTEMPTS: usize = 10;
+
+/// Combines the signature shares of the first value that a threshold of
+/// guardians signed. `shares` holds the value a guardian signed, the public key
+/// set it reported and its signature share.
+fn combine_signature_shares<T: Eq + std::hash::Hash>(
+    shares: BTreeMap<PeerId, (T, threshold_crypto::PublicKeySet, SerdeSignatureShare)>,
+    message: impl Fn(&T) -> sha256::Hash,
+) -> Option<(T, SerdeSignature)> {
+    // The combined signature still has to be verified against the epoch public
+    // key of the client config
+    let pk_set = most_reported_pk_set(shares.values().map(|(_, pk_set, _)| pk_set))?;
+
+    // Guardians that didn't process the same transactions yet sign different
+    // values
+    let mut shares_by_value: HashMap<T, BTreeMap<PeerId, SerdeSignatureShare>> = HashMap::new();
+    for (peer_id, (value, _, signature_share)) in shares {
+        shares_by_value
+            .entry(value)
+            .or_default()
+            .insert(peer_id, signature_share);
+    }
+
+    shares_by_value
+        .into_iter()
+        .find_map(|(value, signature_shares)| {
+            combine_sigs(&pk_set, &signature_shares, &message(&value))
+                .ok()
+                .map(|signature| (value, signature))
+        })
+}
+
+/// Every guardian reports the public key set of the epoch keys, so we trust
+/// the one most of them agree on
+fn most_reported_pk_set<'a>(
+    reported: impl Iterator<Item = &'a threshold_crypto::PublicKeySet>,
+) -> Option<threshold_crypto::PublicKeySet> {
+    let mut pk_sets: Vec<(&threshold_crypto::PublicKeySet, usize)> = vec![];
+    for pk_set in reported {
+        match pk_sets.iter_mut().find(|(known, _)| *known == pk_set) {
+            Some((_, count)) => *count += 1,
+            None => pk_sets.push((pk_set, 1)),
+        }
+    }
+    pk_sets
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(pk_set, _)| pk_set.clone())
+}
+
 /// The API for the global (non-module) endpoints
 #[apply(async_trait_maybe_send!)]
 pub trait GlobalFederationApi {
     async fn submit_transaction(&self, tx: Transaction) -> FederationResult<TransactionId>;
 
+    /// Checks whether the federation would accept `tx` in its current state
+    /// without submitting it, returning the same errors as a submission
+    async fn validate_transaction(&self, tx: &Transaction) -> FederationResult<()>;
+
     async fn await_block(
         &self,
         block_index: u64,
         decoders: &ModuleDecoderRegistry,
     ) -> anyhow::Result<Block>;
 
+    /// Fetches the number of completed epochs, a cheap way to check that the
+    /// federation is alive since no epoch content is downloaded
     async fn fetch_block_count(&self) -> FederationResult<u64>;
 
+    /// Fetches every guardian's commitment to the block of `epoch`, waiting
+    /// until all guardians responded or a deadline passed. Commitments that
+    /// are malformed or were not created by the responding guardian are
+    /// dropped.
+    async fn get_epoch_commitments(
+        &self,
+        epoch: u64,
+    ) -> FederationResult<BTreeMap<PeerId, EpochCommitment>>;
+
+    /// Fetches how every guardian processed the items of each module in
+    /// `epoch`, waiting until all guardians responded or a deadline passed.
+    /// Guardians only keep the metrics of recent epochs in memory, so older
+    /// epochs or ones before a restart have no entries.
+    async fn get_epoch_metrics(
+        &self,
+        epoch: u64,
+    ) -> FederationResult<BTreeMap<PeerId, BTreeMap<ModuleInstanceId, EpochMetrics>>>;
+
     async fn await_transaction(&self, txid: TransactionId) -> FederationResult<TransactionId>;
 
+    /// Streams the status of the transaction `txid`, starting with
+    /// [`TransactionStatus::Pending`] and ending with
+    /// [`TransactionStatus::Accepted`] or [`TransactionStatus::Rejected`] once
+    /// the federation processed it. Failing requests are retried, so the
+    /// stream survives reconnecting to the guardians.
+    fn subscribe_transaction_status(&self, txid: TransactionId)
+        -> BoxStream<'_, TransactionStatus>;
+
+    /// Merges the events of all `subscriptions` into a single stream, tagged
+    /// with the subscription they belong to. All of them share the connections
+    /// to the guardians, failing requests are retried like in
+    /// [`Self::subscribe_transaction_status`].
+    fn multiplexed_subscribe(
+        &self,
+        subscriptions: Vec<SubscriptionType>,
+    ) -> BoxStream<'_, SubscriptionEvent>;
+
     async fn await_output_outcome<R>(
         &self,
         outpoint: OutPoint,
@@ -365,6 +480,45 @@ pub trait GlobalFederationApi {
     /// Fetches the server consensus hash if enough peers agree on it
     async fn consensus_config_hash(&self) -> FederationResult<sha256::Hash>;
 
+    /// Fetches what changed in the module configs since `known_version`,
+    /// returns `None` if it is the current version
+    async fn get_config_diff(&self, known_version: u32) -> FederationResult<Option<ConfigDiff>>;
+
+    /// Fetches the constitution the guardians agreed on during setup, waiting
+    /// until the client config containing it is signed, verify it with
+    /// [`Constitution::verify`]
+    async fn get_federation_constitution(&self) -> FederationResult<Constitution>;
+
+    /// Estimates how long it takes until the notes of a transaction submitted
+    /// now are issued, based on the duration of the last
+    /// [`NOTE_ISSUANCE_ESTIMATE_SESSIONS`] sessions
+    async fn estimate_note_issuance_time(&self) -> FederationResult<Duration>;
+
+    /// Fetches the balance sheet of the federation signed by a threshold of
+    /// guardians, verify it with [`SignedBalanceSheet::verify`] and the epoch
+    /// public key of the client config
+    async fn fetch_balance_sheet(&self) -> FederationResult<SignedBalanceSheet>;
+
+    /// Fetches the consensus health of the federation signed by a threshold of
+    /// guardians, verify it with [`SignedFederationStats::verify`] and the
+    /// epoch public key of the client config
+    async fn fetch_federation_stats(&self) -> FederationResult<SignedFederationStats>;
+
+    /// Fetches the audit of a single module signed by a threshold of
+    /// guardians, verify it with [`ModuleAuditProof::verify`] and the epoch
+    /// public key of the client config
+    async fn get_module_audit_proof(
+        &self,
+        module_id: ModuleInstanceId,
+    ) -> FederationResult<ModuleAuditProof>;
+
+    /// Fetches the most recent price of one bitcoin in `currency` that a
+    /// guardian posted with a valid signature, verify it with
+    /// [`SignedExchangeRate::verify`] and the epoch public key of the client
+    /// config
+    async fn get_ecash_exchange_rate(&self, currency: &str)
+        -> FederationResult<SignedExchangeRate>;
+
     async fn upload_backup(&self, request: &SignedBackupRequest) -> FederationResult<()>;
 
     async fn download_backup(
@@ -414,6 +568,14 @@ where
         .await
     }
 
+    async fn validate_transaction(&self, tx: &Transaction) -> FederationResult<()> {
+        self.request_current_consensus(
+            VALIDATE_TRANSACTION_ENDPOINT.to_owned(),
+            ApiRequestErased::new(&SerdeTransaction::from(tx)),
+        )
+        .await
+    }
+
     async fn await_block(
         &self,
         block_index: u64,
@@ -436,6 +598,48 @@ where
         .await
     }
 
+    async fn get_epoch_commitments(
+        &self,
+        epoch: u64,
+    ) -> FederationResult<BTreeMap<PeerId, EpochCommitment>> {
+        let timeout = Duration::from_secs(60);
+        let decoders = ModuleDecoderRegistry::default();
+
+        Ok(self
+            .request_with_strategy(
+                AllOrDeadline::<SerdeModuleEncoding<EpochCommitment>>::new(
+                    self.all_peers().len(),
+                    now().add(timeout),
+                ),
+                EPOCH_COMMITMENT_ENDPOINT.to_owned(),
+                ApiRequestErased::new(epoch),
+            )
+            .await?
+            .into_iter()
+            .filter_map(|(peer_id, commitment)| {
+                commitment
+                    .try_into_inner(&decoders)
+                    .ok()
+                    .filter(|commitment| commitment.peer_id == peer_id)
+                    .map(|commitment| (peer_id, commitment))
+            })
+            .collect())
+    }
+
+    async fn get_epoch_metrics(
+        &self,
+        epoch: u64,
+    ) -> FederationResult<BTreeMap<PeerId, BTreeMap<ModuleInstanceId, EpochMetrics>>> {
+        let timeout = Duration::from_secs(60);
+
+        self.request_with_strategy(
+            AllOrDeadline::new(self.all_peers().len(), now().add(timeout)),
+            EPOCH_METRICS_ENDPOINT.to_owned(),
+            ApiRequestErased::new(epoch),
+        )
+        .await
+    }
+
     async fn await_transaction(&self, txid: TransactionId) -> FederationResult<TransactionId> {
         self.request_current_consensus(
             WAIT_TRANSACTION_ENDPOINT.to_owned(),
@@ -444,6 +648,90 @@ where
         .await
     }
 
+    fn subscribe_transaction_status(
+        &self,
+        txid: TransactionId,
+    ) -> BoxStream<'_, TransactionStatus> {
+        let outcome = async move {
+            loop {
+                match self
+                    .request_current_consensus::<TransactionStatus>(
+                        AWAIT_TRANSACTION_STATUS_ENDPOINT.to_owned(),
+                        ApiRequestErased::new(txid),
+                    )
+                    .await
+                {
+                    Ok(status) => return status,
+                    Err(e) => {
+                        debug!(%txid, %e, "Awaiting transaction status failed, retrying");
+                        sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        };
+
+        Box::pin(
+            futures::stream::once(async { TransactionStatus::Pending })
+                .chain(futures::stream::once(outcome)),
+        )
+    }
+
+    fn multiplexed_subscribe(
+        &self,
+        subscriptions: Vec<SubscriptionType>,
+    ) -> BoxStream<'_, SubscriptionEvent> {
+        let streams = subscriptions.into_iter().map(|subscription| {
+            let stream: BoxStream<'_, SubscriptionEvent> = match subscription {
+                SubscriptionType::EpochCommit => Box::pin(futures::stream::unfold(
+                    None,
+                    move |mut next_epoch| async move {
+                        loop {
+                            let epoch = match next_epoch {
+                                Some(epoch) => epoch,
+                                None => match self.fetch_block_count().await {
+                                    Ok(block_count) => block_count,
+                                    Err(e) => {
+                                        debug!(%e, "Fetching block count failed, retrying");
+                                        sleep(Duration::from_secs(1)).await;
+                                        continue;
+                                    }
+                                },
+                            };
+                            next_epoch = Some(epoch);
+
+                            // the block is only awaited, so no decoders are needed
+                            match self
+                                .request_current_consensus::<SerdeModuleEncoding<Block>>(
+                                    AWAIT_BLOCK_ENDPOINT.to_owned(),
+                                    ApiRequestErased::new(epoch),
+                                )
+                                .await
+                            {
+                                Ok(_) => {
+                                    return Some((
+                                        SubscriptionEvent::EpochCommit { epoch },
+                                        Some(epoch + 1),
+                                    ))
+                                }
+                                Err(e) => {
+                                    debug!(epoch, %e, "Awaiting block failed, retrying");
+                                    sleep(Duration::from_secs(1)).await;
+                                }
+                            }
+                        }
+                    },
+                )),
+                SubscriptionType::TransactionStatus(txid) => Box::pin(
+                    self.subscribe_transaction_status(txid)
+                        .map(move |status| SubscriptionEvent::TransactionStatus { txid, status }),
+                ),
+            };
+            stream
+        });
+
+        Box::pin(futures::stream::select_all(streams))
+    }
+
     // TODO should become part of the API
     async fn await_output_outcome<R>(
         &self,
@@ -498,6 +786,205 @@ where
             .await
     }
 
+    async fn get_config_diff(&self, known_version: u32) -> FederationResult<Option<ConfigDiff>> {
+        self.request_current_consensus(
+            CONFIG_DIFF_ENDPOINT.to_owned(),
+            ApiRequestErased::new(known_version),
+        )
+        .await
+    }
+
+    async fn get_federation_constitution(&self) -> FederationResult<Constitution> {
+        self.request_current_consensus(
+            CONSTITUTION_ENDPOINT.to_owned(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn estimate_note_issuance_time(&self) -> FederationResult<Duration> {
+        let deadline = now().add(Duration::from_secs(10));
+
+        let mut averages = self
+            .request_with_strategy(
+                AllOrDeadline::<Option<Duration>>::new(self.all_peers().len(), deadline),
+                AVERAGE_SESSION_DURATION_ENDPOINT.to_owned(),
+                ApiRequestErased::new(NOTE_ISSUANCE_ESTIMATE_SESSIONS),
+            )
+            .await?
+            .into_values()
+            .flatten()
+            .collect::<Vec<_>>();
+        averages.sort();
+
+        // the median is robust against a minority of peers reporting wrong durations
+        averages
+            .get(averages.len() / 2)
+            .copied()
+            .ok_or_else(|| FederationError::general(anyhow!("No consensus session completed yet")))
+    }
+
+    async fn fetch_balance_sheet(&self) -> FederationResult<SignedBalanceSheet> {
+        for _ in 0..SIGNED_RESPONSE_ATTEMPTS {
+            let responses = self
+                .request_with_strategy(
+                    AllOrDeadline::<BalanceSheetShare>::new(
+                        self.all_peers().len(),
+                        now().add(Duration::from_secs(10)),
+                    ),
+                    BALANCE_SHEET_ENDPOINT.to_owned(),
+                    ApiRequestErased::default(),
+                )
+                .await?;
+
+            let shares = responses
+                .into_iter()
+                .map(|(peer_id, share)| {
+                    (
+                        peer_id,
+                        (
+                            share.balance_sheet,
+                            share.epoch_pk_set,
+                            share.signature_share,
+                        ),
+                    )
+                })
+                .collect();
+            if let Some((balance_sheet, signature)) =
+                combine_signature_shares(shares, BalanceSheet::message)
+            {
+                return Ok(SignedBalanceSheet {
+                    balance_sheet,
+                    signature,
+                });
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        Err(FederationError::general(anyhow!(
+            "Not enough guardians signed the same balance sheet"
+        )))
+    }
+
+    async fn fetch_federation_stats(&self) -> FederationResult<SignedFederationStats> {
+        for _ in 0..SIGNED_RESPONSE_ATTEMPTS {
+            let responses = self
+                .request_with_strategy(
+                    AllOrDeadline::<FederationStatsShare>::new(
+                        self.all_peers().len(),
+                        now().add(Duration::from_secs(10)),
+                    ),
+                    FEDERATION_STATS_ENDPOINT.to_owned(),
+                    ApiRequestErased::default(),
+                )
+                .await?;
+
+            let shares = responses
+                .into_iter()
+                .map(|(peer_id, share)| {
+                    (
+                        peer_id,
+                        (share.stats, share.epoch_pk_set, share.signature_share),
+                    )
+                })
+                .collect();
+            if let Some((stats, signature)) =
+                combine_signature_shares(shares, FederationStats::message)
+            {
+                return Ok(SignedFederationStats { stats, signature });
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        Err(FederationError::general(anyhow!(
+            "Not enough guardians signed the same federation stats"
+        )))
+    }
+
+    async fn get_module_audit_proof(
+        &self,
+        module_id: ModuleInstanceId,
+    ) -> FederationResult<ModuleAuditProof> {
+        for _ in 0..SIGNED_RESPONSE_ATTEMPTS {
+            let responses = self
+                .request_with_strategy(
+                    AllOrDeadline::<ModuleAuditShare>::new(
+                        self.all_peers().len(),
+                        now().add(Duration::from_secs(10)),
+                    ),
+                    MODULE_AUDIT_ENDPOINT.to_owned(),
+                    ApiRequestErased::new(module_id),
+                )
+                .await?;
+
+            let shares = responses
+                .into_iter()
+                .map(|(peer_id, share)| {
+                    (
+                        peer_id,
+                        (
+                            share.module_audit,
+                            share.epoch_pk_set,
+                            share.signature_share,
+                        ),
+                    )
+                })
+                .collect();
+            if let Some((module_audit, signature)) =
+                combine_signature_shares(shares, ModuleAudit::message)
+            {
+                return Ok(ModuleAuditProof {
+                    module_audit,
+                    signature,
+                });
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        Err(FederationError::general(anyhow!(
+            "Not enough guardians signed the same audit of module {module_id}"
+        )))
+    }
+
+    async fn get_ecash_exchange_rate(
+        &self,
+        currency: &str,
+    ) -> FederationResult<SignedExchangeRate> {
+        let responses = self
+            .request_with_strategy(
+                AllOrDeadline::<ExchangeRateShare>::new(
+                    self.all_peers().len(),
+                    now().add(Duration::from_secs(10)),
+                ),
+                EXCHANGE_RATE_ENDPOINT.to_owned(),
+                ApiRequestErased::new(currency),
+            )
+            .await?;
+
+        let pk_set = most_reported_pk_set(responses.values().map(|share| &share.epoch_pk_set))
+            .ok_or_else(|| FederationError::general(anyhow!("No guardian responded")))?;
+
+        responses
+            .into_iter()
+            .filter_map(|(peer_id, share)| {
+                let (rate, signature_share) = share.signed_rate?;
+                Some(SignedExchangeRate {
+                    peer_id,
+                    rate,
+                    epoch_pk_set: pk_set.clone(),
+                    signature_share,
+                })
+            })
+            .filter(|signed_rate| signed_rate.verify_share())
+            .max_by_key(|signed_rate| signed_rate.rate.timestamp)
+            .ok_or_else(|| {
+                FederationError::general(anyhow!("No guardian posted a {currency} price"))
+            })
+    }
+
     async fn upload_backup(&self, request: &SignedBackupRequest) -> FederationResult<()> {
         self.request_current_consensus(BACKUP_ENDPOINT.to_owned(), ApiRequestErased::new(request))
             .await
@@ -867,6 +1354,42 @@ fn url_to_string_with_default_port(url: &SafeUrl) -> String {
 
 impl<C: JsonRpcClient> WsFederationApi<C> {}
 
+/// The status of a transaction as streamed by
+/// [`GlobalFederationApi::subscribe_transaction_status`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    /// The transaction wasn't processed yet, it might not even be submitted
+    Pending,
+    /// The transaction was accepted by the federation
+    Accepted,
+    /// Processing the transaction failed for the given reason, e.g. since one
+    /// of its inputs was already spent
+    Rejected(String),
+}
+
+/// An event source of [`GlobalFederationApi::multiplexed_subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriptionType {
+    /// Every epoch completed after subscribing
+    EpochCommit,
+    /// The status of a transaction, see
+    /// [`GlobalFederationApi::subscribe_transaction_status`]
+    TransactionStatus(TransactionId),
+}
+
+/// An event streamed by [`GlobalFederationApi::multiplexed_subscribe`], tagged
+/// with the [`SubscriptionType`] it was produced by
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubscriptionEvent {
+    EpochCommit {
+        epoch: u64,
+    },
+    TransactionStatus {
+        txid: TransactionId,
+        status: TransactionStatus,
+    },
+}
+
 /// The status of a server, including how it views its peers
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FederationStatus {
@@ -879,6 +1402,291 @@ pub struct FederationStatus {
     pub peers_flagged: u64,
 }
 
+/// Consensus health of the federation as seen by a guardian. It only contains
+/// data derived from consensus, so a threshold of guardians can sign the same
+/// stats.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct FederationStats {
+    pub epoch_count: u64,
+    pub peers: BTreeMap<PeerId, PeerStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PeerStats {
+    /// The last epoch the peer contributed items to since the guardian started
+    pub last_seen_epoch: Option<u64>,
+    /// The peer didn't contribute to the previous epoch, see
+    /// [`PeerStatus::flagged`]
+    pub is_dropped: bool,
+}
+
+impl FederationStats {
+    /// The message the guardians sign with their epoch keys
+    pub fn message(&self) -> sha256::Hash {
+        self.consensus_hash_tagged(FEDERATION_STATS_SIGNATURE_TAG)
+    }
+}
+
+/// Domain tag of the signed [`FederationStats`] message
+const FEDERATION_STATS_SIGNATURE_TAG: &[u8] = b"fedimint-federation-stats";
+
+/// Federation stats signed by a single guardian, together with the public key
+/// set the signature share can be combined with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FederationStatsShare {
+    pub stats: FederationStats,
+    pub epoch_pk_set: threshold_crypto::PublicKeySet,
+    pub signature_share: SerdeSignatureShare,
+}
+
+/// Federation stats signed by a threshold of guardians
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedFederationStats {
+    pub stats: FederationStats,
+    pub signature: SerdeSignature,
+}
+
+impl SignedFederationStats {
+    /// Verifies the signature against the threshold public key of the
+    /// federation's epoch keys found in the client config
+    pub fn verify(&self, epoch_pk: &PublicKey) -> anyhow::Result<()> {
+        ensure!(
+            epoch_pk.verify(&self.signature.0, self.stats.message()),
+            "Invalid federation stats signature"
+        );
+        Ok(())
+    }
+}
+
+/// How long the recent consensus sessions of a server took to complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsensusMeasurement {
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub median_ms: u64,
+    pub p99_ms: u64,
+}
+
+impl ConsensusMeasurement {
+    /// Summarizes the given session durations, returns `None` if there are
+    /// none
+    pub fn from_durations(durations: impl IntoIterator<Item = Duration>) -> Option<Self> {
+        let mut millis = durations
+            .into_iter()
+            .map(|duration| duration.as_millis() as u64)
+            .collect::<Vec<_>>();
+        millis.sort_unstable();
+
+        if millis.is_empty() {
+            return None;
+        }
+
+        // nearest-rank percentile
+        let percentile = |p: usize| millis[((millis.len() * p + 99) / 100).max(1) - 1];
+
+        Some(ConsensusMeasurement {
+            min_ms: millis[0],
+            max_ms: millis[millis.len() - 1],
+            median_ms: percentile(50),
+            p99_ms: percentile(99),
+        })
+    }
+}
+
+/// The rules and policies of a federation, published in the `meta` part of
+/// its client config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Constitution {
+    pub text: String,
+    pub version: u32,
+    /// The federation's threshold signature over the client config containing
+    /// the constitution
+    pub threshold_signature: SerdeSignature,
+}
+
+impl Constitution {
+    /// Checks that the constitution is the one in `config` and that the
+    /// federation signed `config`
+    pub fn verify(&self, config: &ClientConfig) -> bool {
+        config.constitution() == Some((self.text.as_str(), self.version))
+            && config
+                .global
+                .federation_id
+                .0
+                .verify(&self.threshold_signature.0, config.consensus_hash())
+    }
+}
+
+/// The price of one bitcoin in a fiat currency that a guardian's price oracle
+/// reported
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct ExchangeRate {
+    /// ISO 4217 code of the currency, e.g. `USD`
+    pub currency: String,
+    /// Price in hundredths of the currency's unit, e.g. cents
+    pub price_cents: u64,
+    /// When the guardian posted the price
+    pub timestamp: SystemTime,
+}
+
+impl ExchangeRate {
+    /// The message guardians sign with their epoch key share
+    pub fn message(&self) -> sha256::Hash {
+        self.consensus_hash_tagged(EXCHANGE_RATE_SIGNATURE_TAG)
+    }
+
+    /// Value of `amount` of e-cash in hundredths of the currency's unit,
+    /// rounded down
+    pub fn fiat_value_cents(&self, amount: Amount) -> u64 {
+        (u128::from(amount.msats) * u128::from(self.price_cents) / 100_000_000_000) as u64
+    }
+}
+
+/// Maximum length of [`ExchangeRate::currency`] guardians accept, enough for
+/// ISO 4217 codes and common ticker symbols
+pub const MAX_CURRENCY_CODE_LEN: usize = 8;
+
+/// Domain tag of the signed [`ExchangeRate`] message
+const EXCHANGE_RATE_SIGNATURE_TAG: &[u8] = b"fedimint-exchange-rate";
+
+/// The latest price a guardian posted for a currency signed with its epoch
+/// key share, together with the public key set the share belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExchangeRateShare {
+    pub epoch_pk_set: threshold_crypto::PublicKeySet,
+    /// `None` if the guardian never posted a price for the currency
+    pub signed_rate: Option<(ExchangeRate, SerdeSignatureShare)>,
+}
+
+/// A price signed by a single guardian
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedExchangeRate {
+    pub peer_id: PeerId,
+    pub rate: ExchangeRate,
+    /// The public key set most guardians reported
+    pub epoch_pk_set: threshold_crypto::PublicKeySet,
+    pub signature_share: SerdeSignatureShare,
+}
+
+impl SignedExchangeRate {
+    /// Verifies that `epoch_pk_set` belongs to the federation's epoch keys
+    /// found in the client config and that the guardian signed the price
+    pub fn verify(&self, epoch_pk: &PublicKey) -> anyhow::Result<()> {
+        ensure!(
+            self.epoch_pk_set.public_key() == *epoch_pk,
+            "Public key set doesn't match the epoch public key"
+        );
+        ensure!(self.verify_share(), "Invalid exchange rate signature");
+        Ok(())
+    }
+
+    fn verify_share(&self) -> bool {
+        self.epoch_pk_set
+            .public_key_share(self.peer_id.to_usize())
+            .verify(&self.signature_share.0, self.rate.message())
+    }
+}
+
+/// How a guardian processed the consensus items of a module in one epoch
+///
+/// A transaction counts as an item of every module it has inputs or outputs
+/// of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    /// Number of ordered items, including the ones that were rejected
+    pub items_proposed: usize,
+    /// Number of items that passed validation and were applied
+    pub items_accepted: usize,
+    /// Total time spent processing the items
+    pub processing_time_ms: u64,
+}
+
+/// Kinds of events that can be derived from the epoch history, each module
+/// client classifies the inputs and outputs of its own module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventType {
+    /// A peg-in was claimed by a wallet module input
+    PegIn,
+    /// A peg-out was requested by a wallet module output
+    PegOut,
+    /// A mint module output issued e-cash notes
+    NoteIssuance,
+    /// A mint module input redeemed e-cash notes
+    NoteRedemption,
+}
+
+/// An input or output of an accepted transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FederationEvent {
+    pub epoch: u64,
+    pub event_type: EventType,
+    pub txid: TransactionId,
+    pub module_instance_id: ModuleInstanceId,
+}
+
+/// Summary of the changes made to the module configs of the federation since
+/// a version known to a client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigDiff {
+    pub old_version: u32,
+    pub new_version: u32,
+    /// Paths of the fields that were added, removed or changed, e.g.
+    /// `modules.0.tx_fee`
+    pub changed_fields: Vec<String>,
+}
+
+impl ConfigDiff {
+    /// Compares two versions of the module configs given in their JSON
+    /// representation
+    pub fn new(old_version: u32, old: &Value, new_version: u32, new: &Value) -> Self {
+        let mut changed_fields = vec![];
+        collect_changed_fields("modules", Some(old), Some(new), &mut changed_fields);
+
+        ConfigDiff {
+            old_version,
+            new_version,
+            changed_fields,
+        }
+    }
+}
+
+fn collect_changed_fields(
+    path: &str,
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changed_fields: &mut Vec<String>,
+) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                collect_changed_fields(
+                    &format!("{path}.{key}"),
+                    old.get(key),
+                    new.get(key),
+                    changed_fields,
+                );
+            }
+        }
+        (old, new) if old != new => changed_fields.push(path.to_string()),
+        _ => {}
+    }
+}
+
+/// Information about the software a guardian is running, used by deployments
+/// to check whether it is up-to-date
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    /// Version of the fedimintd binary
+    pub software_version: String,
+    /// Consensus version of each module by its kind
+    pub module_versions: BTreeMap<String, u32>,
+    /// Seconds since the guardian started
+    pub uptime_secs: u64,
+    /// Number of completed consensus sessions
+    pub epoch_count: u64,
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PeerStatus {
     pub last_contribution: Option<u64>,
@@ -1169,4 +1977,23 @@ mod tests {
         let connect_parsed_json: InviteCode = serde_json::from_str(&json).unwrap();
         assert_eq!(connect_parsed_json, connect_parsed);
     }
+
+    #[test]
+    fn measures_session_durations() {
+        assert_eq!(ConsensusMeasurement::from_durations(vec![]), None);
+
+        let durations = (1..=100).rev().map(Duration::from_millis);
+        assert_eq!(
+            ConsensusMeasurement::from_durations(durations),
+            Some(ConsensusMeasurement {
+                min_ms: 1,
+                max_ms: 100,
+                median_ms: 50,
+                p99_ms: 99,
+            })
+        );
+
+        let single = ConsensusMeasurement::from_durations(vec![Duration::from_secs(1)]);
+        assert_eq!(single.map(|m| (m.min_ms, m.p99_ms)), Some((1000, 1000)));
+    }
 }
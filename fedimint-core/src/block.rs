@@ -1,9 +1,12 @@
+use std::collections::BTreeMap;
+
 use bitcoin30::hashes::{sha256, Hash};
 use parity_scale_codec::{Decode, Encode};
+use secp256k1_zkp::schnorr;
 
 use crate::encoding::{Decodable, Encodable};
 use crate::epoch::ConsensusItem;
-use crate::PeerId;
+use crate::{PeerId, TransactionId};
 
 /// If two correct nodes obtain two ordered items from the broadcast they
 /// are guaranteed to be in the same order. However, an ordered items is
@@ -25,26 +28,191 @@ pub struct Block {
     pub items: Vec<AcceptedItem>,
 }
 
+impl AcceptedItem {
+    /// The digest of the item that forms its leaf in the merkle tree of its
+    /// block. A transaction commits to its outputs through a merkle tree of
+    /// their own, see [`transaction_item_digest`], so a single output can be
+    /// proven to be part of a block without revealing the rest of the
+    /// transaction.
+    pub fn merkle_digest(&self) -> sha256::Hash {
+        match &self.item {
+            ConsensusItem::Transaction(tx) => transaction_item_digest(
+                self.peer,
+                tx.tx_hash(),
+                &tx.signature,
+                merkle_root(tx.outputs.iter()),
+            ),
+            _ => tagged_hash(ACCEPTED_ITEM_TAG, self),
+        }
+    }
+}
+
 impl Block {
     /// A blocks header consists of 40 bytes formed by its index in big endian
-    /// bytes concatenated with the merkle root build from the consensus
-    /// hashes of its [AcceptedItem]s or 32 zero bytes if the block is
-    /// empty. The use of a merkle tree allows for efficient inclusion
-    /// proofs of accepted consensus items for clients.
+    /// bytes concatenated with its [`Block::merkle_root`]. The use of a merkle
+    /// tree allows for efficient inclusion proofs of accepted consensus items
+    /// for clients.
     pub fn header(&self, index: u64) -> [u8; 40] {
         let mut header = [0; 40];
 
         header[..8].copy_from_slice(&index.to_be_bytes());
 
-        let leaf_hashes = self.items.iter().map(consensus_hash_sha256);
-
-        // TODO: extract merkle tree calculation and remove bitcoin dep
-        if let Some(root) = bitcoin30::merkle_tree::calculate_root(leaf_hashes) {
-            header[8..].copy_from_slice(&root.to_byte_array());
-        }
+        header[8..].copy_from_slice(&self.merkle_root());
 
         header
     }
+
+    /// The merkle root built from the [`AcceptedItem::merkle_digest`]s of the
+    /// block's items or 32 zero bytes if the block is empty
+    pub fn merkle_root(&self) -> [u8; 32] {
+        merkle_tree_root(self.item_digests())
+    }
+
+    /// Builds the merkle path from the item at `item_index` to the merkle
+    /// root of the header, returns `None` if there is no such item
+    ///
+    /// The path lists the sibling hashes from the leaf up, verify it with
+    /// [`merkle_root_from_path`].
+    pub fn inclusion_proof(&self, item_index: usize) -> Option<Vec<[u8; 32]>> {
+        merkle_tree_path(self.item_digests(), item_index)
+    }
+
+    fn item_digests(&self) -> Vec<sha256::Hash> {
+        self.items.iter().map(AcceptedItem::merkle_digest).collect()
+    }
+}
+
+/// The digest of an accepted transaction by `peer`, committing to the
+/// `outputs_root` built with [`merkle_root`] from its outputs separately from
+/// the rest of the transaction
+pub fn transaction_item_digest(
+    peer: PeerId,
+    txid: TransactionId,
+    signature: &Option<schnorr::Signature>,
+    outputs_root: [u8; 32],
+) -> sha256::Hash {
+    tagged_hash(
+        TRANSACTION_ITEM_TAG,
+        &(peer, txid, signature.clone(), outputs_root),
+    )
+}
+
+/// The merkle root built from the consensus hashes of `leaves` or 32 zero
+/// bytes if there are none
+pub fn merkle_root<'a, E: Encodable + 'a>(leaves: impl Iterator<Item = &'a E>) -> [u8; 32] {
+    merkle_tree_root(leaves.map(consensus_hash_sha256).collect())
+}
+
+/// Builds the merkle path from the leaf at `index` to the [`merkle_root`] of
+/// `leaves`, returns `None` if there is no such leaf
+pub fn merkle_path<'a, E: Encodable + 'a>(
+    leaves: impl Iterator<Item = &'a E>,
+    index: usize,
+) -> Option<Vec<[u8; 32]>> {
+    merkle_tree_path(leaves.map(consensus_hash_sha256).collect(), index)
+}
+
+/// Computes a merkle root from the `digest` of one of its leaves, the leaf's
+/// index and the path returned by [`Block::inclusion_proof`] or
+/// [`merkle_path`]. For a block the digest is the item's
+/// [`AcceptedItem::merkle_digest`] and the item is included in the block if
+/// the result matches the last 32 bytes of the block's header.
+///
+/// Since leaves commit to their index a path only leads to the root for the
+/// index it was built for.
+pub fn merkle_root_from_path(digest: sha256::Hash, index: u64, path: &[[u8; 32]]) -> [u8; 32] {
+    let mut hash = merkle_leaf(index, digest);
+    let mut index = index;
+    for sibling in path {
+        let sibling = sha256::Hash::from_byte_array(*sibling);
+        hash = if index % 2 == 0 {
+            merkle_node(&hash, &sibling)
+        } else {
+            merkle_node(&sibling, &hash)
+        };
+        index /= 2;
+    }
+
+    hash.to_byte_array()
+}
+
+/// Domain tags that keep the leaves and inner nodes of our merkle trees as
+/// well as the digests of different kinds of accepted items apart
+const MERKLE_LEAF_TAG: &[u8] = b"fedimint-merkle-leaf";
+const MERKLE_NODE_TAG: &[u8] = b"fedimint-merkle-node";
+const ACCEPTED_ITEM_TAG: &[u8] = b"fedimint-accepted-item";
+const TRANSACTION_ITEM_TAG: &[u8] = b"fedimint-transaction-item";
+
+fn merkle_tree_root(digests: Vec<sha256::Hash>) -> [u8; 32] {
+    let mut level = merkle_leaves(digests);
+    if level.is_empty() {
+        return [0; 32];
+    }
+
+    while level.len() > 1 {
+        level = merkle_parents(&level);
+    }
+
+    level[0].to_byte_array()
+}
+
+fn merkle_tree_path(digests: Vec<sha256::Hash>, index: usize) -> Option<Vec<[u8; 32]>> {
+    if index >= digests.len() {
+        return None;
+    }
+
+    let mut level = merkle_leaves(digests);
+    let mut index = index;
+    let mut path = vec![];
+
+    while level.len() > 1 {
+        // like bitcoin the last hash of a level with odd length is paired with
+        // itself, but the leaves commit to their index so the duplicate can't be
+        // passed off as another leaf
+        let sibling = level.get(index ^ 1).unwrap_or(&level[index]);
+        path.push(sibling.to_byte_array());
+
+        level = merkle_parents(&level);
+        index /= 2;
+    }
+
+    Some(path)
+}
+
+fn merkle_leaves(digests: Vec<sha256::Hash>) -> Vec<sha256::Hash> {
+    digests
+        .into_iter()
+        .enumerate()
+        .map(|(index, digest)| merkle_leaf(index as u64, digest))
+        .collect()
+}
+
+fn merkle_parents(level: &[sha256::Hash]) -> Vec<sha256::Hash> {
+    level
+        .chunks(2)
+        .map(|pair| merkle_node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+fn merkle_leaf(index: u64, digest: sha256::Hash) -> sha256::Hash {
+    tagged_hash(MERKLE_LEAF_TAG, &(index, digest.to_byte_array()))
+}
+
+fn merkle_node(left: &sha256::Hash, right: &sha256::Hash) -> sha256::Hash {
+    tagged_hash(
+        MERKLE_NODE_TAG,
+        &(left.to_byte_array(), right.to_byte_array()),
+    )
+}
+
+fn tagged_hash<E: Encodable>(tag: &[u8], encodable: &E) -> sha256::Hash {
+    let mut engine = sha256::HashEngine::default();
+    tag.consensus_encode(&mut engine)
+        .expect("Writing to HashEngine cannot fail");
+    encodable
+        .consensus_encode(&mut engine)
+        .expect("Writing to HashEngine cannot fail");
+    sha256::Hash::from_engine(engine)
 }
 
 #[derive(Clone, Debug, Encodable, Decodable, Encode, Decode, PartialEq, Eq, Hash)]
@@ -60,6 +228,66 @@ pub struct SignedBlock {
     pub signatures: std::collections::BTreeMap<PeerId, SchnorrSignature>,
 }
 
+/// A single guardian's commitment to the block of an epoch, where an epoch
+/// corresponds to an AlephBFT session and is identified by the index of the
+/// block it produced. Unlike a [SignedBlock], which only contains a threshold
+/// of signatures, every guardian can produce a commitment for every block it
+/// has stored.
+#[derive(Clone, Debug, Encodable, Decodable, Eq, PartialEq)]
+pub struct EpochCommitment {
+    pub peer_id: PeerId,
+    /// The sha256 hash of the block's header
+    pub hash: bitcoin_hashes::sha256::Hash,
+    /// The guardian's signature over the block's header
+    pub partial_signature: SchnorrSignature,
+}
+
+impl EpochCommitment {
+    /// Returns true if the commitment is for `header` and carries a valid
+    /// signature by the guardian it claims to be from, where
+    /// `broadcast_public_keys` are the guardians' keys from the client config
+    pub fn verify(
+        &self,
+        header: &[u8],
+        broadcast_public_keys: &BTreeMap<PeerId, secp256k1_zkp::PublicKey>,
+    ) -> bool {
+        let Some(public_key) = broadcast_public_keys.get(&self.peer_id) else {
+            return false;
+        };
+        let Ok(signature) =
+            secp256k1_zkp::schnorr::Signature::from_slice(&self.partial_signature.0)
+        else {
+            return false;
+        };
+
+        self.hash == <bitcoin_hashes::sha256::Hash as bitcoin_hashes::Hash>::hash(header)
+            && secp256k1_zkp::SECP256K1
+                .verify_schnorr(
+                    &signature,
+                    &broadcast_message(broadcast_public_keys, header),
+                    &public_key.x_only_public_key().0,
+                )
+                .is_ok()
+    }
+}
+
+/// The message guardians sign with their broadcast keys for `message`. It is
+/// tagged with all of the guardians' keys so signatures can't be reused across
+/// federations.
+pub fn broadcast_message(
+    broadcast_public_keys: &BTreeMap<PeerId, secp256k1_zkp::PublicKey>,
+    message: &[u8],
+) -> secp256k1_zkp::Message {
+    use secp256k1_zkp::hashes::{sha256, Hash, HashEngine};
+
+    let public_key_tag = consensus_hash_sha256(broadcast_public_keys);
+    let mut engine = sha256::HashEngine::default();
+    engine.input(public_key_tag.as_ref());
+    engine.input(message);
+
+    secp256k1_zkp::Message::from(sha256::Hash::from_engine(engine))
+}
+
 // TODO: remove this as soon as we bump bitcoin_hashes in fedimint_core to
 // 0.12.0
 pub fn consensus_hash_sha256<E: Encodable>(encodable: &E) -> sha256::Hash {
@@ -69,3 +297,55 @@ pub fn consensus_hash_sha256<E: Encodable>(encodable: &E) -> sha256::Hash {
         .expect("Writing to HashEngine cannot fail");
     sha256::Hash::from_engine(engine)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{merkle_root_from_path, AcceptedItem, Block};
+    use crate::epoch::ConsensusItem;
+    use crate::transaction::Transaction;
+    use crate::PeerId;
+
+    fn block(num_items: usize) -> Block {
+        let items = (0..num_items)
+            .map(|peer| AcceptedItem {
+                item: ConsensusItem::Transaction(Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    signature: None,
+                }),
+                peer: PeerId::from(peer as u16),
+            })
+            .collect();
+
+        Block { items }
+    }
+
+    #[test]
+    fn inclusion_proofs_lead_to_header_merkle_root() {
+        for num_items in 1..=9 {
+            let block = block(num_items);
+            let root = &block.header(7)[8..];
+
+            for (index, item) in block.items.iter().enumerate() {
+                let path = block.inclusion_proof(index).expect("Item exists");
+                let digest = item.merkle_digest();
+                assert_eq!(merkle_root_from_path(digest, index as u64, &path), root);
+
+                if num_items > 1 {
+                    let other_item = &block.items[(index + 1) % num_items];
+                    let other_digest = other_item.merkle_digest();
+                    assert_ne!(
+                        merkle_root_from_path(other_digest, index as u64, &path),
+                        root
+                    );
+                }
+
+                // the last item of a level with odd length is paired with itself,
+                // which must not let its path pass for the position of its duplicate
+                assert_ne!(merkle_root_from_path(digest, index as u64 ^ 1, &path), root);
+            }
+
+            assert_eq!(block.inclusion_proof(num_items), None);
+        }
+    }
+}
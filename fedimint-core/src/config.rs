@@ -154,6 +154,10 @@ pub struct GlobalClientConfig {
     pub api_endpoints: BTreeMap<PeerId, PeerUrl>,
     /// Threshold pubkey for authenticating epoch history
     pub epoch_pk: threshold_crypto::PublicKey,
+    /// Broadcast pubkey of each federation member for authenticating their
+    /// individual epoch commitments
+    #[serde(deserialize_with = "de_int_key")]
+    pub broadcast_public_keys: BTreeMap<PeerId, secp256k1_zkp::PublicKey>,
     /// Core consensus version
     pub consensus_version: CoreConsensusVersion,
     // TODO: make it a String -> serde_json::Value map?
@@ -344,6 +348,17 @@ impl ClientConfig {
     pub fn federation_name(&self) -> Option<&str> {
         self.global.meta.get(META_FEDERATION_NAME_KEY).map(|x| &**x)
     }
+
+    /// Constitution text and version from config metadata (if set)
+    pub fn constitution(&self) -> Option<(&str, u32)> {
+        let text = self.global.meta.get(META_CONSTITUTION_KEY)?;
+        let version = match self.global.meta.get(META_CONSTITUTION_VERSION_KEY) {
+            Some(version) => version.parse().ok()?,
+            None => 0,
+        };
+
+        Some((text, version))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -897,6 +912,15 @@ impl SGroup for G1Projective {
 /// of the config
 pub const META_FEDERATION_NAME_KEY: &str = "federation_name";
 
+/// Key under which the constitution, the rules and policies the guardians
+/// agreed on during setup, can be sent to clients in the `meta` part of the
+/// config
+pub const META_CONSTITUTION_KEY: &str = "constitution";
+
+/// Key under which the version of the constitution can be sent to clients in
+/// the `meta` part of the config, it defaults to 0 if it is not set
+pub const META_CONSTITUTION_VERSION_KEY: &str = "constitution_version";
+
 pub fn load_from_file<T: DeserializeOwned>(path: &Path) -> Result<T, anyhow::Error> {
     let file = std::fs::File::open(path)?;
     Ok(serde_json::from_reader(file)?)
@@ -94,6 +94,10 @@ pub trait IServerModule: Debug {
         module_instance_id: ModuleInstanceId,
     );
 
+    /// Called once the consensus session with the given index is complete, as
+    /// part of the database transaction that stores its signed block.
+    async fn complete_session(&self, dbtx: &mut ModuleDatabaseTransaction<'_>, session_index: u64);
+
     /// Returns a list of custom API endpoints defined by the module. These are
     /// made available both to users as well as to other modules. They thus
     /// should be deterministic, only dependant on their input and the
@@ -230,6 +234,12 @@ where
         <Self as ServerModule>::audit(self, dbtx, audit, module_instance_id).await
     }
 
+    /// Called once the consensus session with the given index is complete, as
+    /// part of the database transaction that stores its signed block.
+    async fn complete_session(&self, dbtx: &mut ModuleDatabaseTransaction<'_>, session_index: u64) {
+        <Self as ServerModule>::complete_session(self, dbtx, session_index).await
+    }
+
     fn api_endpoints(&self) -> Vec<ApiEndpoint<DynServerModule>> {
         <Self as ServerModule>::api_endpoints(self)
             .into_iter()
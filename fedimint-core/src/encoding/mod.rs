@@ -99,6 +99,22 @@ pub trait Encodable {
             .expect("writing to HashEngine cannot fail");
         H::from_engine(engine)
     }
+
+    /// Like [`Encodable::consensus_hash`], but prefixes the encoding with a
+    /// domain `tag` so that signatures over hashes of different types can't be
+    /// confused with each other
+    fn consensus_hash_tagged<H>(&self, tag: &[u8]) -> H
+    where
+        H: bitcoin_hashes::Hash,
+        H::Engine: std::io::Write,
+    {
+        let mut engine = H::engine();
+        tag.consensus_encode(&mut engine)
+            .expect("writing to HashEngine cannot fail");
+        self.consensus_encode(&mut engine)
+            .expect("writing to HashEngine cannot fail");
+        H::from_engine(engine)
+    }
 }
 
 /// Data which can be encoded in a consensus-consistent way
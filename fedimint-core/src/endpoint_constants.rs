@@ -1,25 +1,43 @@
 pub const ACCOUNT_ENDPOINT: &str = "account";
 pub const ADD_CONFIG_GEN_PEER_ENDPOINT: &str = "add_config_gen_peer";
+pub const ADDRESS_PROOF_SIGNATURE_ENDPOINT: &str = "address_proof_signature";
 pub const AUDIT_ENDPOINT: &str = "audit";
 pub const AUTH_ENDPOINT: &str = "auth";
 pub const AWAIT_OUTPUT_OUTCOME_ENDPOINT: &str = "await_output_outcome";
+pub const AVERAGE_SESSION_DURATION_ENDPOINT: &str = "average_session_duration";
 pub const BACKUP_ENDPOINT: &str = "backup";
+pub const BALANCE_SHEET_ENDPOINT: &str = "balance_sheet";
 pub const BLOCK_COUNT_ENDPOINT: &str = "block_count";
 pub const BLOCK_COUNT_LOCAL_ENDPOINT: &str = "block_count_local";
+pub const COMPACT_FILTER_ENDPOINT: &str = "compact_filter";
 pub const CONFIG_ENDPOINT: &str = "config";
+pub const CONFIG_DIFF_ENDPOINT: &str = "config_diff";
 pub const CONFIG_HASH_ENDPOINT: &str = "config_hash";
+pub const CONSTITUTION_ENDPOINT: &str = "constitution";
+pub const CONSENSUS_ROUND_TRIP_ENDPOINT: &str = "consensus_round_trip";
+pub const EPOCH_COMMITMENT_ENDPOINT: &str = "epoch_commitment";
+pub const EPOCH_METRICS_ENDPOINT: &str = "epoch_metrics";
+pub const EXCHANGE_RATE_ENDPOINT: &str = "exchange_rate";
+pub const FEDERATION_STATS_ENDPOINT: &str = "federation_stats";
+pub const FEE_RATE_VOTE_HISTORY_ENDPOINT: &str = "fee_rate_vote_history";
 pub const FETCH_BLOCK_COUNT_ENDPOINT: &str = "fetch_block_count";
 pub const AWAIT_BLOCK_ENDPOINT: &str = "await_block";
 pub const AWAIT_SIGNED_BLOCK_ENDPOINT: &str = "await_signed_block";
+pub const AWAIT_TRANSACTION_STATUS_ENDPOINT: &str = "await_transaction_status";
 pub const GET_CONFIG_GEN_PEERS_ENDPOINT: &str = "get_config_gen_peers";
 pub const GET_CONSENSUS_CONFIG_GEN_PARAMS_ENDPOINT: &str = "get_consensus_config_gen_params";
 pub const GET_DEFAULT_CONFIG_GEN_PARAMS_ENDPOINT: &str = "get_default_config_gen_params";
 pub const GET_VERIFY_CONFIG_HASH_ENDPOINT: &str = "get_verify_config_hash";
 pub const INVITE_CODE_ENDPOINT: &str = "invite_code";
 pub const LIST_GATEWAYS_ENDPOINT: &str = "list_gateways";
+pub const MODULE_AUDIT_ENDPOINT: &str = "module_audit";
 pub const MODULES_CONFIG_JSON_ENDPOINT: &str = "modules_config_json";
+pub const NODE_INFO_ENDPOINT: &str = "node_info";
 pub const OFFER_ENDPOINT: &str = "offer";
+pub const POST_EXCHANGE_RATE_ENDPOINT: &str = "post_exchange_rate";
 pub const PEG_OUT_FEES_ENDPOINT: &str = "peg_out_fees";
+pub const PENDING_PEG_OUTS_ENDPOINT: &str = "pending_peg_outs";
+pub const RBF_CAPABLE_PEG_OUTS_ENDPOINT: &str = "rbf_capable_peg_outs";
 pub const RECOVER_ENDPOINT: &str = "recover";
 pub const REGISTER_GATEWAY_ENDPOINT: &str = "register_gateway";
 pub const RUN_DKG_ENDPOINT: &str = "run_dkg";
@@ -30,8 +48,10 @@ pub const SIGN_MESSAGE_ENDPOINT: &str = "sign_message";
 pub const START_CONSENSUS_ENDPOINT: &str = "start_consensus";
 pub const STATUS_ENDPOINT: &str = "status";
 pub const TRANSACTION_ENDPOINT: &str = "transaction";
+pub const VALIDATE_TRANSACTION_ENDPOINT: &str = "validate_transaction";
 pub const VERIFIED_CONFIGS_ENDPOINT: &str = "verified_configs";
 pub const VERSION_ENDPOINT: &str = "version";
+pub const WALLET_CONFIG_ENDPOINT: &str = "wallet_config";
 pub const WAIT_ACCOUNT_ENDPOINT: &str = "wait_account";
 pub const WAIT_BLOCK_HEIGHT_ENDPOINT: &str = "wait_block_height";
 pub const WAIT_OUTGOING_CONTRACT_CANCELLED_ENDPOINT: &str = "wait_outgoing_contract_cancelled";
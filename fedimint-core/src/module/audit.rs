@@ -1,12 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 
+use anyhow::ensure;
+use bitcoin_hashes::sha256;
 use fedimint_core::core::ModuleInstanceId;
 use futures::StreamExt;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
+use crate::block::merkle_root;
 use crate::db::{DatabaseKey, DatabaseLookup, DatabaseRecord, ModuleDatabaseTransaction};
+use crate::encoding::{Decodable, Encodable};
+use crate::epoch::{SerdeSignature, SerdeSignatureShare};
 
 #[derive(Default)]
 pub struct Audit {
@@ -61,6 +66,7 @@ impl Display for Audit {
     }
 }
 
+#[derive(Clone, Debug, Encodable, Decodable)]
 pub struct AuditItem {
     pub name: String,
     pub milli_sat: i64,
@@ -105,6 +111,181 @@ impl AuditSummary {
     }
 }
 
+/// Sum of the positive and negative audit items of a module, e.g. the
+/// redeemed and issued e-cash of the mint or the held bitcoin and pending
+/// peg-outs of the wallet
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct ModuleBalance {
+    pub kind: String,
+    pub assets_msat: u64,
+    pub liabilities_msat: u64,
+}
+
+impl ModuleBalance {
+    pub fn net_assets(&self) -> i64 {
+        self.assets_msat as i64 - self.liabilities_msat as i64
+    }
+
+    fn add_item(&mut self, item: &AuditItem) {
+        if item.milli_sat >= 0 {
+            self.assets_msat += item.milli_sat as u64;
+        } else {
+            self.liabilities_msat += item.milli_sat.unsigned_abs();
+        }
+    }
+}
+
+/// Balances of all modules of the federation that the guardians can sign to
+/// prove to third parties that the federation is fully reserved
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct BalanceSheet {
+    pub modules: BTreeMap<ModuleInstanceId, ModuleBalance>,
+}
+
+impl BalanceSheet {
+    pub fn from_audit(
+        audit: &Audit,
+        module_instance_id_to_kind: &HashMap<ModuleInstanceId, String>,
+    ) -> Self {
+        let mut modules = module_instance_id_to_kind
+            .iter()
+            .map(|(id, kind)| {
+                (
+                    *id,
+                    ModuleBalance {
+                        kind: kind.clone(),
+                        assets_msat: 0,
+                        liabilities_msat: 0,
+                    },
+                )
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        for item in &audit.items {
+            let Some(balance) = item.module_instance_id.and_then(|id| modules.get_mut(&id)) else {
+                continue;
+            };
+
+            balance.add_item(item);
+        }
+
+        BalanceSheet { modules }
+    }
+
+    pub fn net_assets(&self) -> i64 {
+        self.modules.values().map(ModuleBalance::net_assets).sum()
+    }
+
+    /// The message the guardians sign with their epoch keys
+    pub fn message(&self) -> sha256::Hash {
+        self.consensus_hash_tagged(BALANCE_SHEET_SIGNATURE_TAG)
+    }
+}
+
+/// Domain tag of the signed [`BalanceSheet`] message
+const BALANCE_SHEET_SIGNATURE_TAG: &[u8] = b"fedimint-balance-sheet";
+
+/// A balance sheet signed by a single guardian, together with the public key
+/// set the signature share can be combined with
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BalanceSheetShare {
+    pub balance_sheet: BalanceSheet,
+    pub epoch_pk_set: threshold_crypto::PublicKeySet,
+    pub signature_share: SerdeSignatureShare,
+}
+
+/// A balance sheet signed by a threshold of guardians
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SignedBalanceSheet {
+    pub balance_sheet: BalanceSheet,
+    pub signature: SerdeSignature,
+}
+
+impl SignedBalanceSheet {
+    /// Verifies the signature against the threshold public key of the
+    /// federation's epoch keys found in the client config
+    pub fn verify(&self, epoch_pk: &threshold_crypto::PublicKey) -> anyhow::Result<()> {
+        ensure!(
+            epoch_pk.verify(&self.signature.0, self.balance_sheet.message()),
+            "Invalid balance sheet signature"
+        );
+        Ok(())
+    }
+}
+
+/// The balance of a single module together with a merkle root committing to
+/// all of its audit items, e.g. every UTXO held by the wallet, that the
+/// guardians can sign to prove the module's solvency to third parties
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub struct ModuleAudit {
+    pub module_instance_id: ModuleInstanceId,
+    pub balance: ModuleBalance,
+    /// Merkle root of the consensus hashes of the module's audit items in the
+    /// order the module reported them
+    pub merkle_root: [u8; 32],
+}
+
+impl ModuleAudit {
+    pub fn from_audit(audit: &Audit, module_instance_id: ModuleInstanceId, kind: String) -> Self {
+        let items = audit
+            .items
+            .iter()
+            .filter(|item| item.module_instance_id == Some(module_instance_id))
+            .collect::<Vec<_>>();
+
+        let mut balance = ModuleBalance {
+            kind,
+            assets_msat: 0,
+            liabilities_msat: 0,
+        };
+        for item in &items {
+            balance.add_item(item);
+        }
+
+        ModuleAudit {
+            module_instance_id,
+            balance,
+            merkle_root: merkle_root(items.into_iter()),
+        }
+    }
+
+    /// The message the guardians sign with their epoch keys
+    pub fn message(&self) -> sha256::Hash {
+        self.consensus_hash_tagged(MODULE_AUDIT_SIGNATURE_TAG)
+    }
+}
+
+/// Domain tag of the signed [`ModuleAudit`] message
+const MODULE_AUDIT_SIGNATURE_TAG: &[u8] = b"fedimint-module-audit";
+
+/// A module audit signed by a single guardian, together with the public key
+/// set the signature share can be combined with
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ModuleAuditShare {
+    pub module_audit: ModuleAudit,
+    pub epoch_pk_set: threshold_crypto::PublicKeySet,
+    pub signature_share: SerdeSignatureShare,
+}
+
+/// A module audit signed by a threshold of guardians
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ModuleAuditProof {
+    pub module_audit: ModuleAudit,
+    pub signature: SerdeSignature,
+}
+
+impl ModuleAuditProof {
+    /// Verifies the signature against the threshold public key of the
+    /// federation's epoch keys found in the client config
+    pub fn verify(&self, epoch_pk: &threshold_crypto::PublicKey) -> anyhow::Result<()> {
+        ensure!(
+            epoch_pk.verify(&self.signature.0, self.module_audit.message()),
+            "Invalid module audit signature"
+        );
+        Ok(())
+    }
+}
+
 fn generate_module_summaries<'a>(
     audit_items: impl Iterator<Item = &'a AuditItem>,
     module_instance_id_to_kind: &HashMap<ModuleInstanceId, String>,
@@ -148,6 +329,30 @@ fn create_empty_module_placeholder(module_instance_id: ModuleInstanceId) -> Audi
     }
 }
 
+#[test]
+fn signed_audit_messages_are_domain_separated() {
+    let balance = ModuleBalance {
+        kind: "dummy".to_string(),
+        assets_msat: 0,
+        liabilities_msat: 0,
+    };
+    let module_audit = ModuleAudit {
+        module_instance_id: 0,
+        balance: balance.clone(),
+        merkle_root: [0; 32],
+    };
+    let balance_sheet = BalanceSheet {
+        modules: BTreeMap::from([(0, balance)]),
+    };
+
+    assert_ne!(module_audit.message(), module_audit.consensus_hash());
+    assert_ne!(balance_sheet.message(), balance_sheet.consensus_hash());
+    assert_ne!(
+        module_audit.message(),
+        module_audit.consensus_hash_tagged(BALANCE_SHEET_SIGNATURE_TAG)
+    );
+}
+
 #[test]
 fn creates_audit_summary_from_audit() {
     let audit = Audit {
@@ -144,6 +144,10 @@ impl ApiError {
         Self::new(400, message)
     }
 
+    pub fn too_many_requests(message: String) -> Self {
+        Self::new(429, message)
+    }
+
     pub fn unauthorized() -> Self {
         Self::new(401, "Invalid authorization".to_string())
     }
@@ -854,6 +858,16 @@ pub trait ServerModule: Debug + Sized {
         module_instance_id: ModuleInstanceId,
     );
 
+    /// Called once the consensus session with the given index is complete, as
+    /// part of the database transaction that stores its signed block. Modules
+    /// can use this to keep track of the federation's epochs.
+    async fn complete_session(
+        &self,
+        _dbtx: &mut ModuleDatabaseTransaction<'_>,
+        _session_index: u64,
+    ) {
+    }
+
     /// Returns a list of custom API endpoints defined by the module. These are
     /// made available both to users as well as to other modules. They thus
     /// should be deterministic, only dependant on their input and the
@@ -178,4 +178,6 @@ pub enum TransactionError {
     },
     #[error("The transaction did not have a signature although there were inputs to be signed")]
     MissingSignature,
+    #[error("The mempool is full, try again later")]
+    MempoolFull,
 }
@@ -5,6 +5,7 @@ use anyhow::Context;
 use erased_serde::Serialize;
 use fedimint_client::db::ClientConfigKeyPrefix;
 use fedimint_client::module::init::ClientModuleInitRegistry;
+use fedimint_core::api::ExchangeRate;
 use fedimint_core::config::{ClientConfig, CommonModuleInitRegistry, ServerModuleInitRegistry};
 use fedimint_core::core::ModuleKind;
 use fedimint_core::db::notifications::Notifications;
@@ -332,6 +333,36 @@ impl<'a> DatabaseDump<'a> {
                         "Client Config Download"
                     );
                 }
+                ConsensusRange::DbKeyPrefix::ConfigVersion => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusRange::ConfigVersionPrefix,
+                        ConsensusRange::ConfigVersionKey,
+                        String,
+                        consensus,
+                        "Config Versions"
+                    );
+                }
+                ConsensusRange::DbKeyPrefix::ExchangeRate => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusRange::ExchangeRatePrefix,
+                        ConsensusRange::ExchangeRateKey,
+                        ExchangeRate,
+                        consensus,
+                        "Exchange Rates"
+                    );
+                }
+                ConsensusRange::DbKeyPrefix::RejectedTransaction => {
+                    push_db_pair_items!(
+                        dbtx,
+                        ConsensusRange::RejectedTransactionPrefix,
+                        ConsensusRange::RejectedTransactionKey,
+                        String,
+                        consensus,
+                        "Rejected Transactions"
+                    );
+                }
                 // Module is a global prefix for all module data
                 ConsensusRange::DbKeyPrefix::Module => {}
             }
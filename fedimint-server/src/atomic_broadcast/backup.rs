@@ -1,7 +1,10 @@
 use std::io::Cursor;
 
 use fedimint_core::db::Database;
+use fedimint_core::fmt_utils::OptStacktrace;
+use tracing::warn;
 
+use crate::consensus::CommitBackoff;
 use crate::db::AlephUnitsKey;
 use crate::LOG_CONSENSUS;
 
@@ -59,14 +62,28 @@ impl std::io::Write for UnitSaver {
 
     fn flush(&mut self) -> std::io::Result<()> {
         futures::executor::block_on(async {
-            let mut dbtx = self.db.begin_transaction().await;
-
-            dbtx.insert_new_entry(&AlephUnitsKey(self.units_index), &self.buffer)
-                .await;
-
-            dbtx.commit_tx_result()
-                .await
-                .expect("This is the only place where we write to this key");
+            // a failed commit does not write the key, so we can retry it
+            let mut backoff = CommitBackoff::new();
+            loop {
+                let mut dbtx = self.db.begin_transaction().await;
+
+                dbtx.insert_new_entry(&AlephUnitsKey(self.units_index), &self.buffer)
+                    .await;
+
+                match dbtx.commit_tx_result().await {
+                    Ok(()) => break,
+                    Err(e) => {
+                        warn!(
+                            target: LOG_CONSENSUS,
+                            units_index = self.units_index,
+                            "Committing aleph unit failed, retrying: {}",
+                            OptStacktrace(e)
+                        );
+                        // aleph bft expects the write to be blocking
+                        std::thread::sleep(backoff.next_delay("aleph unit"));
+                    }
+                }
+            }
         });
 
         self.buffer.clear();
@@ -1,10 +1,8 @@
 use std::collections::BTreeMap;
-use std::io::Write;
 
 use aleph_bft::Keychain as KeychainTrait;
-use fedimint_core::block::{consensus_hash_sha256, SchnorrSignature};
-use fedimint_core::{BitcoinHash, PeerId};
-use secp256k1_zkp::hashes::sha256;
+use fedimint_core::block::{broadcast_message, SchnorrSignature};
+use fedimint_core::PeerId;
 use secp256k1_zkp::{schnorr, All, KeyPair, Message, PublicKey, Secp256k1, SecretKey};
 
 #[derive(Clone, Debug)]
@@ -44,21 +42,25 @@ impl Keychain {
         (2 * self.peer_count()) / 3 + 1
     }
 
-    fn tagged_hash(&self, message: &[u8]) -> Message {
-        let public_key_tag = consensus_hash_sha256(&self.public_keys);
-        let mut engine = sha256::HashEngine::default();
-
-        engine
-            .write_all(public_key_tag.as_ref())
-            .expect("Writing to a hash engine can not fail");
-
-        engine
-            .write_all(message)
-            .expect("Writing to a hash engine can not fail");
+    /// Signs `message` as our guardian, the counterpart to
+    /// [`Self::verify_peer_signature`]
+    pub fn sign_peer_message(&self, message: &[u8]) -> SchnorrSignature {
+        KeychainTrait::sign(self, message)
+    }
 
-        let hash = sha256::Hash::from_engine(engine);
+    /// Verifies that `signature` was created by the guardian `peer_id` for
+    /// `message`
+    pub fn verify_peer_signature(
+        &self,
+        message: &[u8],
+        signature: &SchnorrSignature,
+        peer_id: PeerId,
+    ) -> bool {
+        KeychainTrait::verify(self, message, signature, super::to_node_index(peer_id))
+    }
 
-        Message::from(hash)
+    fn tagged_hash(&self, message: &[u8]) -> Message {
+        broadcast_message(&self.public_keys, message)
     }
 }
 
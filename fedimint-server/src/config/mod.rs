@@ -45,6 +45,7 @@ use crate::{ReconnectPeerConnections, TlsTcpConnector};
 pub mod api;
 pub mod distributedgen;
 pub mod io;
+pub mod versions;
 
 /// The default maximum open connections the API can handle
 const DEFAULT_MAX_CLIENT_CONNECTIONS: u32 = 1000;
@@ -192,6 +193,7 @@ impl ServerConfigConsensus {
             global: GlobalClientConfig {
                 federation_id: self.federation_id(),
                 epoch_pk: self.epoch_pk_set.public_key(),
+                broadcast_public_keys: self.broadcast_public_keys.clone(),
                 api_endpoints: self.api_endpoints.clone(),
                 consensus_version: self.version,
                 meta: self.meta.clone(),
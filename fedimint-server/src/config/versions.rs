@@ -0,0 +1,113 @@
+//! Tracks how the module configs of the federation changed across restarts so
+//! clients can show a summary of the changes instead of silently updating
+
+use std::collections::BTreeMap;
+
+use fedimint_core::api::ConfigDiff;
+use fedimint_core::config::JsonWithKind;
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::db::DatabaseTransaction;
+use futures::StreamExt;
+
+use crate::db::{ConfigVersionKey, ConfigVersionPrefix};
+
+/// Stores `modules_json` as a new config version unless it matches the latest
+/// one, returns the current version
+pub async fn record_config_version(
+    dbtx: &mut DatabaseTransaction<'_>,
+    modules_json: &BTreeMap<ModuleInstanceId, JsonWithKind>,
+) -> u32 {
+    let json = serde_json::to_string(modules_json).expect("Config can be serialized");
+
+    let version = match latest_config_version(dbtx).await {
+        Some((version, latest)) if latest == json => return version,
+        Some((version, _)) => version + 1,
+        None => 0,
+    };
+
+    dbtx.insert_new_entry(&ConfigVersionKey(version), &json)
+        .await;
+
+    version
+}
+
+/// Summarizes the changes since `known_version`, returns `None` if it is the
+/// current version or was never recorded
+pub async fn get_config_diff(
+    dbtx: &mut DatabaseTransaction<'_>,
+    known_version: u32,
+) -> Option<ConfigDiff> {
+    let (new_version, new) = latest_config_version(dbtx).await?;
+    if known_version >= new_version {
+        return None;
+    }
+
+    let old = dbtx.get_value(&ConfigVersionKey(known_version)).await?;
+
+    Some(ConfigDiff::new(
+        known_version,
+        &serde_json::from_str(&old).expect("Stored config is valid JSON"),
+        new_version,
+        &serde_json::from_str(&new).expect("Stored config is valid JSON"),
+    ))
+}
+
+async fn latest_config_version(dbtx: &mut DatabaseTransaction<'_>) -> Option<(u32, String)> {
+    dbtx.find_by_prefix(&ConfigVersionPrefix)
+        .await
+        .map(|(ConfigVersionKey(version), json)| (version, json))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .max_by_key(|(version, _)| *version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::JsonWithKind;
+    use fedimint_core::core::{ModuleInstanceId, ModuleKind};
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::Database;
+    use serde_json::json;
+
+    use super::{get_config_diff, record_config_version};
+
+    fn dummy_config(tx_fee: u64) -> BTreeMap<ModuleInstanceId, JsonWithKind> {
+        BTreeMap::from([(
+            0,
+            JsonWithKind::new(
+                ModuleKind::from_static_str("dummy"),
+                json!({ "tx_fee": tx_fee, "public_key_set": "00" }),
+            ),
+        )])
+    }
+
+    #[tokio::test]
+    async fn config_diff_lists_changed_fee() {
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+
+        assert_eq!(
+            record_config_version(&mut dbtx, &dummy_config(1000)).await,
+            0
+        );
+        assert_eq!(
+            record_config_version(&mut dbtx, &dummy_config(1000)).await,
+            0
+        );
+        assert_eq!(get_config_diff(&mut dbtx, 0).await, None);
+
+        // the guardians restarted with higher consensus fees
+        assert_eq!(
+            record_config_version(&mut dbtx, &dummy_config(2000)).await,
+            1
+        );
+        let diff = get_config_diff(&mut dbtx, 0).await.expect("Config changed");
+        assert_eq!(diff.old_version, 0);
+        assert_eq!(diff.new_version, 1);
+        assert_eq!(diff.changed_fields, vec!["modules.0.tx_fee".to_string()]);
+        assert_eq!(get_config_diff(&mut dbtx, 1).await, None);
+    }
+}
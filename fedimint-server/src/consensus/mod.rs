@@ -3,6 +3,8 @@
 pub mod debug;
 pub mod server;
 
+use std::time::Duration;
+
 use fedimint_core::db::DatabaseTransaction;
 use fedimint_core::module::registry::ServerModuleRegistry;
 use fedimint_core::module::TransactionItemAmount;
@@ -90,3 +92,46 @@ impl Default for FundingVerifier {
         }
     }
 }
+
+/// Delays between attempts to re-run a database transaction whose commit
+/// failed, doubling from `COMMIT_RETRY_DELAY_MIN` to `COMMIT_RETRY_DELAY_MAX`
+#[derive(Debug)]
+pub struct CommitBackoff {
+    attempts: u32,
+    delay: Duration,
+}
+
+const COMMIT_RETRY_DELAY_MIN: Duration = Duration::from_millis(10);
+const COMMIT_RETRY_DELAY_MAX: Duration = Duration::from_secs(10);
+/// After this many failed commits we don't expect the storage layer to recover
+/// anymore, so we crash and leave recovery to the restart
+const MAX_COMMIT_ATTEMPTS: u32 = 20;
+
+impl CommitBackoff {
+    pub fn new() -> Self {
+        Self {
+            attempts: 0,
+            delay: COMMIT_RETRY_DELAY_MIN,
+        }
+    }
+
+    /// Returns how long to wait before the next attempt, panics once all
+    /// attempts are used up
+    pub fn next_delay(&mut self, what: &str) -> Duration {
+        self.attempts += 1;
+        assert!(
+            self.attempts < MAX_COMMIT_ATTEMPTS,
+            "Committing {what} failed {MAX_COMMIT_ATTEMPTS} times, giving up"
+        );
+
+        let delay = self.delay;
+        self.delay = (self.delay * 2).min(COMMIT_RETRY_DELAY_MAX);
+        delay
+    }
+}
+
+impl Default for CommitBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use aleph_bft::Keychain as KeychainTrait;
 use anyhow::{anyhow, bail};
@@ -9,6 +9,7 @@ use bitcoin_hashes::sha256;
 use fedimint_core::api::{FederationApiExt, GlobalFederationApi, WsFederationApi};
 use fedimint_core::block::{AcceptedItem, Block, SchnorrSignature, SignedBlock};
 use fedimint_core::config::ServerModuleInitRegistry;
+use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{apply_migrations, Database, DatabaseTransaction};
 use fedimint_core::encoding::Decodable;
 use fedimint_core::endpoint_constants::AWAIT_SIGNED_BLOCK_ENDPOINT;
@@ -22,7 +23,7 @@ use fedimint_core::module::{ApiRequestErased, SerdeModuleEncoding};
 use fedimint_core::query::FilterMap;
 use fedimint_core::task::{sleep, spawn, RwLock, TaskGroup, TaskHandle};
 use fedimint_core::util::SafeUrl;
-use fedimint_core::{timing, PeerId};
+use fedimint_core::{timing, PeerId, TransactionId};
 use futures::StreamExt;
 use tokio::sync::watch;
 use tracing::{debug, info, warn};
@@ -32,20 +33,23 @@ use crate::atomic_broadcast::finalization_handler::FinalizationHandler;
 use crate::atomic_broadcast::network::Network;
 use crate::atomic_broadcast::spawner::Spawner;
 use crate::atomic_broadcast::{to_node_index, Keychain, Message};
+use crate::config::versions::record_config_version;
 use crate::config::ServerConfig;
-use crate::consensus::process_transaction_with_dbtx;
+use crate::consensus::{process_transaction_with_dbtx, CommitBackoff};
 use crate::db::{
     get_global_database_migrations, AcceptedItemKey, AcceptedItemPrefix, AcceptedTransactionKey,
     AlephUnitsPrefix, ClientConfigSignatureKey, ClientConfigSignatureShareKey,
-    ClientConfigSignatureSharePrefix, SignedBlockKey, SignedBlockPrefix, GLOBAL_DATABASE_VERSION,
+    ClientConfigSignatureSharePrefix, RejectedTransactionKey, SignedBlockKey, SignedBlockPrefix,
+    GLOBAL_DATABASE_VERSION,
 };
 use crate::fedimint_core::encoding::Encodable;
+use crate::metrics::{ModuleEpochMetrics, SessionDurations};
 use crate::net::api::{ConsensusApi, ExpiringCache, InvitationCodesTracker};
 use crate::net::connect::{Connector, TlsTcpConnector};
 use crate::net::peers::{DelayCalculator, PeerConnector, ReconnectPeerConnections};
 use crate::{atomic_broadcast, LOG_CONSENSUS, LOG_CORE};
 
-/// How many txs can be stored in memory before blocking the API
+/// How many txs can be stored in memory before the API rejects new ones
 const TRANSACTION_BUFFER: usize = 1000;
 
 pub(crate) type LatestContributionByPeer = HashMap<PeerId, u64>;
@@ -61,6 +65,8 @@ pub struct ConsensusServer {
     cfg: ServerConfig,
     submission_receiver: Receiver<ConsensusItem>,
     latest_contribution_by_peer: Arc<RwLock<LatestContributionByPeer>>,
+    session_durations: SessionDurations,
+    module_epoch_metrics: ModuleEpochMetrics,
 }
 
 impl ConsensusServer {
@@ -113,6 +119,10 @@ impl ConsensusServer {
         )
         .await?;
 
+        let mut dbtx = db.begin_transaction().await;
+        record_config_version(&mut dbtx, &cfg.consensus.modules_json).await;
+        dbtx.commit_tx_result().await?;
+
         for (module_id, module_cfg) in &cfg.consensus.modules {
             let kind = module_cfg.kind.clone();
             let Some(init) = module_inits.get(&kind) else {
@@ -164,6 +174,8 @@ impl ConsensusServer {
 
         // Build API that can handle requests
         let latest_contribution_by_peer = Default::default();
+        let session_durations = SessionDurations::default();
+        let module_epoch_metrics = ModuleEpochMetrics::default();
 
         let consensus_api = ConsensusApi {
             cfg: cfg.clone(),
@@ -177,6 +189,10 @@ impl ConsensusServer {
                 &module_inits,
             ),
             latest_contribution_by_peer: Arc::clone(&latest_contribution_by_peer),
+            session_durations: session_durations.clone(),
+            module_epoch_metrics: module_epoch_metrics.clone(),
+            api_bandwidth: Default::default(),
+            start_time: fedimint_core::time::now(),
             peer_status_channels,
             consensus_status_cache: ExpiringCache::new(Duration::from_millis(500)),
         };
@@ -208,6 +224,8 @@ impl ConsensusServer {
             cfg: cfg.clone(),
             submission_receiver,
             latest_contribution_by_peer,
+            session_durations,
+            module_epoch_metrics,
             modules,
         };
 
@@ -274,6 +292,8 @@ impl ConsensusServer {
         const ROUND_DELAY: f64 = 250.0;
         const BASE: f64 = 1.01;
 
+        let session_start = Instant::now();
+
         // this is the minimum number of unit data that will be ordered before we reach
         // the EXPONENTIAL_SLOWDOWN_OFFSET even if f peers do not attach unit data
         let batches_per_session = EXPECTED_ROUNDS_PER_SESSION * self.keychain.peer_count();
@@ -350,6 +370,8 @@ impl ConsensusServer {
         // for the aleph bft units
         self.complete_session(session_index, signed_block).await;
 
+        self.session_durations.record(session_start.elapsed());
+
         Ok(())
     }
 
@@ -461,23 +483,69 @@ impl ConsensusServer {
     }
 
     pub async fn complete_session(&self, session_index: u64, signed_block: SignedBlock) {
-        let mut dbtx = self.db.begin_transaction().await;
+        // a failed commit leaves the database untouched, hence we can simply
+        // retry until the storage layer recovers
+        let mut backoff = CommitBackoff::new();
+        loop {
+            let mut dbtx = self.db.begin_transaction().await;
 
-        dbtx.remove_by_prefix(&AlephUnitsPrefix).await;
+            dbtx.remove_by_prefix(&AlephUnitsPrefix).await;
 
-        dbtx.remove_by_prefix(&AcceptedItemPrefix).await;
+            dbtx.remove_by_prefix(&AcceptedItemPrefix).await;
 
-        if dbtx
-            .insert_entry(&SignedBlockKey(session_index), &signed_block)
-            .await
-            .is_some()
-        {
-            panic!("We tried to overwrite a signed block");
+            if dbtx
+                .insert_entry(&SignedBlockKey(session_index), &signed_block)
+                .await
+                .is_some()
+            {
+                panic!("We tried to overwrite a signed block");
+            }
+
+            for (module_instance_id, _, module) in self.modules.iter_modules() {
+                module
+                    .complete_session(
+                        &mut dbtx.with_module_prefix(module_instance_id),
+                        session_index,
+                    )
+                    .await;
+            }
+
+            // the hooks may move funds, e.g. the wallet funds batched peg-outs
+            self.assert_positive_balance(&mut dbtx).await;
+
+            match dbtx.commit_tx_result().await {
+                Ok(()) => return,
+                Err(e) => {
+                    warn!(
+                        target: LOG_CONSENSUS,
+                        session_index,
+                        "Committing signed block failed, retrying: {}",
+                        OptStacktrace(e)
+                    );
+                    sleep(backoff.next_delay("signed block")).await;
+                }
+            }
         }
+    }
 
-        dbtx.commit_tx_result()
-            .await
-            .expect("This is the only place where we write to this key");
+    /// Audits all modules and panics if the balance sheet of the federation
+    /// went negative
+    async fn assert_positive_balance(&self, dbtx: &mut DatabaseTransaction<'_>) {
+        let mut audit = Audit::default();
+
+        for (module_instance_id, _, module) in self.modules.iter_modules() {
+            module
+                .audit(
+                    &mut dbtx.with_module_prefix(module_instance_id),
+                    &mut audit,
+                    module_instance_id,
+                )
+                .await
+        }
+
+        if audit.net_assets().milli_sat < 0 {
+            panic!("Balance sheet of the fed has gone negative, this should never happen! {audit}")
+        }
     }
 
     pub async fn process_consensus_item(
@@ -496,10 +564,14 @@ impl ConsensusServer {
             .await
             .insert(peer, session_index);
 
-        let mut dbtx = self.db.begin_transaction().await;
-
-        if let Some(accepted_item) = dbtx
-            .get_value(&AcceptedItemKey(item_index.to_owned()))
+        // Items replayed after a restart were already processed and recorded in the
+        // metrics, only this function writes accepted items so we can check before
+        // processing the item
+        if let Some(accepted_item) = self
+            .db
+            .begin_transaction()
+            .await
+            .get_value(&AcceptedItemKey(item_index))
             .await
         {
             if accepted_item.item == item && accepted_item.peer == peer {
@@ -509,33 +581,73 @@ impl ConsensusServer {
             bail!("Consensus item was discarded before recovery");
         }
 
-        self.process_consensus_item_with_db_transaction(&mut dbtx, item.clone(), peer)
-            .await?;
+        let modules = consensus_item_modules(&item);
+        let processing_start = Instant::now();
 
-        dbtx.insert_entry(&AcceptedItemKey(item_index), &AcceptedItem { item, peer })
-            .await;
+        let result: anyhow::Result<()> = async {
+            // Processing an item only touches the database, so if the commit fails
+            // we can re-run it against a fresh transaction
+            let mut backoff = CommitBackoff::new();
+            loop {
+                let mut dbtx = self.db.begin_transaction().await;
 
-        let mut audit = Audit::default();
+                self.process_consensus_item_with_db_transaction(&mut dbtx, item.clone(), peer)
+                    .await?;
 
-        for (module_instance_id, _, module) in self.modules.iter_modules() {
-            module
-                .audit(
-                    &mut dbtx.with_module_prefix(module_instance_id),
-                    &mut audit,
-                    module_instance_id,
+                dbtx.insert_entry(
+                    &AcceptedItemKey(item_index),
+                    &AcceptedItem {
+                        item: item.clone(),
+                        peer,
+                    },
                 )
-                .await
-        }
+                .await;
 
-        if audit.net_assets().milli_sat < 0 {
-            panic!("Balance sheet of the fed has gone negative, this should never happen! {audit}")
+                self.assert_positive_balance(&mut dbtx).await;
+
+                match dbtx.commit_tx_result().await {
+                    Ok(()) => return Ok(()),
+                    Err(e) => {
+                        warn!(
+                            target: LOG_CONSENSUS,
+                            session_index,
+                            item_index,
+                            "Committing consensus item failed, retrying: {}",
+                            OptStacktrace(e)
+                        );
+                        sleep(backoff.next_delay("consensus item")).await;
+                    }
+                }
+            }
         }
+        .await;
 
-        dbtx.commit_tx_result()
-            .await
-            .expect("Committing consensus epoch failed");
+        self.module_epoch_metrics.record(
+            session_index,
+            modules,
+            result.is_ok(),
+            processing_start.elapsed(),
+        );
 
-        Ok(())
+        result
+    }
+
+    /// Records why a transaction was rejected for clients awaiting its status,
+    /// the transaction of the consensus item is discarded so we use a separate
+    /// one
+    async fn record_rejected_transaction(&self, txid: TransactionId, error: &anyhow::Error) {
+        let mut dbtx = self.db.begin_transaction().await;
+        dbtx.insert_entry(&RejectedTransactionKey(txid), &error.to_string())
+            .await;
+
+        if let Err(e) = dbtx.commit_tx_result().await {
+            warn!(
+                target: LOG_CONSENSUS,
+                %txid,
+                "Recording rejected transaction failed: {}",
+                OptStacktrace(e)
+            );
+        }
     }
 
     async fn process_consensus_item_with_db_transaction(
@@ -573,8 +685,14 @@ impl ConsensusServer {
                     .map(|output| output.module_instance_id())
                     .collect::<Vec<_>>();
 
-                process_transaction_with_dbtx(self.modules.clone(), dbtx, transaction).await?;
+                if let Err(e) =
+                    process_transaction_with_dbtx(self.modules.clone(), dbtx, transaction).await
+                {
+                    self.record_rejected_transaction(txid, &e).await;
+                    return Err(e);
+                }
 
+                dbtx.remove_entry(&RejectedTransactionKey(txid)).await;
                 dbtx.insert_entry(&AcceptedTransactionKey(txid), &modules_ids)
                     .await;
 
@@ -687,6 +805,26 @@ impl ConsensusServer {
     }
 }
 
+/// The modules whose metrics an item counts towards, see
+/// [`fedimint_core::api::EpochMetrics`]
+fn consensus_item_modules(item: &ConsensusItem) -> BTreeSet<ModuleInstanceId> {
+    match item {
+        ConsensusItem::Module(module_item) => BTreeSet::from([module_item.module_instance_id()]),
+        ConsensusItem::Transaction(transaction) => transaction
+            .inputs
+            .iter()
+            .map(|input| input.module_instance_id())
+            .chain(
+                transaction
+                    .outputs
+                    .iter()
+                    .map(|output| output.module_instance_id()),
+            )
+            .collect(),
+        ConsensusItem::ClientConfigSignatureShare(_) => BTreeSet::new(),
+    }
+}
+
 async fn submit_module_consensus_items(
     task_group: &mut TaskGroup,
     db: Database,
@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use fedimint_core::api::ClientConfigDownloadToken;
+use fedimint_core::api::{ClientConfigDownloadToken, ExchangeRate};
 use fedimint_core::block::{AcceptedItem, SignedBlock};
 use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{DatabaseVersion, MigrationMap, MODULE_GLOBAL_PREFIX};
@@ -22,6 +22,9 @@ pub enum DbKeyPrefix {
     ClientConfigSignature = 0x07,
     ClientConfigSignatureShare = 0x3,
     ClientConfigDownload = 0x09,
+    ConfigVersion = 0x0a,
+    ExchangeRate = 0x0b,
+    RejectedTransaction = 0x0c,
     Module = MODULE_GLOBAL_PREFIX,
 }
 
@@ -133,6 +136,53 @@ impl_db_lookup!(
     query_prefix = ClientConfigDownloadKeyPrefix
 );
 
+/// Version of the module configs, the value is their JSON representation
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ConfigVersionKey(pub u32);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ConfigVersionPrefix;
+
+impl_db_record!(
+    key = ConfigVersionKey,
+    value = String,
+    db_prefix = DbKeyPrefix::ConfigVersion,
+);
+impl_db_lookup!(key = ConfigVersionKey, query_prefix = ConfigVersionPrefix);
+
+/// Latest price the guardian posted for the currency, not part of consensus
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct ExchangeRateKey(pub String);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct ExchangeRatePrefix;
+
+impl_db_record!(
+    key = ExchangeRateKey,
+    value = ExchangeRate,
+    db_prefix = DbKeyPrefix::ExchangeRate,
+);
+impl_db_lookup!(key = ExchangeRateKey, query_prefix = ExchangeRatePrefix);
+
+/// Why processing a transaction in consensus failed, removed again if a later
+/// submission of it is accepted
+#[derive(Debug, Encodable, Decodable, Serialize)]
+pub struct RejectedTransactionKey(pub TransactionId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct RejectedTransactionPrefix;
+
+impl_db_record!(
+    key = RejectedTransactionKey,
+    value = String,
+    db_prefix = DbKeyPrefix::RejectedTransaction,
+    notify_on_modify = true,
+);
+impl_db_lookup!(
+    key = RejectedTransactionKey,
+    query_prefix = RejectedTransactionPrefix
+);
+
 pub fn get_global_database_migrations<'a>() -> MigrationMap<'a> {
     MigrationMap::new()
 }
@@ -379,6 +429,12 @@ mod fedimint_migration_tests {
                                 "validate_migrations was not able to read any ClientConfigDownloadKey"
                             );
                         }
+                        // Config versions were introduced after the v0 snapshot was taken
+                        DbKeyPrefix::ConfigVersion => {}
+                        // Exchange rates were introduced after the v0 snapshot was taken
+                        DbKeyPrefix::ExchangeRate => {}
+                        // Rejections are only kept to answer status requests of clients
+                        DbKeyPrefix::RejectedTransaction => {}
                         // Module prefix is reserved for modules, no migration testing is needed
                         DbKeyPrefix::Module => {}
                     }
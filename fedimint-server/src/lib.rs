@@ -27,6 +27,7 @@ use tracing::{error, info};
 
 use crate::config::api::{ConfigGenApi, ConfigGenSettings};
 use crate::consensus::server::ConsensusServer;
+use crate::metrics::ApiBandwidth;
 use crate::net::api::{ConsensusApi, RpcHandlerCtx};
 use crate::net::connect::TlsTcpConnector;
 use crate::net::peers::ReconnectPeerConnections;
@@ -48,6 +49,9 @@ pub mod config;
 /// Implementation of multiplexed peer connections
 pub mod multiplexed;
 
+/// Metrics recorded by the consensus
+pub mod metrics;
+
 /// How long to wait before timing out client connections
 const API_ENDPOINT_TIMEOUT: Duration = Duration::from_secs(60);
 
@@ -136,7 +140,7 @@ impl FedimintServer {
         }
 
         let mut rpc_module = RpcHandlerCtx::new_module(config_gen);
-        Self::attach_endpoints(&mut rpc_module, config::api::server_endpoints(), None);
+        Self::attach_endpoints(&mut rpc_module, config::api::server_endpoints(), None, None);
         let handler =
             Self::spawn_api("config-gen", &self.settings.api_bind, rpc_module, 10, true).await;
 
@@ -153,9 +157,20 @@ impl FedimintServer {
     ) -> FedimintApiHandler {
         let cfg = &api.cfg.local;
         let mut rpc_module = RpcHandlerCtx::new_module(api.clone());
-        Self::attach_endpoints(&mut rpc_module, net::api::server_endpoints(), None);
+        let bandwidth = Some(api.api_bandwidth.clone());
+        Self::attach_endpoints(
+            &mut rpc_module,
+            net::api::server_endpoints(),
+            None,
+            bandwidth.clone(),
+        );
         for (id, _, module) in api.modules.iter_modules() {
-            Self::attach_endpoints(&mut rpc_module, module.api_endpoints(), Some(id));
+            Self::attach_endpoints(
+                &mut rpc_module,
+                module.api_endpoints(),
+                Some(id),
+                bandwidth.clone(),
+            );
         }
 
         Self::spawn_api(
@@ -205,11 +220,13 @@ impl FedimintServer {
         FedimintApiHandler { handle, runtime }
     }
 
-    /// Attaches `endpoints` to the `RpcModule`
+    /// Attaches `endpoints` to the `RpcModule`, recording the bytes they
+    /// receive and send in `bandwidth` if given
     fn attach_endpoints<State, T>(
         rpc_module: &mut RpcModule<RpcHandlerCtx<T>>,
         endpoints: Vec<ApiEndpoint<State>>,
         module_instance_id: Option<ModuleInstanceId>,
+        bandwidth: Option<ApiBandwidth>,
     ) where
         T: HasApiContext<State> + Sync + Send + 'static,
         State: Sync + Send + 'static,
@@ -232,45 +249,59 @@ impl FedimintServer {
             // Another memory leak that is fine because the function is only called once at
             // startup
             let handler: &'static _ = Box::leak(endpoint.handler);
+            let bandwidth: &'static _ = Box::leak(Box::new(bandwidth.clone()));
 
             rpc_module
                 .register_async_method(path, move |params, rpc_state| async move {
                     let params = params.one::<serde_json::Value>()?;
                     let rpc_context = &rpc_state.rpc_context;
+                    let request_bytes = params.to_string().len();
 
-                    // Using AssertUnwindSafe here is far from ideal. In theory this means we could
-                    // end up with an inconsistent state in theory. In practice most API functions
-                    // are only reading and the few that do write anything are atomic. Lastly, this
-                    // is only the last line of defense
-                    AssertUnwindSafe(tokio::time::timeout(API_ENDPOINT_TIMEOUT, async {
+                    let request = tokio::time::timeout(API_ENDPOINT_TIMEOUT, async {
                         let request = serde_json::from_value(params)
                             .map_err(|e| ApiError::bad_request(e.to_string()))?;
                         let (state, context) =
                             rpc_context.context(&request, module_instance_id).await;
 
                         (handler)(state, context, request).await
-                    }))
-                    .catch_unwind()
-                    .await
-                    .map_err(|_| {
-                        error!(
-                            target: LOG_NET_API,
-                            path, "API handler panicked, DO NOT IGNORE, FIX IT!!!"
-                        );
-                        jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
-                            500,
-                            "API handler panicked",
-                            None::<()>,
-                        )))
-                    })?
-                    .map_err(|tokio::time::error::Elapsed { .. }| {
-                        jsonrpsee::core::Error::RequestTimeout
-                    })?
-                    .map_err(|e| {
-                        jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
-                            e.code, e.message, None::<()>,
-                        )))
-                    })
+                    });
+
+                    // Using AssertUnwindSafe here is far from ideal. In theory this means we could
+                    // end up with an inconsistent state in theory. In practice most API functions
+                    // are only reading and the few that do write anything are atomic. Lastly, this
+                    // is only the last line of defense
+                    let response = AssertUnwindSafe(request)
+                        .catch_unwind()
+                        .await
+                        .map_err(|_| {
+                            error!(
+                                target: LOG_NET_API,
+                                path, "API handler panicked, DO NOT IGNORE, FIX IT!!!"
+                            );
+                            jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
+                                500,
+                                "API handler panicked",
+                                None::<()>,
+                            )))
+                        })?
+                        .map_err(|tokio::time::error::Elapsed { .. }| {
+                            jsonrpsee::core::Error::RequestTimeout
+                        })?
+                        .map_err(|e| {
+                            jsonrpsee::core::Error::Call(CallError::Custom(ErrorObject::owned(
+                                e.code, e.message, None::<()>,
+                            )))
+                        });
+
+                    if let Some(bandwidth) = bandwidth {
+                        let response_bytes = response
+                            .as_ref()
+                            .map(|response| response.to_string().len())
+                            .unwrap_or_default();
+                        bandwidth.record(path, request_bytes + response_bytes);
+                    }
+
+                    response
                 })
                 .expect("Failed to register async method");
         }
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use fedimint_core::api::{ConsensusMeasurement, EpochMetrics};
+use fedimint_core::core::ModuleInstanceId;
+use fedimint_metrics::{histogram_opts, lazy_static, register_histogram, Histogram};
+
+/// Number of most recent consensus sessions whose durations are kept
+pub const RECENT_SESSIONS: usize = 100;
+
+lazy_static! {
+    static ref CONSENSUS_SESSION_DURATION_SECONDS: Histogram =
+        register_histogram!(histogram_opts!(
+            "consensus_session_duration_seconds",
+            "Time it took to complete a consensus session",
+            vec![1.0, 10.0, 30.0, 45.0, 60.0, 90.0, 120.0, 300.0]
+        ))
+        .unwrap();
+}
+
+/// Durations of the most recent consensus sessions, shared between the
+/// consensus server recording them and the API reporting them
+#[derive(Debug, Clone, Default)]
+pub struct SessionDurations(Arc<Mutex<VecDeque<Duration>>>);
+
+impl SessionDurations {
+    pub fn record(&self, duration: Duration) {
+        CONSENSUS_SESSION_DURATION_SECONDS.observe(duration.as_secs_f64());
+
+        let mut durations = self.0.lock().expect("Lock poisoned");
+        if durations.len() == RECENT_SESSIONS {
+            durations.pop_front();
+        }
+        durations.push_back(duration);
+    }
+
+    /// Summarizes the durations of the recent sessions, returns `None` if no
+    /// session was completed yet
+    pub fn measure(&self) -> Option<ConsensusMeasurement> {
+        let durations = self.0.lock().expect("Lock poisoned");
+        ConsensusMeasurement::from_durations(durations.iter().copied())
+    }
+
+    /// Averages the durations of the last `num_sessions` sessions, returns
+    /// `None` if no session was completed yet
+    pub fn average(&self, num_sessions: usize) -> Option<Duration> {
+        let durations = self.0.lock().expect("Lock poisoned");
+        let recent = durations
+            .iter()
+            .rev()
+            .take(num_sessions)
+            .copied()
+            .collect::<Vec<_>>();
+
+        if recent.is_empty() {
+            return None;
+        }
+
+        Some(recent.iter().sum::<Duration>() / recent.len() as u32)
+    }
+}
+
+/// Per module metrics of the most recent [`RECENT_SESSIONS`] epochs, shared
+/// between the consensus server recording them and the API reporting them
+#[derive(Debug, Clone, Default)]
+pub struct ModuleEpochMetrics(Arc<Mutex<BTreeMap<u64, BTreeMap<ModuleInstanceId, EpochMetrics>>>>);
+
+impl ModuleEpochMetrics {
+    /// Records that an item of each of the `modules` was processed in `epoch`
+    pub fn record(
+        &self,
+        epoch: u64,
+        modules: impl IntoIterator<Item = ModuleInstanceId>,
+        accepted: bool,
+        processing_time: Duration,
+    ) {
+        let mut epochs = self.0.lock().expect("Lock poisoned");
+        let metrics = epochs.entry(epoch).or_default();
+        for module_instance_id in modules {
+            let module_metrics = metrics.entry(module_instance_id).or_default();
+            module_metrics.items_proposed += 1;
+            module_metrics.items_accepted += usize::from(accepted);
+            module_metrics.processing_time_ms += processing_time.as_millis() as u64;
+        }
+
+        while epochs.len() > RECENT_SESSIONS {
+            epochs.pop_first();
+        }
+    }
+
+    /// The metrics of all modules that had items in `epoch`
+    pub fn get(&self, epoch: u64) -> BTreeMap<ModuleInstanceId, EpochMetrics> {
+        self.0
+            .lock()
+            .expect("Lock poisoned")
+            .get(&epoch)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Bytes received and sent by the API per method since the server started,
+/// the error messages of failed requests are not counted
+#[derive(Debug, Clone, Default)]
+pub struct ApiBandwidth(Arc<Mutex<BTreeMap<String, usize>>>);
+
+impl ApiBandwidth {
+    pub fn record(&self, method: &str, bytes: usize) {
+        *self
+            .0
+            .lock()
+            .expect("Lock poisoned")
+            .entry(method.to_string())
+            .or_default() += bytes;
+    }
+
+    /// Total bytes per method that were called at least once
+    pub fn totals(&self) -> BTreeMap<String, usize> {
+        self.0.lock().expect("Lock poisoned").clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use fedimint_core::api::EpochMetrics;
+
+    use super::{ApiBandwidth, ModuleEpochMetrics, SessionDurations, RECENT_SESSIONS};
+
+    #[test]
+    fn averages_most_recent_sessions() {
+        let durations = SessionDurations::default();
+        assert_eq!(durations.average(10), None);
+
+        for secs in 1..=20 {
+            durations.record(Duration::from_secs(secs));
+        }
+
+        assert_eq!(durations.average(10), Some(Duration::from_millis(15_500)));
+        assert_eq!(durations.average(100), Some(Duration::from_millis(10_500)));
+    }
+
+    #[test]
+    fn accumulates_module_metrics_per_epoch() {
+        let metrics = ModuleEpochMetrics::default();
+        metrics.record(0, [0, 1], true, Duration::from_millis(10));
+        metrics.record(0, [1], false, Duration::from_millis(5));
+        metrics.record(1, [], true, Duration::from_millis(5));
+
+        assert_eq!(
+            metrics.get(0),
+            BTreeMap::from([
+                (
+                    0,
+                    EpochMetrics {
+                        items_proposed: 1,
+                        items_accepted: 1,
+                        processing_time_ms: 10,
+                    }
+                ),
+                (
+                    1,
+                    EpochMetrics {
+                        items_proposed: 2,
+                        items_accepted: 1,
+                        processing_time_ms: 15,
+                    }
+                ),
+            ])
+        );
+        assert_eq!(metrics.get(1), BTreeMap::new());
+
+        for epoch in 2..=RECENT_SESSIONS as u64 + 1 {
+            metrics.record(epoch, [0], true, Duration::ZERO);
+        }
+        assert_eq!(metrics.get(0), BTreeMap::new());
+    }
+
+    #[test]
+    fn accumulates_bandwidth_per_method() {
+        let bandwidth = ApiBandwidth::default();
+        bandwidth.record("transaction", 100);
+        bandwidth.record("config", 20);
+        bandwidth.record("transaction", 50);
+
+        assert_eq!(
+            bandwidth.totals(),
+            BTreeMap::from([("config".to_string(), 20), ("transaction".to_string(), 150)])
+        );
+    }
+}
@@ -2,38 +2,48 @@
 use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
+use aleph_bft::Keychain as KeychainTrait;
 use anyhow::{anyhow, Result};
+use async_channel::TrySendError;
 use async_trait::async_trait;
-use bitcoin_hashes::sha256;
+use bitcoin_hashes::{sha256, Hash};
 use fedimint_core::api::{
-    ClientConfigDownloadToken, FederationStatus, InviteCode, PeerConnectionStatus, PeerStatus,
-    ServerStatus, StatusResponse,
+    ClientConfigDownloadToken, ConfigDiff, ConsensusMeasurement, Constitution, EpochMetrics,
+    ExchangeRate, ExchangeRateShare, FederationStats, FederationStatsShare, FederationStatus,
+    InviteCode, NodeInfo, PeerConnectionStatus, PeerStats, PeerStatus, ServerStatus,
+    StatusResponse, TransactionStatus, MAX_CURRENCY_CODE_LEN,
 };
 use fedimint_core::backup::{ClientBackupKey, ClientBackupSnapshot};
-use fedimint_core::block::{Block, SignedBlock};
+use fedimint_core::block::{Block, EpochCommitment, SignedBlock};
 use fedimint_core::config::{ClientConfig, ClientConfigResponse, JsonWithKind};
 use fedimint_core::core::backup::SignedBackupRequest;
 use fedimint_core::core::{DynOutputOutcome, ModuleInstanceId};
 use fedimint_core::db::{Database, DatabaseTransaction, ModuleDatabaseTransaction};
 use fedimint_core::endpoint_constants::{
-    AUDIT_ENDPOINT, AUTH_ENDPOINT, AWAIT_BLOCK_ENDPOINT, AWAIT_OUTPUT_OUTCOME_ENDPOINT,
-    AWAIT_SIGNED_BLOCK_ENDPOINT, BACKUP_ENDPOINT, CONFIG_ENDPOINT, CONFIG_HASH_ENDPOINT,
-    FETCH_BLOCK_COUNT_ENDPOINT, GET_VERIFY_CONFIG_HASH_ENDPOINT, INVITE_CODE_ENDPOINT,
-    MODULES_CONFIG_JSON_ENDPOINT, RECOVER_ENDPOINT, STATUS_ENDPOINT, TRANSACTION_ENDPOINT,
-    VERSION_ENDPOINT, WAIT_TRANSACTION_ENDPOINT,
+    AUDIT_ENDPOINT, AUTH_ENDPOINT, AVERAGE_SESSION_DURATION_ENDPOINT, AWAIT_BLOCK_ENDPOINT,
+    AWAIT_OUTPUT_OUTCOME_ENDPOINT, AWAIT_SIGNED_BLOCK_ENDPOINT, AWAIT_TRANSACTION_STATUS_ENDPOINT,
+    BACKUP_ENDPOINT, BALANCE_SHEET_ENDPOINT, CONFIG_DIFF_ENDPOINT, CONFIG_ENDPOINT,
+    CONFIG_HASH_ENDPOINT, CONSENSUS_ROUND_TRIP_ENDPOINT, CONSTITUTION_ENDPOINT,
+    EPOCH_COMMITMENT_ENDPOINT, EPOCH_METRICS_ENDPOINT, EXCHANGE_RATE_ENDPOINT,
+    FEDERATION_STATS_ENDPOINT, FETCH_BLOCK_COUNT_ENDPOINT, GET_VERIFY_CONFIG_HASH_ENDPOINT,
+    INVITE_CODE_ENDPOINT, MODULES_CONFIG_JSON_ENDPOINT, MODULE_AUDIT_ENDPOINT, NODE_INFO_ENDPOINT,
+    POST_EXCHANGE_RATE_ENDPOINT, RECOVER_ENDPOINT, STATUS_ENDPOINT, TRANSACTION_ENDPOINT,
+    VALIDATE_TRANSACTION_ENDPOINT, VERSION_ENDPOINT, WAIT_TRANSACTION_ENDPOINT,
+};
+use fedimint_core::epoch::{ConsensusItem, SerdeSignatureShare};
+use fedimint_core::module::audit::{
+    Audit, AuditSummary, BalanceSheet, BalanceSheetShare, ModuleAudit, ModuleAuditShare,
 };
-use fedimint_core::epoch::ConsensusItem;
-use fedimint_core::module::audit::{Audit, AuditSummary};
 use fedimint_core::module::registry::ServerModuleRegistry;
 use fedimint_core::module::{
     api_endpoint, ApiEndpoint, ApiEndpointContext, ApiError, ApiRequestErased, SerdeModuleEncoding,
     SupportedApiVersionsSummary,
 };
 use fedimint_core::server::DynServerModule;
-use fedimint_core::task::TaskGroup;
-use fedimint_core::transaction::{SerdeTransaction, Transaction};
+use fedimint_core::task::{sleep, TaskGroup};
+use fedimint_core::transaction::{SerdeTransaction, Transaction, TransactionError};
 use fedimint_core::{OutPoint, PeerId, TransactionId};
 use fedimint_logging::LOG_NET_API;
 use futures::StreamExt;
@@ -43,19 +53,29 @@ use tokio::sync::RwLock;
 use tracing::{debug, info};
 
 use super::peers::PeerStatusChannels;
+use crate::atomic_broadcast::keychain::Keychain;
 use crate::config::api::get_verification_hashes;
+use crate::config::io::CODE_VERSION;
+use crate::config::versions::get_config_diff;
 use crate::config::ServerConfig;
 use crate::consensus::server::LatestContributionByPeer;
 use crate::consensus::FundingVerifier;
 use crate::db::{
     AcceptedTransactionKey, ClientConfigDownloadKey, ClientConfigDownloadKeyPrefix,
-    ClientConfigSignatureKey, SignedBlockKey, SignedBlockPrefix,
+    ClientConfigSignatureKey, ExchangeRateKey, RejectedTransactionKey, SignedBlockKey,
+    SignedBlockPrefix,
 };
 use crate::fedimint_core::encoding::Encodable;
+use crate::metrics::{ApiBandwidth, ModuleEpochMetrics, SessionDurations};
 use crate::{check_auth, ApiResult, HasApiContext};
 
 pub type SerdeOutputOutcome = SerdeModuleEncoding<DynOutputOutcome>;
 
+/// How long to wait before checking again whether the outcome of an accepted
+/// output was created, doubled after every check up to the maximum
+const OUTPUT_OUTCOME_POLL_DELAY_MIN: Duration = Duration::from_millis(100);
+const OUTPUT_OUTCOME_POLL_DELAY_MAX: Duration = Duration::from_secs(5);
+
 /// A state that has context for the API, passed to each rpc handler callback
 #[derive(Clone)]
 pub struct RpcHandlerCtx<M> {
@@ -189,6 +209,14 @@ pub struct ConsensusApi {
     pub submission_sender: async_channel::Sender<ConsensusItem>,
     pub peer_status_channels: PeerStatusChannels,
     pub latest_contribution_by_peer: Arc<RwLock<LatestContributionByPeer>>,
+    /// Durations of the recently completed consensus sessions
+    pub session_durations: SessionDurations,
+    /// Per module metrics of the recent epochs
+    pub module_epoch_metrics: ModuleEpochMetrics,
+    /// Bytes served per API method
+    pub api_bandwidth: ApiBandwidth,
+    /// When the server was started
+    pub start_time: SystemTime,
     pub consensus_status_cache: ExpiringCache<ApiResult<FederationStatus>>,
     pub supported_api_versions: SupportedApiVersionsSummary,
 }
@@ -215,6 +243,24 @@ impl ConsensusApi {
             return Ok(());
         }
 
+        self.validate_transaction(&transaction).await?;
+
+        // reject the transaction right away instead of waiting for consensus to
+        // catch up if the buffer of pending submissions is full
+        self.submission_sender
+            .try_send(ConsensusItem::Transaction(transaction))
+            .map_err(|e| match e {
+                TrySendError::Full(_) => TransactionError::MempoolFull.into(),
+                TrySendError::Closed(_) => anyhow!("Consensus is not running"),
+            })
+    }
+
+    /// Runs the same checks against the current state as
+    /// [`Self::submit_transaction`] without submitting the transaction. Since
+    /// its inputs are spent an already accepted transaction fails validation.
+    pub async fn validate_transaction(&self, transaction: &Transaction) -> anyhow::Result<()> {
+        let txid = transaction.tx_hash();
+
         // Create read-only DB tx so that the read state is consistent
         let mut dbtx = self.db.begin_transaction().await;
 
@@ -256,10 +302,6 @@ impl ConsensusApi {
 
         funding_verifier.verify_funding()?;
 
-        self.submission_sender
-            .send(ConsensusItem::Transaction(transaction))
-            .await?;
-
         Ok(())
     }
 
@@ -272,6 +314,18 @@ impl ConsensusApi {
             .await
     }
 
+    /// Waits until consensus either accepted or rejected the transaction
+    /// `txid`, a transaction that is never submitted is pending forever
+    pub async fn await_transaction_status(&self, txid: TransactionId) -> TransactionStatus {
+        tokio::select! {
+            biased;
+            _ = self.db.wait_key_exists(&AcceptedTransactionKey(txid)) => TransactionStatus::Accepted,
+            error = self.db.wait_key_exists(&RejectedTransactionKey(txid)) => {
+                TransactionStatus::Rejected(error)
+            }
+        }
+    }
+
     pub async fn await_output_outcome(&self, outpoint: OutPoint) -> Result<SerdeOutputOutcome> {
         let (module_ids, mut dbtx) = self.await_transaction(outpoint.txid).await;
 
@@ -280,14 +334,23 @@ impl ConsensusApi {
             .nth(outpoint.out_idx as usize)
             .ok_or(anyhow!("Outpoint index out of bounds {:?}", outpoint))?;
 
-        let outcome = self
-            .modules
-            .get_expect(module_id)
-            .output_status(&mut dbtx.with_module_prefix(module_id), outpoint, module_id)
-            .await
-            .expect("The transaction is accepted");
+        // Modules may only create the outcome of an accepted output once the session
+        // completes, e.g. the wallet when batching peg-outs
+        let mut delay = OUTPUT_OUTCOME_POLL_DELAY_MIN;
+        loop {
+            if let Some(outcome) = self
+                .modules
+                .get_expect(module_id)
+                .output_status(&mut dbtx.with_module_prefix(module_id), outpoint, module_id)
+                .await
+            {
+                return Ok((&outcome).into());
+            }
 
-        Ok((&outcome).into())
+            sleep(delay).await;
+            delay = (delay * 2).min(OUTPUT_OUTCOME_POLL_DELAY_MAX);
+            dbtx = self.db.begin_transaction().await;
+        }
     }
 
     pub async fn fetch_block_count(&self) -> u64 {
@@ -307,6 +370,24 @@ impl ConsensusApi {
             .0
     }
 
+    /// Waits for the block of the given epoch and signs its header with our
+    /// broadcast key
+    pub async fn epoch_commitment(&self, epoch: u64) -> EpochCommitment {
+        let header = self.await_signed_block(epoch).await.block.header(epoch);
+
+        let keychain = Keychain::new(
+            self.cfg.local.identity,
+            self.cfg.consensus.broadcast_public_keys.clone(),
+            self.cfg.private.broadcast_secret_key,
+        );
+
+        EpochCommitment {
+            peer_id: self.cfg.local.identity,
+            hash: sha256::Hash::hash(&header),
+            partial_signature: keychain.sign(&header),
+        }
+    }
+
     pub async fn download_client_config(&self, info: InviteCode) -> ApiResult<ClientConfig> {
         let token = self.cfg.local.download_token.clone();
 
@@ -329,7 +410,7 @@ impl ConsensusApi {
             .await
             .is_err()
         {
-            return Err(ApiError::bad_request(
+            return Err(ApiError::too_many_requests(
                 "Download token used too many times".to_string(),
             ));
         }
@@ -337,6 +418,48 @@ impl ConsensusApi {
         Ok(self.client_cfg.clone())
     }
 
+    /// Summarizes how long the last [`crate::metrics::RECENT_SESSIONS`]
+    /// consensus sessions took, returns `None` before the first one completed
+    pub fn measure_consensus_round_trip(&self) -> Option<ConsensusMeasurement> {
+        self.session_durations.measure()
+    }
+
+    /// Averages how long the last `num_sessions` consensus sessions took,
+    /// returns `None` before the first one completed
+    pub fn average_session_duration(&self, num_sessions: u64) -> Option<Duration> {
+        self.session_durations.average(num_sessions as usize)
+    }
+
+    /// How this guardian processed the items of each module in `epoch`
+    pub fn get_epoch_metrics(&self, epoch: u64) -> BTreeMap<ModuleInstanceId, EpochMetrics> {
+        self.module_epoch_metrics.get(epoch)
+    }
+
+    /// Summarizes the changes to the module configs since `known_version`
+    pub async fn get_config_diff(&self, known_version: u32) -> Option<ConfigDiff> {
+        get_config_diff(&mut self.db.begin_transaction().await, known_version).await
+    }
+
+    pub async fn get_node_info(&self) -> NodeInfo {
+        let module_versions = self
+            .cfg
+            .consensus
+            .modules
+            .values()
+            .map(|module| (module.kind.to_string(), module.version.0))
+            .collect();
+        let uptime = fedimint_core::time::now()
+            .duration_since(self.start_time)
+            .unwrap_or_default();
+
+        NodeInfo {
+            software_version: CODE_VERSION.to_string(),
+            module_versions,
+            uptime_secs: uptime.as_secs(),
+            epoch_count: self.fetch_block_count().await,
+        }
+    }
+
     pub async fn get_federation_status(&self) -> ApiResult<FederationStatus> {
         let peers_connection_status = self.peer_status_channels.get_all_status().await;
         let latest_contribution_by_peer = self.latest_contribution_by_peer.read().await.clone();
@@ -391,7 +514,123 @@ impl ConsensusApi {
         })
     }
 
-    async fn get_federation_audit(&self) -> ApiResult<AuditSummary> {
+    pub async fn get_federation_audit(&self) -> ApiResult<AuditSummary> {
+        let (audit, module_instance_id_to_kind) = self.audit_modules().await;
+        Ok(AuditSummary::from_audit(
+            &audit,
+            &module_instance_id_to_kind,
+        ))
+    }
+
+    /// Signs the balance sheet of all modules with our epoch key share, so
+    /// clients can combine the shares of a threshold of guardians
+    pub async fn get_balance_sheet_share(&self) -> BalanceSheetShare {
+        let (audit, module_instance_id_to_kind) = self.audit_modules().await;
+        let balance_sheet = BalanceSheet::from_audit(&audit, &module_instance_id_to_kind);
+        let signature_share = self.cfg.private.epoch_sks.0.sign(balance_sheet.message());
+
+        BalanceSheetShare {
+            balance_sheet,
+            epoch_pk_set: self.cfg.consensus.epoch_pk_set.clone(),
+            signature_share: SerdeSignatureShare(signature_share),
+        }
+    }
+
+    /// Signs the audit of a single module with our epoch key share, see
+    /// [`ConsensusApi::get_balance_sheet_share`]
+    pub async fn get_module_audit_share(
+        &self,
+        module_instance_id: ModuleInstanceId,
+    ) -> ApiResult<ModuleAuditShare> {
+        let (audit, module_instance_id_to_kind) = self.audit_modules().await;
+        let kind = module_instance_id_to_kind
+            .get(&module_instance_id)
+            .ok_or_else(|| {
+                ApiError::bad_request(format!("Unknown module instance {module_instance_id}"))
+            })?;
+        let module_audit = ModuleAudit::from_audit(&audit, module_instance_id, kind.clone());
+        let signature_share = self.cfg.private.epoch_sks.0.sign(module_audit.message());
+
+        Ok(ModuleAuditShare {
+            module_audit,
+            epoch_pk_set: self.cfg.consensus.epoch_pk_set.clone(),
+            signature_share: SerdeSignatureShare(signature_share),
+        })
+    }
+
+    /// Signs the consensus health of the federation with our epoch key share,
+    /// so clients can combine the shares of a threshold of guardians
+    pub async fn get_federation_stats_share(&self) -> FederationStatsShare {
+        let latest_contribution_by_peer = self.latest_contribution_by_peer.read().await.clone();
+        let epoch_count = self.fetch_block_count().await;
+
+        let peers = self
+            .cfg
+            .consensus
+            .api_endpoints
+            .keys()
+            .map(|peer| {
+                let last_seen_epoch = latest_contribution_by_peer.get(peer).cloned();
+                let peer_stats = PeerStats {
+                    last_seen_epoch,
+                    is_dropped: last_seen_epoch.unwrap_or(0) + 1 < epoch_count,
+                };
+                (*peer, peer_stats)
+            })
+            .collect();
+        let stats = FederationStats { epoch_count, peers };
+        let signature_share = self.cfg.private.epoch_sks.0.sign(stats.message());
+
+        FederationStatsShare {
+            stats,
+            epoch_pk_set: self.cfg.consensus.epoch_pk_set.clone(),
+            signature_share: SerdeSignatureShare(signature_share),
+        }
+    }
+
+    /// Stores the price of one bitcoin in `currency` reported by the guardian's
+    /// price oracle, replacing the previous one
+    pub async fn post_exchange_rate(&self, currency: String, price_cents: u64) -> ApiResult<()> {
+        if currency.len() > MAX_CURRENCY_CODE_LEN {
+            return Err(ApiError::bad_request(format!(
+                "Currency code is longer than {MAX_CURRENCY_CODE_LEN} bytes"
+            )));
+        }
+
+        let rate = ExchangeRate {
+            currency,
+            price_cents,
+            timestamp: fedimint_core::time::now(),
+        };
+
+        let mut dbtx = self.db.begin_transaction().await;
+        dbtx.insert_entry(&ExchangeRateKey(rate.currency.clone()), &rate)
+            .await;
+        dbtx.commit_tx().await;
+
+        Ok(())
+    }
+
+    /// Signs the latest price posted for `currency` with our epoch key share
+    pub async fn get_exchange_rate_share(&self, currency: &str) -> ExchangeRateShare {
+        let rate = self
+            .db
+            .begin_transaction()
+            .await
+            .get_value(&ExchangeRateKey(currency.to_owned()))
+            .await;
+        let signed_rate = rate.map(|rate| {
+            let signature_share = self.cfg.private.epoch_sks.0.sign(rate.message());
+            (rate, SerdeSignatureShare(signature_share))
+        });
+
+        ExchangeRateShare {
+            epoch_pk_set: self.cfg.consensus.epoch_pk_set.clone(),
+            signed_rate,
+        }
+    }
+
+    async fn audit_modules(&self) -> (Audit, HashMap<ModuleInstanceId, String>) {
         let mut dbtx = self.db.begin_transaction().await;
         let mut audit = Audit::default();
         let mut module_instance_id_to_kind: HashMap<ModuleInstanceId, String> = HashMap::new();
@@ -405,10 +644,7 @@ impl ConsensusApi {
                 )
                 .await
         }
-        Ok(AuditSummary::from_audit(
-            &audit,
-            &module_instance_id_to_kind,
-        ))
+        (audit, module_instance_id_to_kind)
     }
 
     async fn handle_backup_request(
@@ -514,6 +750,20 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(tx_id)
             }
         },
+        api_endpoint! {
+            VALIDATE_TRANSACTION_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, serde_transaction: SerdeTransaction| -> () {
+                let transaction = serde_transaction
+                    .try_into_inner(&fedimint.modules.decoder_registry())
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+                fedimint.validate_transaction(&transaction)
+                    .await
+                    .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+                Ok(())
+            }
+        },
         api_endpoint! {
             WAIT_TRANSACTION_ENDPOINT,
             async |fedimint: &ConsensusApi, _context, tx_hash: TransactionId| -> TransactionId {
@@ -526,6 +776,12 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(tx_hash)
             }
         },
+        api_endpoint! {
+            AWAIT_TRANSACTION_STATUS_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, txid: TransactionId| -> TransactionStatus {
+                Ok(fedimint.await_transaction_status(txid).await)
+            }
+        },
         api_endpoint! {
             AWAIT_OUTPUT_OUTCOME_ENDPOINT,
             async |fedimint: &ConsensusApi, _context, outpoint: OutPoint| -> SerdeOutputOutcome {
@@ -563,6 +819,19 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(fedimint.cfg.consensus.consensus_hash())
             }
         },
+        api_endpoint! {
+            CONSTITUTION_ENDPOINT,
+            async |fedimint: &ConsensusApi, context, _v: ()| -> Constitution {
+                let (text, version) = fedimint.client_cfg.constitution()
+                    .ok_or_else(|| ApiError::not_found("Federation has no constitution".to_string()))?;
+                let threshold_signature = context.wait_key_exists(ClientConfigSignatureKey).await;
+                Ok(Constitution {
+                    text: text.to_owned(),
+                    version,
+                    threshold_signature,
+                })
+            }
+        },
         api_endpoint! {
             STATUS_ENDPOINT,
             async |fedimint: &ConsensusApi, _context, _v: ()| -> StatusResponse {
@@ -594,6 +863,42 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok((&fedimint.await_signed_block(index).await).into())
             }
         },
+        api_endpoint! {
+            EPOCH_COMMITMENT_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, epoch: u64| -> SerdeModuleEncoding<EpochCommitment> {
+                Ok((&fedimint.epoch_commitment(epoch).await).into())
+            }
+        },
+        api_endpoint! {
+            CONSENSUS_ROUND_TRIP_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> Option<ConsensusMeasurement> {
+                Ok(fedimint.measure_consensus_round_trip())
+            }
+        },
+        api_endpoint! {
+            AVERAGE_SESSION_DURATION_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, num_sessions: u64| -> Option<Duration> {
+                Ok(fedimint.average_session_duration(num_sessions))
+            }
+        },
+        api_endpoint! {
+            EPOCH_METRICS_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, epoch: u64| -> BTreeMap<ModuleInstanceId, EpochMetrics> {
+                Ok(fedimint.get_epoch_metrics(epoch))
+            }
+        },
+        api_endpoint! {
+            CONFIG_DIFF_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, known_version: u32| -> Option<ConfigDiff> {
+                Ok(fedimint.get_config_diff(known_version).await)
+            }
+        },
+        api_endpoint! {
+            NODE_INFO_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> NodeInfo {
+                Ok(fedimint.get_node_info().await)
+            }
+        },
         api_endpoint! {
             AUDIT_ENDPOINT,
             async |fedimint: &ConsensusApi, context, _v: ()| -> AuditSummary {
@@ -601,6 +906,38 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                 Ok(fedimint.get_federation_audit().await?)
             }
         },
+        api_endpoint! {
+            BALANCE_SHEET_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> BalanceSheetShare {
+                Ok(fedimint.get_balance_sheet_share().await)
+            }
+        },
+        api_endpoint! {
+            MODULE_AUDIT_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, module_instance_id: ModuleInstanceId| -> ModuleAuditShare {
+                fedimint.get_module_audit_share(module_instance_id).await
+            }
+        },
+        api_endpoint! {
+            FEDERATION_STATS_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, _v: ()| -> FederationStatsShare {
+                Ok(fedimint.get_federation_stats_share().await)
+            }
+        },
+        api_endpoint! {
+            POST_EXCHANGE_RATE_ENDPOINT,
+            async |fedimint: &ConsensusApi, context, params: (String, u64)| -> () {
+                check_auth(context)?;
+                let (currency, price_cents) = params;
+                fedimint.post_exchange_rate(currency, price_cents).await
+            }
+        },
+        api_endpoint! {
+            EXCHANGE_RATE_ENDPOINT,
+            async |fedimint: &ConsensusApi, _context, currency: String| -> ExchangeRateShare {
+                Ok(fedimint.get_exchange_rate_share(&currency).await)
+            }
+        },
         api_endpoint! {
             GET_VERIFY_CONFIG_HASH_ENDPOINT,
             async |fedimint: &ConsensusApi, context, _v: ()| -> BTreeMap<PeerId, sha256::Hash> {
@@ -247,7 +247,7 @@ pub fn parse_host_port(url: SafeUrl) -> anyhow::Result<String> {
 /// Fake network stack used in tests
 #[allow(unused_imports)]
 pub mod mock {
-    use std::collections::HashMap;
+    use std::collections::{BTreeSet, HashMap};
     use std::fmt::Debug;
     use std::future::Future;
     use std::net::SocketAddr;
@@ -260,7 +260,7 @@ pub mod mock {
     use fedimint_core::task::{sleep, spawn};
     use fedimint_core::util::SafeUrl;
     use fedimint_core::{task, PeerId};
-    use futures::{pin_mut, FutureExt, SinkExt, Stream, StreamExt};
+    use futures::{pin_mut, FutureExt, Sink, SinkExt, Stream, StreamExt};
     use rand::Rng;
     use tokio::io::{
         AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf,
@@ -271,7 +271,7 @@ pub mod mock {
     use tracing::error;
 
     use crate::net::connect::{parse_host_port, ConnectResult, Connector};
-    use crate::net::framed::{BidiFramed, FramedTransport};
+    use crate::net::framed::{AnyFramedTransport, BidiFramed, FramedTransport};
 
     struct UnreliableDuplexStream {
         inner: DuplexStream,
@@ -485,21 +485,36 @@ pub mod mock {
         }
     }
 
+    #[derive(Clone)]
     pub struct MockNetwork {
         clients: Arc<Mutex<HashMap<String, Sender<UnreliableDuplexStream>>>>,
+        partition: Arc<std::sync::Mutex<Partition>>,
+        packet_loss: Arc<std::sync::Mutex<FailureRate>>,
     }
 
     pub struct MockConnector {
         id: PeerId,
         clients: Arc<Mutex<HashMap<String, Sender<UnreliableDuplexStream>>>>,
+        partition: Arc<std::sync::Mutex<Partition>>,
+        packet_loss: Arc<std::sync::Mutex<FailureRate>>,
         reliability: StreamReliability,
     }
 
+    /// The peers that are cut off from the rest of the network and the open
+    /// streams between two peers, which break once one of them is cut off
+    #[derive(Default)]
+    struct Partition {
+        isolated: BTreeSet<PeerId>,
+        streams: Vec<(PeerId, PeerId, CancellationToken)>,
+    }
+
     impl MockNetwork {
         #[allow(clippy::new_without_default)]
         pub fn new() -> MockNetwork {
             MockNetwork {
                 clients: Arc::new(Default::default()),
+                partition: Arc::new(Default::default()),
+                packet_loss: Arc::new(std::sync::Mutex::new(FailureRate::new(0.0))),
             }
         }
 
@@ -507,9 +522,43 @@ pub mod mock {
             MockConnector {
                 id,
                 clients: self.clients.clone(),
+                partition: self.partition.clone(),
+                packet_loss: self.packet_loss.clone(),
                 reliability,
             }
         }
+
+        /// Silently drops `loss_rate` of the messages sent between any two
+        /// peers from now on, unlike [`StreamReliability`] the connections
+        /// stay open
+        pub fn set_packet_loss(&self, loss_rate: f64) {
+            *self.packet_loss.lock().expect("Packet loss lock poisoned") =
+                FailureRate::new(loss_rate);
+        }
+
+        /// Cuts `peers` off from all other peers until [`Self::heal`] is
+        /// called, breaking their open connections
+        pub fn isolate(&self, peers: &[PeerId]) {
+            let mut partition = self.partition.lock().expect("Partition lock poisoned");
+            partition.isolated.extend(peers);
+
+            let isolated = partition.isolated.clone();
+            partition.streams.retain(|(from, to, broken)| {
+                if isolated.contains(from) || isolated.contains(to) {
+                    broken.cancel();
+                }
+                !broken.is_cancelled()
+            });
+        }
+
+        /// Reconnects all peers cut off by [`Self::isolate`]
+        pub fn heal(&self) {
+            self.partition
+                .lock()
+                .expect("Partition lock poisoned")
+                .isolated
+                .clear();
+        }
     }
 
     #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -636,7 +685,14 @@ pub mod mock {
     where
         M: Debug + serde::Serialize + serde::de::DeserializeOwned + Send + Unpin + 'static,
     {
-        async fn connect_framed(&self, destination: SafeUrl, _peer: PeerId) -> ConnectResult<M> {
+        async fn connect_framed(&self, destination: SafeUrl, peer: PeerId) -> ConnectResult<M> {
+            {
+                let partition = self.partition.lock().expect("Partition lock poisoned");
+                if partition.isolated.contains(&self.id) || partition.isolated.contains(&peer) {
+                    return Err(anyhow!("Peers {} and {peer} are partitioned", self.id));
+                }
+            }
+
             let mut clients_lock = self.clients.try_lock().map_err(|e| {
                 anyhow!("Mock network mutex busy or poisoned, the network stack will re-try anyway: {e:?}")
             })?;
@@ -644,6 +700,15 @@ pub mod mock {
                 let (stream_our, stream_theirs) = tokio::io::duplex(43_689);
                 let mut stream_our = UnreliableDuplexStream::new(stream_our, self.reliability);
                 let stream_theirs = UnreliableDuplexStream::new(stream_theirs, self.reliability);
+                {
+                    let mut partition = self.partition.lock().expect("Partition lock poisoned");
+                    partition
+                        .streams
+                        .push((self.id, peer, stream_our.broken.clone()));
+                    partition
+                        .streams
+                        .push((self.id, peer, stream_theirs.broken.clone()));
+                }
                 client.send(stream_theirs).await?;
                 let peer = do_handshake(self.id, &mut stream_our).await?;
                 let framed = BidiFramed::<
@@ -652,7 +717,7 @@ pub mod mock {
                     ReadHalf<UnreliableDuplexStream>,
                 >::new(stream_our)
                 .into_dyn();
-                Ok((peer, framed))
+                Ok((peer, LossyFramed::new(framed, self.packet_loss.clone())))
             } else {
                 return Err(anyhow::anyhow!("can't connect"));
             }
@@ -676,7 +741,9 @@ pub mod mock {
             }
 
             let our_id = self.id;
+            let packet_loss = self.packet_loss.clone();
             let stream = futures::stream::unfold(receive, move |mut receive| {
+                let packet_loss = packet_loss.clone();
                 Box::pin(async move {
                     let mut connection = receive.recv().await.unwrap();
                     let peer = match do_handshake(our_id, &mut connection).await {
@@ -692,13 +759,94 @@ pub mod mock {
                         )
                         .into_dyn();
 
-                    Some((Ok((peer, framed)), receive))
+                    Some((Ok((peer, LossyFramed::new(framed, packet_loss))), receive))
                 })
             });
             Ok(Box::pin(stream))
         }
     }
 
+    /// Framed transport that drops messages sent while the packet loss of the
+    /// [`MockNetwork`] is set
+    struct LossyFramed<M> {
+        inner: AnyFramedTransport<M>,
+        packet_loss: Arc<std::sync::Mutex<FailureRate>>,
+    }
+
+    impl<M> LossyFramed<M>
+    where
+        M: Send + 'static,
+    {
+        fn new(
+            inner: AnyFramedTransport<M>,
+            packet_loss: Arc<std::sync::Mutex<FailureRate>>,
+        ) -> AnyFramedTransport<M> {
+            Box::new(LossyFramed { inner, packet_loss })
+        }
+    }
+
+    impl<M> Sink<M> for LossyFramed<M> {
+        type Error = anyhow::Error;
+
+        fn poll_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready_unpin(cx)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: M) -> Result<(), Self::Error> {
+            if self
+                .packet_loss
+                .lock()
+                .expect("Packet loss lock poisoned")
+                .random_fail()
+            {
+                tracing::debug!("Dropping message on lossy stream");
+                return Ok(());
+            }
+            self.inner.start_send_unpin(item)
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            self.inner.poll_flush_unpin(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            self.inner.poll_close_unpin(cx)
+        }
+    }
+
+    impl<M> Stream for LossyFramed<M> {
+        type Item = Result<M, anyhow::Error>;
+
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Self::Item>> {
+            self.inner.poll_next_unpin(cx)
+        }
+    }
+
+    impl<M> FramedTransport<M> for LossyFramed<M> {
+        /// The halves bypass the packet loss, which only applies to messages
+        /// sent through the [`Sink`] implementation
+        fn borrow_split(
+            &mut self,
+        ) -> (
+            &'_ mut (dyn Sink<M, Error = anyhow::Error> + Send + Unpin),
+            &'_ mut (dyn Stream<Item = Result<M, anyhow::Error>> + Send + Unpin),
+        ) {
+            self.inner.borrow_split()
+        }
+    }
+
     async fn do_handshake<S>(our_id: PeerId, stream: &mut S) -> Result<PeerId, anyhow::Error>
     where
         S: AsyncRead + AsyncWrite + Unpin,
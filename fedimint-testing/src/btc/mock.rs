@@ -25,7 +25,7 @@ use fedimint_core::{Amount, Feerate};
 use rand::rngs::OsRng;
 use tracing::debug;
 
-use super::BitcoinTest;
+use super::{AddressType, BitcoinTest};
 
 #[derive(Debug, Clone)]
 pub struct FakeBitcoinFactory {
@@ -209,6 +209,26 @@ impl BitcoinTest for FakeBitcoinTest {
         Address::p2wpkh(&bitcoin::PublicKey::new(public_key), Network::Regtest).unwrap()
     }
 
+    async fn get_new_address_of_type(&self, address_type: AddressType) -> Address {
+        let ctx = bitcoin::secp256k1::Secp256k1::new();
+        let (_, public_key) = ctx.generate_keypair(&mut OsRng);
+
+        match address_type {
+            AddressType::Legacy => {
+                Address::p2pkh(&bitcoin::PublicKey::new(public_key), Network::Regtest)
+            }
+            AddressType::SegwitV0 => {
+                Address::p2wpkh(&bitcoin::PublicKey::new(public_key), Network::Regtest).unwrap()
+            }
+            AddressType::Taproot => Address::p2tr(
+                &ctx,
+                public_key.x_only_public_key().0,
+                None,
+                Network::Regtest,
+            ),
+        }
+    }
+
     async fn mine_block_and_get_received(&self, address: &Address) -> Amount {
         self.mine_blocks(1).await;
         let sats = self
@@ -253,6 +273,32 @@ impl BitcoinTest for FakeBitcoinTest {
             return fee;
         }
     }
+
+    async fn list_unspent_for_address(
+        &self,
+        address: &Address,
+    ) -> Vec<(OutPoint, bitcoin::Amount)> {
+        let blocks = self.blocks.lock().unwrap().clone();
+        let txs = blocks.iter().flat_map(|block| block.txdata.iter());
+
+        let mut unspent = BTreeMap::new();
+        for tx in txs.clone() {
+            for (vout, output) in tx.output.iter().enumerate() {
+                if output.script_pubkey == address.payload.script_pubkey() {
+                    let outpoint = OutPoint {
+                        txid: tx.txid(),
+                        vout: vout as u32,
+                    };
+                    unspent.insert(outpoint, bitcoin::Amount::from_sat(output.value));
+                }
+            }
+        }
+        for input in txs.flat_map(|tx| tx.input.iter()) {
+            unspent.remove(&input.previous_output);
+        }
+
+        unspent.into_iter().collect()
+    }
 }
 
 #[async_trait]
@@ -271,6 +317,12 @@ impl IBitcoindRpc for FakeBitcoinTest {
             .block_hash())
     }
 
+    async fn get_block(&self, hash: &BlockHash) -> BitcoinRpcResult<Block> {
+        let blocks = self.blocks.lock().unwrap();
+        let block = blocks.iter().find(|block| block.block_hash() == *hash);
+        Ok(block.ok_or(format_err!("No block found"))?.clone())
+    }
+
     async fn get_fee_rate(&self, _confirmation_target: u16) -> BitcoinRpcResult<Option<Feerate>> {
         Ok(None)
     }
@@ -2,10 +2,21 @@ pub mod mock;
 pub mod real;
 
 use async_trait::async_trait;
-use bitcoin::{Address, Transaction, Txid};
+use bitcoin::{Address, OutPoint, Transaction, Txid};
 use fedimint_core::txoproof::TxOutProof;
 use fedimint_core::Amount;
 
+/// The kind of address returned by [`BitcoinTest::get_new_address_of_type`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    /// Base58 encoded P2PKH address
+    Legacy,
+    /// Bech32 encoded P2WPKH address
+    SegwitV0,
+    /// Bech32m encoded P2TR address
+    Taproot,
+}
+
 #[async_trait]
 pub trait BitcoinTest {
     /// Make the underlying instance act as if it was exclusively available
@@ -35,10 +46,18 @@ pub trait BitcoinTest {
     /// Returns a new address.
     async fn get_new_address(&self) -> Address;
 
+    /// Returns a new address of the given type.
+    async fn get_new_address_of_type(&self, address_type: AddressType) -> Address;
+
     /// Mine a block to include any pending transactions then get the amount
     /// received to an address
     async fn mine_block_and_get_received(&self, address: &Address) -> Amount;
 
     /// Waits till tx is found in mempool and returns the fees
     async fn get_mempool_tx_fee(&self, txid: &Txid) -> Amount;
+
+    /// Returns the confirmed outputs paying to `address` that are not spent
+    /// by a confirmed transaction
+    async fn list_unspent_for_address(&self, address: &Address)
+        -> Vec<(OutPoint, bitcoin::Amount)>;
 }
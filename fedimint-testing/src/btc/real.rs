@@ -4,7 +4,8 @@ use std::time::Duration;
 
 use anyhow::Context;
 use async_trait::async_trait;
-use bitcoin::{Address, Transaction, Txid};
+use bitcoin::{Address, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::json::ScanTxOutRequest;
 use bitcoincore_rpc::{Client, RpcApi};
 use fedimint_bitcoind::DynBitcoindRpc;
 use fedimint_core::encoding::Decodable;
@@ -17,7 +18,7 @@ use fedimint_logging::LOG_TEST;
 use lazy_static::lazy_static;
 use tracing::{debug, trace};
 
-use crate::btc::BitcoinTest;
+use crate::btc::{AddressType, BitcoinTest};
 
 lazy_static! {
     /// Global lock we use to isolate tests that need exclusive control over shared `bitcoind`
@@ -140,6 +141,17 @@ impl BitcoinTest for RealBitcoinTestNoLock {
         self.client.get_new_address(None, None).expect(Self::ERROR)
     }
 
+    async fn get_new_address_of_type(&self, address_type: AddressType) -> Address {
+        let address_type = match address_type {
+            AddressType::Legacy => bitcoincore_rpc::json::AddressType::Legacy,
+            AddressType::SegwitV0 => bitcoincore_rpc::json::AddressType::Bech32,
+            AddressType::Taproot => bitcoincore_rpc::json::AddressType::Bech32m,
+        };
+        self.client
+            .get_new_address(None, Some(address_type))
+            .expect(Self::ERROR)
+    }
+
     async fn get_mempool_tx_fee(&self, txid: &Txid) -> Amount {
         loop {
             match self.client.get_mempool_entry(txid) {
@@ -151,6 +163,28 @@ impl BitcoinTest for RealBitcoinTestNoLock {
             }
         }
     }
+
+    async fn list_unspent_for_address(
+        &self,
+        address: &Address,
+    ) -> Vec<(OutPoint, bitcoin::Amount)> {
+        // bitcoind's wallet doesn't watch the address, so we scan the UTXO set
+        self.client
+            .scan_tx_out_set_blocking(&[ScanTxOutRequest::Single(format!("addr({address})"))])
+            .expect(Self::ERROR)
+            .unspents
+            .into_iter()
+            .map(|utxo| {
+                (
+                    OutPoint {
+                        txid: utxo.txid,
+                        vout: utxo.vout,
+                    },
+                    utxo.amount,
+                )
+            })
+            .collect()
+    }
 }
 
 /// Fixture implementing bitcoin node under test by talking to a `bitcoind` -
@@ -230,6 +264,11 @@ impl BitcoinTest for RealBitcoinTest {
         self.inner.get_new_address().await
     }
 
+    async fn get_new_address_of_type(&self, address_type: AddressType) -> Address {
+        let _lock = self.lock_exclusive().await;
+        self.inner.get_new_address_of_type(address_type).await
+    }
+
     async fn mine_block_and_get_received(&self, address: &Address) -> Amount {
         let _lock = self.lock_exclusive().await;
         self.inner.mine_block_and_get_received(address).await
@@ -239,6 +278,14 @@ impl BitcoinTest for RealBitcoinTest {
         let _lock = self.lock_exclusive().await;
         self.inner.get_mempool_tx_fee(txid).await
     }
+
+    async fn list_unspent_for_address(
+        &self,
+        address: &Address,
+    ) -> Vec<(OutPoint, bitcoin::Amount)> {
+        let _lock = self.lock_exclusive().await;
+        self.inner.list_unspent_for_address(address).await
+    }
 }
 
 #[async_trait]
@@ -270,6 +317,10 @@ impl BitcoinTest for RealBitcoinTestLocked {
         self.inner.get_new_address().await
     }
 
+    async fn get_new_address_of_type(&self, address_type: AddressType) -> Address {
+        self.inner.get_new_address_of_type(address_type).await
+    }
+
     async fn mine_block_and_get_received(&self, address: &Address) -> Amount {
         self.inner.mine_block_and_get_received(address).await
     }
@@ -277,4 +328,11 @@ impl BitcoinTest for RealBitcoinTestLocked {
     async fn get_mempool_tx_fee(&self, txid: &Txid) -> Amount {
         self.inner.get_mempool_tx_fee(txid).await
     }
+
+    async fn list_unspent_for_address(
+        &self,
+        address: &Address,
+    ) -> Vec<(OutPoint, bitcoin::Amount)> {
+        self.inner.list_unspent_for_address(address).await
+    }
 }
@@ -1,10 +1,16 @@
+use std::collections::BTreeSet;
 use std::fs::read_dir;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{env, fs, io};
 
 use anyhow::{bail, format_err, Context};
-use fedimint_core::db::{Database, DatabaseTransaction};
+use async_trait::async_trait;
+use fedimint_core::db::{
+    Database, DatabaseTransaction, IDatabase, IDatabaseTransactionOps,
+    ISingleUseDatabaseTransaction, PrefixStream,
+};
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_rocksdb::RocksDb;
 use futures::future::BoxFuture;
@@ -182,3 +188,191 @@ pub fn copy_directory(src: &Path, dst: &Path) -> io::Result<()> {
 
     Ok(())
 }
+
+/// Kind of storage operation that should fail when injecting an error with
+/// [`StorageFaultInjector::inject`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StorageErrorType {
+    /// Reading a key or prefix fails. Fedimint treats this as unrecoverable.
+    Read,
+    /// Inserting or removing a key fails. Fedimint treats this as
+    /// unrecoverable.
+    Write,
+    /// Committing a transaction fails, discarding all of its writes
+    Commit,
+}
+
+#[derive(Debug)]
+struct StorageFault {
+    error_type: StorageErrorType,
+    remaining_ops: usize,
+    /// Only count commits of transactions that wrote a key with this prefix
+    key_prefix: Option<u8>,
+}
+
+/// Handle to make a [`FaultInjectingDatabase`] fail a single operation
+#[derive(Debug, Clone, Default)]
+pub struct StorageFaultInjector(Arc<std::sync::Mutex<Option<StorageFault>>>);
+
+impl StorageFaultInjector {
+    /// Lets the database succeed `trigger_after_ops` further operations of
+    /// type `error_type` and fail the next one. Replaces any fault that has
+    /// not been triggered yet.
+    pub fn inject(&self, error_type: StorageErrorType, trigger_after_ops: usize) {
+        *self.0.lock().expect("Lock poisoned") = Some(StorageFault {
+            error_type,
+            remaining_ops: trigger_after_ops,
+            key_prefix: None,
+        });
+    }
+
+    /// Like [`Self::inject`] with [`StorageErrorType::Commit`], but only
+    /// counts commits of transactions that wrote a key starting with
+    /// `key_prefix`
+    pub fn inject_commit_of_prefix(&self, key_prefix: u8, trigger_after_ops: usize) {
+        *self.0.lock().expect("Lock poisoned") = Some(StorageFault {
+            error_type: StorageErrorType::Commit,
+            remaining_ops: trigger_after_ops,
+            key_prefix: Some(key_prefix),
+        });
+    }
+
+    /// Returns true if the injected fault has not been triggered yet
+    pub fn is_pending(&self) -> bool {
+        self.0.lock().expect("Lock poisoned").is_some()
+    }
+
+    fn check(&self, op: StorageErrorType) -> anyhow::Result<()> {
+        self.check_written(op, &BTreeSet::new())
+    }
+
+    fn check_written(
+        &self,
+        op: StorageErrorType,
+        written_prefixes: &BTreeSet<u8>,
+    ) -> anyhow::Result<()> {
+        let mut fault = self.0.lock().expect("Lock poisoned");
+
+        if let Some(pending) = fault.as_mut().filter(|fault| {
+            fault.error_type == op
+                && fault
+                    .key_prefix
+                    .map_or(true, |prefix| written_prefixes.contains(&prefix))
+        }) {
+            if pending.remaining_ops == 0 {
+                *fault = None;
+                bail!("Injected {op:?} storage error");
+            }
+
+            pending.remaining_ops -= 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Database wrapper that fails operations on demand of its
+/// [`StorageFaultInjector`]
+#[derive(Debug)]
+pub struct FaultInjectingDatabase<DB> {
+    inner: DB,
+    injector: StorageFaultInjector,
+}
+
+impl<DB: IDatabase> FaultInjectingDatabase<DB> {
+    pub fn new(inner: DB, injector: StorageFaultInjector) -> Self {
+        Self { inner, injector }
+    }
+}
+
+#[async_trait]
+impl<DB: IDatabase> IDatabase for FaultInjectingDatabase<DB> {
+    async fn begin_transaction<'a>(&'a self) -> Box<dyn ISingleUseDatabaseTransaction<'a>> {
+        Box::new(FaultInjectingTransaction {
+            inner: self.inner.begin_transaction().await,
+            injector: &self.injector,
+            written_prefixes: BTreeSet::new(),
+        })
+    }
+}
+
+struct FaultInjectingTransaction<'a> {
+    inner: Box<dyn ISingleUseDatabaseTransaction<'a>>,
+    injector: &'a StorageFaultInjector,
+    /// First bytes of all keys written by this transaction
+    written_prefixes: BTreeSet<u8>,
+}
+
+impl<'a> FaultInjectingTransaction<'a> {
+    fn record_write(&mut self, key: &[u8]) {
+        if let Some(prefix) = key.first() {
+            self.written_prefixes.insert(*prefix);
+        }
+    }
+}
+
+#[async_trait]
+impl<'a> IDatabaseTransactionOps<'a> for FaultInjectingTransaction<'a> {
+    async fn raw_insert_bytes(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        self.injector.check(StorageErrorType::Write)?;
+        self.record_write(key);
+        self.inner.raw_insert_bytes(key, value).await
+    }
+
+    async fn raw_get_bytes(&mut self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.injector.check(StorageErrorType::Read)?;
+        self.inner.raw_get_bytes(key).await
+    }
+
+    async fn raw_remove_entry(&mut self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.injector.check(StorageErrorType::Write)?;
+        self.record_write(key);
+        self.inner.raw_remove_entry(key).await
+    }
+
+    async fn raw_find_by_prefix(&mut self, key_prefix: &[u8]) -> anyhow::Result<PrefixStream<'_>> {
+        self.injector.check(StorageErrorType::Read)?;
+        self.inner.raw_find_by_prefix(key_prefix).await
+    }
+
+    async fn raw_find_by_prefix_sorted_descending(
+        &mut self,
+        key_prefix: &[u8],
+    ) -> anyhow::Result<PrefixStream<'_>> {
+        self.injector.check(StorageErrorType::Read)?;
+        self.inner
+            .raw_find_by_prefix_sorted_descending(key_prefix)
+            .await
+    }
+
+    async fn raw_remove_by_prefix(&mut self, key_prefix: &[u8]) -> anyhow::Result<()> {
+        self.injector.check(StorageErrorType::Write)?;
+        self.record_write(key_prefix);
+        self.inner.raw_remove_by_prefix(key_prefix).await
+    }
+
+    async fn rollback_tx_to_savepoint(&mut self) -> anyhow::Result<()> {
+        self.inner.rollback_tx_to_savepoint().await
+    }
+
+    async fn set_tx_savepoint(&mut self) -> anyhow::Result<()> {
+        self.inner.set_tx_savepoint().await
+    }
+}
+
+#[async_trait]
+impl<'a> ISingleUseDatabaseTransaction<'a> for FaultInjectingTransaction<'a> {
+    async fn commit_tx(&mut self) -> anyhow::Result<()> {
+        self.injector
+            .check_written(StorageErrorType::Commit, &self.written_prefixes)?;
+        self.inner.commit_tx().await
+    }
+
+    fn add_notification_key(&mut self, key: &[u8]) -> anyhow::Result<()> {
+        self.inner.add_notification_key(key)
+    }
+}
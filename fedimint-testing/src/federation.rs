@@ -1,38 +1,107 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, ensure, Context};
 
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::secret::PlainRootSecretStrategy;
 use fedimint_client::{Client, ClientBuilder};
 use fedimint_core::admin_client::{ConfigGenParamsConsensus, PeerServerParams};
-use fedimint_core::api::InviteCode;
+use fedimint_core::api::{ConsensusMeasurement, InviteCode, NodeInfo};
+use fedimint_core::block::{consensus_hash_sha256, SchnorrSignature, SignedBlock};
 use fedimint_core::config::{
     ClientConfig, FederationId, ServerModuleConfigGenParamsRegistry, ServerModuleInitRegistry,
-    META_FEDERATION_NAME_KEY,
+    META_CONSTITUTION_KEY, META_FEDERATION_NAME_KEY,
 };
-use fedimint_core::core::ModuleInstanceId;
+use fedimint_core::core::{DynInput, ModuleInstanceId, ModuleKind};
 use fedimint_core::db::mem_impl::MemDatabase;
 use fedimint_core::db::Database;
-use fedimint_core::module::ApiAuth;
-use fedimint_core::task::TaskGroup;
-use fedimint_core::PeerId;
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::module::audit::{AuditSummary, BalanceSheet};
+use fedimint_core::module::{ApiAuth, ApiVersion, MultiApiVersion};
+use fedimint_core::task::{sleep, timeout, TaskGroup};
+use fedimint_core::transaction::Transaction;
+use fedimint_core::{Amount, NumPeers, OutPoint, PeerId};
 use fedimint_logging::LOG_TEST;
+use fedimint_server::atomic_broadcast::keychain::Keychain;
 use fedimint_server::config::api::ConfigGenParamsLocal;
 use fedimint_server::config::{gen_cert_and_key, ConfigGenParams, ServerConfig};
 use fedimint_server::consensus::server::ConsensusServer;
+use fedimint_server::consensus::FundingVerifier;
+use fedimint_server::db::AcceptedTransactionKey;
+use fedimint_server::net::api::ConsensusApi;
 use fedimint_server::net::connect::mock::{MockNetwork, StreamReliability};
 use fedimint_server::net::connect::{parse_host_port, Connector};
 use fedimint_server::net::peers::DelayCalculator;
 use fedimint_server::FedimintServer;
+use ln_gateway::Gateway;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use tokio_rustls::rustls;
-use tracing::info;
+use tracing::{debug, info};
+
+use crate::db::{FaultInjectingDatabase, StorageErrorType, StorageFaultInjector};
+
+/// Constitution the guardians of test federations agree on
+pub const TEST_CONSTITUTION: &str = "Guardians keep their keys offline and upgrade together";
+
+/// Upper bound on the number of fuzzed items submitted per epoch
+const MAX_FUZZED_ITEMS_PER_EPOCH: usize = 8;
+
+/// Time after which a peer is considered stuck while fuzzing
+const FUZZED_EPOCH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time after which a peer is considered stuck while its mempool is spammed
+const SPAMMED_EPOCH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time after which a peer is considered stuck while messages are dropped
+const LOSSY_EPOCH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time the peers get to pass their pending submissions on to consensus
+const PENDING_SUBMISSIONS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time an expected item gets to show up in the pending proposals of the peers
+const PENDING_PROPOSALS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time a peer gets to catch up with the epochs of the other peers
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Time the gateway gets to claim the ecash of a settled payment
+const GATEWAY_SETTLEMENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time lagging guardians get to process the epochs that changed the balance
+/// sheet of the others
+const BALANCE_SHEET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Identifies what spending an input consumes, e.g. the nonces of notes or the
+/// outpoint of a peg-in, see [`crate::fixtures::Fixtures::with_input_spend_keys`]
+pub type InputSpendKeys = fn(&DynInput) -> Vec<Vec<u8>>;
 
 /// Test fixture for a running fedimint federation
 pub struct FederationTest {
     configs: BTreeMap<PeerId, ServerConfig>,
+    params: ServerModuleConfigGenParamsRegistry,
     server_init: ServerModuleInitRegistry,
     client_init: ClientModuleInitRegistry,
     primary_client: ModuleInstanceId,
-    _task: TaskGroup,
+    input_spend_keys: BTreeMap<ModuleKind, InputSpendKeys>,
+    storage_faults: BTreeMap<PeerId, StorageFaultInjector>,
+    consensus_apis: BTreeMap<PeerId, ConsensusApi>,
+    network: MockNetwork,
+    task: TaskGroup,
+    verified_history: tokio::sync::Mutex<VerifiedHistory>,
+}
+
+/// How far [`FederationTest::verify_epoch_history`] got, so every epoch is
+/// only verified once
+#[derive(Default)]
+struct VerifiedHistory {
+    next_epoch: u64,
+    /// The spend keys of all inputs accepted so far, by module instance
+    spent_inputs: BTreeSet<(ModuleInstanceId, Vec<u8>)>,
 }
 
 impl FederationTest {
@@ -79,6 +148,1184 @@ impl FederationTest {
             .federation_id
     }
 
+    /// Returns the configs of all guardians, e.g. to inspect the config of a
+    /// module
+    pub fn configs(&self) -> &BTreeMap<PeerId, ServerConfig> {
+        &self.configs
+    }
+
+    /// Returns the API of the guardian `peer_id`, its database can be used to
+    /// inspect the state of a module
+    pub fn consensus_api(&self, peer_id: PeerId) -> &ConsensusApi {
+        &self.consensus_apis[&peer_id]
+    }
+
+    /// Checks that the inputs of `tx` cover its outputs plus fees, using the
+    /// amounts the modules of `client` (e.g. mint and wallet) assign to them
+    pub fn verify_transaction_balance(client: &Client, tx: &Transaction) -> anyhow::Result<()> {
+        let mut funding_verifier = FundingVerifier::default();
+
+        for input in &tx.inputs {
+            let module = client.get_module_client_dyn(input.module_instance_id())?;
+            funding_verifier.add_input(module.input_amount(input));
+        }
+
+        for output in &tx.outputs {
+            let module = client.get_module_client_dyn(output.module_instance_id())?;
+            funding_verifier.add_output(module.output_amount(output));
+        }
+
+        Ok(funding_verifier.verify_funding()?)
+    }
+
+    /// Asserts that the inputs of `tx` exactly cover its outputs plus fees
+    pub fn assert_balanced_transaction(client: &Client, tx: &Transaction) {
+        if let Err(e) = Self::verify_transaction_balance(client, tx) {
+            panic!("Transaction {} is not balanced: {e}", tx.tx_hash());
+        }
+    }
+
+    /// Summarizes how long the recent consensus sessions of the first peer
+    /// took, see [`ConsensusApi::measure_consensus_round_trip`]
+    pub fn measure_consensus_round_trip(&self) -> Option<ConsensusMeasurement> {
+        self.consensus_apis[&PeerId::from(0)].measure_consensus_round_trip()
+    }
+
+    /// Returns the software and module versions run by the first peer
+    pub async fn get_node_info(&self) -> NodeInfo {
+        self.consensus_apis[&PeerId::from(0)].get_node_info().await
+    }
+
+    /// Starts recording the bytes the API of every guardian receives and sends
+    /// per method until the recording is stopped with
+    /// [`FederationTest::stop_bandwidth_recording`]
+    pub fn start_bandwidth_recording(&self) -> BandwidthRecorder {
+        BandwidthRecorder {
+            start: self.bandwidth_totals(),
+        }
+    }
+
+    /// Reports the bytes used per API method by all guardians combined since
+    /// `recorder` was started
+    pub fn stop_bandwidth_recording(&self, recorder: BandwidthRecorder) -> BandwidthReport {
+        let per_method = self
+            .bandwidth_totals()
+            .into_iter()
+            .map(|(method, bytes)| {
+                let start = recorder.start.get(&method).copied().unwrap_or_default();
+                (method, bytes - start)
+            })
+            .filter(|(_, bytes)| *bytes > 0)
+            .collect();
+
+        BandwidthReport { per_method }
+    }
+
+    /// Starts auditing every guardian after each epoch until the monitoring is
+    /// stopped with [`FederationTest::stop_monitoring`], recording every
+    /// negative balance sheet
+    pub async fn start_balance_sheet_monitoring(&self) -> BalanceSheetMonitor {
+        let apis = self.consensus_apis.clone();
+        let negative = Arc::new(std::sync::Mutex::new(vec![]));
+        let mut task = TaskGroup::new();
+
+        let mut next_epoch = u64::MAX;
+        for api in apis.values() {
+            next_epoch = next_epoch.min(api.fetch_block_count().await);
+        }
+
+        let recorded = negative.clone();
+        task.spawn("balance sheet monitor", move |handle| async move {
+            while !handle.is_shutting_down() {
+                let mut block_count = u64::MAX;
+                for api in apis.values() {
+                    block_count = block_count.min(api.fetch_block_count().await);
+                }
+
+                if block_count <= next_epoch {
+                    sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+
+                let balances = negative_balance_sheets(&apis, next_epoch).await;
+                recorded.lock().expect("Locking failed").extend(balances);
+                next_epoch = block_count;
+            }
+        })
+        .await;
+
+        BalanceSheetMonitor { negative, task }
+    }
+
+    /// Stops `monitor` and fails if any guardian reported a negative balance
+    /// sheet since it was started or reports one now
+    pub async fn stop_monitoring(&self, monitor: BalanceSheetMonitor) -> anyhow::Result<()> {
+        monitor.task.shutdown_join_all(None).await?;
+
+        let epoch = self.consensus_apis[&PeerId::from(0)]
+            .fetch_block_count()
+            .await;
+        let mut negative = monitor.negative.lock().expect("Locking failed").clone();
+        negative.extend(negative_balance_sheets(&self.consensus_apis, epoch).await);
+
+        ensure!(
+            negative.is_empty(),
+            "Guardians reported negative balance sheets: {negative:?}"
+        );
+        Ok(())
+    }
+
+    fn bandwidth_totals(&self) -> BTreeMap<String, usize> {
+        let mut totals = BTreeMap::new();
+        for api in self.consensus_apis.values() {
+            for (method, bytes) in api.api_bandwidth.totals() {
+                *totals.entry(method).or_default() += bytes;
+            }
+        }
+        totals
+    }
+
+    /// Submits `capacity + overflow_count` transactions to the first peer
+    /// while its mempool, limited to `capacity` transactions, is not drained
+    /// by consensus and reports which of them were rejected.
+    ///
+    /// The submitted transactions are empty, so they are valid without any
+    /// funds but all of them have the same id.
+    pub async fn run_with_mempool_full(
+        &self,
+        capacity: usize,
+        overflow_count: usize,
+    ) -> OverflowResult {
+        let (submission_sender, _submission_receiver) = async_channel::bounded(capacity);
+        let api = ConsensusApi {
+            submission_sender,
+            ..self.consensus_apis[&PeerId::from(0)].clone()
+        };
+
+        let mut result = OverflowResult::default();
+        for index in 0..capacity + overflow_count {
+            let transaction = Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                signature: None,
+            };
+            match api.submit_transaction(transaction).await {
+                Ok(()) => result.accepted.push(index),
+                Err(e) => result.rejected.push((index, e)),
+            }
+        }
+
+        result
+    }
+
+    /// Makes the storage layer of `peer` fail the operation of type
+    /// `error_type` that follows the next `trigger_after_ops` ones
+    pub fn inject_storage_error_on_peer(
+        &self,
+        peer: u16,
+        error_type: StorageErrorType,
+        trigger_after_ops: usize,
+    ) {
+        self.storage_faults[&PeerId::from(peer)].inject(error_type, trigger_after_ops);
+    }
+
+    /// Makes the storage layer of `peer` fail the commit that follows the next
+    /// `trigger_after_ops` commits writing a key with prefix `key_prefix`
+    pub fn inject_commit_error_on_peer(&self, peer: u16, key_prefix: u8, trigger_after_ops: usize) {
+        self.storage_faults[&PeerId::from(peer)]
+            .inject_commit_of_prefix(key_prefix, trigger_after_ops);
+    }
+
+    /// Returns true if the storage error injected on `peer` has not been
+    /// triggered yet
+    pub fn storage_error_pending_on_peer(&self, peer: u16) -> bool {
+        self.storage_faults[&PeerId::from(peer)].is_pending()
+    }
+
+    /// Cuts the `peers` off from the rest of the federation until
+    /// [`Self::heal_partition`] is called
+    pub fn partition(&self, peers: &[u16]) {
+        let peers = peers.iter().copied().map(PeerId::from).collect::<Vec<_>>();
+        self.network.isolate(&peers);
+    }
+
+    /// Lets the peers cut off by [`Self::partition`] reconnect
+    pub fn heal_partition(&self) {
+        self.network.heal();
+    }
+
+    /// Asserts that all peers stored the same block for every epoch up to
+    /// `max_epoch`, waiting for peers that didn't reach it yet
+    pub async fn assert_no_consensus_forks(&self, max_epoch: u64) {
+        for epoch in 0..=max_epoch {
+            let mut headers = BTreeMap::new();
+            for (peer_id, api) in &self.consensus_apis {
+                let signed_block = timeout(CATCH_UP_TIMEOUT, api.await_signed_block(epoch))
+                    .await
+                    .unwrap_or_else(|_| panic!("Peer {peer_id} didn't reach epoch {epoch}"));
+                headers.insert(*peer_id, signed_block.block.header(epoch));
+            }
+
+            let (first_peer, header) = headers.iter().next().expect("Has peers");
+            for (peer_id, other_header) in &headers {
+                assert_eq!(
+                    other_header, header,
+                    "Peers {first_peer} and {peer_id} forked at epoch {epoch}"
+                );
+            }
+        }
+    }
+
+    /// Waits for the next `n` epochs to complete and checks after each of
+    /// them that
+    /// * all guardians report the same balance sheet and it is not negative
+    /// * no input was spent by more than one accepted transaction
+    /// * every output of an accepted transaction has an outcome
+    /// * all guardians stored the same validly signed epoch history
+    /// * no guardian lost the connection to any of its peers
+    pub async fn run_n_epochs_and_verify_all_invariants(&self, n: usize) -> anyhow::Result<()> {
+        let mut next_epoch = u64::MAX;
+        for api in self.consensus_apis.values() {
+            next_epoch = next_epoch.min(api.fetch_block_count().await);
+        }
+
+        for epoch in next_epoch..next_epoch + n as u64 {
+            for api in self.consensus_apis.values() {
+                api.await_signed_block(epoch).await;
+            }
+
+            info!(target: LOG_TEST, epoch, "Verifying federation invariants");
+            self.verify_balance_sheets().await?;
+            self.verify_epoch_history(epoch).await?;
+            self.verify_peer_connections().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits for the next `n` epochs to be committed by all guardians and calls
+    /// `observer` with the index and signed block of each of them as soon as
+    /// it is
+    pub async fn run_consensus_epochs_observed(
+        &self,
+        n: usize,
+        observer: impl Fn(u64, &SignedBlock),
+    ) {
+        let mut next_epoch = u64::MAX;
+        for api in self.consensus_apis.values() {
+            next_epoch = next_epoch.min(api.fetch_block_count().await);
+        }
+
+        for epoch in next_epoch..next_epoch + n as u64 {
+            for api in self.consensus_apis.values() {
+                api.await_signed_block(epoch).await;
+            }
+
+            let signed_block = self.consensus_apis[&PeerId::from(0)]
+                .await_signed_block(epoch)
+                .await;
+            observer(epoch, &signed_block);
+        }
+    }
+
+    /// Runs `epochs` epochs and panics if any of them takes longer than
+    /// `max_duration` to be signed by all peers
+    pub async fn assert_epoch_latency_within(&self, epochs: usize, max_duration: Duration) {
+        let mut next_epoch = u64::MAX;
+        for api in self.consensus_apis.values() {
+            next_epoch = next_epoch.min(api.fetch_block_count().await);
+        }
+
+        for epoch in next_epoch..next_epoch + epochs as u64 {
+            let start = Instant::now();
+            for api in self.consensus_apis.values() {
+                api.await_signed_block(epoch).await;
+            }
+
+            let latency = start.elapsed();
+            info!(target: LOG_TEST, epoch, ?latency, "Epoch was signed by all peers");
+            assert!(
+                latency <= max_duration,
+                "Epoch {epoch} took {latency:?}, expected at most {max_duration:?}"
+            );
+        }
+    }
+
+    /// Runs `epochs` epochs and asserts that the config fields at
+    /// `field_paths` still have the values they were generated with on every
+    /// peer
+    ///
+    /// Paths are dot-separated and start either with a field of the consensus
+    /// config (e.g. `epoch_pk_set`) or with the kind of a module (e.g.
+    /// `wallet.network`).
+    pub async fn assert_config_immutable_after_genesis(&self, field_paths: &[&str], epochs: usize) {
+        let genesis = field_paths
+            .iter()
+            .map(|path| (*path, config_field(&self.configs[&PeerId::from(0)], path)))
+            .collect::<Vec<_>>();
+
+        self.run_consensus_epochs_observed(epochs, |_, _| {}).await;
+
+        for (peer_id, api) in &self.consensus_apis {
+            for (path, value) in &genesis {
+                assert_eq!(
+                    &config_field(&api.cfg, path),
+                    value,
+                    "Config field {path} of peer {peer_id} changed after genesis"
+                );
+            }
+        }
+    }
+
+    async fn verify_balance_sheets(&self) -> anyhow::Result<()> {
+        let mut audits = BTreeMap::new();
+        for (peer_id, api) in &self.consensus_apis {
+            let audit = api
+                .get_federation_audit()
+                .await
+                .map_err(|e| anyhow!("Peer {peer_id} failed to audit: {e:?}"))?;
+            audits.insert(*peer_id, audit);
+        }
+
+        let (first_peer, first_audit) = audits.iter().next().expect("Has peers");
+        for (peer_id, audit) in &audits {
+            ensure!(
+                audit.net_assets >= 0,
+                "Balance sheet of peer {peer_id} is negative: {audit:?}"
+            );
+            ensure!(
+                audit == first_audit,
+                "Balance sheets of peers {first_peer} and {peer_id} differ"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks the history of all epochs up to and including `last_epoch` that
+    /// weren't checked by a previous call
+    async fn verify_epoch_history(&self, last_epoch: u64) -> anyhow::Result<()> {
+        let keychain = self.keychain(PeerId::from(0));
+
+        let mut verified = self.verified_history.lock().await;
+        for epoch in verified.next_epoch..=last_epoch {
+            let mut blocks = BTreeMap::new();
+            for (peer_id, api) in &self.consensus_apis {
+                let signed_block = api.await_signed_block(epoch).await;
+                let header = signed_block.block.header(epoch);
+                for (signer, signature) in &signed_block.signatures {
+                    ensure!(
+                        keychain.verify_peer_signature(&header, signature, *signer),
+                        "Peer {peer_id} stored an invalid signature of {signer} for epoch {epoch}"
+                    );
+                }
+                blocks.insert(*peer_id, signed_block.block);
+            }
+
+            let (first_peer, block) = blocks.iter().next().expect("Has peers");
+            for (peer_id, other_block) in &blocks {
+                ensure!(
+                    other_block == block,
+                    "Peers {first_peer} and {peer_id} stored different blocks for epoch {epoch}"
+                );
+            }
+
+            for accepted_item in &block.items {
+                let ConsensusItem::Transaction(tx) = &accepted_item.item else {
+                    continue;
+                };
+                let txid = tx.tx_hash();
+
+                for input in &tx.inputs {
+                    let module_instance_id = input.module_instance_id();
+                    let kind =
+                        &self.configs[&PeerId::from(0)].consensus.modules[&module_instance_id].kind;
+                    let Some(input_spend_keys) = self.input_spend_keys.get(kind) else {
+                        // the inputs of modules without spend keys may repeat
+                        continue;
+                    };
+                    for spend_key in input_spend_keys(input) {
+                        ensure!(
+                            verified
+                                .spent_inputs
+                                .insert((module_instance_id, spend_key)),
+                            "Transaction {txid} in epoch {epoch} spends an input twice"
+                        );
+                    }
+                }
+
+                for (out_idx, output) in tx.outputs.iter().enumerate() {
+                    let out_point = OutPoint {
+                        txid,
+                        out_idx: out_idx as u64,
+                    };
+                    let module_instance_id = output.module_instance_id();
+
+                    for (peer_id, api) in &self.consensus_apis {
+                        let mut dbtx = api.db.begin_transaction().await;
+                        let outcome = api
+                            .modules
+                            .get_expect(module_instance_id)
+                            .output_status(
+                                &mut dbtx.with_module_prefix(module_instance_id),
+                                out_point,
+                                module_instance_id,
+                            )
+                            .await;
+                        ensure!(
+                            outcome.is_some(),
+                            "Peer {peer_id} has no outcome for output {out_point}"
+                        );
+                    }
+                }
+            }
+
+            verified.next_epoch = epoch + 1;
+        }
+
+        Ok(())
+    }
+
+    async fn verify_peer_connections(&self) -> anyhow::Result<()> {
+        for (peer_id, api) in &self.consensus_apis {
+            let status = api
+                .get_federation_status()
+                .await
+                .map_err(|e| anyhow!("Peer {peer_id} failed to report its status: {e:?}"))?;
+            ensure!(
+                status.peers_offline == 0,
+                "Peer {peer_id} lost the connection to {} peers",
+                status.peers_offline
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Makes `proposer_peer` propose `malicious_items` to consensus without
+    /// the checks its API would apply to them and waits for the next `epochs`
+    /// epochs. AlephBFT has no leader, so a byzantine peer can only harm the
+    /// federation through the items it proposes.
+    ///
+    /// Fails unless the other peers reject all of the items while still
+    /// completing the epochs with all invariants holding.
+    pub async fn simulate_byzantine_proposer(
+        &self,
+        proposer_peer: u16,
+        malicious_items: Vec<ConsensusItem>,
+        epochs: usize,
+    ) -> anyhow::Result<()> {
+        let proposer_id = PeerId::from(proposer_peer);
+        let proposer = &self.consensus_apis[&proposer_id];
+        let first_epoch = self
+            .override_proposal(proposer_peer, malicious_items.clone())
+            .await?;
+
+        self.run_n_epochs_and_verify_all_invariants(epochs).await?;
+
+        for epoch in first_epoch..first_epoch + epochs as u64 {
+            let signed_block = proposer.await_signed_block(epoch).await;
+            for accepted_item in &signed_block.block.items {
+                ensure!(
+                    !malicious_items.contains(&accepted_item.item),
+                    "Malicious item proposed by {proposer_id} was accepted in epoch {epoch}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes `peer` propose `items` to consensus without the checks its API
+    /// would apply to them, returns the first epoch they can be part of
+    pub async fn override_proposal(
+        &self,
+        peer: u16,
+        items: Vec<ConsensusItem>,
+    ) -> anyhow::Result<u64> {
+        let peer_id = PeerId::from(peer);
+        let api = &self.consensus_apis[&peer_id];
+        let next_epoch = api.fetch_block_count().await;
+
+        for item in items {
+            api.submission_sender
+                .send(item)
+                .await
+                .map_err(|_| anyhow!("Peer {peer_id} stopped accepting items"))?;
+        }
+
+        Ok(next_epoch)
+    }
+
+    /// Asserts that no item was accepted more than once in `epoch`, no matter
+    /// how often or by how many peers it was proposed
+    pub async fn assert_epoch_items_deduplicated(&self, epoch: u64) {
+        for (peer_id, api) in &self.consensus_apis {
+            let signed_block = timeout(CATCH_UP_TIMEOUT, api.await_signed_block(epoch))
+                .await
+                .unwrap_or_else(|_| panic!("Peer {peer_id} didn't reach epoch {epoch}"));
+
+            let mut item_hashes = BTreeSet::new();
+            for accepted_item in &signed_block.block.items {
+                assert!(
+                    item_hashes.insert(consensus_hash_sha256(&accepted_item.item)),
+                    "Peer {peer_id} accepted item {:?} twice in epoch {epoch}",
+                    accepted_item.item
+                );
+            }
+        }
+    }
+
+    /// Captures the module consensus items every peer would propose for the
+    /// next epoch, without items submitted through the API
+    pub async fn pending_proposals(&self) -> BTreeMap<PeerId, Vec<ConsensusItem>> {
+        let mut proposals = BTreeMap::new();
+
+        for (peer_id, api) in &self.consensus_apis {
+            let mut dbtx = api.db.begin_transaction().await;
+            dbtx.ignore_uncommitted();
+
+            let mut items = Vec::new();
+            for (instance_id, _, module) in api.modules.iter_modules() {
+                items.extend(
+                    module
+                        .consensus_proposal(&mut dbtx.with_module_prefix(instance_id), instance_id)
+                        .await
+                        .into_iter()
+                        .map(ConsensusItem::Module),
+                );
+            }
+
+            proposals.insert(*peer_id, items);
+        }
+
+        proposals
+    }
+
+    /// Panics unless every item of `expected_items` is part of the pending
+    /// proposals of at least a threshold of peers within
+    /// [`PENDING_PROPOSALS_TIMEOUT`]
+    pub async fn assert_pending_epoch_contains(&self, expected_items: &[ConsensusItem]) {
+        let threshold = self.configs.threshold();
+        let missing = |proposals: &BTreeMap<PeerId, Vec<ConsensusItem>>| {
+            expected_items
+                .iter()
+                .filter(|item| {
+                    proposals
+                        .values()
+                        .filter(|items| items.contains(item))
+                        .count()
+                        < threshold
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let proposed = timeout(PENDING_PROPOSALS_TIMEOUT, async {
+            while !missing(&self.pending_proposals().await).is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+
+        assert!(
+            proposed.is_ok(),
+            "Items not proposed by a threshold of {threshold} peers: {:?}",
+            missing(&self.pending_proposals().await)
+        );
+    }
+
+    /// Waits for all peers to pass their pending submissions on to consensus
+    /// and panics if any of them is still holding some after
+    /// [`PENDING_SUBMISSIONS_TIMEOUT`]
+    pub async fn assert_no_stuck_transactions(&self) {
+        let pending = || {
+            self.consensus_apis
+                .iter()
+                .map(|(peer_id, api)| (*peer_id, api.submission_sender.len()))
+                .filter(|(_, len)| *len != 0)
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let drained = timeout(PENDING_SUBMISSIONS_TIMEOUT, async {
+            while !pending().is_empty() {
+                sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+
+        assert!(
+            drained.is_ok(),
+            "Peers still have pending submissions: {:?}",
+            pending()
+        );
+    }
+
+    /// Submits `tx` to every peer, waits until all of them accepted it and
+    /// submits it again, panicking unless the resubmission succeeds without
+    /// queueing the transaction for consensus a second time
+    pub async fn assert_transaction_idempotency(&self, tx: Transaction) {
+        let txid = tx.tx_hash();
+
+        for (peer_id, api) in &self.consensus_apis {
+            api.submit_transaction(tx.clone())
+                .await
+                .unwrap_or_else(|e| panic!("Peer {peer_id} rejected transaction {txid}: {e}"));
+        }
+
+        for (peer_id, api) in &self.consensus_apis {
+            timeout(CATCH_UP_TIMEOUT, api.await_transaction(txid))
+                .await
+                .unwrap_or_else(|_| panic!("Peer {peer_id} didn't accept transaction {txid}"));
+        }
+        self.assert_no_stuck_transactions().await;
+
+        for (peer_id, api) in &self.consensus_apis {
+            let pending = api.submission_sender.len();
+            api.submit_transaction(tx.clone())
+                .await
+                .unwrap_or_else(|e| panic!("Peer {peer_id} rejected resubmission of {txid}: {e}"));
+            assert_eq!(
+                api.submission_sender.len(),
+                pending,
+                "Peer {peer_id} queued the accepted transaction {txid} again"
+            );
+        }
+    }
+
+    /// Runs `settle` and panics unless the ecash balance of the `gateway` in
+    /// this federation changed by `expected_delta` within
+    /// [`GATEWAY_SETTLEMENT_TIMEOUT`] afterwards, i.e. the settled payment
+    /// amount minus fees
+    pub async fn assert_gateway_settlement_correct<F>(
+        &self,
+        gateway: &Gateway,
+        expected_delta: Amount,
+        settle: F,
+    ) -> anyhow::Result<()>
+    where
+        F: Future<Output = anyhow::Result<()>>,
+    {
+        let client = gateway.select_client(self.id()).await?;
+        let balance_before = client.get_balance().await;
+
+        settle.await?;
+
+        let expected_balance = balance_before + expected_delta;
+        let settled = timeout(GATEWAY_SETTLEMENT_TIMEOUT, async {
+            while client.get_balance().await != expected_balance {
+                sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+
+        let balance_after = client.get_balance().await;
+        assert!(
+            settled.is_ok(),
+            "Gateway balance changed from {balance_before} to {balance_after}, expected a change of {expected_delta}"
+        );
+
+        Ok(())
+    }
+
+    /// Returns the signed history of all epochs completed by the first peer
+    pub async fn epoch_history(&self) -> Vec<SignedBlock> {
+        let api = &self.consensus_apis[&PeerId::from(0)];
+
+        let mut history = vec![];
+        for epoch in 0..api.fetch_block_count().await {
+            history.push(api.await_signed_block(epoch).await);
+        }
+
+        history
+    }
+
+    /// Returns the balance sheet reported by the first peer
+    pub async fn audit(&self) -> AuditSummary {
+        self.consensus_apis[&PeerId::from(0)]
+            .get_federation_audit()
+            .await
+            .expect("Failed to audit the federation")
+    }
+
+    /// Asserts that all guardians report the same assets and liabilities for
+    /// every module within [`BALANCE_SHEET_TIMEOUT`], e.g. the same amount of
+    /// e-cash issued, redeemed and outstanding for the mint
+    pub async fn assert_guardians_have_identical_balancesheets(&self) {
+        let differing = |balance_sheets: &BTreeMap<PeerId, BalanceSheet>| {
+            let first_sheet = balance_sheets.values().next().expect("Has peers");
+            balance_sheets
+                .iter()
+                .filter(|(_, sheet)| *sheet != first_sheet)
+                .map(|(peer_id, _)| *peer_id)
+                .collect::<Vec<_>>()
+        };
+
+        let converged = timeout(BALANCE_SHEET_TIMEOUT, async {
+            while !differing(&self.balance_sheets().await).is_empty() {
+                sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+
+        let balance_sheets = self.balance_sheets().await;
+        assert!(
+            converged.is_ok(),
+            "Balance sheets of peers {:?} differ from the first peer: {balance_sheets:?}",
+            differing(&balance_sheets)
+        );
+    }
+
+    async fn balance_sheets(&self) -> BTreeMap<PeerId, BalanceSheet> {
+        let mut balance_sheets = BTreeMap::new();
+        for (peer_id, api) in &self.consensus_apis {
+            let share = api.get_balance_sheet_share().await;
+            balance_sheets.insert(*peer_id, share.balance_sheet);
+        }
+
+        balance_sheets
+    }
+
+    /// Submits random sets of consensus items to random peers over the next
+    /// `num_epochs` epochs while checking all invariants after each of them.
+    ///
+    /// The items are derived from the epoch history by replaying, mixing and
+    /// mutating accepted items, so they decode just fine but are mostly
+    /// invalid. None of them may change the balance sheet of the federation
+    /// or make a peer stop producing epochs.
+    pub async fn fuzz_consensus_items(
+        &self,
+        rng: &mut StdRng,
+        num_epochs: usize,
+    ) -> anyhow::Result<()> {
+        let seeds = self
+            .epoch_history()
+            .await
+            .into_iter()
+            .flat_map(|signed_block| signed_block.block.items)
+            .map(|accepted_item| accepted_item.item)
+            .collect::<Vec<_>>();
+        ensure!(!seeds.is_empty(), "Fuzzing requires a non-empty history");
+
+        let audit = self.audit().await;
+        let peers = self.consensus_apis.keys().copied().collect::<Vec<_>>();
+
+        for _ in 0..num_epochs {
+            for _ in 0..rng.gen_range(1..=MAX_FUZZED_ITEMS_PER_EPOCH) {
+                let item = fuzz_consensus_item(rng, &seeds);
+                let peer_id = peers.choose(rng).expect("Has peers");
+                debug!(target: LOG_TEST, %peer_id, "Submitting fuzzed item {item:?}");
+                self.consensus_apis[peer_id]
+                    .submission_sender
+                    .send(item)
+                    .await
+                    .map_err(|_| anyhow!("Peer {peer_id} stopped accepting items"))?;
+            }
+
+            // a peer that panicked will never complete the epoch
+            timeout(
+                FUZZED_EPOCH_TIMEOUT,
+                self.run_n_epochs_and_verify_all_invariants(1),
+            )
+            .await
+            .map_err(|_| anyhow!("Federation stopped producing epochs"))??;
+        }
+
+        ensure!(
+            self.audit().await == audit,
+            "Fuzzed consensus items changed the balance sheet"
+        );
+
+        Ok(())
+    }
+
+    /// Floods the mempools of the peers with `spam_tx_count` invalid
+    /// transactions before each of the next `epochs` epochs and fails if any
+    /// of them was accepted or the federation stopped producing epochs.
+    ///
+    /// The spam bypasses the validation of the API like a malicious guardian
+    /// would. Every spam transaction reuses the inputs and signature of an
+    /// accepted transaction from the history but repeats its outputs, so it
+    /// has a unique id and fails the signature check.
+    pub async fn run_consensus_with_adversarial_mempool(
+        &self,
+        spam_tx_count: usize,
+        epochs: usize,
+    ) -> anyhow::Result<()> {
+        let seed = self
+            .epoch_history()
+            .await
+            .into_iter()
+            .flat_map(|signed_block| signed_block.block.items)
+            .find_map(|accepted_item| match accepted_item.item {
+                ConsensusItem::Transaction(tx)
+                    if !tx.inputs.is_empty() && !tx.outputs.is_empty() =>
+                {
+                    Some(tx)
+                }
+                _ => None,
+            })
+            .context("Spamming requires an accepted transaction with inputs and outputs")?;
+
+        let peers = self.consensus_apis.keys().copied().collect::<Vec<_>>();
+        let mut spam_txids = vec![];
+
+        for _ in 0..epochs {
+            for _ in 0..spam_tx_count {
+                let spam = Transaction {
+                    inputs: seed.inputs.clone(),
+                    outputs: seed
+                        .outputs
+                        .iter()
+                        .cycle()
+                        .take(seed.outputs.len() + spam_txids.len() + 1)
+                        .cloned()
+                        .collect(),
+                    signature: seed.signature,
+                };
+                let peer_id = peers[spam_txids.len() % peers.len()];
+                spam_txids.push(spam.tx_hash());
+
+                self.consensus_apis[&peer_id]
+                    .submission_sender
+                    .send(ConsensusItem::Transaction(spam))
+                    .await
+                    .map_err(|_| anyhow!("Peer {peer_id} stopped accepting items"))?;
+            }
+
+            timeout(
+                SPAMMED_EPOCH_TIMEOUT,
+                self.run_n_epochs_and_verify_all_invariants(1),
+            )
+            .await
+            .map_err(|_| anyhow!("Federation stopped producing epochs"))??;
+        }
+
+        for (peer_id, api) in &self.consensus_apis {
+            let mut dbtx = api.db.begin_transaction().await;
+            for txid in &spam_txids {
+                ensure!(
+                    dbtx.get_value(&AcceptedTransactionKey(*txid))
+                        .await
+                        .is_none(),
+                    "Peer {peer_id} accepted spam transaction {txid}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `epochs` while randomly dropping `loss_rate` of the messages sent
+    /// between the peers, verifying all invariants after every epoch
+    ///
+    /// Messages are dropped without closing the connections, so the peers
+    /// have to tolerate the loss in consensus instead of reconnecting.
+    pub async fn run_consensus_with_packet_loss(
+        &self,
+        loss_rate: f64,
+        epochs: usize,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            (0.0..1.0).contains(&loss_rate),
+            "Loss rate {loss_rate} has to be in [0, 1)"
+        );
+
+        self.network.set_packet_loss(loss_rate);
+        let result = timeout(
+            LOSSY_EPOCH_TIMEOUT,
+            self.run_n_epochs_and_verify_all_invariants(epochs),
+        )
+        .await
+        .map_err(|_| anyhow!("Federation stopped producing epochs at {loss_rate} packet loss"));
+        self.network.set_packet_loss(0.0);
+
+        result?
+    }
+
+    /// Starts a fresh federation from the configs of this one, e.g. restored
+    /// from a cold backup, and replays `epochs` on all of its peers before
+    /// they resume consensus. The new federation listens on its own ports so
+    /// it can run alongside this one.
+    pub async fn replay_from_epoch_history(&self, epochs: Vec<SignedBlock>) -> FederationTest {
+        Self::start(
+            self.configs_on_new_ports(),
+            epochs,
+            BTreeSet::new(),
+            self.params.clone(),
+            self.server_init.clone(),
+            self.client_init.clone(),
+            self.primary_client,
+            self.input_spend_keys.clone(),
+        )
+        .await
+    }
+
+    /// Simulates a rolling upgrade by restarting the federation from its epoch
+    /// history with `new_version_peers` running a newer release than the
+    /// remaining guardians, then checks that the mixed federation completes
+    /// another `epochs` epochs with all invariants holding.
+    ///
+    /// All guardians run the same code, the newer release is simulated by
+    /// advertising a newer minor version of each supported core API.
+    ///
+    /// The restarted federation listens on new ports, so existing clients
+    /// have to be recreated.
+    pub async fn simulate_guardian_upgrade_rollout(
+        &mut self,
+        new_version_peers: &[u16],
+        epochs: usize,
+    ) -> anyhow::Result<()> {
+        let upgraded = new_version_peers
+            .iter()
+            .map(|peer| PeerId::from(*peer))
+            .collect::<BTreeSet<_>>();
+        ensure!(
+            upgraded
+                .iter()
+                .all(|peer_id| self.configs.contains_key(peer_id)),
+            "Not all of {new_version_peers:?} are guardians"
+        );
+
+        let old_versions = self
+            .consensus_apis
+            .values()
+            .next()
+            .expect("Has peers")
+            .api_versions_summary()
+            .core
+            .api
+            .clone();
+        let new_versions = upgraded_api_versions(&old_versions);
+
+        info!(target: LOG_TEST, ?upgraded, ?new_versions, "Upgrading guardians");
+        let federation = Self::start(
+            self.configs_on_new_ports(),
+            self.epoch_history().await,
+            upgraded.clone(),
+            self.params.clone(),
+            self.server_init.clone(),
+            self.client_init.clone(),
+            self.primary_client,
+            self.input_spend_keys.clone(),
+        )
+        .await;
+        self.task.shutdown();
+        *self = federation;
+
+        for (peer_id, api) in &self.consensus_apis {
+            let versions = api.api_versions_summary().core.api.clone();
+            let expected = if upgraded.contains(peer_id) {
+                &new_versions
+            } else {
+                &old_versions
+            };
+            ensure!(
+                versions.into_iter().eq(expected.into_iter()),
+                "Peer {peer_id} supports API versions {versions:?}, expected {expected:?}"
+            );
+        }
+
+        self.run_n_epochs_and_verify_all_invariants(epochs).await
+    }
+
+    /// Signs the approval of `peer` to reset the federation to
+    /// `target_epoch`, see [`FederationTest::request_epoch_reset`]
+    pub async fn sign_epoch_reset(&self, peer: u16, target_epoch: u64) -> SchnorrSignature {
+        let peer_id = PeerId::from(peer);
+        let signed_block = self.consensus_apis[&peer_id]
+            .await_signed_block(target_epoch)
+            .await;
+
+        self.keychain(peer_id)
+            .sign_peer_message(&epoch_reset_message(target_epoch, &signed_block))
+    }
+
+    /// Lets the price oracle of `peer` post the price of one bitcoin in
+    /// `currency`
+    pub async fn post_exchange_rate(
+        &self,
+        peer: u16,
+        currency: &str,
+        price_cents: u64,
+    ) -> anyhow::Result<()> {
+        self.consensus_apis[&PeerId::from(peer)]
+            .post_exchange_rate(currency.to_owned(), price_cents)
+            .await
+            .map_err(|e| anyhow!("Posting exchange rate failed: {}", e.message))
+    }
+
+    /// Resets the federation to the state after `target_epoch` if a threshold
+    /// of guardians approved it, discarding all later epochs.
+    ///
+    /// The federation is restarted from its epoch history up to and including
+    /// `target_epoch` on new ports, so existing clients need to be recreated.
+    ///
+    /// This only exists in the test harness, guardians have no API to reset a
+    /// running federation since module state can't be rolled back in place.
+    /// Operators reset by restarting from a truncated epoch history like this
+    /// helper does.
+    pub async fn request_epoch_reset(
+        &mut self,
+        target_epoch: u64,
+        guardian_signatures: Vec<(PeerId, SchnorrSignature)>,
+    ) -> anyhow::Result<()> {
+        let mut history = self.epoch_history().await;
+        ensure!(
+            target_epoch < history.len() as u64,
+            "Epoch {target_epoch} was not completed yet"
+        );
+        history.truncate(target_epoch as usize + 1);
+
+        let keychain = self.keychain(PeerId::from(0));
+        let message = epoch_reset_message(target_epoch, &history[target_epoch as usize]);
+        let signers = guardian_signatures
+            .iter()
+            .filter(|(peer_id, signature)| {
+                keychain.verify_peer_signature(&message, signature, *peer_id)
+            })
+            .map(|(peer_id, _)| *peer_id)
+            .collect::<BTreeSet<_>>();
+        ensure!(
+            signers.len() >= keychain.threshold(),
+            "Got {} valid guardian signatures, expected at least {}",
+            signers.len(),
+            keychain.threshold()
+        );
+
+        info!(target: LOG_TEST, target_epoch, "Resetting federation");
+        let federation = self.replay_from_epoch_history(history).await;
+        self.task.shutdown();
+        *self = federation;
+
+        Ok(())
+    }
+
+    /// Simulates that the keys of `peer` leaked, so the remaining guardians run
+    /// a new DKG without it and the federation restarts with the new configs.
+    ///
+    /// Since consensus requires consecutive peer ids the remaining guardians
+    /// are renumbered. The new keys also change the federation id, so clients
+    /// have to join the new federation.
+    pub async fn simulate_guardian_key_compromise(
+        &mut self,
+        peer: u16,
+    ) -> anyhow::Result<KeyCompromiseResponse> {
+        let compromised = PeerId::from(peer);
+        ensure!(
+            self.configs.contains_key(&compromised),
+            "Peer {peer} is not a guardian"
+        );
+        let remaining = self
+            .configs
+            .keys()
+            .filter(|peer_id| **peer_id != compromised)
+            .zip(0..)
+            .map(|(old_id, new_id)| (*old_id, PeerId::from(new_id)))
+            .collect::<BTreeMap<_, _>>();
+        let new_peers = remaining.values().copied().collect::<Vec<_>>();
+
+        info!(target: LOG_TEST, %compromised, "Rotating keys without compromised guardian");
+        let num_peers = new_peers.len() as u16;
+        let base_port =
+            tokio::task::block_in_place(|| fedimint_portalloc::port_alloc(num_peers * 2))
+                .expect("Failed to allocate a port range");
+        let params = local_config_gen_params(&new_peers, base_port, self.params.clone())?;
+        let dkg = params.iter().map(|(peer_id, params)| {
+            let server_init = self.server_init.clone();
+            async move {
+                let mut task_group = TaskGroup::new();
+                let config = ServerConfig::distributed_gen(
+                    params,
+                    server_init,
+                    DelayCalculator::TEST_DEFAULT,
+                    &mut task_group,
+                )
+                .await;
+                task_group.shutdown_join_all(None).await?;
+                let config = config.map_err(|e| anyhow!("DKG of peer {peer_id} failed: {e:?}"))?;
+                Ok::<_, anyhow::Error>((*peer_id, config))
+            }
+        });
+        let configs = futures::future::try_join_all(dkg)
+            .await?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let old_consensus = &self.configs[&compromised].consensus;
+        let new_consensus = &configs[&PeerId::from(0)].consensus;
+        ensure!(
+            !new_consensus
+                .broadcast_public_keys
+                .values()
+                .any(|key| *key == old_consensus.broadcast_public_keys[&compromised]),
+            "New broadcast keys contain the compromised key"
+        );
+        ensure!(
+            new_consensus.epoch_pk_set != old_consensus.epoch_pk_set
+                && new_consensus.auth_pk_set != old_consensus.auth_pk_set,
+            "Threshold keys were not rotated"
+        );
+
+        let old_federation_id = self.id();
+        let federation = Self::start(
+            configs,
+            vec![],
+            BTreeSet::new(),
+            self.params.clone(),
+            self.server_init.clone(),
+            self.client_init.clone(),
+            self.primary_client,
+            self.input_spend_keys.clone(),
+        )
+        .await;
+        self.task.shutdown();
+        *self = federation;
+
+        Ok(KeyCompromiseResponse {
+            compromised,
+            remaining,
+            old_federation_id,
+            new_federation_id: self.id(),
+        })
+    }
+
+    /// Our configs with the API of every peer moved to a newly allocated port,
+    /// so a federation started from them can run alongside this one
+    fn configs_on_new_ports(&self) -> BTreeMap<PeerId, ServerConfig> {
+        let num_peers = self.configs.len() as u16;
+        let base_port = tokio::task::block_in_place(|| fedimint_portalloc::port_alloc(num_peers))
+            .expect("Failed to allocate a port range");
+
+        let mut configs = self.configs.clone();
+        for config in configs.values_mut() {
+            for (peer_id, endpoint) in config.consensus.api_endpoints.iter_mut() {
+                endpoint.url = format!("ws://127.0.0.1:{}", base_port + u16::from(*peer_id))
+                    .parse()
+                    .expect("Should parse");
+            }
+            let api_url = config.consensus.api_endpoints[&config.local.identity]
+                .url
+                .clone();
+            config.local.api_bind = parse_host_port(api_url)
+                .expect("Valid url")
+                .parse()
+                .expect("Valid address");
+        }
+
+        configs
+    }
+
+    fn keychain(&self, peer_id: PeerId) -> Keychain {
+        let config = &self.configs[&peer_id];
+        Keychain::new(
+            config.local.identity,
+            config.consensus.broadcast_public_keys.clone(),
+            config.private.broadcast_secret_key,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn new(
         num_peers: u16,
         base_port: u16,
@@ -86,24 +1333,66 @@ impl FederationTest {
         server_init: ServerModuleInitRegistry,
         client_init: ClientModuleInitRegistry,
         primary_client: ModuleInstanceId,
+        input_spend_keys: BTreeMap<ModuleKind, InputSpendKeys>,
+        download_token_limit: Option<u64>,
     ) -> Self {
         let peers = (0..num_peers).map(PeerId::from).collect::<Vec<_>>();
-        let params =
+        let module_params = params.clone();
+        let mut params =
             local_config_gen_params(&peers, base_port, params).expect("Generates local config");
+        for peer_params in params.values_mut() {
+            peer_params.local.download_token_limit = download_token_limit;
+        }
 
         let configs = ServerConfig::trusted_dealer_gen(&params, server_init.clone());
+
+        Self::start(
+            configs,
+            vec![],
+            BTreeSet::new(),
+            module_params,
+            server_init,
+            client_init,
+            primary_client,
+            input_spend_keys,
+        )
+        .await
+    }
+
+    /// Runs a peer for each of `configs` on a fresh database, each of them
+    /// first replaying the signed blocks of `history`. The `upgraded` peers
+    /// advertise the API versions of a newer release, see
+    /// [`FederationTest::simulate_guardian_upgrade_rollout`].
+    #[allow(clippy::too_many_arguments)]
+    async fn start(
+        configs: BTreeMap<PeerId, ServerConfig>,
+        history: Vec<SignedBlock>,
+        upgraded: BTreeSet<PeerId>,
+        params: ServerModuleConfigGenParamsRegistry,
+        server_init: ServerModuleInitRegistry,
+        client_init: ClientModuleInitRegistry,
+        primary_client: ModuleInstanceId,
+        input_spend_keys: BTreeMap<ModuleKind, InputSpendKeys>,
+    ) -> Self {
         let network = MockNetwork::new();
 
         let mut task = TaskGroup::new();
+        let mut storage_faults = BTreeMap::new();
+        let mut consensus_apis = BTreeMap::new();
         for (peer_id, config) in configs.clone() {
             let reliability = StreamReliability::INTEGRATION_TEST;
             let connections = network.connector(peer_id, reliability).into_dyn();
 
             let instances = config.consensus.iter_module_instances();
             let decoders = server_init.available_decoders(instances).unwrap();
-            let db = Database::new(MemDatabase::new(), decoders);
+            let storage_fault = StorageFaultInjector::default();
+            storage_faults.insert(peer_id, storage_fault.clone());
+            let db = Database::new(
+                FaultInjectingDatabase::new(MemDatabase::new(), storage_fault),
+                decoders,
+            );
 
-            let (consensus_server, consensus_api) = ConsensusServer::new_with(
+            let (consensus_server, mut consensus_api) = ConsensusServer::new_with(
                 config.clone(),
                 db.clone(),
                 server_init.clone(),
@@ -113,6 +1402,29 @@ impl FederationTest {
             )
             .await
             .expect("Failed to init server");
+            if upgraded.contains(&peer_id) {
+                consensus_api.supported_api_versions.core.api =
+                    upgraded_api_versions(&consensus_api.supported_api_versions.core.api);
+            }
+            consensus_apis.insert(peer_id, consensus_api.clone());
+
+            for (session_index, signed_block) in history.iter().enumerate() {
+                let session_index = session_index as u64;
+                for (item_index, accepted_item) in signed_block.block.items.iter().enumerate() {
+                    consensus_server
+                        .process_consensus_item(
+                            session_index,
+                            item_index as u64,
+                            accepted_item.item.clone(),
+                            accepted_item.peer,
+                        )
+                        .await
+                        .expect("Replayed consensus item was rejected");
+                }
+                consensus_server
+                    .complete_session(session_index, signed_block.clone())
+                    .await;
+            }
 
             let api_handle = FedimintServer::spawn_consensus_api(consensus_api, false).await;
 
@@ -125,12 +1437,196 @@ impl FederationTest {
 
         Self {
             configs,
+            params,
             server_init,
             client_init,
             primary_client,
-            _task: task,
+            input_spend_keys,
+            storage_faults,
+            consensus_apis,
+            network,
+            task,
+            verified_history: Default::default(),
+        }
+    }
+}
+
+/// API versions a newer release supports, it adds a minor version to each of
+/// the major versions of `api`
+fn upgraded_api_versions(api: &MultiApiVersion) -> MultiApiVersion {
+    MultiApiVersion::try_from_iter(api.into_iter().map(|version| ApiVersion {
+        major: version.major,
+        minor: version.minor + 1,
+    }))
+    .expect("Major versions stay unique")
+}
+
+/// The message guardians sign to approve resetting the federation to the
+/// `signed_block` of `target_epoch`
+fn epoch_reset_message(target_epoch: u64, signed_block: &SignedBlock) -> Vec<u8> {
+    let mut message = b"epoch reset".to_vec();
+    message.extend(signed_block.block.header(target_epoch));
+    message
+}
+
+/// Outcome of [`FederationTest::simulate_guardian_key_compromise`]
+#[derive(Debug)]
+pub struct KeyCompromiseResponse {
+    /// The guardian whose keys leaked
+    pub compromised: PeerId,
+    /// The new ids of the remaining guardians by their previous ids
+    pub remaining: BTreeMap<PeerId, PeerId>,
+    pub old_federation_id: FederationId,
+    pub new_federation_id: FederationId,
+}
+
+/// Outcome of [`FederationTest::run_with_mempool_full`]
+#[derive(Debug, Default)]
+pub struct OverflowResult {
+    /// Indices of the submissions that made it into the mempool
+    pub accepted: Vec<usize>,
+    /// Indices of the rejected submissions along with the reason
+    pub rejected: Vec<(usize, anyhow::Error)>,
+}
+
+/// Audits of the guardians recorded by
+/// [`FederationTest::start_balance_sheet_monitoring`]
+pub struct BalanceSheetMonitor {
+    negative: Arc<std::sync::Mutex<Vec<NegativeBalanceSheet>>>,
+    task: TaskGroup,
+}
+
+/// A balance sheet with negative net assets, which means a guardian owes
+/// more than it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NegativeBalanceSheet {
+    /// The first epoch that wasn't completed by all guardians yet when the
+    /// balance sheet was audited
+    pub epoch: u64,
+    pub peer_id: PeerId,
+    pub net_assets: i64,
+}
+
+async fn negative_balance_sheets(
+    apis: &BTreeMap<PeerId, ConsensusApi>,
+    epoch: u64,
+) -> Vec<NegativeBalanceSheet> {
+    let mut negative = vec![];
+    for (peer_id, api) in apis {
+        let audit = api
+            .get_federation_audit()
+            .await
+            .expect("Failed to audit the federation");
+        if audit.net_assets < 0 {
+            negative.push(NegativeBalanceSheet {
+                epoch,
+                peer_id: *peer_id,
+                net_assets: audit.net_assets,
+            });
+        }
+    }
+
+    negative
+}
+
+/// Bandwidth used by the guardians' APIs when the recording was started by
+/// [`FederationTest::start_bandwidth_recording`]
+#[derive(Debug)]
+pub struct BandwidthRecorder {
+    start: BTreeMap<String, usize>,
+}
+
+/// Outcome of [`FederationTest::stop_bandwidth_recording`]
+#[derive(Debug, Default)]
+pub struct BandwidthReport {
+    /// Bytes of requests and responses per API method that was called
+    pub per_method: BTreeMap<String, usize>,
+}
+
+impl BandwidthReport {
+    /// The called API methods, starting with the one that used the most bytes
+    pub fn most_expensive(&self) -> Vec<(&str, usize)> {
+        let mut methods = self
+            .per_method
+            .iter()
+            .map(|(method, bytes)| (method.as_str(), *bytes))
+            .collect::<Vec<_>>();
+        methods.sort_by(|(_, a), (_, b)| b.cmp(a));
+        methods
+    }
+}
+
+/// Derives a consensus item from one of the `seeds` by applying a few random
+/// mutations to the transactions among them
+fn fuzz_consensus_item(rng: &mut StdRng, seeds: &[ConsensusItem]) -> ConsensusItem {
+    let mut item = seeds.choose(rng).expect("Has seeds").clone();
+
+    let ConsensusItem::Transaction(tx) = &mut item else {
+        return item;
+    };
+
+    for _ in 0..rng.gen_range(1..=3) {
+        match rng.gen_range(0..7) {
+            0 if !tx.inputs.is_empty() => {
+                tx.inputs.remove(rng.gen_range(0..tx.inputs.len()));
+            }
+            1 if !tx.inputs.is_empty() => {
+                let input = tx.inputs.choose(rng).expect("Not empty").clone();
+                tx.inputs.push(input);
+            }
+            2 if !tx.outputs.is_empty() => {
+                tx.outputs.remove(rng.gen_range(0..tx.outputs.len()));
+            }
+            3 if !tx.outputs.is_empty() => {
+                let output = tx.outputs.choose(rng).expect("Not empty").clone();
+                tx.outputs.push(output);
+            }
+            4 => tx.signature = None,
+            5 => {
+                tx.inputs.shuffle(rng);
+                tx.outputs.shuffle(rng);
+            }
+            _ => {
+                if let Some(ConsensusItem::Transaction(other)) = seeds.choose(rng) {
+                    tx.inputs.extend(other.inputs.iter().cloned());
+                    tx.outputs.extend(other.outputs.iter().cloned());
+                }
+            }
         }
     }
+
+    item
+}
+
+/// Looks up the value of a config field, see
+/// [`FederationTest::assert_config_immutable_after_genesis`] for the format of
+/// `path`
+fn config_field(cfg: &ServerConfig, path: &str) -> serde_json::Value {
+    let mut segments = path.split('.');
+    let first = segments.next().expect("Split yields at least one segment");
+
+    let consensus = serde_json::to_value(&cfg.consensus).expect("Config can be serialized");
+    let mut value = match consensus.get(first) {
+        Some(value) => value.clone(),
+        None => {
+            let module = cfg
+                .consensus
+                .modules_json
+                .values()
+                .find(|module| module.kind().as_str() == first)
+                .unwrap_or_else(|| panic!("No config field or module named {first}"));
+            module.value().clone()
+        }
+    };
+
+    for segment in segments {
+        value = value
+            .get(segment)
+            .unwrap_or_else(|| panic!("Config field {path} does not exist"))
+            .clone();
+    }
+
+    value
 }
 
 /// Creates the config gen params for each peer
@@ -189,10 +1685,16 @@ pub fn local_config_gen_params(
                 },
                 consensus: ConfigGenParamsConsensus {
                     peers: connections.clone(),
-                    meta: BTreeMap::from([(
-                        META_FEDERATION_NAME_KEY.to_owned(),
-                        "federation_name".to_string(),
-                    )]),
+                    meta: BTreeMap::from([
+                        (
+                            META_FEDERATION_NAME_KEY.to_owned(),
+                            "federation_name".to_string(),
+                        ),
+                        (
+                            META_CONSTITUTION_KEY.to_owned(),
+                            TEST_CONSTITUTION.to_string(),
+                        ),
+                    ]),
                     modules: server_config_gen.clone(),
                 },
             };
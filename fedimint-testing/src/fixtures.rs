@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -21,7 +22,7 @@ use tracing::info;
 use crate::btc::mock::FakeBitcoinFactory;
 use crate::btc::real::RealBitcoinTest;
 use crate::btc::BitcoinTest;
-use crate::federation::FederationTest;
+use crate::federation::{FederationTest, InputSpendKeys};
 use crate::gateway::GatewayTest;
 use crate::ln::mock::FakeLightningTest;
 use crate::ln::real::{ClnLightningTest, LdkLightningTest, LndLightningTest};
@@ -37,10 +38,12 @@ pub struct Fixtures {
     servers: Vec<DynServerModuleInit>,
     params: ServerModuleConfigGenParamsRegistry,
     primary_client: ModuleInstanceId,
+    input_spend_keys: BTreeMap<ModuleKind, InputSpendKeys>,
     bitcoin_rpc: BitcoinRpcConfig,
     bitcoin: Arc<dyn BitcoinTest>,
     dyn_bitcoin_rpc: DynBitcoindRpc,
     id: ModuleInstanceId,
+    download_token_limit: Option<u64>,
 }
 
 impl Fixtures {
@@ -80,10 +83,12 @@ impl Fixtures {
             servers: vec![],
             params: Default::default(),
             primary_client: 0,
+            input_spend_keys: BTreeMap::new(),
             bitcoin_rpc: config,
             bitcoin,
             dyn_bitcoin_rpc,
             id: 0,
+            download_token_limit: None,
         }
         .with_module(client, server, params)
     }
@@ -109,6 +114,21 @@ impl Fixtures {
         self
     }
 
+    /// Lets the fed check that no two accepted inputs of the modules of `kind`
+    /// share a spend key, e.g. spend the same note. Inputs of modules
+    /// without spend keys may legitimately repeat and aren't checked.
+    pub fn with_input_spend_keys(mut self, kind: ModuleKind, spend_keys: InputSpendKeys) -> Self {
+        self.input_spend_keys.insert(kind, spend_keys);
+        self
+    }
+
+    /// Limits how many times each guardian serves its client config for the
+    /// download token of its invite code, `None` allows unlimited downloads
+    pub fn with_download_token_limit(mut self, limit: Option<u64>) -> Self {
+        self.download_token_limit = limit;
+        self
+    }
+
     /// Starts a new federation with default number of peers for testing
     pub async fn new_fed(&self) -> FederationTest {
         self.new_fed_with_peers(self.num_peers).await
@@ -125,6 +145,8 @@ impl Fixtures {
             ServerModuleInitRegistry::from(self.servers.clone()),
             ClientModuleInitRegistry::from(self.clients.clone()),
             self.primary_client,
+            self.input_spend_keys.clone(),
+            self.download_token_limit,
         )
         .await
     }
@@ -66,9 +66,13 @@ pub struct ServerOpts {
     /// The bitcoin network that fedimint will be running on
     #[arg(long, env = "FM_BITCOIN_NETWORK", default_value = "regtest")]
     network: bitcoin::network::constants::Network,
-    /// The bitcoin network that fedimint will be running on
-    #[arg(long, env = "FM_FINALITY_DELAY", default_value = "10")]
-    finality_delay: u32,
+    /// Overrides the default finality delay of the bitcoin network
+    #[arg(long, env = "FM_FINALITY_DELAY")]
+    finality_delay: Option<u32>,
+    /// How many times the config can be downloaded with the token of our
+    /// invite code, unlimited if not set
+    #[arg(long, env = "FM_DOWNLOAD_TOKEN_LIMIT")]
+    download_token_limit: Option<u64>,
 
     #[arg(long, env = "FM_BIND_METRICS_API")]
     bind_metrics_api: Option<SocketAddr>,
@@ -286,7 +290,7 @@ async fn run(
     let mut api = FedimintServer {
         data_dir: opts.data_dir,
         settings: ConfigGenSettings {
-            download_token_limit: None,
+            download_token_limit: opts.download_token_limit,
             p2p_bind: opts.bind_p2p,
             api_bind: opts.bind_api,
             p2p_url: opts.p2p_url,
@@ -14,7 +14,7 @@ use fedimint_ln_server::LightningGen;
 use fedimint_mint_server::common::config::{MintGenParams, MintGenParamsConsensus};
 use fedimint_mint_server::MintGen;
 use fedimint_wallet_server::common::config::{
-    WalletGenParams, WalletGenParamsConsensus, WalletGenParamsLocal,
+    NetworkFinality, WalletGenParams, WalletGenParamsConsensus, WalletGenParamsLocal,
 };
 use fedimint_wallet_server::WalletGen;
 
@@ -26,7 +26,7 @@ pub fn attach_default_module_init_params(
     bitcoin_rpc: BitcoinRpcConfig,
     module_init_params: &mut ServerModuleConfigGenParamsRegistry,
     network: Network,
-    finality_delay: u32,
+    finality_delay: Option<u32>,
 ) {
     module_init_params
         .attach_config_gen_params(
@@ -40,7 +40,11 @@ pub fn attach_default_module_init_params(
                     network,
                     // TODO this is not very elegant, but I'm planning to get rid of it in a next
                     // commit anyway
-                    finality_delay,
+                    finality_delay: finality_delay
+                        .map(NetworkFinality::uniform)
+                        .unwrap_or_default(),
+                    dust_change_policy: None,
+                    peg_out_policy: Default::default(),
                     client_default_bitcoin_rpc: default_esplora_server(network),
                 },
             },
@@ -71,8 +71,8 @@ use crate::gateway_lnrpc::intercept_htlc_response::Forward;
 use crate::lnrpc_client::GatewayLightningBuilder;
 use crate::rpc::rpc_server::run_webserver;
 use crate::rpc::{
-    BackupPayload, BalancePayload, ConnectFedPayload, DepositAddressPayload, GatewayInfo,
-    InfoPayload, RestorePayload, WithdrawPayload,
+    BackupPayload, BalancePayload, ConnectFedPayload, DepositAddressPayload, GatewayAuth,
+    GatewayInfo, InfoPayload, RestorePayload, WithdrawPayload,
 };
 use crate::state_machine::GatewayExtPayStates;
 
@@ -637,6 +637,26 @@ impl Gateway {
             .await)
     }
 
+    /// Returns the ecash the gateway holds across all federations it is
+    /// connected to, which includes the funds claimed with its redeem key when
+    /// settling payments
+    pub async fn get_gateway_balance(&self, auth: GatewayAuth) -> Result<Amount> {
+        let authorized = self
+            .get_gateway_configuration()
+            .await
+            .map_or(false, |config| config.password == auth.password);
+        if !authorized {
+            return Err(GatewayError::Unauthorized);
+        }
+
+        let mut balance = Amount::ZERO;
+        for client in self.clients.read().await.clone().into_values() {
+            balance += client.get_balance().await;
+        }
+
+        Ok(balance)
+    }
+
     pub async fn handle_address_msg(&self, payload: DepositAddressPayload) -> Result<Address> {
         let (_, address) = self
             .select_client(payload.federation_id)
@@ -1180,6 +1200,8 @@ pub enum GatewayError {
     Disconnected,
     #[error("The password field is required when initially configuring the gateway")]
     GatewayConfigurationError,
+    #[error("Invalid gateway credentials")]
+    Unauthorized,
 }
 
 impl IntoResponse for GatewayError {
@@ -42,6 +42,13 @@ pub struct BalancePayload {
     pub federation_id: FederationId,
 }
 
+/// Credentials of the gateway operator, required to query the gateway's
+/// funds
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GatewayAuth {
+    pub password: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DepositAddressPayload {
     pub federation_id: FederationId,
@@ -41,7 +41,7 @@ use lightning::routing::gossip::RoutingFees;
 use lightning_invoice::Bolt11Invoice;
 use ln_gateway::gateway_lnrpc::GetNodeInfoResponse;
 use ln_gateway::rpc::rpc_client::{GatewayRpcClient, GatewayRpcError, GatewayRpcResult};
-use ln_gateway::rpc::{BalancePayload, ConnectFedPayload, SetConfigurationPayload};
+use ln_gateway::rpc::{BalancePayload, ConnectFedPayload, GatewayAuth, SetConfigurationPayload};
 use ln_gateway::state_machine::{
     GatewayClientExt, GatewayClientModule, GatewayClientStateMachines, GatewayExtPayStates,
     GatewayExtReceiveStates, GatewayMeta, Htlc, GW_ANNOUNCEMENT_TTL,
@@ -205,6 +205,7 @@ async fn test_gateway_can_pay_ldk_node() -> anyhow::Result<()> {
         assert_eq!(user_client.get_balance().await, sats(1000 - 250));
         assert_eq!(gateway.get_balance().await, sats(250));
 
+        fed.assert_no_stuck_transactions().await;
         Ok(())
     })
     .await
@@ -228,6 +229,104 @@ async fn test_gateway_client_pay_valid_invoice() -> anyhow::Result<()> {
             assert_eq!(user_client.get_balance().await, sats(1000 - 250));
             assert_eq!(gateway.get_balance().await, sats(250));
 
+            fed.assert_no_stuck_transactions().await;
+            Ok(())
+        },
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_balance_accumulates_settled_payments() -> anyhow::Result<()> {
+    single_federation_test(
+        |gateway, other_lightning_client, fed, user_client, _| async move {
+            // Print money for user_client
+            let (_, outpoint) = user_client.print_money(sats(2000)).await?;
+            user_client.receive_money(outpoint).await?;
+
+            let auth = GatewayAuth {
+                password: DEFAULT_GATEWAY_PASSWORD.to_string(),
+            };
+            assert_eq!(
+                gateway.gateway.get_gateway_balance(auth.clone()).await?,
+                sats(0)
+            );
+
+            for payment in 1..=5 {
+                // The gateway picks up and settles the payment on its own
+                let invoice = other_lightning_client.invoice(sats(250), None).await?;
+                let OutgoingLightningPayment { payment_type, .. } =
+                    user_client.pay_bolt11_invoice(invoice).await?;
+                let PayType::Lightning(pay_op) = payment_type else {
+                    panic!("Expected Lightning payment!");
+                };
+                let mut pay_sub = user_client.subscribe_ln_pay(pay_op).await?.into_stream();
+                assert_eq!(pay_sub.ok().await?, LnPayState::Created);
+                assert_eq!(pay_sub.ok().await?, LnPayState::Funded);
+                assert_eq!(pay_sub.ok().await?, LnPayState::AwaitingChange);
+                assert_matches!(pay_sub.ok().await?, LnPayState::Success { .. });
+
+                let expected_balance = sats(250 * payment);
+                retry(
+                    "Get gateway balance".to_string(),
+                    || async {
+                        let balance = gateway.gateway.get_gateway_balance(auth.clone()).await?;
+                        anyhow::ensure!(
+                            balance == expected_balance,
+                            "Expected balance {expected_balance}, got {balance}"
+                        );
+                        Ok(())
+                    },
+                    Duration::from_millis(500),
+                    20,
+                )
+                .await?;
+            }
+
+            // Wrong credentials are rejected
+            let wrong_auth = GatewayAuth {
+                password: "wrong_password".to_string(),
+            };
+            assert!(gateway
+                .gateway
+                .get_gateway_balance(wrong_auth)
+                .await
+                .is_err());
+
+            fed.assert_no_stuck_transactions().await;
+            Ok(())
+        },
+    )
+    .await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_settlement_increases_balance_by_payment() -> anyhow::Result<()> {
+    single_federation_test(
+        |gateway, other_lightning_client, fed, user_client, _| async move {
+            // Print money for user_client
+            let (_, outpoint) = user_client.print_money(sats(1000)).await?;
+            user_client.receive_money(outpoint).await?;
+
+            let invoice = other_lightning_client.invoice(sats(250), None).await?;
+            fed.assert_gateway_settlement_correct(&gateway.gateway, sats(250), async {
+                // The gateway picks up and settles the payment on its own
+                let OutgoingLightningPayment { payment_type, .. } =
+                    user_client.pay_bolt11_invoice(invoice).await?;
+                let PayType::Lightning(pay_op) = payment_type else {
+                    panic!("Expected Lightning payment!");
+                };
+                let mut pay_sub = user_client.subscribe_ln_pay(pay_op).await?.into_stream();
+                assert_eq!(pay_sub.ok().await?, LnPayState::Created);
+                assert_eq!(pay_sub.ok().await?, LnPayState::Funded);
+                assert_eq!(pay_sub.ok().await?, LnPayState::AwaitingChange);
+                assert_matches!(pay_sub.ok().await?, LnPayState::Success { .. });
+                Ok(())
+            })
+            .await?;
+
+            assert_eq!(user_client.get_balance().await, sats(1000 - 250));
+            fed.assert_no_stuck_transactions().await;
             Ok(())
         },
     )
@@ -294,6 +393,7 @@ async fn test_gateway_cannot_claim_invalid_preimage() -> anyhow::Result<()> {
                 .await
                 .is_err());
             assert_eq!(gateway.get_balance().await, sats(0));
+            fed.assert_no_stuck_transactions().await;
             Ok(())
         },
     )
@@ -343,6 +443,7 @@ async fn test_gateway_client_pay_unpayable_invoice() -> anyhow::Result<()> {
                 _ => panic!("Expected Lightning payment!"),
             }
 
+            fed.assert_no_stuck_transactions().await;
             Ok(())
         },
     )
@@ -395,6 +496,7 @@ async fn test_gateway_client_intercept_valid_htlc() -> anyhow::Result<()> {
             gateway.get_balance().await
         );
 
+        fed.assert_no_stuck_transactions().await;
         Ok(())
     })
     .await
@@ -428,6 +530,7 @@ async fn test_gateway_client_intercept_offer_does_not_exist() -> anyhow::Result<
             Err(e) => assert_eq!(e.to_string(), "Timed out fetching the offer".to_string()),
         }
 
+        fed.assert_no_stuck_transactions().await;
         Ok(())
     })
     .await
@@ -563,6 +666,7 @@ async fn test_gateway_client_intercept_htlc_invalid_offer() -> anyhow::Result<()
                 ),
             }
 
+            fed.assert_no_stuck_transactions().await;
             Ok(())
         },
     )
@@ -667,6 +771,7 @@ async fn test_gateway_cannot_pay_expired_invoice() -> anyhow::Result<()> {
             assert_eq!(user_client.get_balance().await, sats(2000));
             assert_eq!(gateway.get_balance().await, sats(0));
 
+            fed.assert_no_stuck_transactions().await;
             Ok(())
         },
     )
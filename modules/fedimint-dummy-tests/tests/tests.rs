@@ -1,19 +1,36 @@
-use std::sync::Arc;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use anyhow::bail;
-use fedimint_client::transaction::{ClientOutput, TransactionBuilder};
-use fedimint_core::api::GlobalFederationApi;
+use fedimint_client::module::ClientModule;
+use fedimint_client::sm::OperationId;
+use fedimint_client::transaction::{ClientInput, ClientOutput, TransactionBuilder};
+use fedimint_core::api::{
+    GlobalFederationApi, SubscriptionEvent, SubscriptionType, TransactionStatus,
+    MAX_CURRENCY_CODE_LEN,
+};
+use fedimint_core::block::EpochCommitment;
 use fedimint_core::config::ClientModuleConfig;
 use fedimint_core::core::{IntoDynInstance, ModuleKind};
+use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::module::ModuleConsensusVersion;
-use fedimint_core::{sats, Amount};
+use fedimint_core::transaction::TransactionError;
+use fedimint_core::{sats, Amount, OutPoint, PeerId};
 use fedimint_dummy_client::states::DummyStateMachine;
 use fedimint_dummy_client::{DummyClientExt, DummyClientGen, DummyClientModule};
 use fedimint_dummy_common::config::{DummyClientConfig, DummyGenParams};
-use fedimint_dummy_common::DummyOutput;
+use fedimint_dummy_common::{fed_key_pair, DummyInput, DummyOutput};
 use fedimint_dummy_server::DummyGen;
+use fedimint_server::db::DbKeyPrefix;
+use fedimint_testing::federation::{FederationTest, TEST_CONSTITUTION};
 use fedimint_testing::fixtures::Fixtures;
-use secp256k1::Secp256k1;
+use futures::StreamExt;
+use proptest::prelude::*;
+use proptest::test_runner::TestRunner;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use secp256k1::{KeyPair, Secp256k1};
 use tracing::debug;
 
 fn fixtures() -> Fixtures {
@@ -33,6 +50,7 @@ async fn can_print_and_send_money() -> anyhow::Result<()> {
     client2.receive_money(outpoint).await?;
     assert_eq!(client1.get_balance().await, sats(750));
     assert_eq!(client2.get_balance().await, sats(250));
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -46,6 +64,284 @@ async fn can_threshold_sign_message() {
     assert!(client.fed_public_key().verify(&sig, message));
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn observer_sees_items_of_every_epoch() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let item_kinds = Mutex::new(BTreeSet::new());
+    let epochs = Mutex::new(vec![]);
+    let observe = fed.run_consensus_epochs_observed(5, |epoch, signed_block| {
+        epochs.lock().unwrap().push(epoch);
+        for accepted in &signed_block.block.items {
+            let kind = match accepted.item {
+                ConsensusItem::ClientConfigSignatureShare(_) => "client_config_signature",
+                ConsensusItem::Transaction(_) => "transaction",
+                ConsensusItem::Module(_) => "module",
+            };
+            item_kinds.lock().unwrap().insert(kind);
+        }
+    });
+    let use_fed = async {
+        let (op, outpoint) = client.print_money(sats(1000)).await?;
+        client.await_primary_module_output(op, outpoint).await?;
+        client.fed_signature("Hello fed!").await?;
+        anyhow::Ok(())
+    };
+    let ((), used) = futures::join!(observe, use_fed);
+    used?;
+
+    let epochs = epochs.into_inner().unwrap();
+    assert_eq!(epochs.len(), 5);
+    assert!(epochs.windows(2).all(|epochs| epochs[1] == epochs[0] + 1));
+    // Printing money is a transaction, threshold signing a message is done with
+    // module items
+    let item_kinds = item_kinds.into_inner().unwrap();
+    assert!(item_kinds.contains("transaction"));
+    assert!(item_kinds.contains("module"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn epochs_are_signed_within_two_seconds() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed_with_peers(4).await;
+
+    fed.assert_epoch_latency_within(5, Duration::from_secs(2))
+        .await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn block_count_increases_with_every_epoch() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let mut block_count = client.api().fetch_block_count().await?;
+    for _ in 0..3 {
+        // The block at index `block_count` is the one of the running epoch
+        client
+            .api()
+            .await_block(block_count, client.decoders())
+            .await?;
+        let new_block_count = client.api().fetch_block_count().await?;
+        assert!(new_block_count > block_count);
+        block_count += 1;
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federation_stats_are_signed_by_threshold() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+
+    let signed = client.api().fetch_federation_stats().await?;
+    signed.verify(&client.get_config().epoch_pk)?;
+    assert!(signed.stats.epoch_count > 0);
+    assert_eq!(
+        signed.stats.peers.keys().collect::<Vec<_>>(),
+        client
+            .get_config()
+            .global
+            .api_endpoints
+            .keys()
+            .collect::<Vec<_>>()
+    );
+    // The peer that proposed the transaction was seen in its epoch
+    assert!(signed
+        .stats
+        .peers
+        .values()
+        .any(|peer| peer.last_seen_epoch.is_some()));
+
+    // Tampered stats fail verification
+    let mut tampered = signed.clone();
+    tampered.stats.epoch_count += 1;
+    assert!(tampered.verify(&client.get_config().epoch_pk).is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federation_constitution_is_signed() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let constitution = client.api().get_federation_constitution().await?;
+    assert!(!constitution.text.is_empty());
+    assert_eq!(constitution.text, TEST_CONSTITUTION);
+    assert_eq!(constitution.version, 0);
+    assert!(constitution.verify(client.get_config()));
+
+    let mut tampered = constitution.clone();
+    tampered.text.push_str(" most of the time");
+    assert!(!tampered.verify(client.get_config()));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_verifies_exchange_rate_posted_by_guardian() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let epoch_pk = client.get_config().global.epoch_pk;
+
+    assert!(client.api().get_ecash_exchange_rate("USD").await.is_err());
+
+    fed.post_exchange_rate(1, "USD", 3_000_000).await?;
+    let rate = client.api().get_ecash_exchange_rate("USD").await?;
+    rate.verify(&epoch_pk)?;
+    assert_eq!(rate.peer_id, PeerId::from(1));
+    assert_eq!(rate.rate.currency, "USD");
+    assert_eq!(rate.rate.price_cents, 3_000_000);
+    assert_eq!(rate.rate.fiat_value_cents(sats(100_000)), 3_000);
+
+    // The most recent price wins
+    fed.post_exchange_rate(2, "USD", 3_100_000).await?;
+    let rate = client.api().get_ecash_exchange_rate("USD").await?;
+    rate.verify(&epoch_pk)?;
+    assert_eq!(rate.peer_id, PeerId::from(2));
+
+    let mut tampered = rate.clone();
+    tampered.rate.price_cents = 1;
+    assert!(tampered.verify(&epoch_pk).is_err());
+    assert!(client.api().get_ecash_exchange_rate("EUR").await.is_err());
+
+    // Guardians don't store arbitrarily long currency codes
+    let long_currency = "X".repeat(MAX_CURRENCY_CODE_LEN + 1);
+    assert!(fed.post_exchange_rate(1, &long_currency, 1).await.is_err());
+    assert!(client
+        .api()
+        .get_ecash_exchange_rate(&long_currency)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn all_peers_commit_to_first_epoch() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let num_peers = client.get_config().global.api_endpoints.len();
+
+    let block = client.api().await_block(0, client.decoders()).await?;
+    let header = block.header(0);
+
+    let broadcast_public_keys = &client.get_config().global.broadcast_public_keys;
+    let commitments = client.api().get_epoch_commitments(0).await?;
+    assert_eq!(commitments.len(), num_peers);
+    for (peer_id, commitment) in &commitments {
+        assert_eq!(*peer_id, commitment.peer_id);
+        assert!(commitment.verify(&header, broadcast_public_keys));
+    }
+
+    // a commitment does not verify for another peer's key or another header
+    let (_, commitment) = commitments.first_key_value().expect("Has commitments");
+    let impersonated = EpochCommitment {
+        peer_id: PeerId::from(1),
+        ..commitment.clone()
+    };
+    assert!(!impersonated.verify(&header, broadcast_public_keys));
+    assert!(!commitment.verify(&block.header(1), broadcast_public_keys));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federation_recovers_from_failed_commit() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    // fail the first commit of a signed block, i.e. the end of the first session
+    fed.inject_commit_error_on_peer(0, DbKeyPrefix::SignedBlock as u8, 0);
+
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(1000));
+
+    // the peer re-ran the failed commit and went on to store the first block
+    let commitments = client.api().get_epoch_commitments(0).await?;
+    assert!(commitments.contains_key(&PeerId::from(0)));
+    assert!(!fed.storage_error_pending_on_peer(0));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_fork_persists_after_partition_heals() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    // The other three peers still reach the threshold without peer 3
+    fed.partition(&[3]);
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    fed.heal_partition();
+
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(2000));
+
+    let max_epoch = client.api().fetch_block_count().await? - 1;
+    fed.assert_no_consensus_forks(max_epoch).await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_completes_while_half_the_guardians_are_upgraded() -> anyhow::Result<()> {
+    let mut fed = fixtures().new_fed_with_peers(4).await;
+    let client = fed.new_client().await;
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    let audit = fed.audit().await;
+
+    fed.simulate_guardian_upgrade_rollout(&[2, 3], 3).await?;
+    assert_eq!(fed.audit().await, audit);
+
+    // the mixed federation keeps processing transactions
+    let client = fed.new_client().await;
+    let (_, outpoint) = client.print_money(sats(500)).await?;
+    client.receive_money(outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(500));
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compromised_guardian_is_excluded_from_new_keys() -> anyhow::Result<()> {
+    let mut fed = fixtures().new_fed_with_peers(4).await;
+    let old_client = fed.new_client().await;
+
+    let response = fed.simulate_guardian_key_compromise(3).await?;
+    assert_eq!(response.compromised, PeerId::from(3));
+    assert_eq!(
+        response.remaining.keys().copied().collect::<Vec<_>>(),
+        (0..3).map(PeerId::from).collect::<Vec<_>>()
+    );
+    assert_ne!(response.old_federation_id, response.new_federation_id);
+    assert_ne!(
+        old_client.get_config().epoch_pk,
+        fed.new_client().await.get_config().epoch_pk
+    );
+
+    // The remaining guardians keep processing transactions with the new keys
+    let client = fed.new_client().await;
+    assert_eq!(client.get_config().global.api_endpoints.len(), 3);
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(1000));
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn client_ignores_unknown_module() {
     let fed = fixtures().new_fed().await;
@@ -112,8 +408,31 @@ async fn unbalanced_transactions_get_rejected() -> anyhow::Result<()> {
         },
         state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
     };
-    let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+    let tx = TransactionBuilder::new().with_output(output.clone().into_dyn(instance.id));
     let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    assert!(FederationTest::verify_transaction_balance(&client, &tx).is_err());
+
+    // funding the same output with an input of the same amount balances it
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: fed_key_pair().x_only_public_key().0,
+        },
+        keys: vec![fed_key_pair()],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let (balanced_tx, _) = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id))
+        .build(&Secp256k1::new(), rand::thread_rng());
+    FederationTest::assert_balanced_transaction(&client, &balanced_tx);
+
+    let err = client
+        .api()
+        .validate_transaction(&tx)
+        .await
+        .expect_err("Should have failed");
+    assert!(err.to_string().contains("The transaction is unbalanced"));
     let result = client.api().submit_transaction(tx).await;
     match result {
         Ok(_) => bail!("Should have failed"),
@@ -121,3 +440,612 @@ async fn unbalanced_transactions_get_rejected() -> anyhow::Result<()> {
         Err(e) => bail!("Unexpected error: {e:?}"),
     }
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn validating_transactions_has_no_side_effects() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (_, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.receive_money(outpoint).await?;
+
+    let (dummy, instance) =
+        client1.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let mut dbtx = instance.db.begin_transaction().await;
+    let input = ClientModule::create_sufficient_input(
+        dummy,
+        &mut dbtx.get_isolated(),
+        OperationId::new_random(),
+        sats(1000),
+    )
+    .await?;
+    dbtx.commit_tx().await;
+    let build_tx = |account| {
+        let output = ClientOutput {
+            output: DummyOutput {
+                amount: sats(1000),
+                account,
+            },
+            state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+        };
+        TransactionBuilder::new()
+            .with_input(input.clone().into_dyn(instance.id))
+            .with_output(output.into_dyn(instance.id))
+            .build(&Secp256k1::new(), rand::thread_rng())
+            .0
+    };
+
+    // Validating repeatedly doesn't spend the funds of the input
+    let tx = build_tx(client2.account());
+    client1.api().validate_transaction(&tx).await?;
+    client1.api().validate_transaction(&tx).await?;
+    let txid = client1.api().submit_transaction(tx).await?;
+    client1.api().await_transaction(txid).await?;
+    client2.receive_money(OutPoint { txid, out_idx: 0 }).await?;
+    assert_eq!(client2.get_balance().await, sats(1000));
+
+    // Once submitted the funds are gone for validations and submissions alike
+    let replay = build_tx(client1.account());
+    let err = client1
+        .api()
+        .validate_transaction(&replay)
+        .await
+        .expect_err("Funds were already spent");
+    assert!(err.to_string().contains("Not enough funds"));
+    let err = client1
+        .api()
+        .submit_transaction(replay)
+        .await
+        .expect_err("Funds were already spent");
+    assert!(err.to_string().contains("Not enough funds"));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resubmitting_accepted_transaction_is_idempotent() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: fed_key_pair().x_only_public_key().0,
+        },
+        keys: vec![fed_key_pair()],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id));
+    let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    let txid = tx.tx_hash();
+
+    fed.assert_transaction_idempotency(tx).await;
+
+    // the output was only created once
+    client.receive_money(OutPoint { txid, out_idx: 0 }).await?;
+    assert_eq!(client.get_balance().await, sats(1000));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transaction_status_is_streamed_from_before_submission() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: fed_key_pair().x_only_public_key().0,
+        },
+        keys: vec![fed_key_pair()],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id));
+    let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    let txid = tx.tx_hash();
+
+    let mut status = client.api().subscribe_transaction_status(txid);
+    assert_eq!(status.next().await, Some(TransactionStatus::Pending));
+
+    client.api().submit_transaction(tx).await?;
+    assert_eq!(status.next().await, Some(TransactionStatus::Accepted));
+    assert_eq!(status.next().await, None);
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn transaction_status_stream_ends_when_rejected() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    // the account has no funds to spend, so processing the transaction fails
+    let key = KeyPair::new(&Secp256k1::new(), &mut rand::thread_rng());
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: key.x_only_public_key().0,
+        },
+        keys: vec![key],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id));
+    let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    let txid = tx.tx_hash();
+
+    let mut status = client.api().subscribe_transaction_status(txid);
+    assert_eq!(status.next().await, Some(TransactionStatus::Pending));
+
+    // the API would reject the transaction before it reaches consensus
+    fed.override_proposal(0, vec![ConsensusItem::Transaction(tx)])
+        .await?;
+    assert!(matches!(
+        status.next().await,
+        Some(TransactionStatus::Rejected(_))
+    ));
+    assert_eq!(status.next().await, None);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multiplexed_subscription_streams_epochs_and_transaction_status() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: fed_key_pair().x_only_public_key().0,
+        },
+        keys: vec![fed_key_pair()],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id));
+    let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    let txid = tx.tx_hash();
+
+    let mut events = client.api().multiplexed_subscribe(vec![
+        SubscriptionType::EpochCommit,
+        SubscriptionType::TransactionStatus(txid),
+    ]);
+    client.api().submit_transaction(tx).await?;
+
+    let mut statuses = vec![];
+    let mut epochs = vec![];
+    while statuses.len() < 2 || epochs.is_empty() {
+        match events.next().await.expect("Epoch commits never end") {
+            SubscriptionEvent::EpochCommit { epoch } => epochs.push(epoch),
+            SubscriptionEvent::TransactionStatus {
+                txid: event_txid,
+                status,
+            } => {
+                assert_eq!(event_txid, txid);
+                statuses.push(status);
+            }
+        }
+    }
+
+    assert_eq!(
+        statuses,
+        vec![TransactionStatus::Pending, TransactionStatus::Accepted]
+    );
+    assert!(epochs.windows(2).all(|pair| pair[1] == pair[0] + 1));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invariants_hold_under_random_operations() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    for _ in 0..50 {
+        let amount = sats(rand::thread_rng().gen_range(1..1000));
+        let operation = rand::thread_rng().gen_range(0..3);
+        match operation {
+            0 => {
+                let (_, outpoint) = client1.print_money(amount).await?;
+                client1.receive_money(outpoint).await?;
+            }
+            1 if client1.get_balance().await >= amount => {
+                let outpoint = client1.send_money(client2.account(), amount).await?;
+                client2.receive_money(outpoint).await?;
+            }
+            // leave the epoch empty
+            _ => {}
+        }
+
+        fed.run_n_epochs_and_verify_all_invariants(1).await?;
+    }
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federation_can_be_restored_from_epoch_history() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    let (_, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.receive_money(outpoint).await?;
+    let outpoint = client1.send_money(client2.account(), sats(250)).await?;
+    client2.receive_money(outpoint).await?;
+    fed.run_n_epochs_and_verify_all_invariants(10).await?;
+
+    let history = fed.epoch_history().await;
+    let audit = fed.audit().await;
+
+    let restored = fed.replay_from_epoch_history(history.clone()).await;
+    assert_eq!(restored.audit().await, audit);
+    assert_eq!(restored.id(), fed.id());
+
+    // the restored federation resumes consensus on top of the replayed history
+    restored.run_n_epochs_and_verify_all_invariants(1).await?;
+    let restored_history = restored.epoch_history().await;
+    assert!(restored_history.len() > history.len());
+    assert_eq!(restored_history[..history.len()], history[..]);
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federation_can_be_reset_to_earlier_epoch() -> anyhow::Result<()> {
+    let mut fed = fixtures().new_fed().await;
+    fed.run_n_epochs_and_verify_all_invariants(10).await?;
+    let history = fed.epoch_history().await;
+    assert!(history.len() >= 10);
+
+    let mut signatures = vec![];
+    for peer in 0..3 {
+        let signature = fed.sign_epoch_reset(peer, 5).await;
+        signatures.push((PeerId::from(peer), signature));
+    }
+
+    // a signature for another epoch does not count towards the threshold
+    let wrong_epoch = (PeerId::from(2), fed.sign_epoch_reset(2, 4).await);
+    let insufficient = vec![signatures[0].clone(), signatures[1].clone(), wrong_epoch];
+    assert!(fed.request_epoch_reset(5, insufficient).await.is_err());
+
+    fed.request_epoch_reset(5, signatures).await?;
+
+    fed.run_n_epochs_and_verify_all_invariants(1).await?;
+    let reset_history = fed.epoch_history().await;
+    assert!(reset_history.len() > 6);
+    assert_eq!(reset_history[..=5], history[..=5]);
+
+    Ok(())
+}
+
+async fn config_downloads_are_limited_to(limit: Option<u64>) -> anyhow::Result<()> {
+    let fed = fixtures().with_download_token_limit(limit).new_fed().await;
+    let client = fed.new_client().await;
+    let invite_code = fed.invite_code();
+
+    // Without a limit we just check that downloads keep working for a while
+    let allowed = limit.unwrap_or(20);
+    for _ in 0..allowed {
+        let config = client.api().download_client_config(&invite_code).await?;
+        assert_eq!(config.global.federation_id, invite_code.id);
+    }
+
+    let next = client.api().download_client_config(&invite_code).await;
+    match limit {
+        Some(_) => {
+            let error = next.expect_err("Download limit is exceeded").to_string();
+            assert!(error.contains("Download token used too many times"));
+        }
+        None => assert!(next.is_ok()),
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_can_be_downloaded_once() -> anyhow::Result<()> {
+    config_downloads_are_limited_to(Some(1)).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_can_be_downloaded_three_times() -> anyhow::Result<()> {
+    config_downloads_are_limited_to(Some(3)).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_downloads_are_unlimited_by_default() -> anyhow::Result<()> {
+    config_downloads_are_limited_to(None).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_round_trip_is_measured() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    assert_eq!(fed.measure_consensus_round_trip(), None);
+
+    fed.run_n_epochs_and_verify_all_invariants(100).await?;
+
+    let measurement = fed
+        .measure_consensus_round_trip()
+        .expect("Sessions were completed");
+    assert!(0 < measurement.min_ms);
+    assert!(measurement.min_ms <= measurement.median_ms);
+    assert!(measurement.median_ms <= measurement.p99_ms);
+    assert!(measurement.p99_ms <= measurement.max_ms);
+    // sessions are expected to take about a minute
+    assert!(measurement.max_ms < Duration::from_secs(10 * 60).as_millis() as u64);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn note_issuance_time_is_estimated_from_recent_sessions() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    assert!(client.api().estimate_note_issuance_time().await.is_err());
+
+    // start measuring at a session boundary
+    fed.run_n_epochs_and_verify_all_invariants(1).await?;
+    let start = Instant::now();
+    fed.run_n_epochs_and_verify_all_invariants(10).await?;
+    let actual_average = start.elapsed() / 10;
+
+    let estimate = client.api().estimate_note_issuance_time().await?;
+    assert!(
+        actual_average / 2 <= estimate,
+        "{estimate:?} vs {actual_average:?}"
+    );
+    assert!(
+        estimate <= actual_average * 2,
+        "{estimate:?} vs {actual_average:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malicious_items_of_byzantine_proposer_are_rejected() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    let accepted_tx = fed
+        .epoch_history()
+        .await
+        .into_iter()
+        .flat_map(|signed_block| signed_block.block.items)
+        .find_map(|accepted_item| match accepted_item.item {
+            ConsensusItem::Transaction(tx) if tx.tx_hash() == outpoint.txid => Some(tx),
+            _ => None,
+        })
+        .expect("Transaction was accepted");
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+    let (unbalanced_tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+
+    // a double spend and a transaction printing money from nothing
+    let malicious_items = vec![
+        ConsensusItem::Transaction(accepted_tx),
+        ConsensusItem::Transaction(unbalanced_tx),
+    ];
+    fed.simulate_byzantine_proposer(0, malicious_items, 2)
+        .await?;
+
+    // the federation keeps processing honest transactions
+    let (_, outpoint) = client.print_money(sats(1000)).await?;
+    client.receive_money(outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(2000));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn duplicate_items_are_accepted_only_once() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (_dummy, instance) =
+        client.get_first_module::<DummyClientModule>(&fedimint_dummy_common::KIND);
+    let input = ClientInput {
+        input: DummyInput {
+            amount: sats(1000),
+            account: fed_key_pair().x_only_public_key().0,
+        },
+        keys: vec![fed_key_pair()],
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let output = ClientOutput {
+        output: DummyOutput {
+            amount: sats(1000),
+            account: client.account(),
+        },
+        state_machines: Arc::new(move |_, _| Vec::<DummyStateMachine>::new()),
+    };
+    let tx = TransactionBuilder::new()
+        .with_input(input.into_dyn(instance.id))
+        .with_output(output.into_dyn(instance.id));
+    let (tx, _) = tx.build(&Secp256k1::new(), rand::thread_rng());
+    let txid = tx.tx_hash();
+
+    // one peer proposes the transaction twice, another one proposes it again
+    let item = ConsensusItem::Transaction(tx);
+    let first_epoch = fed
+        .override_proposal(0, vec![item.clone(), item.clone()])
+        .await?;
+    fed.override_proposal(1, vec![item]).await?;
+    fed.run_n_epochs_and_verify_all_invariants(2).await?;
+
+    for epoch in first_epoch..first_epoch + 2 {
+        fed.assert_epoch_items_deduplicated(epoch).await;
+    }
+
+    // the output was only created once
+    client.receive_money(OutPoint { txid, out_idx: 0 }).await?;
+    assert_eq!(client.get_balance().await, sats(1000));
+    assert_eq!(fed.audit().await.net_assets, 0);
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submissions_are_rejected_if_mempool_is_full() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+
+    let result = fed.run_with_mempool_full(10, 5).await;
+    assert_eq!(result.accepted, (0..10).collect::<Vec<_>>());
+    assert_eq!(result.rejected.len(), 5);
+    for (index, error) in &result.rejected {
+        assert!(*index >= 10);
+        assert!(matches!(
+            error.downcast_ref::<TransactionError>(),
+            Some(TransactionError::MempoolFull)
+        ));
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn legitimate_transactions_are_accepted_despite_mempool_spam() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (_, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.receive_money(outpoint).await?;
+
+    let legitimate = async {
+        let submitted_epoch = client1.api().fetch_block_count().await?;
+        let outpoint = client1.send_money(client2.account(), sats(250)).await?;
+        client2.receive_money(outpoint).await?;
+        let accepted_epoch = client1.api().fetch_block_count().await?;
+        anyhow::Ok(accepted_epoch - submitted_epoch)
+    };
+    let (spam, epochs_until_accepted) = tokio::join!(
+        fed.run_consensus_with_adversarial_mempool(50, 3),
+        legitimate
+    );
+    spam?;
+    assert!(epochs_until_accepted? <= 2);
+    assert_eq!(client2.get_balance().await, sats(250));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consensus_completes_despite_packet_loss() -> anyhow::Result<()> {
+    // the default federation has 4 peers and tolerates one faulty peer
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let mut expected_balance = sats(0);
+    for loss_rate in [0.1, 0.2, 0.3] {
+        let payment = async {
+            let (_, outpoint) = client.print_money(sats(1000)).await?;
+            client.receive_money(outpoint).await
+        };
+        let (consensus, payment) =
+            tokio::join!(fed.run_consensus_with_packet_loss(loss_rate, 2), payment);
+        consensus?;
+        payment?;
+
+        expected_balance += sats(1000);
+        assert_eq!(client.get_balance().await, expected_balance);
+    }
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fuzzed_consensus_items_do_not_move_funds() -> anyhow::Result<()> {
+    // All cases run against the same federation, starting one per case is too slow
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    let (_, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.receive_money(outpoint).await?;
+    let outpoint = client1.send_money(client2.account(), sats(250)).await?;
+    client2.receive_money(outpoint).await?;
+    fed.run_n_epochs_and_verify_all_invariants(1).await?;
+
+    let handle = tokio::runtime::Handle::current();
+    let mut runner = TestRunner::new(ProptestConfig::with_cases(20));
+    tokio::task::block_in_place(|| {
+        runner.run(&any::<u64>(), |seed| {
+            let result =
+                handle.block_on(fed.fuzz_consensus_items(&mut StdRng::seed_from_u64(seed), 1));
+            prop_assert!(
+                result.is_ok(),
+                "Fuzzing with seed {seed} failed: {result:?}"
+            );
+            prop_assert_eq!(handle.block_on(fed.audit()).net_assets, 0);
+            Ok(())
+        })
+    })
+    .map_err(|e| anyhow::anyhow!("{e}"))?;
+
+    assert_eq!(client1.get_balance().await, sats(750));
+    assert_eq!(client2.get_balance().await, sats(250));
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
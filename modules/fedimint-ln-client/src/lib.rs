@@ -20,7 +20,7 @@ use fedimint_client::module::{ClientModule, IClientModule};
 use fedimint_client::oplog::UpdateStreamOrOutcome;
 use fedimint_client::sm::util::MapStateTransitions;
 use fedimint_client::sm::{DynState, ModuleNotifier, OperationId, State, StateTransition};
-use fedimint_client::transaction::{ClientOutput, TransactionBuilder};
+use fedimint_client::transaction::{ClientInput, ClientOutput, TransactionBuilder};
 use fedimint_client::{sm_enum_variant_translation, Client, DynGlobalClientContext};
 use fedimint_core::api::DynModuleApi;
 use fedimint_core::config::FederationId;
@@ -37,7 +37,13 @@ use fedimint_core::{
 };
 use fedimint_ln_common::api::LnFederationApi;
 use fedimint_ln_common::config::LightningClientConfig;
+use fedimint_ln_common::contracts::conditional::{
+    ConditionalContract, ConditionalContractAccount, PaymentCondition,
+};
 use fedimint_ln_common::contracts::incoming::{IncomingContract, IncomingContractOffer};
+use fedimint_ln_common::contracts::multisig::{
+    MultisigContract, MultisigContractAccount, PartialSpendSignature,
+};
 use fedimint_ln_common::contracts::outgoing::{
     OutgoingContract, OutgoingContractAccount, OutgoingContractData,
 };
@@ -46,8 +52,8 @@ use fedimint_ln_common::contracts::{
 };
 use fedimint_ln_common::{
     ln_operation, ContractOutput, LightningClientContext, LightningCommonGen, LightningGateway,
-    LightningGatewayAnnouncement, LightningGatewayRegistration, LightningModuleTypes,
-    LightningOutput, KIND,
+    LightningGatewayAnnouncement, LightningGatewayRegistration, LightningInput,
+    LightningModuleTypes, LightningOutput, KIND,
 };
 use futures::StreamExt;
 use incoming::IncomingSmError;
@@ -98,6 +104,12 @@ pub trait LightningClientExt {
         invoice: Bolt11Invoice,
     ) -> anyhow::Result<OutgoingLightningPayment>;
 
+    /// Pays a LN invoice trying the registered gateways one after the other,
+    /// starting with the active one. Gateways whose API is unreachable are
+    /// skipped before any funds are locked, a gateway that fails to route the
+    /// payment cancels its contract and the next one is tried with the refund.
+    async fn pay_invoice_multipath(&self, invoice: Bolt11Invoice) -> anyhow::Result<Preimage>;
+
     async fn subscribe_internal_pay(
         &self,
         operation_id: OperationId,
@@ -121,6 +133,66 @@ pub trait LightningClientExt {
         &self,
         operation_id: OperationId,
     ) -> anyhow::Result<UpdateStreamOrOutcome<LnReceiveState>>;
+
+    /// Locks ecash in a contract that only the owner of `recipient` can claim
+    /// once the `condition` is met, see
+    /// [`LightningClientExt::conditional_payment_public_key`]. The returned
+    /// [`ConditionalPayment`] has to be handed to the recipient, who can claim
+    /// it using [`LightningClientExt::claim_conditional_payment`].
+    async fn create_conditional_payment(
+        &self,
+        amount: Amount,
+        condition: PaymentCondition,
+        recipient: secp256k1::XOnlyPublicKey,
+    ) -> anyhow::Result<ConditionalPayment>;
+
+    /// The key conditional payments to this client have to be locked to
+    fn conditional_payment_public_key(&self) -> secp256k1::XOnlyPublicKey;
+
+    /// Claims a [`ConditionalPayment`] locked to our key, the `preimage` is
+    /// only required for [`PaymentCondition::RevealPreimage`]
+    async fn claim_conditional_payment(
+        &self,
+        payment: ConditionalPayment,
+        preimage: Option<Preimage>,
+    ) -> anyhow::Result<(OperationId, TransactionId)>;
+
+    /// The key this client uses as a participant of multisig contracts
+    fn multisig_public_key(&self) -> secp256k1::XOnlyPublicKey;
+
+    /// Locks `amount` of ecash in a contract that can be claimed by any key
+    /// approved by at least `threshold` of the `participants`, see
+    /// [`LightningClientExt::multisig_public_key`]
+    async fn create_federated_multisig(
+        &self,
+        participants: Vec<secp256k1::XOnlyPublicKey>,
+        threshold: usize,
+        amount: Amount,
+    ) -> anyhow::Result<MultisigAddress>;
+
+    /// Approves the participant owning `claim_key` to claim the funds of
+    /// `address`, fails if we are not a participant
+    fn sign_federated_multisig_spend(
+        &self,
+        address: &MultisigAddress,
+        claim_key: secp256k1::XOnlyPublicKey,
+    ) -> anyhow::Result<PartialSpendSignature>;
+
+    /// Submits the `sigs` of the participants approving our
+    /// [`LightningClientExt::multisig_public_key`] to claim the funds of
+    /// `address`, see [`LightningClientExt::claim_federated_multisig`]
+    async fn combine_and_submit_multisig(
+        &self,
+        address: &MultisigAddress,
+        sigs: Vec<PartialSpendSignature>,
+    ) -> anyhow::Result<(OperationId, TransactionId)>;
+
+    /// Claims the funds of `address` into our wallet once our
+    /// [`LightningClientExt::multisig_public_key`] was approved
+    async fn claim_federated_multisig(
+        &self,
+        address: &MultisigAddress,
+    ) -> anyhow::Result<(OperationId, TransactionId)>;
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
@@ -218,6 +290,153 @@ async fn invoice_routes_back_to_federation(
     })
 }
 
+/// Pays `invoice` like [`LightningClientExt::pay_bolt11_invoice`], but through
+/// `gateway` instead of the active one if it is set
+async fn pay_bolt11_invoice_via(
+    client: &Client,
+    invoice: Bolt11Invoice,
+    gateway: Option<LightningGateway>,
+) -> anyhow::Result<OutgoingLightningPayment> {
+    let (lightning, instance) = client.get_first_module::<LightningClientModule>(&KIND);
+    let mut dbtx = instance.db.begin_transaction().await;
+    let prev_payment_result = lightning
+        .get_prev_payment_result(invoice.payment_hash(), &mut dbtx)
+        .await;
+
+    if let Some(completed_payment) = prev_payment_result.completed_payment {
+        return Ok(completed_payment);
+    }
+
+    // Verify that no previous payment attempt is still running
+    let prev_operation_id = lightning
+        .get_payment_operation_id(invoice.payment_hash(), prev_payment_result.index)
+        .await;
+    if client.has_active_states(prev_operation_id).await {
+        return Err(anyhow::anyhow!("Previous payment attempt still in progress. Previous Operation Id: {prev_operation_id}"));
+    }
+
+    let next_index = prev_payment_result.index + 1;
+    let operation_id = lightning
+        .get_payment_operation_id(invoice.payment_hash(), next_index)
+        .await;
+
+    let new_payment_result = PaymentResult {
+        index: next_index,
+        completed_payment: None,
+    };
+
+    dbtx.insert_entry(
+        &PaymentResultKey {
+            payment_hash: *invoice.payment_hash(),
+        },
+        &new_payment_result,
+    )
+    .await;
+
+    let is_internal_payment =
+        invoice_has_internal_payment_markers(&invoice, client.get_internal_payment_markers()?)
+            .await
+            || invoice_routes_back_to_federation(
+                &invoice,
+                client
+                    .fetch_registered_gateways()
+                    .await?
+                    .into_iter()
+                    .map(|gw| gw.info)
+                    .collect(),
+            )
+            .await;
+
+    let (pay_type, output, contract_id) = if is_internal_payment {
+        let (output, contract_id) = lightning
+            .create_incoming_output(operation_id, invoice.clone())
+            .await?;
+        (PayType::Internal(operation_id), output, contract_id)
+    } else {
+        let active_gateway = match gateway {
+            Some(gateway) => gateway,
+            None => client.select_active_gateway().await?,
+        };
+        let (output, contract_id) = lightning
+            .create_outgoing_output(
+                operation_id,
+                instance.api,
+                invoice.clone(),
+                active_gateway,
+                client.get_config().global.federation_id,
+                rand::rngs::OsRng,
+            )
+            .await?;
+        (PayType::Lightning(operation_id), output, contract_id)
+    };
+
+    // Verify that no other outgoing contract exists or the value is empty
+    if let Ok(contract) = lightning.module_api.fetch_contract(contract_id).await {
+        if contract.amount.msats != 0 {
+            return Err(anyhow::anyhow!(
+                "Funded contract already exists. ContractId: {contract_id}"
+            ));
+        }
+    }
+
+    // TODO: return fee from create_outgoing_output or even let user supply
+    // it/bounds for it
+    let fee = match &output.output {
+        LightningOutput::Contract(contract) => {
+            let fee_msat = contract
+                .amount
+                .msats
+                .checked_sub(
+                    invoice
+                        .amount_milli_satoshis()
+                        .ok_or(anyhow::anyhow!("MissingInvoiceAmount"))?,
+                )
+                .expect("Contract amount should be greater or equal than invoice amount");
+            Amount::from_msats(fee_msat)
+        }
+        _ => unreachable!("User client will only create contract outputs on spend"),
+    };
+    let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+    let operation_meta_gen = |txid, change_outpoint| LightningOperationMeta::Pay {
+        out_point: OutPoint { txid, out_idx: 0 },
+        invoice: invoice.clone(),
+        fee,
+        change_outpoint,
+    };
+
+    // Write the new payment index into the database, fail the payment if the commit
+    // to the database fails.
+    dbtx.commit_tx_result().await?;
+
+    client
+        .finalize_and_submit_transaction(
+            operation_id,
+            LightningCommonGen::KIND.as_str(),
+            operation_meta_gen,
+            tx,
+        )
+        .await?;
+
+    Ok(OutgoingLightningPayment {
+        payment_type: pay_type,
+        contract_id,
+        fee,
+    })
+}
+
+/// Checks whether the public API of `gateway` is reachable
+async fn gateway_is_online(gateway: &LightningGateway) -> bool {
+    let Ok(url) = gateway.api.join("id") else {
+        return false;
+    };
+    reqwest::Client::new()
+        .get(url.as_str())
+        .send()
+        .await
+        .map(|response| response.status().is_success())
+        .unwrap_or(false)
+}
+
 #[apply(async_trait_maybe_send!)]
 impl LightningClientExt for Client {
     async fn select_active_gateway(&self) -> anyhow::Result<LightningGateway> {
@@ -267,126 +486,74 @@ impl LightningClientExt for Client {
         &self,
         invoice: Bolt11Invoice,
     ) -> anyhow::Result<OutgoingLightningPayment> {
-        let (lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
-        let mut dbtx = instance.db.begin_transaction().await;
-        let prev_payment_result = lightning
-            .get_prev_payment_result(invoice.payment_hash(), &mut dbtx)
-            .await;
-
-        if let Some(completed_payment) = prev_payment_result.completed_payment {
-            return Ok(completed_payment);
-        }
-
-        // Verify that no previous payment attempt is still running
-        let prev_operation_id = lightning
-            .get_payment_operation_id(invoice.payment_hash(), prev_payment_result.index)
-            .await;
-        if self.has_active_states(prev_operation_id).await {
-            return Err(anyhow::anyhow!("Previous payment attempt still in progress. Previous Operation Id: {prev_operation_id}"));
-        }
-
-        let next_index = prev_payment_result.index + 1;
-        let operation_id = lightning
-            .get_payment_operation_id(invoice.payment_hash(), next_index)
-            .await;
-
-        let new_payment_result = PaymentResult {
-            index: next_index,
-            completed_payment: None,
-        };
+        pay_bolt11_invoice_via(self, invoice, None).await
+    }
 
-        dbtx.insert_entry(
-            &PaymentResultKey {
-                payment_hash: *invoice.payment_hash(),
-            },
-            &new_payment_result,
-        )
-        .await;
+    async fn pay_invoice_multipath(&self, invoice: Bolt11Invoice) -> anyhow::Result<Preimage> {
+        let active_gateway_id = self
+            .select_active_gateway()
+            .await
+            .ok()
+            .map(|gateway| gateway.gateway_id);
+        let mut gateways = self
+            .fetch_registered_gateways()
+            .await?
+            .into_iter()
+            .map(|gateway| gateway.info)
+            .collect::<Vec<_>>();
+        // Try the active gateway first
+        gateways.sort_by_key(|gateway| Some(gateway.gateway_id) != active_gateway_id);
+
+        for gateway in gateways {
+            if !gateway_is_online(&gateway).await {
+                debug!(gateway_id = %gateway.gateway_id, "Skipping offline gateway");
+                continue;
+            }
 
-        let is_internal_payment =
-            invoice_has_internal_payment_markers(&invoice, self.get_internal_payment_markers()?)
-                .await
-                || invoice_routes_back_to_federation(
-                    &invoice,
-                    self.fetch_registered_gateways()
+            let gateway_id = gateway.gateway_id;
+            let payment = pay_bolt11_invoice_via(self, invoice.clone(), Some(gateway)).await?;
+            let operation_id = match payment.payment_type {
+                PayType::Lightning(operation_id) => operation_id,
+                PayType::Internal(operation_id) => {
+                    let mut updates = self
+                        .subscribe_internal_pay(operation_id)
                         .await?
-                        .into_iter()
-                        .map(|gw| gw.info)
-                        .collect(),
-                )
-                .await;
-
-        let (pay_type, output, contract_id) = if is_internal_payment {
-            let (output, contract_id) = lightning
-                .create_incoming_output(operation_id, invoice.clone())
-                .await?;
-            (PayType::Internal(operation_id), output, contract_id)
-        } else {
-            let active_gateway = self.select_active_gateway().await?;
-            let (output, contract_id) = lightning
-                .create_outgoing_output(
-                    operation_id,
-                    instance.api,
-                    invoice.clone(),
-                    active_gateway,
-                    self.get_config().global.federation_id,
-                    rand::rngs::OsRng,
-                )
-                .await?;
-            (PayType::Lightning(operation_id), output, contract_id)
-        };
-
-        // Verify that no other outgoing contract exists or the value is empty
-        if let Ok(contract) = lightning.module_api.fetch_contract(contract_id).await {
-            if contract.amount.msats != 0 {
-                return Err(anyhow::anyhow!(
-                    "Funded contract already exists. ContractId: {contract_id}"
-                ));
+                        .into_stream();
+                    while let Some(state) = updates.next().await {
+                        match state {
+                            InternalPayState::Funding => {}
+                            InternalPayState::Preimage(preimage) => return Ok(preimage),
+                            state => bail!("Internal payment failed: {state:?}"),
+                        }
+                    }
+                    bail!("Internal payment update stream ended unexpectedly");
+                }
+            };
+
+            let mut updates = self.subscribe_ln_pay(operation_id).await?.into_stream();
+            while let Some(state) = updates.next().await {
+                match state {
+                    LnPayState::Success { preimage } => {
+                        let preimage: Vec<u8> =
+                            bitcoin_hashes::hex::FromHex::from_hex(preimage.as_str())?;
+                        return Ok(Preimage(
+                            preimage
+                                .try_into()
+                                .map_err(|_| format_err!("Preimage has an invalid length"))?,
+                        ));
+                    }
+                    LnPayState::Refunded { gateway_error } => {
+                        debug!(%gateway_id, ?gateway_error, "Gateway failed to pay, trying the next one");
+                        break;
+                    }
+                    LnPayState::Canceled => bail!("Funding transaction was rejected"),
+                    LnPayState::UnexpectedError { error_message } => bail!(error_message),
+                    _ => {}
+                }
             }
         }
 
-        // TODO: return fee from create_outgoing_output or even let user supply
-        // it/bounds for it
-        let fee = match &output.output {
-            LightningOutput::Contract(contract) => {
-                let fee_msat = contract
-                    .amount
-                    .msats
-                    .checked_sub(
-                        invoice
-                            .amount_milli_satoshis()
-                            .ok_or(anyhow::anyhow!("MissingInvoiceAmount"))?,
-                    )
-                    .expect("Contract amount should be greater or equal than invoice amount");
-                Amount::from_msats(fee_msat)
-            }
-            _ => unreachable!("User client will only create contract outputs on spend"),
-        };
-        let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
-        let operation_meta_gen = |txid, change_outpoint| LightningOperationMeta::Pay {
-            out_point: OutPoint { txid, out_idx: 0 },
-            invoice: invoice.clone(),
-            fee,
-            change_outpoint,
-        };
-
-        // Write the new payment index into the database, fail the payment if the commit
-        // to the database fails.
-        dbtx.commit_tx_result().await?;
-
-        self.finalize_and_submit_transaction(
-            operation_id,
-            LightningCommonGen::KIND.as_str(),
-            operation_meta_gen,
-            tx,
-        )
-        .await?;
-
-        Ok(OutgoingLightningPayment {
-            payment_type: pay_type,
-            contract_id,
-            fee,
-        })
+        bail!("None of the registered gateways could pay the invoice")
     }
 
     async fn create_bolt11_invoice<M: Serialize + Send + Sync>(
@@ -613,6 +780,272 @@ impl LightningClientExt for Client {
             }
         }))
     }
+
+    async fn create_conditional_payment(
+        &self,
+        amount: Amount,
+        condition: PaymentCondition,
+        recipient: secp256k1::XOnlyPublicKey,
+    ) -> anyhow::Result<ConditionalPayment> {
+        let (_lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
+
+        let contract = ConditionalContract {
+            condition,
+            recipient_key: recipient,
+            nonce: rand::rngs::OsRng.gen(),
+        };
+        let contract_id = contract.contract_id();
+        let operation_id = OperationId(contract_id.into_inner());
+
+        let output = ClientOutput::<LightningOutput, LightningClientStateMachines> {
+            output: LightningOutput::Contract(ContractOutput {
+                amount,
+                contract: Contract::Conditional(contract.clone()),
+            }),
+            // The contract is claimed by the recipient, so there is nothing to track here
+            state_machines: Arc::new(|_, _| vec![]),
+        };
+        let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+        let operation_meta_gen =
+            |txid, change_outpoint| LightningOperationMeta::CreateConditionalPayment {
+                out_point: OutPoint { txid, out_idx: 0 },
+                contract_id,
+                change_outpoint,
+            };
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                LightningCommonGen::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        self.transaction_updates(operation_id)
+            .await
+            .await_tx_accepted(txid)
+            .await
+            .map_err(|e| anyhow::anyhow!("Conditional payment was not accepted: {e:?}"))?;
+
+        Ok(ConditionalPayment {
+            contract: ConditionalContractAccount { amount, contract },
+        })
+    }
+
+    fn conditional_payment_public_key(&self) -> secp256k1::XOnlyPublicKey {
+        let (lightning, _instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        lightning.conditional_payment_key.x_only_public_key().0
+    }
+
+    async fn claim_conditional_payment(
+        &self,
+        payment: ConditionalPayment,
+        preimage: Option<Preimage>,
+    ) -> anyhow::Result<(OperationId, TransactionId)> {
+        let (lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        ensure!(
+            payment.contract.contract.recipient_key
+                == lightning.conditional_payment_key.x_only_public_key().0,
+            "Conditional payment is locked to another recipient"
+        );
+        let operation_id = OperationId::new_random();
+        let contract_id = payment.contract.contract.contract_id();
+
+        let input = ClientInput::<LightningInput, LightningClientStateMachines> {
+            input: payment.contract.claim(preimage),
+            keys: vec![lightning.conditional_payment_key],
+            // The claimed funds are turned into change of the primary module, so no new state
+            // machines need to be created
+            state_machines: Arc::new(|_, _| vec![]),
+        };
+        let tx = TransactionBuilder::new().with_input(input.into_dyn(instance.id));
+        let operation_meta_gen =
+            |_, change_outpoint| LightningOperationMeta::ClaimConditionalPayment {
+                contract_id,
+                change_outpoint,
+            };
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                LightningCommonGen::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        Ok((operation_id, txid))
+    }
+
+    fn multisig_public_key(&self) -> secp256k1::XOnlyPublicKey {
+        let (lightning, _instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        lightning.multisig_key.x_only_public_key().0
+    }
+
+    async fn create_federated_multisig(
+        &self,
+        participants: Vec<secp256k1::XOnlyPublicKey>,
+        threshold: usize,
+        amount: Amount,
+    ) -> anyhow::Result<MultisigAddress> {
+        ensure!(
+            0 < threshold && threshold <= participants.len(),
+            "Threshold has to be between 1 and the number of participants"
+        );
+
+        let (_lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        let contract = MultisigContract {
+            participants,
+            threshold: threshold as u64,
+            nonce: rand::rngs::OsRng.gen(),
+            approved_claim_key: None,
+        };
+        let contract_id = contract.contract_id();
+        let operation_id = OperationId(contract_id.into_inner());
+
+        let output = ClientOutput::<LightningOutput, LightningClientStateMachines> {
+            output: LightningOutput::Contract(ContractOutput {
+                amount,
+                contract: Contract::Multisig(contract.clone()),
+            }),
+            // The contract is claimed by one of the participants, so there is nothing to track here
+            state_machines: Arc::new(|_, _| vec![]),
+        };
+        let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+        let operation_meta_gen =
+            |txid, change_outpoint| LightningOperationMeta::CreateFederatedMultisig {
+                out_point: OutPoint { txid, out_idx: 0 },
+                contract_id,
+                change_outpoint,
+            };
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                LightningCommonGen::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        self.transaction_updates(operation_id)
+            .await
+            .await_tx_accepted(txid)
+            .await
+            .map_err(|e| anyhow::anyhow!("Multisig contract was not accepted: {e:?}"))?;
+
+        Ok(MultisigAddress {
+            contract: MultisigContractAccount { amount, contract },
+        })
+    }
+
+    fn sign_federated_multisig_spend(
+        &self,
+        address: &MultisigAddress,
+        claim_key: secp256k1::XOnlyPublicKey,
+    ) -> anyhow::Result<PartialSpendSignature> {
+        let (lightning, _instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        let participant = lightning.multisig_key.x_only_public_key().0;
+        ensure!(
+            address
+                .contract
+                .contract
+                .participants
+                .contains(&participant),
+            "We are not a participant of this multisig contract"
+        );
+
+        let message = address.contract.contract.approval_message(&claim_key);
+        Ok(PartialSpendSignature {
+            participant,
+            signature: lightning
+                .secp
+                .sign_schnorr(&message.into(), &lightning.multisig_key),
+        })
+    }
+
+    async fn combine_and_submit_multisig(
+        &self,
+        address: &MultisigAddress,
+        sigs: Vec<PartialSpendSignature>,
+    ) -> anyhow::Result<(OperationId, TransactionId)> {
+        let (lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        let operation_id = OperationId::new_random();
+        let contract_id = address.contract.contract.contract_id();
+        let claim_key = lightning.multisig_key.x_only_public_key().0;
+
+        let output = ClientOutput::<LightningOutput, LightningClientStateMachines> {
+            output: address.contract.approve_claim(claim_key, sigs),
+            // Approving the claim key doesn't move any funds
+            state_machines: Arc::new(|_, _| vec![]),
+        };
+        let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+        let operation_meta_gen =
+            |_, change_outpoint| LightningOperationMeta::ApproveFederatedMultisigClaim {
+                contract_id,
+                change_outpoint,
+            };
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                LightningCommonGen::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        Ok((operation_id, txid))
+    }
+
+    async fn claim_federated_multisig(
+        &self,
+        address: &MultisigAddress,
+    ) -> anyhow::Result<(OperationId, TransactionId)> {
+        let (lightning, instance) = self.get_first_module::<LightningClientModule>(&KIND);
+        let operation_id = OperationId::new_random();
+        let contract_id = address.contract.contract.contract_id();
+
+        let input = ClientInput::<LightningInput, LightningClientStateMachines> {
+            input: address.contract.claim(),
+            keys: vec![lightning.multisig_key],
+            // The claimed funds are turned into change of the primary module, so no new state
+            // machines need to be created
+            state_machines: Arc::new(|_, _| vec![]),
+        };
+        let tx = TransactionBuilder::new().with_input(input.into_dyn(instance.id));
+        let operation_meta_gen =
+            |_, change_outpoint| LightningOperationMeta::ClaimFederatedMultisig {
+                contract_id,
+                change_outpoint,
+            };
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                LightningCommonGen::KIND.as_str(),
+                operation_meta_gen,
+                tx,
+            )
+            .await?;
+
+        Ok((operation_id, txid))
+    }
+}
+
+/// Ecash locked in a [`MultisigContract`], see
+/// [`LightningClientExt::create_federated_multisig`]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct MultisigAddress {
+    pub contract: MultisigContractAccount,
+}
+
+/// Ecash locked in a [`ConditionalContract`] to the key of its recipient, see
+/// [`LightningClientExt::create_conditional_payment`]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct ConditionalPayment {
+    pub contract: ConditionalContractAccount,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -629,6 +1062,28 @@ pub enum LightningOperationMeta {
         invoice: Bolt11Invoice,
         extra_meta: serde_json::Value,
     },
+    CreateConditionalPayment {
+        out_point: OutPoint,
+        contract_id: ContractId,
+        change_outpoint: Option<OutPoint>,
+    },
+    ClaimConditionalPayment {
+        contract_id: ContractId,
+        change_outpoint: Option<OutPoint>,
+    },
+    CreateFederatedMultisig {
+        out_point: OutPoint,
+        contract_id: ContractId,
+        change_outpoint: Option<OutPoint>,
+    },
+    ApproveFederatedMultisigClaim {
+        contract_id: ContractId,
+        change_outpoint: Option<OutPoint>,
+    },
+    ClaimFederatedMultisig {
+        contract_id: ContractId,
+        change_outpoint: Option<OutPoint>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -696,6 +1151,14 @@ impl ClientModuleInit for LightningClientGen {
                 .module_root_secret()
                 .child_key(ChildId(0))
                 .to_secp_key(&secp),
+            multisig_key: args
+                .module_root_secret()
+                .child_key(ChildId(1))
+                .to_secp_key(&secp),
+            conditional_payment_key: args
+                .module_root_secret()
+                .child_key(ChildId(2))
+                .to_secp_key(&secp),
             secp,
             module_api: args.module_api().clone(),
         })
@@ -707,6 +1170,8 @@ pub struct LightningClientModule {
     pub cfg: LightningClientConfig,
     notifier: ModuleNotifier<DynGlobalClientContext, LightningClientStateMachines>,
     redeem_key: KeyPair,
+    multisig_key: KeyPair,
+    conditional_payment_key: KeyPair,
     secp: Secp256k1<All>,
     module_api: DynModuleApi,
 }
@@ -739,12 +1204,12 @@ impl ClientModule for LightningClientModule {
                 amount: account_output.amount,
                 fee: self.cfg.fee_consensus.contract_output,
             },
-            LightningOutput::Offer(_) | LightningOutput::CancelOutgoing { .. } => {
-                TransactionItemAmount {
-                    amount: Amount::ZERO,
-                    fee: Amount::ZERO,
-                }
-            }
+            LightningOutput::Offer(_)
+            | LightningOutput::CancelOutgoing { .. }
+            | LightningOutput::ApproveMultisigClaim { .. } => TransactionItemAmount {
+                amount: Amount::ZERO,
+                fee: Amount::ZERO,
+            },
         }
     }
 }
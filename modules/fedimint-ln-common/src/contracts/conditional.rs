@@ -0,0 +1,58 @@
+use bitcoin_hashes::Hash as BitcoinHash;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use super::Preimage;
+use crate::contracts::{ContractId, IdentifiableContract};
+use crate::LightningInput;
+
+/// Condition that has to be fulfilled before a [`ConditionalContract`] can be
+/// claimed by its recipient
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub enum PaymentCondition {
+    /// The recipient has to reveal the preimage of the given hash
+    RevealPreimage(bitcoin_hashes::sha256::Hash),
+    /// The recipient can claim the funds once the given epoch is complete
+    AfterEpoch(u64),
+}
+
+/// Contract locking ecash to a recipient until a [`PaymentCondition`] is met.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct ConditionalContract {
+    /// Condition under which the recipient can claim the funds
+    pub condition: PaymentCondition,
+    /// Public key of the recipient allowed to claim the funds
+    pub recipient_key: secp256k1::XOnlyPublicKey,
+    /// Random nonce allowing multiple payments with the same condition to the
+    /// same recipient
+    pub nonce: [u8; 32],
+}
+
+impl IdentifiableContract for ConditionalContract {
+    fn contract_id(&self) -> ContractId {
+        let mut engine = ContractId::engine();
+        Encodable::consensus_encode(&self.condition, &mut engine).expect("Hashing never fails");
+        Encodable::consensus_encode(&self.recipient_key, &mut engine).expect("Hashing never fails");
+        Encodable::consensus_encode(&self.nonce, &mut engine).expect("Hashing never fails");
+        ContractId::from_engine(engine)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct ConditionalContractAccount {
+    pub amount: Amount,
+    pub contract: ConditionalContract,
+}
+
+impl ConditionalContractAccount {
+    /// Input claiming the contract, `preimage` is only required for
+    /// [`PaymentCondition::RevealPreimage`]
+    pub fn claim(&self, preimage: Option<Preimage>) -> LightningInput {
+        LightningInput {
+            contract_id: self.contract.contract_id(),
+            amount: self.amount,
+            witness: preimage,
+        }
+    }
+}
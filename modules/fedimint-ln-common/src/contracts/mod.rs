@@ -1,4 +1,6 @@
+pub mod conditional;
 pub mod incoming;
+pub mod multisig;
 pub mod outgoing;
 
 use std::io::Error;
@@ -29,6 +31,8 @@ hash_newtype!(
 pub enum Contract {
     Incoming(incoming::IncomingContract),
     Outgoing(outgoing::OutgoingContract),
+    Conditional(conditional::ConditionalContract),
+    Multisig(multisig::MultisigContract),
 }
 
 /// A contract after execution as saved in the database
@@ -37,6 +41,8 @@ pub enum Contract {
 pub enum FundedContract {
     Incoming(incoming::FundedIncomingContract),
     Outgoing(outgoing::OutgoingContract),
+    Conditional(conditional::ConditionalContract),
+    Multisig(multisig::MultisigContract),
 }
 
 /// Outcome of a contract. Only incoming contracts currently need to communicate
@@ -45,6 +51,8 @@ pub enum FundedContract {
 pub enum ContractOutcome {
     Incoming(DecryptedPreimage),
     Outgoing(OutgoingContractOutcome),
+    Conditional(ConditionalContractOutcome),
+    Multisig(MultisigContractOutcome),
 }
 
 impl ContractOutcome {
@@ -52,6 +60,8 @@ impl ContractOutcome {
         match self {
             ContractOutcome::Incoming(o) => o.is_permanent(),
             ContractOutcome::Outgoing(_) => true,
+            ContractOutcome::Conditional(_) => true,
+            ContractOutcome::Multisig(_) => true,
         }
     }
 }
@@ -59,11 +69,19 @@ impl ContractOutcome {
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
 pub struct OutgoingContractOutcome {}
 
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct ConditionalContractOutcome {}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MultisigContractOutcome {}
+
 impl IdentifiableContract for Contract {
     fn contract_id(&self) -> ContractId {
         match self {
             Contract::Incoming(c) => c.contract_id(),
             Contract::Outgoing(c) => c.contract_id(),
+            Contract::Conditional(c) => c.contract_id(),
+            Contract::Multisig(c) => c.contract_id(),
         }
     }
 }
@@ -73,6 +91,8 @@ impl IdentifiableContract for FundedContract {
         match self {
             FundedContract::Incoming(c) => c.contract.contract_id(),
             FundedContract::Outgoing(c) => c.contract_id(),
+            FundedContract::Conditional(c) => c.contract_id(),
+            FundedContract::Multisig(c) => c.contract_id(),
         }
     }
 }
@@ -84,6 +104,8 @@ impl Contract {
         match self {
             Contract::Incoming(_) => ContractOutcome::Incoming(DecryptedPreimage::Pending),
             Contract::Outgoing(_) => ContractOutcome::Outgoing(OutgoingContractOutcome {}),
+            Contract::Conditional(_) => ContractOutcome::Conditional(ConditionalContractOutcome {}),
+            Contract::Multisig(_) => ContractOutcome::Multisig(MultisigContractOutcome {}),
         }
     }
 
@@ -97,6 +119,8 @@ impl Contract {
                 })
             }
             Contract::Outgoing(outgoing) => FundedContract::Outgoing(outgoing),
+            Contract::Conditional(conditional) => FundedContract::Conditional(conditional),
+            Contract::Multisig(multisig) => FundedContract::Multisig(multisig),
         }
     }
 }
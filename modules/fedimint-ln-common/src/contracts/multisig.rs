@@ -0,0 +1,103 @@
+use bitcoin_hashes::Hash as BitcoinHash;
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+
+use crate::contracts::{ContractId, IdentifiableContract};
+use crate::{LightningInput, LightningOutput};
+
+const APPROVAL_TAG: &str = "multisig contract approval";
+
+/// Contract locking ecash to a group of participants, `threshold` of which
+/// have to approve the key that is allowed to claim the funds.
+///
+/// The approval is submitted as a [`LightningOutput::ApproveMultisigClaim`],
+/// afterwards the funds are claimed like any other contract by a transaction
+/// signed with the approved key.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MultisigContract {
+    /// Public keys of the participants that can approve a claim
+    pub participants: Vec<secp256k1::XOnlyPublicKey>,
+    /// Number of distinct participants that have to approve a claim
+    pub threshold: u64,
+    /// Random nonce allowing the same group to create multiple contracts
+    pub nonce: [u8; 32],
+    /// Key approved by the participants to claim the funds, has to be `None`
+    /// when funding the contract
+    pub approved_claim_key: Option<secp256k1::XOnlyPublicKey>,
+}
+
+impl IdentifiableContract for MultisigContract {
+    fn contract_id(&self) -> ContractId {
+        let mut engine = ContractId::engine();
+        Encodable::consensus_encode(&self.participants, &mut engine).expect("Hashing never fails");
+        Encodable::consensus_encode(&self.threshold, &mut engine).expect("Hashing never fails");
+        Encodable::consensus_encode(&self.nonce, &mut engine).expect("Hashing never fails");
+        ContractId::from_engine(engine)
+    }
+}
+
+impl MultisigContract {
+    /// The message participants sign to approve `claim_key` claiming the
+    /// contract
+    pub fn approval_message(
+        &self,
+        claim_key: &secp256k1::XOnlyPublicKey,
+    ) -> bitcoin_hashes::sha256::Hash {
+        let mut engine = bitcoin_hashes::sha256::Hash::engine();
+        Encodable::consensus_encode(&APPROVAL_TAG.as_bytes(), &mut engine)
+            .expect("Hashing never fails");
+        Encodable::consensus_encode(&self.contract_id(), &mut engine).expect("Hashing never fails");
+        Encodable::consensus_encode(claim_key, &mut engine).expect("Hashing never fails");
+        bitcoin_hashes::sha256::Hash::from_engine(engine)
+    }
+}
+
+/// Approval of a single participant of a [`MultisigContract`] for a claim key
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct PartialSpendSignature {
+    pub participant: secp256k1::XOnlyPublicKey,
+    pub signature: secp256k1::schnorr::Signature,
+}
+
+/// Witness required to claim a [`MultisigContract`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct MultisigWitness {
+    /// Key that signs the claiming transaction
+    pub claim_key: secp256k1::XOnlyPublicKey,
+    /// Approvals of `claim_key` by at least `threshold` participants
+    pub signatures: Vec<PartialSpendSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct MultisigContractAccount {
+    pub amount: Amount,
+    pub contract: MultisigContract,
+}
+
+impl MultisigContractAccount {
+    /// Output approving `claim_key` to claim the contract
+    pub fn approve_claim(
+        &self,
+        claim_key: secp256k1::XOnlyPublicKey,
+        signatures: Vec<PartialSpendSignature>,
+    ) -> LightningOutput {
+        LightningOutput::ApproveMultisigClaim {
+            contract: self.contract.contract_id(),
+            witness: MultisigWitness {
+                claim_key,
+                signatures,
+            },
+        }
+    }
+
+    /// Input claiming the contract, has to be signed with the approved claim
+    /// key
+    pub fn claim(&self) -> LightningInput {
+        LightningInput {
+            contract_id: self.contract.contract_id(),
+            amount: self.amount,
+            witness: None,
+        }
+    }
+}
@@ -20,6 +20,7 @@ pub enum DbKeyPrefix {
     BlockCountVote = 0x46,
     EncryptedPreimageIndex = 0x47,
     LightningAuditItem = 0x48,
+    CompletedEpochCount = 0x49,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -76,6 +77,8 @@ impl_db_lookup!(
 pub enum LightningAuditItemKey {
     Incoming(ContractId),
     Outgoing(ContractId),
+    Conditional(ContractId),
+    Multisig(ContractId),
 }
 
 impl LightningAuditItemKey {
@@ -87,6 +90,12 @@ impl LightningAuditItemKey {
             FundedContract::Incoming(incoming) => {
                 LightningAuditItemKey::Incoming(incoming.contract.contract_id())
             }
+            FundedContract::Conditional(conditional) => {
+                LightningAuditItemKey::Conditional(conditional.contract_id())
+            }
+            FundedContract::Multisig(multisig) => {
+                LightningAuditItemKey::Multisig(multisig.contract_id())
+            }
         }
     }
 }
@@ -205,3 +214,21 @@ impl_db_record!(
 );
 
 impl_db_lookup!(key = BlockCountVoteKey, query_prefix = BlockCountVotePrefix);
+
+/// Number of consensus epochs that have been completed, used to evaluate
+/// [`crate::contracts::conditional::PaymentCondition::AfterEpoch`]
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct CompletedEpochCountKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct CompletedEpochCountKeyPrefix;
+
+impl_db_record!(
+    key = CompletedEpochCountKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::CompletedEpochCount,
+);
+impl_db_lookup!(
+    key = CompletedEpochCountKey,
+    query_prefix = CompletedEpochCountKeyPrefix
+);
@@ -32,6 +32,7 @@ use thiserror::Error;
 use tracing::error;
 
 use crate::contracts::incoming::OfferId;
+use crate::contracts::multisig::MultisigWitness;
 use crate::contracts::{Contract, ContractId, ContractOutcome, Preimage, PreimageDecryptionShare};
 
 pub const KIND: ModuleKind = ModuleKind::from_static_str("ln");
@@ -84,6 +85,13 @@ pub enum LightningOutput {
         /// Signature of gateway
         gateway_signature: secp256k1::schnorr::Signature,
     },
+    /// Approve the key that may claim a multisig contract
+    ApproveMultisigClaim {
+        /// Contract to update
+        contract: ContractId,
+        /// Approvals of the participants
+        witness: MultisigWitness,
+    },
 }
 
 impl std::fmt::Display for LightningOutput {
@@ -104,6 +112,22 @@ impl std::fmt::Display for LightningOutput {
                         amount, outgoing.hash
                     )
                 }
+                Contract::Conditional(conditional) => {
+                    write!(
+                        f,
+                        "LN Conditional Contract for {} to {}",
+                        amount, conditional.recipient_key
+                    )
+                }
+                Contract::Multisig(multisig) => {
+                    write!(
+                        f,
+                        "LN {}-of-{} Multisig Contract for {}",
+                        multisig.threshold,
+                        multisig.participants.len(),
+                        amount
+                    )
+                }
             },
             LightningOutput::Offer(offer) => {
                 write!(f, "LN offer for {} with hash {}", offer.amount, offer.hash)
@@ -111,6 +135,13 @@ impl std::fmt::Display for LightningOutput {
             LightningOutput::CancelOutgoing { contract, .. } => {
                 write!(f, "LN outgoing contract cancellation {contract}")
             }
+            LightningOutput::ApproveMultisigClaim { contract, witness } => {
+                write!(
+                    f,
+                    "LN multisig contract {contract} claim approval for {}",
+                    witness.claim_key
+                )
+            }
         }
     }
 }
@@ -139,6 +170,9 @@ pub enum LightningOutputOutcome {
     CancelOutgoingContract {
         id: ContractId,
     },
+    ApproveMultisigClaim {
+        id: ContractId,
+    },
 }
 
 impl LightningOutputOutcome {
@@ -147,6 +181,7 @@ impl LightningOutputOutcome {
             LightningOutputOutcome::Contract { id: _, outcome } => outcome.is_permanent(),
             LightningOutputOutcome::Offer { .. } => true,
             LightningOutputOutcome::CancelOutgoingContract { .. } => true,
+            LightningOutputOutcome::ApproveMultisigClaim { .. } => true,
         }
     }
 }
@@ -163,6 +198,9 @@ impl std::fmt::Display for LightningOutputOutcome {
             LightningOutputOutcome::CancelOutgoingContract { id: contract_id } => {
                 write!(f, "LN Outgoing Contract Cancellation {contract_id}")
             }
+            LightningOutputOutcome::ApproveMultisigClaim { id: contract_id } => {
+                write!(f, "LN Multisig Contract Claim Approval {contract_id}")
+            }
         }
     }
 }
@@ -471,6 +509,26 @@ pub enum LightningError {
     NotOutgoingContract,
     #[error("Cancellation request wasn't properly signed")]
     InvalidCancellationSignature,
+    #[error("Conditional contract can only be claimed after epoch {0} (completed epochs: {1})")]
+    EpochNotReached(u64, u64),
+    #[error("The claim key of the multisig contract wasn't approved yet")]
+    MultisigClaimNotApproved,
+    #[error("The claim key of the multisig contract was already approved")]
+    MultisigClaimAlreadyApproved,
+    #[error("Multisig contracts can't be funded with an approved claim key")]
+    PreapprovedMultisigClaim,
+    #[error("Multisig contract threshold {0} has to be between 1 and its {1} participants")]
+    InvalidMultisigThreshold(u64, u64),
+    #[error("Multisig contract lists participant {0} more than once")]
+    DuplicateParticipant(secp256k1::XOnlyPublicKey),
+    #[error("Only multisig contracts support claim approvals")]
+    NotMultisigContract,
+    #[error("Multisig contract spend was approved by an unknown participant {0}")]
+    UnknownParticipant(secp256k1::XOnlyPublicKey),
+    #[error("Multisig contract spend carries an invalid approval by {0}")]
+    InvalidApproval(secp256k1::XOnlyPublicKey),
+    #[error("Multisig contract spend needs {0} approvals, got {1}")]
+    NotEnoughApprovals(u64, u64),
 }
 
 pub async fn ln_operation(
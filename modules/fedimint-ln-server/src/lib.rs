@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
 use anyhow::{bail, Context};
@@ -32,6 +32,7 @@ use fedimint_ln_common::config::{
     FeeConsensus, LightningClientConfig, LightningConfig, LightningConfigConsensus,
     LightningConfigLocal, LightningConfigPrivate, LightningGenParams,
 };
+use fedimint_ln_common::contracts::conditional::PaymentCondition;
 use fedimint_ln_common::contracts::incoming::{IncomingContractAccount, IncomingContractOffer};
 use fedimint_ln_common::contracts::{
     Contract, ContractId, ContractOutcome, DecryptedPreimage, EncryptedPreimage, FundedContract,
@@ -39,11 +40,12 @@ use fedimint_ln_common::contracts::{
 };
 use fedimint_ln_common::db::{
     AgreedDecryptionShareContractIdPrefix, AgreedDecryptionShareKey,
-    AgreedDecryptionShareKeyPrefix, BlockCountVoteKey, BlockCountVotePrefix, ContractKey,
-    ContractKeyPrefix, ContractUpdateKey, ContractUpdateKeyPrefix, DbKeyPrefix,
-    EncryptedPreimageIndexKey, EncryptedPreimageIndexKeyPrefix, LightningAuditItemKey,
-    LightningAuditItemKeyPrefix, LightningGatewayKey, LightningGatewayKeyPrefix, OfferKey,
-    OfferKeyPrefix, ProposeDecryptionShareKey, ProposeDecryptionShareKeyPrefix,
+    AgreedDecryptionShareKeyPrefix, BlockCountVoteKey, BlockCountVotePrefix,
+    CompletedEpochCountKey, CompletedEpochCountKeyPrefix, ContractKey, ContractKeyPrefix,
+    ContractUpdateKey, ContractUpdateKeyPrefix, DbKeyPrefix, EncryptedPreimageIndexKey,
+    EncryptedPreimageIndexKeyPrefix, LightningAuditItemKey, LightningAuditItemKeyPrefix,
+    LightningGatewayKey, LightningGatewayKeyPrefix, OfferKey, OfferKeyPrefix,
+    ProposeDecryptionShareKey, ProposeDecryptionShareKeyPrefix,
 };
 use fedimint_ln_common::{
     ContractAccount, LightningCommonGen, LightningConsensusItem, LightningError,
@@ -82,6 +84,16 @@ lazy_static! {
         "contracts::FundedContract::Outgoing"
     ))
     .unwrap();
+    static ref LN_FUNDED_CONTRACT_CONDITIONAL: IntCounter = register_int_counter!(opts!(
+        "ln_funded_contract_conditional",
+        "contracts::FundedContract::Conditional"
+    ))
+    .unwrap();
+    static ref LN_FUNDED_CONTRACT_MULTISIG: IntCounter = register_int_counter!(opts!(
+        "ln_funded_contract_multisig",
+        "contracts::FundedContract::Multisig"
+    ))
+    .unwrap();
     static ref AMOUNTS_BUCKETS_SATS: Vec<f64> = vec![0.0, 0.5, 1.0, 1000.0];
     static ref LN_FUNDED_CONTRACT_INCOMING_ACCOUNT_AMOUNTS_SATS: Histogram =
         register_histogram!(histogram_opts!(
@@ -97,11 +109,13 @@ lazy_static! {
             AMOUNTS_BUCKETS_SATS.clone()
         ))
         .unwrap();
-    static ref ALL_METRICS: [Box<dyn prometheus::core::Collector>; 6] = [
+    static ref ALL_METRICS: [Box<dyn prometheus::core::Collector>; 8] = [
         Box::new(LN_INCOMING_OFFER.clone()),
         Box::new(LN_OUTPUT_OUTCOME_CANCEL_OUTGOING_CONTRACT.clone()),
         Box::new(LN_FUNDED_CONTRACT_INCOMING.clone()),
         Box::new(LN_FUNDED_CONTRACT_OUTGOING.clone()),
+        Box::new(LN_FUNDED_CONTRACT_CONDITIONAL.clone()),
+        Box::new(LN_FUNDED_CONTRACT_MULTISIG.clone()),
         Box::new(LN_FUNDED_CONTRACT_INCOMING_ACCOUNT_AMOUNTS_SATS.clone()),
         Box::new(LN_FUNDED_CONTRACT_OUTGOING_ACCOUNT_AMOUNTS_SATS.clone()),
     ];
@@ -216,6 +230,16 @@ impl ExtendsCommonModuleInit for LightningGen {
                         "Lightning Audit Items"
                     );
                 }
+                DbKeyPrefix::CompletedEpochCount => {
+                    push_db_pair_items!(
+                        dbtx,
+                        CompletedEpochCountKeyPrefix,
+                        CompletedEpochCountKey,
+                        u64,
+                        lightning,
+                        "Completed Epoch Count"
+                    );
+                }
             }
         }
 
@@ -416,6 +440,12 @@ impl ServerModule for Lightning {
                     FundedContract::Outgoing(..) => {
                         bail!("Contract account for this decryption share is outgoing");
                     }
+                    FundedContract::Conditional(..) => {
+                        bail!("Contract account for this decryption share is conditional");
+                    }
+                    FundedContract::Multisig(..) => {
+                        bail!("Contract account for this decryption share is multisig");
+                    }
                 };
 
                 if contract.decrypted_preimage != DecryptedPreimage::Pending {
@@ -600,6 +630,38 @@ impl ServerModule for Lightning {
                 // … or the gateway may claim back funds for not receiving the advertised preimage.
                 DecryptedPreimage::Invalid => incoming.contract.gateway_key,
             },
+            FundedContract::Conditional(conditional) => {
+                match &conditional.condition {
+                    PaymentCondition::RevealPreimage(hash) => {
+                        let preimage_hash = bitcoin_hashes::sha256::Hash::hash(
+                            &input
+                                .witness
+                                .as_ref()
+                                .ok_or(LightningError::MissingPreimage)
+                                .into_module_error_other()?
+                                .0,
+                        );
+
+                        if preimage_hash != *hash {
+                            return Err(LightningError::InvalidPreimage).into_module_error_other();
+                        }
+                    }
+                    PaymentCondition::AfterEpoch(epoch) => {
+                        let completed_epochs = self.completed_epoch_count(dbtx).await;
+
+                        if completed_epochs <= *epoch {
+                            return Err(LightningError::EpochNotReached(*epoch, completed_epochs))
+                                .into_module_error_other();
+                        }
+                    }
+                }
+
+                conditional.recipient_key
+            }
+            FundedContract::Multisig(multisig) => multisig
+                .approved_claim_key
+                .ok_or(LightningError::MultisigClaimNotApproved)
+                .into_module_error_other()?,
         };
 
         account.amount -= input.amount;
@@ -656,6 +718,33 @@ impl ServerModule for Lightning {
                     return Err(LightningError::ZeroOutput).into_module_error_other();
                 }
 
+                if let Contract::Multisig(multisig) = &contract.contract {
+                    // The claim key can only be approved by the participants
+                    if multisig.approved_claim_key.is_some() {
+                        return Err(LightningError::PreapprovedMultisigClaim)
+                            .into_module_error_other();
+                    }
+
+                    // Without approvals anyone could claim the contract, with more approvals
+                    // than participants nobody could
+                    let participants = multisig.participants.len() as u64;
+                    if multisig.threshold == 0 || multisig.threshold > participants {
+                        return Err(LightningError::InvalidMultisigThreshold(
+                            multisig.threshold,
+                            participants,
+                        ))
+                        .into_module_error_other();
+                    }
+
+                    let mut distinct = BTreeSet::new();
+                    for participant in &multisig.participants {
+                        if !distinct.insert(participant) {
+                            return Err(LightningError::DuplicateParticipant(*participant))
+                                .into_module_error_other();
+                        }
+                    }
+                }
+
                 let contract_db_key = ContractKey(contract.contract.contract_id());
 
                 let updated_contract_account = dbtx
@@ -694,6 +783,12 @@ impl ServerModule for Lightning {
                                 .observe(updated_contract_account.amount.msats as f64 / 1000.0);
                             LN_FUNDED_CONTRACT_OUTGOING.inc();
                         }
+                        FundedContract::Conditional(_) => {
+                            LN_FUNDED_CONTRACT_CONDITIONAL.inc();
+                        }
+                        FundedContract::Multisig(_) => {
+                            LN_FUNDED_CONTRACT_MULTISIG.inc();
+                        }
                     }
                 }
 
@@ -820,6 +915,59 @@ impl ServerModule for Lightning {
 
                 LN_OUTPUT_OUTCOME_CANCEL_OUTGOING_CONTRACT.inc();
 
+                Ok(TransactionItemAmount::ZERO)
+            }
+            LightningOutput::ApproveMultisigClaim { contract, witness } => {
+                let mut contract_account = dbtx
+                    .get_value(&ContractKey(*contract))
+                    .await
+                    .ok_or(LightningError::UnknownContract(*contract))
+                    .into_module_error_other()?;
+
+                let FundedContract::Multisig(multisig) = &mut contract_account.contract else {
+                    return Err(LightningError::NotMultisigContract).into_module_error_other();
+                };
+
+                if multisig.approved_claim_key.is_some() {
+                    return Err(LightningError::MultisigClaimAlreadyApproved)
+                        .into_module_error_other();
+                }
+
+                let message = multisig.approval_message(&witness.claim_key);
+                let mut approvals = BTreeSet::new();
+                for approval in &witness.signatures {
+                    if !multisig.participants.contains(&approval.participant) {
+                        return Err(LightningError::UnknownParticipant(approval.participant))
+                            .into_module_error_other();
+                    }
+
+                    secp256k1::global::SECP256K1
+                        .verify_schnorr(&approval.signature, &message.into(), &approval.participant)
+                        .map_err(|_| LightningError::InvalidApproval(approval.participant))
+                        .into_module_error_other()?;
+
+                    approvals.insert(approval.participant);
+                }
+
+                if (approvals.len() as u64) < multisig.threshold {
+                    return Err(LightningError::NotEnoughApprovals(
+                        multisig.threshold,
+                        approvals.len() as u64,
+                    ))
+                    .into_module_error_other();
+                }
+
+                multisig.approved_claim_key = Some(witness.claim_key);
+
+                dbtx.insert_entry(&ContractKey(*contract), &contract_account)
+                    .await;
+
+                dbtx.insert_new_entry(
+                    &ContractUpdateKey(out_point),
+                    &LightningOutputOutcome::ApproveMultisigClaim { id: *contract },
+                )
+                .await;
+
                 Ok(TransactionItemAmount::ZERO)
             }
         }
@@ -851,6 +999,11 @@ impl ServerModule for Lightning {
             .await;
     }
 
+    async fn complete_session(&self, dbtx: &mut ModuleDatabaseTransaction<'_>, session_index: u64) {
+        dbtx.insert_entry(&CompletedEpochCountKey, &(session_index + 1))
+            .await;
+    }
+
     fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
         vec![
             api_endpoint! {
@@ -961,6 +1114,10 @@ impl Lightning {
         counts[peer_count / 2]
     }
 
+    async fn completed_epoch_count(&self, dbtx: &mut ModuleDatabaseTransaction<'_>) -> u64 {
+        dbtx.get_value(&CompletedEpochCountKey).await.unwrap_or(0)
+    }
+
     async fn wait_block_height(&self, block_height: u64, dbtx: &mut ModuleDatabaseTransaction<'_>) {
         while block_height >= self.consensus_block_count(dbtx).await {
             sleep(Duration::from_secs(5)).await;
@@ -1138,7 +1295,7 @@ mod tests {
     use fedimint_core::db::mem_impl::MemDatabase;
     use fedimint_core::db::Database;
     use fedimint_core::encoding::Encodable;
-    use fedimint_core::module::{InputMeta, ServerModuleInit, TransactionItemAmount};
+    use fedimint_core::module::{InputMeta, ModuleError, ServerModuleInit, TransactionItemAmount};
     use fedimint_core::task::TaskGroup;
     use fedimint_core::{Amount, OutPoint, PeerId, ServerModule, TransactionId};
     use fedimint_ln_common::config::{
@@ -1148,12 +1305,16 @@ mod tests {
     use fedimint_ln_common::contracts::incoming::{
         FundedIncomingContract, IncomingContract, IncomingContractOffer,
     };
+    use fedimint_ln_common::contracts::multisig::MultisigContract;
     use fedimint_ln_common::contracts::outgoing::OutgoingContract;
     use fedimint_ln_common::contracts::{
-        DecryptedPreimage, EncryptedPreimage, FundedContract, IdentifiableContract, Preimage,
+        Contract, DecryptedPreimage, EncryptedPreimage, FundedContract, IdentifiableContract,
+        Preimage,
     };
     use fedimint_ln_common::db::{ContractKey, LightningAuditItemKey};
-    use fedimint_ln_common::{ContractAccount, LightningInput, LightningOutput};
+    use fedimint_ln_common::{
+        ContractAccount, ContractOutput, LightningError, LightningInput, LightningOutput,
+    };
     use lightning_invoice::Bolt11Invoice;
     use rand::rngs::OsRng;
     use secp256k1::{generate_keypair, XOnlyPublicKey};
@@ -1378,6 +1539,57 @@ mod tests {
         let audit_item = module_dbtx.get_value(&audit_key).await;
         assert_eq!(audit_item, None);
     }
+
+    #[test_log::test(tokio::test)]
+    async fn multisig_contracts_need_valid_participants() {
+        let (server_cfg, _) = build_configs();
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let mut module_dbtx = dbtx.with_module_prefix(42);
+        let mut tg = TaskGroup::new();
+        let server = Lightning::new(server_cfg[0].clone(), &mut tg).unwrap();
+
+        let key1 = random_x_only_pub_key();
+        let key2 = random_x_only_pub_key();
+        let out_point = OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx: 0,
+        };
+
+        for (participants, threshold, expected) in [
+            (
+                vec![key1, key2],
+                0,
+                LightningError::InvalidMultisigThreshold(0, 2),
+            ),
+            (
+                vec![key1, key2],
+                3,
+                LightningError::InvalidMultisigThreshold(3, 2),
+            ),
+            (
+                vec![key1, key2, key1],
+                2,
+                LightningError::DuplicateParticipant(key1),
+            ),
+        ] {
+            let output = LightningOutput::Contract(ContractOutput {
+                amount: Amount::from_sats(10),
+                contract: Contract::Multisig(MultisigContract {
+                    participants,
+                    threshold,
+                    nonce: [0; 32],
+                    approved_claim_key: None,
+                }),
+            });
+
+            let ModuleError::Other(error) = server
+                .process_output(&mut module_dbtx, &output, out_point)
+                .await
+                .expect_err("Invalid multisig contract is rejected");
+            assert_eq!(error.downcast_ref::<LightningError>(), Some(&expected));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1402,8 +1614,8 @@ mod fedimint_migration_tests {
     };
     use fedimint_ln_common::db::{
         AgreedDecryptionShareKey, AgreedDecryptionShareKeyPrefix, BlockCountVoteKey,
-        BlockCountVotePrefix, ContractKey, ContractKeyPrefix, ContractUpdateKey,
-        ContractUpdateKeyPrefix, DbKeyPrefix, EncryptedPreimageIndexKey,
+        BlockCountVotePrefix, CompletedEpochCountKeyPrefix, ContractKey, ContractKeyPrefix,
+        ContractUpdateKey, ContractUpdateKeyPrefix, DbKeyPrefix, EncryptedPreimageIndexKey,
         EncryptedPreimageIndexKeyPrefix, LightningAuditItemKey, LightningAuditItemKeyPrefix,
         LightningGatewayKey, LightningGatewayKeyPrefix, OfferKey, OfferKeyPrefix,
         ProposeDecryptionShareKey, ProposeDecryptionShareKeyPrefix,
@@ -1699,6 +1911,15 @@ mod fedimint_migration_tests {
                                 "validate_migrations was not able to read both LightningAuditItemKeys"
                             );
                         }
+                        // The completed epoch count was introduced after v0, so there is
+                        // nothing to read yet
+                        DbKeyPrefix::CompletedEpochCount => {
+                            let _ = dbtx
+                                .find_by_prefix(&CompletedEpochCountKeyPrefix)
+                                .await
+                                .collect::<Vec<_>>()
+                                .await;
+                        }
                     }
                 }
                 Ok(())
@@ -2,8 +2,10 @@ use std::str::FromStr;
 
 use anyhow::bail;
 use assert_matches::assert_matches;
+use bitcoin::hashes::{sha256, Hash};
+use fedimint_core::api::GlobalFederationApi;
 use fedimint_core::util::NextOrPending;
-use fedimint_core::{sats, Amount};
+use fedimint_core::{sats, Amount, OutPoint};
 use fedimint_dummy_client::{DummyClientExt, DummyClientGen};
 use fedimint_dummy_common::config::DummyGenParams;
 use fedimint_dummy_server::DummyGen;
@@ -12,6 +14,8 @@ use fedimint_ln_client::{
     LnReceiveState, OutgoingLightningPayment, PayType,
 };
 use fedimint_ln_common::config::LightningGenParams;
+use fedimint_ln_common::contracts::conditional::PaymentCondition;
+use fedimint_ln_common::contracts::Preimage;
 use fedimint_ln_common::ln_operation;
 use fedimint_ln_server::LightningGen;
 use fedimint_testing::federation::FederationTest;
@@ -121,6 +125,7 @@ async fn test_can_attach_extra_meta_to_receive_operation() -> anyhow::Result<()>
     };
     assert_eq!(serde_json::to_string(&extra_meta)?, op_meta);
 
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -179,6 +184,7 @@ async fn cannot_pay_same_internal_invoice_twice() -> anyhow::Result<()> {
     let same_balance = client2.get_balance().await;
     assert_eq!(prev_balance, same_balance);
 
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -238,6 +244,47 @@ async fn cannot_pay_same_external_invoice_twice() -> anyhow::Result<()> {
 
     drop(gw);
 
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multipath_payment_falls_back_to_online_gateway() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let client = fed.new_client().await;
+    let mut offline_gateway = fixtures
+        .new_gateway(
+            fixtures.cln().await,
+            0,
+            Some(DEFAULT_GATEWAY_PASSWORD.to_string()),
+        )
+        .await;
+    offline_gateway.connect_fed(&fed).await;
+    let online_gateway = gateway(&fixtures, &fed).await;
+    assert_eq!(client.fetch_registered_gateways().await?.len(), 2);
+
+    // The offline gateway stays registered with the federation
+    client
+        .set_active_gateway(&offline_gateway.get_gateway_id())
+        .await?;
+    drop(offline_gateway);
+
+    // Print money for client
+    let (op, outpoint) = client.print_money(sats(1000)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+
+    let cln = fixtures.cln().await;
+    let invoice = cln.invoice(Amount::from_sats(100), None).await?;
+    let preimage = client.pay_invoice_multipath(invoice.clone()).await?;
+    assert_eq!(sha256::Hash::hash(&preimage.0), *invoice.payment_hash());
+    // Only the online gateway was paid, no funds are locked in a contract with the
+    // offline one
+    assert_eq!(client.get_balance().await, sats(900));
+
+    drop(online_gateway);
+
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -303,6 +350,7 @@ async fn makes_internal_payments_within_federation() -> anyhow::Result<()> {
         _ => panic!("Expected internal payment!"),
     }
 
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -334,3 +382,171 @@ async fn rejects_wrong_network_invoice() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conditional_payment_requires_preimage() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let preimage = Preimage([42; 32]);
+    let hash = sha256::Hash::hash(&preimage.0);
+    let payment = client1
+        .create_conditional_payment(
+            sats(500),
+            PaymentCondition::RevealPreimage(hash),
+            client2.conditional_payment_public_key(),
+        )
+        .await?;
+
+    // Claiming without or with the wrong preimage fails …
+    for wrong_preimage in [None, Some(Preimage([0; 32]))] {
+        let (op, txid) = client2
+            .claim_conditional_payment(payment.clone(), wrong_preimage)
+            .await?;
+        let accepted = client2
+            .transaction_updates(op)
+            .await
+            .await_tx_accepted(txid)
+            .await;
+        assert!(accepted.is_err());
+    }
+    assert_eq!(client2.get_balance().await, sats(0));
+
+    // … and so does claiming it as anyone but the recipient …
+    assert!(client1
+        .claim_conditional_payment(payment.clone(), Some(preimage))
+        .await
+        .is_err());
+
+    // … while revealing the preimage allows the recipient to claim the funds
+    let (op, txid) = client2
+        .claim_conditional_payment(payment, Some(preimage))
+        .await?;
+    client2
+        .await_primary_module_output(op, OutPoint { txid, out_idx: 0 })
+        .await?;
+    assert!(client2.get_balance().await > sats(0));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conditional_payment_requires_epoch() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let epoch = client1.api().fetch_block_count().await?;
+    let payment = client1
+        .create_conditional_payment(
+            sats(500),
+            PaymentCondition::AfterEpoch(epoch),
+            client2.conditional_payment_public_key(),
+        )
+        .await?;
+
+    // Claiming before the epoch is complete fails …
+    let (op, txid) = client2
+        .claim_conditional_payment(payment.clone(), None)
+        .await?;
+    let accepted = client2
+        .transaction_updates(op)
+        .await
+        .await_tx_accepted(txid)
+        .await;
+    assert!(accepted.is_err());
+    assert_eq!(client2.get_balance().await, sats(0));
+
+    // … while claiming afterwards succeeds
+    client2.api().await_block(epoch, client2.decoders()).await?;
+    let (op, txid) = client2.claim_conditional_payment(payment, None).await?;
+    client2
+        .await_primary_module_output(op, OutPoint { txid, out_idx: 0 })
+        .await?;
+    assert!(client2.get_balance().await > sats(0));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn multisig_contract_requires_threshold_approvals() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let client3 = fed.new_client().await;
+
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let participants = vec![
+        client1.multisig_public_key(),
+        client2.multisig_public_key(),
+        client3.multisig_public_key(),
+    ];
+    let address = client1
+        .create_federated_multisig(participants, 2, sats(500))
+        .await?;
+
+    // client3 wants to claim the funds, which requires a second approval …
+    let claim_key = client3.multisig_public_key();
+    let sig2 = client2.sign_federated_multisig_spend(&address, claim_key)?;
+    let sig3 = client3.sign_federated_multisig_spend(&address, claim_key)?;
+
+    for sigs in [vec![sig3.clone()], vec![sig3.clone(), sig3.clone()]] {
+        let (op, txid) = client3.combine_and_submit_multisig(&address, sigs).await?;
+        let accepted = client3
+            .transaction_updates(op)
+            .await
+            .await_tx_accepted(txid)
+            .await;
+        assert!(accepted.is_err());
+    }
+    assert_eq!(client3.get_balance().await, sats(0));
+
+    // … approving a different key doesn't count …
+    let wrong_sig =
+        client2.sign_federated_multisig_spend(&address, client1.multisig_public_key())?;
+    let (op, txid) = client3
+        .combine_and_submit_multisig(&address, vec![wrong_sig, sig3.clone()])
+        .await?;
+    let accepted = client3
+        .transaction_updates(op)
+        .await
+        .await_tx_accepted(txid)
+        .await;
+    assert!(accepted.is_err());
+
+    // … and the funds can't be claimed without an approval …
+    let (op, txid) = client3.claim_federated_multisig(&address).await?;
+    let accepted = client3
+        .transaction_updates(op)
+        .await
+        .await_tx_accepted(txid)
+        .await;
+    assert!(accepted.is_err());
+
+    // … but two distinct participants do
+    let (op, txid) = client3
+        .combine_and_submit_multisig(&address, vec![sig2, sig3])
+        .await?;
+    client3
+        .transaction_updates(op)
+        .await
+        .await_tx_accepted(txid)
+        .await
+        .map_err(|e| anyhow::anyhow!("Approval was not accepted: {e:?}"))?;
+    let (op, txid) = client3.claim_federated_multisig(&address).await?;
+    client3
+        .await_primary_module_output(op, OutPoint { txid, out_idx: 0 })
+        .await?;
+    assert!(client3.get_balance().await > sats(0));
+
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
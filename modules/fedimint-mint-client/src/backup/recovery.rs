@@ -1,11 +1,11 @@
 use std::cmp::{self, max};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::ops::Range;
 
 use fedimint_client::sm::{OperationId, State, StateTransition};
 use fedimint_client::DynGlobalClientContext;
-use fedimint_core::api::{DynGlobalApi, GlobalFederationApi};
+use fedimint_core::api::{DynGlobalApi, GlobalFederationApi, IGlobalFederationApi};
 use fedimint_core::core::LEGACY_HARDCODED_INSTANCE_ID_MINT;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::epoch::ConsensusItem;
@@ -29,7 +29,8 @@ use crate::output::{
     NoteIssuanceRequest,
 };
 use crate::{
-    MintClientContext, MintClientModule, MintClientStateMachines, NoteIndex, SpendableNote,
+    MintClientContext, MintClientModule, MintClientStateMachines, NoteIndex, RestoreStrategy,
+    SpendableNote,
 };
 
 /// Restore will progress in chunks of a fixed epoch count,
@@ -38,7 +39,7 @@ use crate::{
 /// storage and overhead  but risk loosing more progress each time the
 /// client app is closed. Some time based, or even "save on close"
 /// scheme would be better, but currently not implemented.
-const PROGRESS_SNAPSHOT_EPOCHS: u64 = 500;
+pub(crate) const PROGRESS_SNAPSHOT_EPOCHS: u64 = 500;
 
 #[derive(Debug)]
 pub struct EcashRecoveryFinalState {
@@ -52,6 +53,23 @@ pub struct EcashRecoveryFinalState {
     next_note_idx: Tiered<NoteIndex>,
 }
 
+impl EcashRecoveryFinalState {
+    /// Spendable notes whose nonce is one of `nonces` and the note index to
+    /// continue deriving new notes from
+    pub(crate) fn into_notes_with_nonces(
+        self,
+        nonces: &BTreeSet<Nonce>,
+    ) -> (Vec<(Amount, SpendableNote)>, Tiered<NoteIndex>) {
+        let notes = self
+            .spendable_notes
+            .into_iter()
+            .filter(|(_amount, note)| nonces.contains(&note.nonce()))
+            .collect();
+
+        (notes, self.next_note_idx)
+    }
+}
+
 /// Newtype over [`BlindedMessage`] to enable `Ord`
 #[derive(
     Debug, Clone, Eq, PartialEq, PartialOrd, Ord, Decodable, Encodable, Serialize, Deserialize,
@@ -398,6 +416,45 @@ impl MintRestoreInProgressState {
         s
     }
 
+    /// Follows the epoch history for at most `max_epochs` epochs without
+    /// exceeding the end epoch, used when the caller waits for the result
+    /// and persists the progress itself
+    pub(crate) async fn scan_epochs(
+        mut self,
+        api: &(dyn IGlobalFederationApi + 'static),
+        decoders: &ModuleDecoderRegistry,
+        secret: &DerivableSecret,
+        max_epochs: u64,
+        strategy: RestoreStrategy,
+    ) -> anyhow::Result<Self> {
+        let end_epoch = cmp::min(self.next_epoch.saturating_add(max_epochs), self.end_epoch);
+        let mut block_stream = futures::stream::iter(self.next_epoch..end_epoch)
+            .map(|block_idx| async move { (block_idx, api.await_block(block_idx, decoders).await) })
+            .buffered(strategy.parallelism());
+        while let Some((block_idx, block)) = block_stream.next().await {
+            debug!(target: LOG_CLIENT_RECOVERY_MINT, block_idx, "Processing epoch");
+            let block = block?;
+
+            let mut processed_txs = Default::default();
+            for accepted_item in &block.items {
+                self.handle_consensus_item(
+                    accepted_item.peer,
+                    &accepted_item.item,
+                    &mut processed_txs,
+                    secret,
+                );
+            }
+            self.next_epoch = block_idx + 1;
+        }
+
+        Ok(self)
+    }
+
+    /// Continues a finished or checkpointed scan up to the new `end_epoch`
+    pub(crate) fn extend_to_epoch(&mut self, end_epoch: u64) {
+        self.end_epoch = max(self.end_epoch, end_epoch);
+    }
+
     /// Fill each tier pool to the gap limit
     fn fill_initial_pending_nonces(&mut self, amount: Amount, secret: &DerivableSecret) {
         info!(%amount, count=self.gap_limit, "Generating initial set of nonces for amount tier");
@@ -782,7 +839,7 @@ impl MintRestoreInProgressState {
         }
     }
 
-    fn finalize(self) -> EcashRecoveryFinalState {
+    pub(crate) fn finalize(self) -> EcashRecoveryFinalState {
         EcashRecoveryFinalState {
             spendable_notes: self
                 .spendable_note_by_nonce
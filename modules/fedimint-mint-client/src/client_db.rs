@@ -4,6 +4,7 @@ use fedimint_mint_common::Nonce;
 use serde::Serialize;
 use strum_macros::EnumIter;
 
+use crate::backup::recovery::MintRestoreInProgressState;
 use crate::SpendableNote;
 
 #[repr(u8)]
@@ -11,6 +12,7 @@ use crate::SpendableNote;
 pub enum DbKeyPrefix {
     Note = 0x20,
     NextECashNoteIndex = 0x2a,
+    RawNonceRestoreCheckpoint = 0x2b,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -50,3 +52,21 @@ impl_db_lookup!(
     key = NextECashNoteIndexKey,
     query_prefix = NextECashNoteIndexKeyPrefix
 );
+
+/// Progress of the epoch history scan of
+/// [`crate::MintClientExt::restore_from_raw_nonces`]
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub(crate) struct RawNonceRestoreCheckpointKey;
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub(crate) struct RawNonceRestoreCheckpointKeyPrefix;
+
+impl_db_record!(
+    key = RawNonceRestoreCheckpointKey,
+    value = MintRestoreInProgressState,
+    db_prefix = DbKeyPrefix::RawNonceRestoreCheckpoint,
+);
+impl_db_lookup!(
+    key = RawNonceRestoreCheckpointKey,
+    query_prefix = RawNonceRestoreCheckpointKeyPrefix
+);
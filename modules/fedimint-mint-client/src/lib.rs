@@ -10,7 +10,7 @@ mod oob;
 mod output;
 
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
@@ -20,6 +20,7 @@ use std::time::Duration;
 use anyhow::{anyhow, bail, ensure, Context as AnyhowContext};
 use async_stream::stream;
 use backup::recovery::{MintRestoreStateMachine, MintRestoreStates};
+use bech32::{FromBase32, ToBase32, Variant};
 use bitcoin_hashes::{sha256, sha256t, Hash, HashEngine as BitcoinHashEngine};
 use client_db::DbKeyPrefix;
 use fedimint_client::module::init::{ClientModuleInit, ClientModuleInitArgs};
@@ -31,20 +32,25 @@ use fedimint_client::sm::{
 };
 use fedimint_client::transaction::{ClientInput, ClientOutput, TransactionBuilder};
 use fedimint_client::{sm_enum_variant_translation, Client, DynGlobalClientContext};
-use fedimint_core::api::{DynGlobalApi, GlobalFederationApi};
-use fedimint_core::config::{FederationId, FederationIdPrefix};
+use fedimint_core::api::{DynGlobalApi, EventType, GlobalFederationApi};
+use fedimint_core::block::{
+    consensus_hash_sha256, merkle_path, merkle_root_from_path, transaction_item_digest,
+};
+use fedimint_core::config::{FederationId, FederationIdPrefix, PeerUrl};
 use fedimint_core::core::{Decoder, IntoDynInstance, ModuleInstanceId};
 use fedimint_core::db::{AutocommitError, DatabaseTransaction, ModuleDatabaseTransaction};
 use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::epoch::ConsensusItem;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
 use fedimint_core::module::{
-    ApiVersion, CommonModuleInit, ExtendsCommonModuleInit, ModuleCommon, MultiApiVersion,
-    TransactionItemAmount,
+    ApiVersion, CommonModuleInit, ExtendsCommonModuleInit, ModuleCommon, ModuleError,
+    MultiApiVersion, TransactionItemAmount,
 };
+use fedimint_core::task::{sleep, TaskGroup};
 use fedimint_core::util::{BoxStream, NextOrPending};
 use fedimint_core::{
-    apply, async_trait_maybe_send, push_db_pair_items, Amount, OutPoint, Tiered, TieredMulti,
-    TieredSummary, TransactionId,
+    apply, async_trait_maybe_send, push_db_pair_items, Amount, OutPoint, PeerId, Tiered,
+    TieredMulti, TieredSummary, TransactionId,
 };
 use fedimint_derive_secret::{ChildId, DerivableSecret};
 pub use fedimint_mint_common as common;
@@ -58,15 +64,18 @@ use tbs::AggregatePublicKey;
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
-use crate::backup::recovery::MintRestoreInProgressState;
+use crate::backup::recovery::{MintRestoreInProgressState, PROGRESS_SNAPSHOT_EPOCHS};
 use crate::backup::EcashBackup;
 use crate::client_db::{
     NextECashNoteIndexKey, NextECashNoteIndexKeyPrefix, NoteKey, NoteKeyPrefix,
+    RawNonceRestoreCheckpointKey, RawNonceRestoreCheckpointKeyPrefix,
 };
 use crate::input::{
     MintInputCommon, MintInputStateCreated, MintInputStateMachine, MintInputStates,
 };
-use crate::oob::{MintOOBStateMachine, MintOOBStates, MintOOBStatesCreated};
+use crate::oob::{
+    MintOOBStateMachine, MintOOBStates, MintOOBStatesCreated, MintOOBStatesCreatedOffline,
+};
 use crate::output::{
     MintOutputCommon, MintOutputStateMachine, MintOutputStates, MintOutputStatesCreated,
     MultiNoteIssuanceRequest, NoteIssuanceRequest,
@@ -76,6 +85,18 @@ const MINT_E_CASH_TYPE_CHILD_ID: ChildId = ChildId(0);
 
 const MINT_BACKUP_RESTORE_OPERATION_ID: OperationId = OperationId([0x01; 32]);
 
+/// The number of note combinations we try to find an exact match for a spend
+/// before falling back to selecting notes greedily
+const NOTE_SELECTION_SEARCH_BUDGET: usize = 100_000;
+
+/// The number of note indices per amount tier we look ahead of the last used
+/// one when restoring from raw nonces
+const RAW_NONCE_RESTORE_GAP_LIMIT: u64 = 30;
+
+/// How often the wallet is checked for notes to consolidate, see
+/// [`MintClientExt::set_auto_consolidation_threshold`]
+const AUTO_CONSOLIDATION_INTERVAL: Duration = Duration::from_secs(1);
+
 pub const LOG_TARGET: &str = "client::module::mint";
 
 /// An encapsulation of [`FederationId`] and e-cash notes in the form of
@@ -139,11 +160,168 @@ impl OOBNotes {
     }
 }
 
+/// Human readable part of the bech32 payload of [`PaymentLink`]s
+const PAYMENT_LINK_HRP: &str = "fedpay";
+
+/// URI scheme of [`PaymentLink`]s
+const PAYMENT_LINK_SCHEME: &str = "fedimint:";
+
+/// A shareable request for an e-cash payment, see
+/// [`MintClientExt::generate_payment_link`]
+///
+/// The link is a `fedimint:` URL followed by the bech32m encoded
+/// [`ParsedPaymentLink`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PaymentLink(String);
+
+impl PaymentLink {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for PaymentLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The payment requested by a [`PaymentLink`]
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable)]
+pub struct ParsedPaymentLink {
+    /// The federation the e-cash has to be issued by
+    pub federation_id: FederationId,
+    /// API endpoints of the guardians, so a payer who isn't a member of the
+    /// federation yet can join it
+    pub api_endpoints: BTreeMap<PeerId, PeerUrl>,
+    pub amount: Amount,
+    pub description: String,
+    /// Random nonce that makes every link unique, so the payee can tell
+    /// payments for otherwise identical requests apart
+    pub nonce: [u8; 32],
+}
+
+impl ParsedPaymentLink {
+    pub fn to_link(&self) -> PaymentLink {
+        let mut bytes = Vec::new();
+        Encodable::consensus_encode(self, &mut bytes).expect("encodes correctly");
+        let payload = bech32::encode(PAYMENT_LINK_HRP, bytes.to_base32(), Variant::Bech32m)
+            .expect("HRP is valid");
+        PaymentLink(format!("{PAYMENT_LINK_SCHEME}{payload}"))
+    }
+}
+
+impl FromStr for ParsedPaymentLink {
+    type Err = anyhow::Error;
+
+    /// Decodes a payment link, with or without its URI scheme
+    fn from_str(link: &str) -> Result<Self, Self::Err> {
+        let payload = link.strip_prefix(PAYMENT_LINK_SCHEME).unwrap_or(link);
+        let (hrp, data, variant) = bech32::decode(payload)?;
+
+        ensure!(hrp == PAYMENT_LINK_HRP, "Invalid HRP in bech32 encoding");
+        ensure!(variant == Variant::Bech32m, "Expected Bech32m encoding");
+
+        let bytes = Vec::<u8>::from_base32(&data)?;
+        Ok(Decodable::consensus_decode(
+            &mut std::io::Cursor::new(bytes),
+            &ModuleDecoderRegistry::default(),
+        )?)
+    }
+}
+
+/// Out-of-band e-cash notes the sender asks the recipient to redeem before
+/// the epoch `deadline_epoch` is completed, see
+/// [`MintClientExt::request_offline_note`]
+///
+/// The deadline is advisory: the federation doesn't know about it, so the
+/// notes stay redeemable with [`MintClientExt::reissue_external_notes`] until
+/// the sender's client refunds them after the deadline.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OfflineNote {
+    pub notes: OOBNotes,
+    pub deadline_epoch: u64,
+}
+
+/// Proves that a note was issued by the output `out_idx` of the transaction
+/// `txid` accepted in `epoch`, see [`MintClientExt::get_note_creation_proof`]
+///
+/// Only the output issuing the note is revealed, the rest of the transaction
+/// is represented by its id and signature. The proof only links the note to
+/// `merkle_root`, a verifier has to check that it matches the header of the
+/// epoch's block signed by the federation.
+#[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable)]
+pub struct NoteCreationProof {
+    pub epoch: u64,
+    /// The merkle root of the accepted items of the epoch's block
+    pub merkle_root: [u8; 32],
+    /// The peer that proposed the transaction
+    pub peer: PeerId,
+    pub txid: TransactionId,
+    pub signature: Option<secp256k1_zkp::schnorr::Signature>,
+    /// The index of the transaction in the epoch's block
+    pub item_index: u64,
+    /// The sibling hashes from the transaction up to `merkle_root`
+    pub merkle_path: Vec<[u8; 32]>,
+    pub module_instance_id: ModuleInstanceId,
+    /// The output containing the note's blinded nonce
+    pub output: MintOutput,
+    pub out_idx: u64,
+    /// The sibling hashes from `output` up to the root of the transaction's
+    /// outputs
+    pub output_path: Vec<[u8; 32]>,
+    /// The blinded nonce the federation signed to issue the note
+    pub blind_nonce: BlindNonce,
+}
+
+impl NoteCreationProof {
+    /// Checks that `output` contains `blind_nonce` and is an output of the
+    /// transaction included in the block with `merkle_root`
+    pub fn verify(&self) -> bool {
+        let output = self.output.clone().into_dyn(self.module_instance_id);
+        let outputs_root = merkle_root_from_path(
+            consensus_hash_sha256(&output),
+            self.out_idx,
+            &self.output_path,
+        );
+        let digest = transaction_item_digest(self.peer, self.txid, &self.signature, outputs_root);
+
+        self.output
+            .0
+            .iter_items()
+            .any(|(_, blind_nonce)| blind_nonce == &self.blind_nonce)
+            && merkle_root_from_path(digest, self.item_index, &self.merkle_path) == self.merkle_root
+    }
+}
+
+/// Whether `item` is a transaction requesting a signature for `blind_nonce`
+fn issues_blind_nonce(item: &ConsensusItem, blind_nonce: &BlindNonce) -> bool {
+    let ConsensusItem::Transaction(tx) = item else {
+        return false;
+    };
+    tx.outputs
+        .iter()
+        .filter_map(|output| output.as_any().downcast_ref::<MintOutput>())
+        .any(|output| output.0.iter_items().any(|(_, bn)| bn == blind_nonce))
+}
+
+/// When we try to take back out-of-band e-cash that wasn't reissued by the
+/// recipient
+#[derive(Debug, Clone, Copy)]
+enum OOBDeadline {
+    After(Duration),
+    AfterEpoch(u64),
+}
+
 #[apply(async_trait_maybe_send!)]
 pub trait MintClientExt {
     /// Try to reissue e-cash notes received from a third party to receive them
     /// in our wallet. The progress and outcome can be observed using
     /// [`MintClientExt::subscribe_reissue_external_notes`].
+    ///
+    /// The operation id is derived from the notes, so retrying a call whose
+    /// result got lost returns the id of the existing operation instead of
+    /// reissuing the notes again.
     async fn reissue_external_notes<M: Serialize + Send>(
         &self,
         oob_notes: OOBNotes,
@@ -175,6 +353,50 @@ pub trait MintClientExt {
         extra_meta: M,
     ) -> anyhow::Result<(OperationId, OOBNotes)>;
 
+    /// Like [`MintClientExt::spend_notes`], but the client tries to refund the
+    /// notes after a deadline of `deadline_epochs` epochs instead of a timeout,
+    /// so they can be handed to a recipient, e.g. a merchant, who redeems them
+    /// later with [`MintClientExt::redeem_offline_note`].
+    ///
+    /// Once the last epoch is completed the client tries to refund the notes,
+    /// which only succeeds if the recipient didn't redeem them yet. The
+    /// deadline is an advisory client-side one: the federation doesn't know
+    /// about it, so the notes stay redeemable until this client refunds them.
+    async fn request_offline_note<M: Serialize + Send>(
+        &self,
+        amount: Amount,
+        deadline_epochs: u64,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, OfflineNote)>;
+
+    /// Reissues an offline note created with
+    /// [`MintClientExt::request_offline_note`] unless its deadline passed
+    /// already, see [`MintClientExt::reissue_external_notes`]. Since the
+    /// deadline is advisory, the check only protects the recipient from
+    /// accepting notes the sender is about to refund.
+    async fn redeem_offline_note<M: Serialize + Send>(
+        &self,
+        offline_note: OfflineNote,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId>;
+
+    /// Searches the epoch history for the epoch in which `note` was issued.
+    ///
+    /// Only works for notes this client requested, since the blinded nonce the
+    /// federation signed is rederived from the client's secret.
+    async fn get_note_issuance_epoch(&self, note: &SpendableNote) -> anyhow::Result<u64>;
+
+    /// Builds a proof that `note` was issued in a completed epoch, which a
+    /// third party can check with [`NoteCreationProof::verify`] without
+    /// learning the note's nonce.
+    ///
+    /// Like [`MintClientExt::get_note_issuance_epoch`] this only works for
+    /// notes this client requested.
+    async fn get_note_creation_proof(
+        &self,
+        note: &SpendableNote,
+    ) -> anyhow::Result<NoteCreationProof>;
+
     /// Validate the given notes and return the total amount of the notes.
     /// Validation checks that:
     /// - the federation ID is correct
@@ -182,6 +404,14 @@ pub trait MintClientExt {
     /// - the spend key is correct.
     async fn validate_notes(&self, oob_notes: OOBNotes) -> anyhow::Result<Amount>;
 
+    /// Creates a link requesting a payment of `amount` in our federation that
+    /// can be shared with the payer
+    fn generate_payment_link(&self, amount: Amount, description: &str) -> PaymentLink;
+
+    /// Decodes a link created with [`MintClientExt::generate_payment_link`],
+    /// which may be for a different federation than ours
+    fn parse_payment_link(&self, link: &str) -> anyhow::Result<ParsedPaymentLink>;
+
     /// Try to cancel a spend operation started with
     /// [`MintClientExt::spend_notes`]. If the e-cash notes have already been
     /// spent this operation will fail which can be observed using
@@ -197,6 +427,56 @@ pub trait MintClientExt {
 
     /// Awaits the backup restoration to complete
     async fn await_restore_finished(&self) -> anyhow::Result<()>;
+
+    /// Recovers the notes with the given `nonces` that were issued to this
+    /// client and not spent yet by scanning the whole epoch history. This is
+    /// useful if the notes themselves were lost but the nonces, e.g. from a
+    /// log of sent notes, are still known. Returns the total amount of the
+    /// recovered notes.
+    ///
+    /// Unlike [`ClientModule::restore`] this doesn't run as a state machine,
+    /// instead the progress of the scan is checkpointed in the database so
+    /// interrupted and subsequent calls only scan the epochs that weren't
+    /// processed yet.
+    async fn restore_from_raw_nonces(&self, nonces: Vec<Nonce>) -> anyhow::Result<Amount>;
+
+    /// Like [`MintClientExt::restore_from_raw_nonces`] but fetches the epoch
+    /// history according to `strategy`
+    async fn restore_from_raw_nonces_with_strategy(
+        &self,
+        nonces: Vec<Nonce>,
+        strategy: RestoreStrategy,
+    ) -> anyhow::Result<Amount>;
+
+    /// Spawns a task on `task_group` that reissues all e-cash notes into as few
+    /// notes as possible whenever the wallet holds more than `max_notes` notes.
+    /// Consolidation is skipped while other operations are still active to
+    /// not compete with them for notes.
+    ///
+    /// Each call spawns a new task, shutting down `task_group` stops it. Since
+    /// notes are only removed from the wallet when the reissue transaction is
+    /// submitted this doesn't lose any notes.
+    async fn set_auto_consolidation_threshold(&self, max_notes: usize, task_group: &mut TaskGroup);
+}
+
+/// How the epoch history is fetched when restoring notes from raw nonces,
+/// the epochs are always processed in order
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RestoreStrategy {
+    /// Fetches one epoch after the other
+    Sequential,
+    /// Fetches up to the given number of epochs at once
+    Concurrent(usize),
+}
+
+impl RestoreStrategy {
+    /// Number of epochs that are fetched at once
+    pub fn parallelism(&self) -> usize {
+        match self {
+            RestoreStrategy::Sequential => 1,
+            RestoreStrategy::Concurrent(n) => (*n).max(1),
+        }
+    }
 }
 
 /// The high-level state of a reissue operation started with
@@ -282,14 +562,15 @@ impl MintClientExt for Client {
             extra_meta: extra_meta.clone(),
         };
 
-        self.finalize_and_submit_transaction(
+        // A retry after a lost response finds the operation of the first call, which
+        // is checked in the same dbtx the operation would be created in
+        self.finalize_and_submit_transaction_if_new(
             operation_id,
             MintCommonGen::KIND.as_str(),
             operation_meta_gen,
             tx,
         )
-        .await
-        .context("We already reissued these notes")?;
+        .await?;
 
         Ok(operation_id)
     }
@@ -343,61 +624,146 @@ impl MintClientExt for Client {
         try_cancel_after: Duration,
         extra_meta: M,
     ) -> anyhow::Result<(OperationId, OOBNotes)> {
-        let (mint, instance) = self.get_first_module::<MintClientModule>(&KIND);
         let extra_meta = serde_json::to_value(extra_meta)
             .expect("MintClientExt::spend_notes extra_meta is serializable");
 
-        self.db()
-            .autocommit(
-                move |dbtx| {
-                    let extra_meta = extra_meta.clone();
-                    Box::pin(async move {
-                        let (operation_id, states, notes) = mint
-                            .spend_notes_oob(
-                                &mut dbtx.with_module_prefix(instance.id),
-                                min_amount,
-                                try_cancel_after,
-                            )
-                            .await?;
-                        let oob_notes = OOBNotes {
-                            federation_id_prefix: mint.federation_id.to_prefix(),
-                            notes,
-                        };
-
-                        let dyn_states = states
-                            .into_iter()
-                            .map(|s| s.into_dyn(instance.id))
-                            .collect();
-
-                        self.add_state_machines(dbtx, dyn_states).await?;
-                        self.operation_log()
-                            .add_operation_log_entry(
-                                dbtx,
-                                operation_id,
-                                MintCommonGen::KIND.as_str(),
-                                MintOperationMeta {
-                                    variant: MintOperationMetaVariants::SpendOOB {
-                                        requested_amount: min_amount,
-                                        oob_notes: oob_notes.clone(),
-                                    },
-                                    amount: oob_notes.total_amount(),
-                                    extra_meta,
-                                },
-                            )
-                            .await;
+        spend_oob(
+            self,
+            min_amount,
+            OOBDeadline::After(try_cancel_after),
+            extra_meta,
+        )
+        .await
+    }
 
-                        Ok((operation_id, oob_notes))
-                    })
-                },
-                Some(100),
-            )
+    async fn request_offline_note<M: Serialize + Send>(
+        &self,
+        amount: Amount,
+        deadline_epochs: u64,
+        extra_meta: M,
+    ) -> anyhow::Result<(OperationId, OfflineNote)> {
+        ensure!(
+            deadline_epochs > 0,
+            "Offline notes have to be valid for an epoch"
+        );
+        let extra_meta = serde_json::to_value(extra_meta)
+            .expect("MintClientExt::request_offline_note extra_meta is serializable");
+
+        let deadline_epoch = self.api().fetch_block_count().await? + deadline_epochs - 1;
+        let (operation_id, notes) = spend_oob(
+            self,
+            amount,
+            OOBDeadline::AfterEpoch(deadline_epoch),
+            extra_meta,
+        )
+        .await?;
+
+        Ok((
+            operation_id,
+            OfflineNote {
+                notes,
+                deadline_epoch,
+            },
+        ))
+    }
+
+    async fn redeem_offline_note<M: Serialize + Send>(
+        &self,
+        offline_note: OfflineNote,
+        extra_meta: M,
+    ) -> anyhow::Result<OperationId> {
+        let block_count = self.api().fetch_block_count().await?;
+        ensure!(
+            block_count <= offline_note.deadline_epoch,
+            "The deadline of the offline note passed in epoch {}",
+            offline_note.deadline_epoch
+        );
+
+        self.reissue_external_notes(offline_note.notes, extra_meta)
             .await
-            .map_err(|e| match e {
-                AutocommitError::ClosureError { error, .. } => error,
-                AutocommitError::CommitFailed { last_error, .. } => {
-                    anyhow!("Commit to DB failed: {last_error}")
-                }
+    }
+
+    async fn get_note_issuance_epoch(&self, note: &SpendableNote) -> anyhow::Result<u64> {
+        let (mint, instance) = self.get_first_module::<MintClientModule>(&KIND);
+        let blind_nonce = {
+            let mut dbtx = self.db().begin_transaction().await;
+            mint.rederive_blind_nonce(&mut dbtx.with_module_prefix(instance.id), note)
+                .await
+                .context("Note was not requested by this client")?
+        };
+
+        for epoch in 0..self.api().fetch_block_count().await? {
+            let block = self.api().await_block(epoch, self.decoders()).await?;
+            let issued = block
+                .items
+                .iter()
+                .any(|accepted_item| issues_blind_nonce(&accepted_item.item, &blind_nonce));
+
+            if issued {
+                return Ok(epoch);
+            }
+        }
+
+        bail!("Note was not issued yet")
+    }
+
+    async fn get_note_creation_proof(
+        &self,
+        note: &SpendableNote,
+    ) -> anyhow::Result<NoteCreationProof> {
+        let (mint, instance) = self.get_first_module::<MintClientModule>(&KIND);
+        let blind_nonce = {
+            let mut dbtx = self.db().begin_transaction().await;
+            mint.rederive_blind_nonce(&mut dbtx.with_module_prefix(instance.id), note)
+                .await
+                .context("Note was not requested by this client")?
+        };
+
+        let epoch = self.get_note_issuance_epoch(note).await?;
+        let block = self.api().await_block(epoch, self.decoders()).await?;
+        let item_index = block
+            .items
+            .iter()
+            .position(|accepted_item| issues_blind_nonce(&accepted_item.item, &blind_nonce))
+            .context("Epoch contains the note's issuance")?;
+        let merkle_path = block
+            .inclusion_proof(item_index)
+            .context("Item is part of the block")?;
+
+        let accepted_item = &block.items[item_index];
+        let ConsensusItem::Transaction(tx) = &accepted_item.item else {
+            unreachable!("Only transactions issue notes");
+        };
+        let (out_idx, module_instance_id, output) = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .find_map(|(out_idx, output)| {
+                let mint_output = output.as_any().downcast_ref::<MintOutput>()?;
+                mint_output
+                    .0
+                    .iter_items()
+                    .any(|(_, bn)| bn == &blind_nonce)
+                    .then(|| (out_idx, output.module_instance_id(), mint_output.clone()))
             })
+            .expect("Transaction issues the note");
+        let output_path =
+            merkle_path(tx.outputs.iter(), out_idx).expect("Output is part of the transaction");
+
+        Ok(NoteCreationProof {
+            epoch,
+            merkle_root: block.merkle_root(),
+            peer: accepted_item.peer,
+            txid: tx.tx_hash(),
+            signature: tx.signature,
+            item_index: item_index as u64,
+            merkle_path,
+            module_instance_id,
+            output,
+            out_idx: out_idx as u64,
+            output_path,
+            blind_nonce,
+        })
     }
 
     async fn validate_notes(&self, oob_notes: OOBNotes) -> anyhow::Result<Amount> {
@@ -431,6 +797,22 @@ impl MintClientExt for Client {
         Ok(notes.total_amount())
     }
 
+    fn generate_payment_link(&self, amount: Amount, description: &str) -> PaymentLink {
+        let global = &self.get_config().global;
+        ParsedPaymentLink {
+            federation_id: global.federation_id.clone(),
+            api_endpoints: global.api_endpoints.clone(),
+            amount,
+            description: description.to_owned(),
+            nonce: rand::random(),
+        }
+        .to_link()
+    }
+
+    fn parse_payment_link(&self, link: &str) -> anyhow::Result<ParsedPaymentLink> {
+        link.parse()
+    }
+
     async fn try_cancel_spend_notes(&self, operation_id: OperationId) {
         let (mint, _instance) = self.get_first_module::<MintClientModule>(&KIND);
 
@@ -502,6 +884,241 @@ impl MintClientExt for Client {
         let (mint, _instance) = self.get_first_module::<MintClientModule>(&KIND);
         mint.await_restore_finished().await
     }
+
+    async fn restore_from_raw_nonces(&self, nonces: Vec<Nonce>) -> anyhow::Result<Amount> {
+        self.restore_from_raw_nonces_with_strategy(nonces, RestoreStrategy::Sequential)
+            .await
+    }
+
+    async fn restore_from_raw_nonces_with_strategy(
+        &self,
+        nonces: Vec<Nonce>,
+        strategy: RestoreStrategy,
+    ) -> anyhow::Result<Amount> {
+        let (mint, instance) = self.get_first_module::<MintClientModule>(&KIND);
+        let nonces = nonces.into_iter().collect::<BTreeSet<_>>();
+
+        let current_block_count = self.api().fetch_block_count().await?;
+        let checkpoint = {
+            let mut dbtx = self.db().begin_transaction().await;
+            dbtx.with_module_prefix(instance.id)
+                .get_value(&RawNonceRestoreCheckpointKey)
+                .await
+        };
+        let mut state = match checkpoint {
+            Some(mut state) => {
+                state.extend_to_epoch(current_block_count);
+                state
+            }
+            None => MintRestoreInProgressState::from_backup(
+                current_block_count,
+                EcashBackup::new_empty(),
+                RAW_NONCE_RESTORE_GAP_LIMIT,
+                mint.cfg.tbs_pks.clone(),
+                mint.cfg.peer_tbs_pks.clone(),
+                &mint.secret,
+            ),
+        };
+
+        // Only checkpoint fully processed epochs so an interrupted scan resumes
+        // with a consistent state
+        while !state.is_done() {
+            state = state
+                .scan_epochs(
+                    self.api(),
+                    self.decoders(),
+                    &mint.secret,
+                    PROGRESS_SNAPSHOT_EPOCHS,
+                    strategy,
+                )
+                .await?;
+
+            let mut dbtx = self.db().begin_transaction().await;
+            dbtx.with_module_prefix(instance.id)
+                .insert_entry(&RawNonceRestoreCheckpointKey, &state)
+                .await;
+            dbtx.commit_tx_result().await?;
+        }
+
+        let (notes, next_note_idx) = state.finalize().into_notes_with_nonces(&nonces);
+
+        let mut dbtx = self.db().begin_transaction().await;
+        let mut module_dbtx = dbtx.with_module_prefix(instance.id);
+        for (amount, note) in &notes {
+            let key = NoteKey {
+                amount: *amount,
+                nonce: note.nonce(),
+            };
+            module_dbtx.insert_entry(&key, note).await;
+        }
+        // Don't derive the secrets of recovered notes again for new notes
+        for (amount, note_idx) in next_note_idx.iter() {
+            let key = NextECashNoteIndexKey(amount);
+            let known_idx = module_dbtx.get_value(&key).await.unwrap_or(0);
+            if known_idx < note_idx.as_u64() {
+                module_dbtx.insert_entry(&key, &note_idx.as_u64()).await;
+            }
+        }
+        dbtx.commit_tx_result().await?;
+
+        info!(
+            target: LOG_TARGET,
+            len = notes.len(),
+            "Restored notes from raw nonces"
+        );
+
+        Ok(notes.into_iter().map(|(amount, _note)| amount).sum())
+    }
+
+    async fn set_auto_consolidation_threshold(&self, max_notes: usize, task_group: &mut TaskGroup) {
+        let client = self.clone();
+        task_group
+            .spawn("mint note consolidation", move |handle| async move {
+                while !handle.is_shutting_down() {
+                    sleep(AUTO_CONSOLIDATION_INTERVAL).await;
+
+                    if !client.get_active_operations().await.is_empty() {
+                        continue;
+                    }
+
+                    match consolidate_notes(&client, max_notes).await {
+                        Ok(Some(operation_id)) => {
+                            info!(target: LOG_TARGET, ?operation_id, "Consolidating e-cash notes");
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            warn!(target: LOG_TARGET, "Failed to consolidate e-cash notes: {e:?}");
+                        }
+                    }
+                }
+            })
+            .await;
+    }
+}
+
+/// Takes notes of at least `min_amount` out of the wallet for an out-of-band
+/// spend that is refunded after `deadline` unless the recipient reissued them
+async fn spend_oob(
+    client: &Client,
+    min_amount: Amount,
+    deadline: OOBDeadline,
+    extra_meta: serde_json::Value,
+) -> anyhow::Result<(OperationId, OOBNotes)> {
+    let (mint, instance) = client.get_first_module::<MintClientModule>(&KIND);
+
+    client
+        .db()
+        .autocommit(
+            move |dbtx| {
+                let extra_meta = extra_meta.clone();
+                Box::pin(async move {
+                    let (operation_id, states, notes) = mint
+                        .spend_notes_oob(
+                            &mut dbtx.with_module_prefix(instance.id),
+                            min_amount,
+                            deadline,
+                        )
+                        .await?;
+                    let oob_notes = OOBNotes {
+                        federation_id_prefix: mint.federation_id.to_prefix(),
+                        notes,
+                    };
+
+                    let dyn_states = states
+                        .into_iter()
+                        .map(|s| s.into_dyn(instance.id))
+                        .collect();
+
+                    client.add_state_machines(dbtx, dyn_states).await?;
+                    client
+                        .operation_log()
+                        .add_operation_log_entry(
+                            dbtx,
+                            operation_id,
+                            MintCommonGen::KIND.as_str(),
+                            MintOperationMeta {
+                                variant: MintOperationMetaVariants::SpendOOB {
+                                    requested_amount: min_amount,
+                                    oob_notes: oob_notes.clone(),
+                                },
+                                amount: oob_notes.total_amount(),
+                                extra_meta,
+                            },
+                        )
+                        .await;
+
+                    Ok((operation_id, oob_notes))
+                })
+            },
+            Some(100),
+        )
+        .await
+        .map_err(|e| match e {
+            AutocommitError::ClosureError { error, .. } => error,
+            AutocommitError::CommitFailed { last_error, .. } => {
+                anyhow!("Commit to DB failed: {last_error}")
+            }
+        })
+}
+
+/// Reissues all notes of the wallet into the fewest notes possible if it holds
+/// more than `max_notes` notes, returns the id of the reissue operation
+async fn consolidate_notes(
+    client: &Client,
+    max_notes: usize,
+) -> anyhow::Result<Option<OperationId>> {
+    let (mint, instance) = client.get_first_module::<MintClientModule>(&KIND);
+    let operation_id = OperationId::new_random();
+
+    let mut dbtx = client.db().begin_transaction().await;
+    let mut module_dbtx = dbtx.with_module_prefix(instance.id);
+    let summary = mint.get_wallet_summary(&mut module_dbtx).await;
+    if summary.count_items() <= max_notes {
+        return Ok(None);
+    }
+
+    // The notes themselves are only selected as input when the transaction gets
+    // submitted, so only the note indices of the output are committed here
+    let fees = &mint.cfg.fee_consensus;
+    let spendable = summary
+        .total_amount()
+        .saturating_sub(fees.note_spend_abs * summary.count_items() as u64);
+    let issuance_fees = fees.note_issuance_abs
+        * TieredSummary::represent_amount(
+            spendable,
+            &TieredSummary::default(),
+            &mint.cfg.tbs_pks,
+            0,
+        )
+        .count_items() as u64;
+    let amount = spendable.saturating_sub(issuance_fees);
+    ensure!(amount > Amount::ZERO, "Notes are not worth the fees");
+
+    let output = mint
+        .create_output(&mut module_dbtx, operation_id, 0, amount)
+        .await;
+    drop(module_dbtx);
+    dbtx.commit_tx_result().await?;
+
+    let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+    let operation_meta_gen = move |txid, _| MintOperationMeta {
+        variant: MintOperationMetaVariants::Reissuance {
+            out_point: OutPoint { txid, out_idx: 0 },
+        },
+        amount,
+        extra_meta: serde_json::Value::Null,
+    };
+
+    client
+        .finalize_and_submit_transaction(
+            operation_id,
+            MintCommonGen::KIND.as_str(),
+            operation_meta_gen,
+            tx,
+        )
+        .await?;
+
+    Ok(Some(operation_id))
 }
 
 async fn mint_operation(
@@ -579,6 +1196,16 @@ impl ExtendsCommonModuleInit for MintClientGen {
                         "NextECashNoteIndex"
                     );
                 }
+                DbKeyPrefix::RawNonceRestoreCheckpoint => {
+                    push_db_pair_items!(
+                        dbtx,
+                        RawNonceRestoreCheckpointKeyPrefix,
+                        RawNonceRestoreCheckpointKey,
+                        MintRestoreInProgressState,
+                        mint_client_items,
+                        "RawNonceRestoreCheckpoint"
+                    );
+                }
             }
         }
 
@@ -693,6 +1320,66 @@ impl ClientModule for MintClientModule {
         }
     }
 
+    fn input_event_type(
+        &self,
+        _input: &<Self::Common as ModuleCommon>::Input,
+    ) -> Option<EventType> {
+        Some(EventType::NoteRedemption)
+    }
+
+    fn output_event_type(
+        &self,
+        _output: &<Self::Common as ModuleCommon>::Output,
+    ) -> Option<EventType> {
+        Some(EventType::NoteIssuance)
+    }
+
+    /// Checks the denominations of the notes against the limits the mint
+    /// enforces when processing the transaction
+    fn pre_submit_hook(
+        &self,
+        inputs: &[&MintInput],
+        outputs: &[&MintOutput],
+    ) -> Result<(), Vec<ModuleError>> {
+        let mut errors = vec![];
+
+        let input_tiers = inputs
+            .iter()
+            .flat_map(|input| input.0.iter_items())
+            .map(|(amount, _)| amount);
+        let output_tiers = outputs
+            .iter()
+            .flat_map(|output| output.0.iter_items())
+            .map(|(amount, _)| amount);
+        for amount in input_tiers
+            .chain(output_tiers)
+            .filter(|amount| self.cfg.tbs_pks.get(*amount).is_none())
+            .collect::<BTreeSet<_>>()
+        {
+            errors.push(MintError::InvalidAmountTier(amount));
+        }
+
+        let max_tier = self.cfg.tbs_pks.max_tier();
+        for output in outputs {
+            let longest_tier = output.0.longest_tier_except(max_tier);
+            if longest_tier > self.cfg.max_notes_per_denomination.into() {
+                errors.push(MintError::ExceededMaxNotes(
+                    self.cfg.max_notes_per_denomination,
+                    longest_tier,
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors
+                .into_iter()
+                .map(|error| ModuleError::Other(error.into()))
+                .collect())
+        }
+    }
+
     async fn handle_cli_command(
         &self,
         client: &Client,
@@ -894,7 +1581,7 @@ impl ClientModule for MintClientModule {
                         // We only trigger on created since refunds are already covered under the
                         // output state
                         MintClientStateMachines::OOB(MintOOBStateMachine {
-                            state: MintOOBStates::Created(_),
+                            state: MintOOBStates::Created(_) | MintOOBStates::CreatedOffline(_),
                             ..
                         }) => Some(()),
                         // We don't want to scare users, so we only trigger on success instead of
@@ -1088,7 +1775,7 @@ impl MintClientModule {
         &self,
         dbtx: &mut ModuleDatabaseTransaction<'_>,
         min_amount: Amount,
-        try_cancel_after: Duration,
+        deadline: OOBDeadline,
     ) -> anyhow::Result<(
         OperationId,
         Vec<MintClientStateMachines>,
@@ -1111,12 +1798,22 @@ impl MintClientModule {
             .await;
         }
 
-        let state_machines = vec![MintClientStateMachines::OOB(MintOOBStateMachine {
-            operation_id,
-            state: MintOOBStates::Created(MintOOBStatesCreated {
-                notes: spendable_selected_notes.clone(),
+        let notes = spendable_selected_notes.clone();
+        let state = match deadline {
+            OOBDeadline::After(try_cancel_after) => MintOOBStates::Created(MintOOBStatesCreated {
+                notes,
                 timeout: fedimint_core::time::now() + try_cancel_after,
             }),
+            OOBDeadline::AfterEpoch(deadline_epoch) => {
+                MintOOBStates::CreatedOffline(MintOOBStatesCreatedOffline {
+                    notes,
+                    deadline_epoch,
+                })
+            }
+        };
+        let state_machines = vec![MintClientStateMachines::OOB(MintOOBStateMachine {
+            operation_id,
+            state,
         })];
 
         Ok((operation_id, state_machines, spendable_selected_notes))
@@ -1141,7 +1838,7 @@ impl MintClientModule {
                             user_triggered: true,
                             transaction_id: refund.refund_txid,
                         }),
-                        MintOOBStates::Created(_) => None,
+                        MintOOBStates::Created(_) | MintOOBStates::CreatedOffline(_) => None,
                     }
                 }),
         )
@@ -1177,7 +1874,8 @@ impl MintClientModule {
 
     /// Select notes with total amount of *at least* `amount`. If more than
     /// requested amount of notes are returned it was because exact change
-    /// couldn't be made, and the next smallest amount will be returned.
+    /// couldn't be made, and the next smallest amount will be returned, see
+    /// [`select_notes_branch_and_bound`].
     ///
     /// The caller can request change from the federation.
     async fn select_notes(
@@ -1188,7 +1886,7 @@ impl MintClientModule {
             .find_by_prefix_sorted_descending(&NoteKeyPrefix)
             .await
             .map(|(key, note)| (key.amount, note));
-        select_notes_from_stream(note_stream, amount).await
+        select_notes_branch_and_bound(note_stream, amount, NOTE_SELECTION_SEARCH_BUDGET).await
     }
 
     async fn get_all_spendable_notes(
@@ -1223,6 +1921,29 @@ impl MintClientModule {
         )
     }
 
+    /// Rederives the blinded nonce of a `note` issued to this client by trying
+    /// all note indices used so far, returns `None` if none of them matches
+    async fn rederive_blind_nonce(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        note: &SpendableNote,
+    ) -> Option<BlindNonce> {
+        for amount in self.cfg.tbs_pks.tiers() {
+            let next_idx = self.get_next_note_index(dbtx, *amount).await;
+            for idx in 0..next_idx.as_u64() {
+                let (request, blind_nonce) = NoteIssuanceRequest::new(
+                    &self.secp,
+                    Self::new_note_secret_static(&self.secret, *amount, NoteIndex(idx)),
+                );
+                if request.nonce() == note.nonce() {
+                    return Some(blind_nonce);
+                }
+            }
+        }
+
+        None
+    }
+
     /// Derive the note `DerivableSecret` from the Mint's `secret` the `amount`
     /// tier and `note_idx`
     ///
@@ -1358,6 +2079,82 @@ pub async fn select_notes_from_stream<Note>(
     }
 }
 
+/// Selects notes adding up to exactly `requested_amount` if such a combination
+/// is found within `search_budget` tries, so the spend doesn't create change.
+/// Otherwise falls back to [`select_notes_from_stream`].
+///
+/// Like the latter it expects the notes sorted in descending order.
+pub async fn select_notes_branch_and_bound<Note>(
+    stream: impl futures::Stream<Item = (Amount, Note)>,
+    requested_amount: Amount,
+    search_budget: usize,
+) -> Result<TieredMulti<Note>, InsufficientBalanceError> {
+    let notes = stream.collect::<Vec<_>>().await;
+
+    let mut tiers: Vec<(Amount, usize)> = vec![];
+    for (amount, _) in &notes {
+        match tiers.last_mut() {
+            Some((last_amount, count)) if last_amount == amount => *count += 1,
+            _ => tiers.push((*amount, 1)),
+        }
+    }
+
+    let mut counts = vec![];
+    let mut tries = search_budget;
+    if !find_exact_note_counts(&tiers, requested_amount.msats, &mut counts, &mut tries) {
+        return select_notes_from_stream(futures::stream::iter(notes), requested_amount).await;
+    }
+
+    let mut selected = vec![];
+    let mut notes = notes.into_iter();
+    for ((_, tier_count), count) in tiers.into_iter().zip(counts) {
+        let mut tier_notes = notes.by_ref().take(tier_count);
+        selected.extend(tier_notes.by_ref().take(count));
+        tier_notes.for_each(drop);
+    }
+
+    Ok(selected.into_iter().collect())
+}
+
+/// Depth-first search for the number of notes to take from each of the
+/// descending `tiers` to add up to `remaining_msats`, largest notes first.
+/// Every visited tier uses up one of the `tries`.
+fn find_exact_note_counts(
+    tiers: &[(Amount, usize)],
+    remaining_msats: u64,
+    counts: &mut Vec<usize>,
+    tries: &mut usize,
+) -> bool {
+    if remaining_msats == 0 {
+        return true;
+    }
+
+    let Some(((amount, count), lower_tiers)) = tiers.split_first() else {
+        return false;
+    };
+
+    let available_msats: u64 = tiers
+        .iter()
+        .map(|(amount, count)| amount.msats * *count as u64)
+        .sum();
+    if *tries == 0 || amount.msats == 0 || available_msats < remaining_msats {
+        return false;
+    }
+    *tries -= 1;
+
+    let max_count = (*count as u64).min(remaining_msats / amount.msats);
+    for tier_count in (0..=max_count).rev() {
+        counts.push(tier_count as usize);
+        let tier_msats = tier_count * amount.msats;
+        if find_exact_note_counts(lower_tiers, remaining_msats - tier_msats, counts, tries) {
+            return true;
+        }
+        counts.pop();
+    }
+
+    false
+}
+
 #[derive(Debug, Clone, Error)]
 pub struct InsufficientBalanceError {
     pub requested_amount: Amount,
@@ -1446,7 +2243,7 @@ pub struct SpendableNote {
 }
 
 impl SpendableNote {
-    fn nonce(&self) -> Nonce {
+    pub fn nonce(&self) -> Nonce {
         Nonce(self.spend_key.x_only_public_key().0)
     }
 
@@ -1529,11 +2326,15 @@ impl sha256t::Tag for OOBReissueTag {
 
 #[cfg(test)]
 mod tests {
-    use fedimint_core::config::FederationId;
-    use fedimint_core::{Amount, Tiered, TieredMulti, TieredSummary};
+    use std::collections::BTreeMap;
+
+    use fedimint_core::config::{FederationId, PeerUrl};
+    use fedimint_core::{Amount, PeerId, Tiered, TieredMulti, TieredSummary};
     use itertools::Itertools;
 
-    use crate::{select_notes_from_stream, OOBNotes};
+    use crate::{
+        select_notes_branch_and_bound, select_notes_from_stream, OOBNotes, ParsedPaymentLink,
+    };
 
     #[test_log::test(tokio::test)]
     async fn select_notes_avg_test() {
@@ -1614,6 +2415,50 @@ mod tests {
         assert_eq!(error.total_amount, Amount::from_sats(10));
     }
 
+    #[test_log::test(tokio::test)]
+    async fn select_notes_branch_and_bound_avoids_change_if_exact_match_exists() {
+        let f = || {
+            reverse_sorted_note_stream(vec![(Amount::from_sats(3), 2), (Amount::from_sats(5), 1)])
+        };
+        // greedily taking the biggest note first needs change
+        assert_eq!(
+            select_notes_from_stream(f(), Amount::from_sats(6))
+                .await
+                .unwrap(),
+            notes(vec![(Amount::from_sats(3), 1), (Amount::from_sats(5), 1)])
+        );
+        assert_eq!(
+            select_notes_branch_and_bound(f(), Amount::from_sats(6), 1000)
+                .await
+                .unwrap(),
+            notes(vec![(Amount::from_sats(3), 2)])
+        );
+        // without search budget we fall back to the greedy selection
+        assert_eq!(
+            select_notes_branch_and_bound(f(), Amount::from_sats(6), 0)
+                .await
+                .unwrap(),
+            notes(vec![(Amount::from_sats(3), 1), (Amount::from_sats(5), 1)])
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn select_notes_branch_and_bound_falls_back_if_exact_change_cannot_be_made() {
+        let f = || {
+            reverse_sorted_note_stream(vec![(Amount::from_sats(3), 2), (Amount::from_sats(5), 1)])
+        };
+        assert_eq!(
+            select_notes_branch_and_bound(f(), Amount::from_sats(7), 1000)
+                .await
+                .unwrap(),
+            notes(vec![(Amount::from_sats(3), 1), (Amount::from_sats(5), 1)])
+        );
+        let error = select_notes_branch_and_bound(f(), Amount::from_sats(12), 1000)
+            .await
+            .unwrap_err();
+        assert_eq!(error.total_amount, Amount::from_sats(11));
+    }
+
     fn reverse_sorted_note_stream(
         notes: Vec<(Amount, usize)>,
     ) -> impl futures::Stream<Item = (Amount, String)> {
@@ -1647,4 +2492,36 @@ mod tests {
 
         assert!(res.is_err(), "An empty OOB notes string should not parse");
     }
+
+    #[test]
+    fn payment_link_round_trips() {
+        let request = ParsedPaymentLink {
+            federation_id: FederationId(threshold_crypto::SecretKey::random().public_key()),
+            api_endpoints: BTreeMap::from([(
+                PeerId::from(0),
+                PeerUrl {
+                    url: "wss://fedimint-server-1:5000".parse().unwrap(),
+                    name: "guardian-1".into(),
+                },
+            )]),
+            amount: Amount::from_sats(21_000),
+            description: "Coffee".into(),
+            nonce: rand::random(),
+        };
+
+        let link = request.to_link();
+        assert!(link.as_str().starts_with("fedimint:fedpay1"));
+        assert_eq!(link.as_str().parse::<ParsedPaymentLink>().unwrap(), request);
+        assert_eq!(
+            link.as_str()
+                .trim_start_matches("fedimint:")
+                .parse::<ParsedPaymentLink>()
+                .unwrap(),
+            request
+        );
+
+        let mut corrupted = link.to_string();
+        corrupted.pop();
+        assert!(corrupted.parse::<ParsedPaymentLink>().is_err());
+    }
 }
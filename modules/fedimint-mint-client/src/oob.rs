@@ -1,9 +1,10 @@
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use fedimint_client::sm::{ClientSMDatabaseTransaction, OperationId, State, StateTransition};
 use fedimint_client::transaction::ClientInput;
 use fedimint_client::DynGlobalClientContext;
+use fedimint_core::api::GlobalFederationApi;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::{task, TieredMulti, TransactionId};
 use fedimint_mint_common::MintInput;
@@ -13,6 +14,10 @@ use crate::input::{
 };
 use crate::{MintClientContext, MintClientStateMachines, SpendableNote};
 
+/// How long to wait before retrying to fetch the deadline epoch of an offline
+/// note
+const DEADLINE_EPOCH_RETRY_DELAY: Duration = Duration::from_secs(10);
+
 #[aquamarine::aquamarine]
 /// State machine managing e-cash that has been taken out of the wallet for
 /// out-of-band transmission.
@@ -21,6 +26,8 @@ use crate::{MintClientContext, MintClientStateMachines, SpendableNote};
 /// graph LR
 ///     Created -- User triggered refund --> RefundU["User Refund"]
 ///     Created -- Timeout triggered refund --> RefundT["Timeout Refund"]
+///     CreatedOffline["Created Offline"] -- User triggered refund --> RefundU
+///     CreatedOffline -- Deadline epoch triggered refund --> RefundT
 /// ```
 #[derive(Debug, Clone, Eq, PartialEq, Decodable, Encodable)]
 pub enum MintOOBStates {
@@ -33,6 +40,10 @@ pub enum MintOOBStates {
     /// refund. This refund *failing* is the expected behavior since the
     /// recipient is supposed to have already reissued it.
     TimeoutRefund(MintOOBStatesTimeoutRefund),
+    /// The e-cash has been taken out of the wallet as an offline note and we
+    /// are waiting for the recipient to reissue it before its deadline or the
+    /// user to trigger a refund.
+    CreatedOffline(MintOOBStatesCreatedOffline),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Decodable, Encodable)]
@@ -47,6 +58,13 @@ pub struct MintOOBStatesCreated {
     pub(crate) timeout: SystemTime,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq, Decodable, Encodable)]
+pub struct MintOOBStatesCreatedOffline {
+    pub(crate) notes: TieredMulti<SpendableNote>,
+    /// Once this epoch is completed we try to refund the notes
+    pub(crate) deadline_epoch: u64,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Decodable, Encodable)]
 pub struct MintOOBStatesUserRefund {
     pub(crate) refund_txid: TransactionId,
@@ -70,6 +88,9 @@ impl State for MintOOBStateMachine {
             MintOOBStates::Created(created) => {
                 created.transitions(self.operation_id, context, global_context)
             }
+            MintOOBStates::CreatedOffline(created) => {
+                created.transitions(self.operation_id, context, global_context)
+            }
             MintOOBStates::UserRefund(_) => {
                 vec![]
             }
@@ -115,6 +136,36 @@ impl MintOOBStatesCreated {
     }
 }
 
+impl MintOOBStatesCreatedOffline {
+    fn transitions(
+        &self,
+        operation_id: OperationId,
+        context: &MintClientContext,
+        global_context: &DynGlobalClientContext,
+    ) -> Vec<StateTransition<MintOOBStateMachine>> {
+        let user_cancel_gc = global_context.clone();
+        let deadline_cancel_gc = global_context.clone();
+        vec![
+            StateTransition::new(
+                await_user_cancels(operation_id, context.subscribe_cancel_oob_payment()),
+                move |dbtx, (), state| {
+                    Box::pin(transition_user_cancel(state, dbtx, user_cancel_gc.clone()))
+                },
+            ),
+            StateTransition::new(
+                await_deadline_epoch(global_context.clone(), self.deadline_epoch),
+                move |dbtx, (), state| {
+                    Box::pin(transition_timeout_cancel(
+                        state,
+                        dbtx,
+                        deadline_cancel_gc.clone(),
+                    ))
+                },
+            ),
+        ]
+    }
+}
+
 async fn await_user_cancels(
     operation_id: OperationId,
     mut oob_cancel_receiver: tokio::sync::broadcast::Receiver<OperationId>,
@@ -134,6 +185,7 @@ async fn transition_user_cancel(
 ) -> MintOOBStateMachine {
     let spendable_notes = match prev_state.state {
         MintOOBStates::Created(created) => created.notes,
+        MintOOBStates::CreatedOffline(created) => created.notes,
         _ => panic!("Invalid previous state: {prev_state:?}"),
     };
 
@@ -156,6 +208,17 @@ async fn await_timeout_cancel(deadline: SystemTime) {
     }
 }
 
+async fn await_deadline_epoch(global_context: DynGlobalClientContext, deadline_epoch: u64) {
+    while global_context
+        .api()
+        .await_block(deadline_epoch, global_context.decoders())
+        .await
+        .is_err()
+    {
+        task::sleep(DEADLINE_EPOCH_RETRY_DELAY).await;
+    }
+}
+
 async fn transition_timeout_cancel(
     prev_state: MintOOBStateMachine,
     dbtx: &mut ClientSMDatabaseTransaction<'_, '_>,
@@ -163,6 +226,7 @@ async fn transition_timeout_cancel(
 ) -> MintOOBStateMachine {
     let spendable_notes = match prev_state.state {
         MintOOBStates::Created(created) => created.notes,
+        MintOOBStates::CreatedOffline(created) => created.notes,
         _ => panic!("Invalid previous state: {prev_state:?}"),
     };
 
@@ -1,4 +1,5 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
 
 use fedimint_core::config::EmptyGenParams;
 use fedimint_core::core::ModuleKind;
@@ -7,8 +8,9 @@ use fedimint_core::module::__reexports::serde_json;
 use fedimint_core::{plugin_types_trait_impl_config, Amount, PeerId, Tiered};
 use serde::{Deserialize, Serialize};
 use tbs::{AggregatePublicKey, PublicKeyShare};
+use thiserror::Error;
 
-use crate::MintCommonGen;
+use crate::{MintCommonGen, DEFAULT_PARTIAL_SIG_TIMEOUT};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintGenParams {
@@ -19,6 +21,62 @@ pub struct MintGenParams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintGenParamsConsensus {
     denomination_base: u16,
+    #[serde(default)]
+    max_ecash_outstanding_sats: Option<u64>,
+    /// Replaces the powers of `denomination_base` if set
+    #[serde(default)]
+    denominations: Option<DenominationSet>,
+}
+
+/// A custom set of note denominations for a federation, e.g. 1/5/10/50/100
+/// sats instead of powers of a base
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "BTreeSet<Amount>", into = "BTreeSet<Amount>")]
+pub struct DenominationSet(BTreeSet<Amount>);
+
+impl DenominationSet {
+    /// Fails unless the set contains a 1 msat denomination, since clients
+    /// couldn't represent every amount with notes otherwise
+    pub fn new(denominations: BTreeSet<Amount>) -> Result<Self, DenominationSetError> {
+        if !denominations.contains(&Amount::from_msats(1)) {
+            return Err(DenominationSetError::MissingSmallestDenomination);
+        }
+
+        if let Some(too_large) = denominations
+            .iter()
+            .find(|denomination| **denomination > MAX_DENOMINATION_SIZE)
+        {
+            return Err(DenominationSetError::DenominationTooLarge(*too_large));
+        }
+
+        Ok(Self(denominations))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Amount> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl TryFrom<BTreeSet<Amount>> for DenominationSet {
+    type Error = DenominationSetError;
+
+    fn try_from(denominations: BTreeSet<Amount>) -> Result<Self, Self::Error> {
+        Self::new(denominations)
+    }
+}
+
+impl From<DenominationSet> for BTreeSet<Amount> {
+    fn from(set: DenominationSet) -> Self {
+        set.0
+    }
+}
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum DenominationSetError {
+    #[error("Denominations have to include 1 msat")]
+    MissingSmallestDenomination,
+    #[error("Denomination {0} exceeds the maximum note size")]
+    DenominationTooLarge(Amount),
 }
 
 // The maximum size of an E-Cash note (1,000,000 coins)
@@ -28,14 +86,40 @@ const MAX_DENOMINATION_SIZE: Amount = Amount::from_sats(1_000_000 * 100_000_000)
 
 impl MintGenParamsConsensus {
     pub fn new(denomination_base: u16) -> Self {
-        Self { denomination_base }
+        Self {
+            denomination_base,
+            max_ecash_outstanding_sats: None,
+            denominations: None,
+        }
+    }
+
+    /// Issues notes of exactly the given `denominations` instead of the powers
+    /// of the denomination base
+    pub fn with_denominations(mut self, denominations: DenominationSet) -> Self {
+        self.denominations = Some(denominations);
+        self
+    }
+
+    /// Caps the total amount of e-cash the federation issues and that wasn't
+    /// redeemed yet
+    pub fn with_max_ecash_outstanding_sats(mut self, max_ecash_outstanding_sats: u64) -> Self {
+        self.max_ecash_outstanding_sats = Some(max_ecash_outstanding_sats);
+        self
     }
 
     pub fn denomination_base(&self) -> u16 {
         self.denomination_base
     }
 
+    pub fn max_ecash_outstanding_sats(&self) -> Option<u64> {
+        self.max_ecash_outstanding_sats
+    }
+
     pub fn gen_denominations(&self) -> Vec<Amount> {
+        if let Some(denominations) = &self.denominations {
+            return denominations.iter().collect();
+        }
+
         Tiered::gen_denominations(self.denomination_base, MAX_DENOMINATION_SIZE)
             .tiers()
             .cloned()
@@ -71,6 +155,21 @@ pub struct MintConfigConsensus {
     pub fee_consensus: FeeConsensus,
     /// The maximum amount of change a client can request
     pub max_notes_per_denomination: u16,
+    /// How long guardians may take to contribute their partial signature for
+    /// an output before they are reported as slow signers, defaults to
+    /// [`DEFAULT_PARTIAL_SIG_TIMEOUT`] if unset
+    #[serde(default)]
+    pub partial_sig_timeout: Option<Duration>,
+    /// The maximum amount of issued e-cash that wasn't redeemed yet, no more
+    /// notes are issued once it's reached
+    pub max_ecash_outstanding_sats: Option<u64>,
+}
+
+impl MintConfigConsensus {
+    pub fn partial_sig_timeout(&self) -> Duration {
+        self.partial_sig_timeout
+            .unwrap_or(DEFAULT_PARTIAL_SIG_TIMEOUT)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -16,6 +16,8 @@ pub enum DbKeyPrefix {
     OutputOutcome = 0x13,
     MintAuditItem = 0x14,
     EcashBackup = 0x15,
+    DenominationUsage = 0x16,
+    OutstandingEcash = 0x17,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -134,3 +136,49 @@ pub struct ECashUserBackupSnapshot {
     #[serde(with = "fedimint_core::hex::serde")]
     pub data: Vec<u8>,
 }
+
+/// Usage counters of a single denomination
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize,
+)]
+pub struct DenominationStats {
+    pub issued_count: u64,
+    pub redeemed_count: u64,
+    /// Notes issued but not redeemed yet. Notes issued before the counters
+    /// were introduced are not included.
+    pub outstanding_count: u64,
+}
+
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct DenominationUsageKey(pub Amount);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct DenominationUsageKeyPrefix;
+
+impl_db_record!(
+    key = DenominationUsageKey,
+    value = DenominationStats,
+    db_prefix = DbKeyPrefix::DenominationUsage,
+);
+impl_db_lookup!(
+    key = DenominationUsageKey,
+    query_prefix = DenominationUsageKeyPrefix
+);
+
+/// Running total of e-cash issued minus the amount redeemed, kept so the
+/// issuance cap can be checked without scanning the audit items
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize)]
+pub struct OutstandingEcashKey;
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct OutstandingEcashKeyPrefix;
+
+impl_db_record!(
+    key = OutstandingEcashKey,
+    value = Amount,
+    db_prefix = DbKeyPrefix::OutstandingEcash,
+);
+impl_db_lookup!(
+    key = OutstandingEcashKey,
+    query_prefix = OutstandingEcashKeyPrefix
+);
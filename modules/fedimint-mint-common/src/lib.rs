@@ -1,4 +1,5 @@
 use std::hash::Hash;
+use std::time::Duration;
 
 pub use common::{BackupRequest, SignedBackupRequest};
 use config::MintClientConfig;
@@ -23,6 +24,10 @@ const CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion(0);
 /// By default, the maximum notes per denomination when change-making for users
 pub const DEFAULT_MAX_NOTES_PER_DENOMINATION: u16 = 3;
 
+/// By default, how long guardians may take to contribute their partial
+/// signature for an output before they are reported as slow signers
+pub const DEFAULT_PARTIAL_SIG_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Data structures taking into account different amount tiers
 
 /// A consenus item from one of the federation members contributing partials
@@ -261,6 +266,8 @@ pub enum MintError {
     InvalidSignature,
     #[error("Exceeded maximum notes per denomination {0}, found {1}")]
     ExceededMaxNotes(u16, usize),
+    #[error("Issuing {0} would exceed the maximum outstanding e-cash of {1}")]
+    ExceededMaxEcashOutstanding(Amount, Amount),
 }
 
 impl From<InvalidAmountTierError> for MintError {
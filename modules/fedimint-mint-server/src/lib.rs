@@ -1,5 +1,7 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::iter::FromIterator;
+use std::sync::Mutex;
+use std::time::Instant;
 
 use anyhow::{bail, Context};
 use fedimint_core::config::{
@@ -26,9 +28,11 @@ use fedimint_mint_common::config::{
     MintConfigPrivate, MintGenParams,
 };
 use fedimint_mint_common::db::{
-    DbKeyPrefix, ECashUserBackupSnapshot, EcashBackupKey, EcashBackupKeyPrefix, MintAuditItemKey,
+    DbKeyPrefix, DenominationStats, DenominationUsageKey, DenominationUsageKeyPrefix,
+    ECashUserBackupSnapshot, EcashBackupKey, EcashBackupKeyPrefix, MintAuditItemKey,
     MintAuditItemKeyPrefix, NonceKey, NonceKeyPrefix, OutputOutcomeKey, OutputOutcomeKeyPrefix,
-    ProposedPartialSignatureKey, ProposedPartialSignaturesKeyPrefix, ReceivedPartialSignatureKey,
+    OutstandingEcashKey, OutstandingEcashKeyPrefix, ProposedPartialSignatureKey,
+    ProposedPartialSignaturesKeyPrefix, ReceivedPartialSignatureKey,
     ReceivedPartialSignatureKeyOutputPrefix, ReceivedPartialSignaturesKeyPrefix,
 };
 pub use fedimint_mint_common::{BackupRequest, SignedBackupRequest};
@@ -49,7 +53,7 @@ use tbs::{
     AggregatePublicKey, PublicKeyShare, SecretKeyShare,
 };
 use threshold_crypto::group::Curve;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone)]
 pub struct MintGen;
@@ -122,6 +126,26 @@ impl ExtendsCommonModuleInit for MintGen {
                         "User Ecash Backup"
                     );
                 }
+                DbKeyPrefix::DenominationUsage => {
+                    push_db_pair_items!(
+                        dbtx,
+                        DenominationUsageKeyPrefix,
+                        DenominationUsageKey,
+                        DenominationStats,
+                        mint,
+                        "Denomination Usage"
+                    );
+                }
+                DbKeyPrefix::OutstandingEcash => {
+                    push_db_pair_items!(
+                        dbtx,
+                        OutstandingEcashKeyPrefix,
+                        OutstandingEcashKey,
+                        fedimint_core::Amount,
+                        mint,
+                        "Outstanding Ecash"
+                    );
+                }
             }
         }
 
@@ -185,6 +209,8 @@ impl ServerModuleInit for MintGen {
                             .collect(),
                         fee_consensus: FeeConsensus::default(),
                         max_notes_per_denomination: DEFAULT_MAX_NOTES_PER_DENOMINATION,
+                        partial_sig_timeout: None,
+                        max_ecash_outstanding_sats: params.consensus.max_ecash_outstanding_sats(),
                     },
                     private: MintConfigPrivate {
                         tbs_sks: params
@@ -247,6 +273,8 @@ impl ServerModuleInit for MintGen {
                     .collect(),
                 fee_consensus: Default::default(),
                 max_notes_per_denomination: DEFAULT_MAX_NOTES_PER_DENOMINATION,
+                partial_sig_timeout: None,
+                max_ecash_outstanding_sats: params.consensus.max_ecash_outstanding_sats(),
             },
         };
 
@@ -317,6 +345,10 @@ pub struct Mint {
     sec_key: Tiered<SecretKeyShare>,
     pub_key_shares: BTreeMap<PeerId, Tiered<PublicKeyShare>>,
     pub_key: HashMap<Amount, AggregatePublicKey>,
+    /// Since when we propose our signature share for out points still lacking
+    /// a threshold of shares. Only tracked while building our consensus
+    /// proposal to report slow signers, never while processing consensus.
+    proposed_since: Mutex<HashMap<OutPoint, Instant>>,
 }
 #[apply(async_trait_maybe_send!)]
 impl ServerModule for Mint {
@@ -327,14 +359,20 @@ impl ServerModule for Mint {
         &self,
         dbtx: &mut ModuleDatabaseTransaction<'_>,
     ) -> Vec<MintConsensusItem> {
-        dbtx.find_by_prefix(&ProposedPartialSignaturesKeyPrefix)
+        let items = dbtx
+            .find_by_prefix(&ProposedPartialSignaturesKeyPrefix)
             .await
             .map(|(key, signatures)| MintConsensusItem {
                 out_point: key.0,
                 signatures,
             })
             .collect::<Vec<MintConsensusItem>>()
-            .await
+            .await;
+
+        let pending = items.iter().map(|item| item.out_point).collect();
+        self.report_slow_signers(dbtx, &pending).await;
+
+        items
     }
 
     async fn process_consensus_item<'a, 'b>(
@@ -401,7 +439,9 @@ impl ServerModule for Mint {
             .collect::<Vec<_>>()
             .await;
 
-        // check if we have enough signature shares to combine
+        // check if we have enough signature shares to combine, since any subset
+        // meeting the threshold yields the same signature we don't wait for the
+        // shares of slow peers
         if signature_shares.len() < self.cfg.consensus.peer_tbs_pks.threshold() {
             return Ok(());
         }
@@ -499,8 +539,21 @@ impl ServerModule for Mint {
 
             dbtx.insert_new_entry(&MintAuditItemKey::Redemption(NonceKey(note.nonce)), &amount)
                 .await;
+
+            let mut stats = self.denomination_stats(dbtx, amount).await;
+            stats.redeemed_count += 1;
+            stats.outstanding_count = stats.outstanding_count.saturating_sub(1);
+            dbtx.insert_entry(&DenominationUsageKey(amount), &stats)
+                .await;
         }
 
+        let outstanding = self.outstanding_ecash(dbtx).await;
+        dbtx.insert_entry(
+            &OutstandingEcashKey,
+            &outstanding.saturating_sub(input.total_amount()),
+        )
+        .await;
+
         Ok(InputMeta {
             amount: TransactionItemAmount {
                 amount: input.total_amount(),
@@ -540,17 +593,38 @@ impl ServerModule for Mint {
             return Err(MintError::InvalidAmountTier(amount)).into_module_error_other();
         }
 
+        let outstanding = self.outstanding_ecash(dbtx).await + output.total_amount();
+        if let Some(max_outstanding) = self.cfg.consensus.max_ecash_outstanding_sats {
+            let max_outstanding = Amount::from_sats(max_outstanding);
+            if outstanding > max_outstanding {
+                return Err(MintError::ExceededMaxEcashOutstanding(
+                    output.total_amount(),
+                    max_outstanding,
+                ))
+                .into_module_error_other();
+            }
+        }
+
         // TODO: move actual signing to worker thread
         let partial_sig = self.blind_sign(&output.0).into_module_error_other()?;
 
         dbtx.insert_new_entry(&ProposedPartialSignatureKey(out_point), &partial_sig)
             .await;
+        dbtx.insert_entry(&OutstandingEcashKey, &outstanding).await;
         dbtx.insert_new_entry(
             &MintAuditItemKey::Issuance(out_point),
             &output.total_amount(),
         )
         .await;
 
+        for (amount, _) in output.iter_items() {
+            let mut stats = self.denomination_stats(dbtx, amount).await;
+            stats.issued_count += 1;
+            stats.outstanding_count += 1;
+            dbtx.insert_entry(&DenominationUsageKey(amount), &stats)
+                .await;
+        }
+
         Ok(TransactionItemAmount {
             amount: output.total_amount(),
             fee: self.cfg.consensus.fee_consensus.note_issuance_abs * (output.count_items() as u64),
@@ -664,6 +738,58 @@ impl Mint {
     ) -> Option<ECashUserBackupSnapshot> {
         dbtx.get_value(&EcashBackupKey(id)).await
     }
+
+    /// How many notes of each denomination were issued and redeemed, helps
+    /// operators to choose the denominations of a future federation
+    pub async fn denomination_usage_stats(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> BTreeMap<Amount, DenominationStats> {
+        dbtx.find_by_prefix(&DenominationUsageKeyPrefix)
+            .await
+            .map(|(DenominationUsageKey(amount), stats)| (amount, stats))
+            .collect()
+            .await
+    }
+
+    async fn denomination_stats(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        amount: Amount,
+    ) -> DenominationStats {
+        dbtx.get_value(&DenominationUsageKey(amount))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Total amount of e-cash issued minus the amount redeemed so far
+    ///
+    /// The total is kept under [`OutstandingEcashKey`]. Databases created
+    /// before the key was introduced compute it from the audit items once.
+    async fn outstanding_ecash(&self, dbtx: &mut ModuleDatabaseTransaction<'_>) -> Amount {
+        if let Some(outstanding) = dbtx.get_value(&OutstandingEcashKey).await {
+            return outstanding;
+        }
+
+        let mut issuances = Amount::ZERO;
+        let mut redemptions = Amount::ZERO;
+        dbtx.find_by_prefix(&MintAuditItemKeyPrefix)
+            .await
+            .for_each(|(key, amount)| {
+                match key {
+                    MintAuditItemKey::Issuance(_) | MintAuditItemKey::IssuanceTotal => {
+                        issuances += amount
+                    }
+                    MintAuditItemKey::Redemption(_) | MintAuditItemKey::RedemptionTotal => {
+                        redemptions += amount
+                    }
+                }
+                futures::future::ready(())
+            })
+            .await;
+
+        issuances.saturating_sub(redemptions)
+    }
 }
 
 impl Mint {
@@ -724,7 +850,65 @@ impl Mint {
             sec_key: cfg.private.tbs_sks,
             pub_key_shares: cfg.consensus.peer_tbs_pks.into_iter().collect(),
             pub_key: aggregate_pub_keys,
+            proposed_since: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Warns about the peers that haven't contributed a signature share for
+    /// an out point that is still `pending` after we proposed our own share for
+    /// longer than the partial signature timeout and returns them per out
+    /// point. Signing never waits for them since any subset of shares meeting
+    /// the threshold yields the same signature.
+    async fn report_slow_signers(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        pending: &BTreeSet<OutPoint>,
+    ) -> BTreeMap<OutPoint, BTreeSet<PeerId>> {
+        let timeout = self.cfg.consensus.partial_sig_timeout();
+        let now = Instant::now();
+
+        let overdue = {
+            let mut proposed_since = self.proposed_since.lock().expect("poisoned");
+            proposed_since.retain(|out_point, _| pending.contains(out_point));
+
+            let mut overdue = vec![];
+            for out_point in pending {
+                let since = proposed_since.entry(*out_point).or_insert(now);
+                if timeout <= now.duration_since(*since) {
+                    // warn again after another timeout if we keep waiting
+                    *since = now;
+                    overdue.push(*out_point);
+                }
+            }
+            overdue
+        };
+
+        let mut slow_signers = BTreeMap::new();
+        for out_point in overdue {
+            let contributed = dbtx
+                .find_by_prefix(&ReceivedPartialSignatureKeyOutputPrefix(out_point))
+                .await
+                .map(|(key, _)| key.1)
+                .collect::<BTreeSet<_>>()
+                .await;
+            let slow = self
+                .pub_key_shares
+                .keys()
+                .filter(|peer| !contributed.contains(peer))
+                .copied()
+                .collect::<BTreeSet<_>>();
+
+            if !slow.is_empty() {
+                warn!(
+                    %out_point,
+                    ?slow,
+                    "Peers did not contribute a signature share within {timeout:?}"
+                );
+                slow_signers.insert(out_point, slow);
+            }
         }
+
+        slow_signers
     }
 
     pub fn pub_key(&self) -> HashMap<Amount, AggregatePublicKey> {
@@ -747,14 +931,22 @@ impl Mint {
 
 #[cfg(test)]
 mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::time::Duration;
+
     use assert_matches::assert_matches;
+    use bitcoin_hashes::Hash;
     use fedimint_core::config::{ClientModuleConfig, ConfigGenModuleParams, ServerModuleConfig};
     use fedimint_core::db::mem_impl::MemDatabase;
     use fedimint_core::db::Database;
-    use fedimint_core::module::{ModuleConsensusVersion, ServerModuleInit};
-    use fedimint_core::{Amount, PeerId, ServerModule};
+    use fedimint_core::module::{ModuleConsensusVersion, ModuleError, ServerModuleInit};
+    use fedimint_core::task::sleep;
+    use fedimint_core::{Amount, NumPeers, OutPoint, PeerId, ServerModule, TransactionId};
     use fedimint_mint_common::config::FeeConsensus;
-    use fedimint_mint_common::{MintInput, Nonce, Note};
+    use fedimint_mint_common::db::{DenominationStats, OutputOutcomeKey, OutstandingEcashKey};
+    use fedimint_mint_common::{
+        BlindNonce, MintConsensusItem, MintError, MintInput, MintOutput, Nonce, Note,
+    };
     use tbs::blind_message;
 
     use crate::common::config::MintGenParamsConsensus;
@@ -766,12 +958,18 @@ mod test {
     const MINTS: usize = 5;
 
     fn build_configs() -> (Vec<ServerModuleConfig>, ClientModuleConfig) {
+        build_configs_with_params(MintGenParamsConsensus::new(2))
+    }
+
+    fn build_configs_with_params(
+        consensus: MintGenParamsConsensus,
+    ) -> (Vec<ServerModuleConfig>, ClientModuleConfig) {
         let peers = (0..MINTS as u16).map(PeerId::from).collect::<Vec<_>>();
         let mint_cfg = MintGen.trusted_dealer_gen(
             &peers,
             &ConfigGenModuleParams::from_typed(MintGenParams {
                 local: Default::default(),
-                consensus: MintGenParamsConsensus::new(2),
+                consensus,
             })
             .unwrap(),
         );
@@ -804,6 +1002,8 @@ mod test {
                     .peer_tbs_pks,
                 fee_consensus: FeeConsensus::default(),
                 max_notes_per_denomination: 0,
+                partial_sig_timeout: None,
+                max_ecash_outstanding_sats: None,
             },
             private: MintConfigPrivate {
                 tbs_sks: mint_server_cfg1[0]
@@ -849,6 +1049,265 @@ mod test {
         (note_key, Note { nonce, signature })
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_signing_does_not_wait_for_slow_peer() {
+        let (mint_server_cfg, _) = build_configs();
+        let mints = mint_server_cfg
+            .iter()
+            .map(|cfg| Mint::new(cfg.to_typed().unwrap()))
+            .collect::<Vec<_>>();
+        let threshold = mints[0].cfg.consensus.peer_tbs_pks.threshold();
+        let slow_peer = MINTS - 1;
+        assert!(threshold <= slow_peer);
+
+        let denomination = Amount::from_msats(1024);
+        let blind_msg = blind_message(
+            tbs::Message::from_bytes(b"test note"),
+            tbs::BlindingKey::random(),
+        );
+        let output = MintOutput(
+            vec![(denomination, BlindNonce(blind_msg))]
+                .into_iter()
+                .collect(),
+        );
+        let out_point = OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx: 0,
+        };
+
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let mut dbtx = dbtx.with_module_prefix(42);
+        mints[0]
+            .process_output(&mut dbtx, &output, out_point)
+            .await
+            .expect("Valid output");
+
+        // the slow peer signs concurrently with the others but only after a delay,
+        // the others combine their signatures as soon as a threshold of shares
+        // arrived
+        let slow_signatures = async {
+            sleep(Duration::from_millis(200)).await;
+            mints[slow_peer].blind_sign(&output.0).unwrap()
+        };
+        let signing = async {
+            for (peer, mint) in mints.iter().enumerate().take(threshold) {
+                assert!(dbtx.get_value(&OutputOutcomeKey(out_point)).await.is_none());
+                let item = MintConsensusItem {
+                    out_point,
+                    signatures: mint.blind_sign(&output.0).unwrap(),
+                };
+                mints[0]
+                    .process_consensus_item(&mut dbtx, item, PeerId::from(peer as u16))
+                    .await
+                    .expect("Valid signature share");
+            }
+
+            assert!(dbtx.get_value(&OutputOutcomeKey(out_point)).await.is_some());
+        };
+        let (slow_signatures, ()) = tokio::join!(slow_signatures, signing);
+
+        // The share of the slow peer is not needed anymore once it arrives
+        let slow_peer_id = PeerId::from(slow_peer as u16);
+        let item = MintConsensusItem {
+            out_point,
+            signatures: slow_signatures,
+        };
+        assert_matches!(
+            mints[0]
+                .process_consensus_item(&mut dbtx, item, slow_peer_id)
+                .await,
+            Err(_)
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_slow_signers_are_reported() {
+        let (mint_server_cfg, _) = build_configs();
+        let mints = mint_server_cfg
+            .iter()
+            .map(|cfg| {
+                let mut cfg = cfg.to_typed::<MintConfig>().unwrap();
+                // every pending out point is overdue as soon as we propose our share
+                cfg.consensus.partial_sig_timeout = Some(Duration::ZERO);
+                Mint::new(cfg)
+            })
+            .collect::<Vec<_>>();
+        let contributing_peers = mints[0].cfg.consensus.peer_tbs_pks.threshold() - 1;
+
+        let output = MintOutput(
+            vec![(
+                Amount::from_msats(1024),
+                BlindNonce(blind_message(
+                    tbs::Message::from_bytes(b"test note"),
+                    tbs::BlindingKey::random(),
+                )),
+            )]
+            .into_iter()
+            .collect(),
+        );
+        let out_point = OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx: 0,
+        };
+
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let mut dbtx = dbtx.with_module_prefix(42);
+        mints[0]
+            .process_output(&mut dbtx, &output, out_point)
+            .await
+            .expect("Valid output");
+
+        for (peer, mint) in mints.iter().enumerate().take(contributing_peers) {
+            let item = MintConsensusItem {
+                out_point,
+                signatures: mint.blind_sign(&output.0).unwrap(),
+            };
+            mints[0]
+                .process_consensus_item(&mut dbtx, item, PeerId::from(peer as u16))
+                .await
+                .expect("Valid signature share");
+        }
+
+        let slow_peers = (contributing_peers..MINTS)
+            .map(|peer| PeerId::from(peer as u16))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            mints[0]
+                .report_slow_signers(&mut dbtx, &BTreeSet::from([out_point]))
+                .await,
+            BTreeMap::from([(out_point, slow_peers)])
+        );
+
+        // nothing is reported once the out point is no longer pending
+        assert_eq!(
+            mints[0]
+                .report_slow_signers(&mut dbtx, &BTreeSet::new())
+                .await,
+            BTreeMap::new()
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_issuance_is_capped_by_max_ecash_outstanding() {
+        let (mint_server_cfg, _) = build_configs_with_params(
+            MintGenParamsConsensus::new(2).with_max_ecash_outstanding_sats(4),
+        );
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+
+        let output = |msats: &[u64]| {
+            MintOutput(
+                msats
+                    .iter()
+                    .map(|msats| {
+                        let blind_msg = blind_message(
+                            tbs::Message::from_bytes(&rand::random::<[u8; 32]>()),
+                            tbs::BlindingKey::random(),
+                        );
+                        (Amount::from_msats(*msats), BlindNonce(blind_msg))
+                    })
+                    .collect(),
+            )
+        };
+        let out_point = |out_idx| OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx,
+        };
+
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let mut dbtx = dbtx.with_module_prefix(42);
+
+        // Issuance below the cap succeeds
+        mint.process_output(&mut dbtx, &output(&[2048]), out_point(0))
+            .await
+            .expect("Issuance stays below the cap");
+
+        // Issuance up to exactly the cap of 4000 msats succeeds
+        mint.process_output(&mut dbtx, &output(&[1024, 512, 256, 128, 32]), out_point(1))
+            .await
+            .expect("Issuance reaches the cap exactly");
+
+        // Issuance beyond the cap fails
+        let ModuleError::Other(error) = mint
+            .process_output(&mut dbtx, &output(&[1]), out_point(2))
+            .await
+            .expect_err("Issuance beyond the cap is rejected");
+        assert_eq!(
+            error.downcast_ref::<MintError>(),
+            Some(&MintError::ExceededMaxEcashOutstanding(
+                Amount::from_msats(1),
+                Amount::from_sats(4),
+            ))
+        );
+        assert_eq!(
+            dbtx.get_value(&OutstandingEcashKey).await,
+            Some(Amount::from_sats(4))
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_denomination_usage_stats() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+
+        let blind_nonce = || {
+            BlindNonce(blind_message(
+                tbs::Message::from_bytes(&rand::random::<[u8; 32]>()),
+                tbs::BlindingKey::random(),
+            ))
+        };
+        let output = MintOutput(
+            vec![
+                (Amount::from_msats(1024), blind_nonce()),
+                (Amount::from_msats(1024), blind_nonce()),
+                (Amount::from_msats(2048), blind_nonce()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let out_point = OutPoint {
+            txid: TransactionId::all_zeros(),
+            out_idx: 0,
+        };
+
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let mut dbtx = dbtx.with_module_prefix(42);
+        mint.process_output(&mut dbtx, &output, out_point)
+            .await
+            .expect("Valid output");
+
+        let (_, note) = issue_note(&mint_server_cfg, Amount::from_msats(1024));
+        let input = MintInput(vec![(Amount::from_msats(1024), note)].into_iter().collect());
+        mint.process_input(&mut dbtx, &input)
+            .await
+            .expect("Spend of valid e-cash works");
+
+        assert_eq!(
+            mint.denomination_usage_stats(&mut dbtx).await,
+            BTreeMap::from([
+                (
+                    Amount::from_msats(1024),
+                    DenominationStats {
+                        issued_count: 2,
+                        redeemed_count: 1,
+                        outstanding_count: 1,
+                    }
+                ),
+                (
+                    Amount::from_msats(2048),
+                    DenominationStats {
+                        issued_count: 1,
+                        redeemed_count: 0,
+                        outstanding_count: 1,
+                    }
+                ),
+            ])
+        );
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_detect_double_spends() {
         let (mint_server_cfg, _) = build_configs();
@@ -891,6 +1350,27 @@ mod test {
             Err(_)
         );
     }
+
+    #[test_log::test(tokio::test)]
+    async fn test_reject_note_signed_for_other_denomination() {
+        let (mint_server_cfg, _) = build_configs();
+        let mint = Mint::new(mint_server_cfg[0].to_typed().unwrap());
+
+        // The note is signed for 1024 msats but claims to be worth 2048 msats
+        let (_, note) = issue_note(&mint_server_cfg, Amount::from_msats(1024));
+        let input = MintInput(vec![(Amount::from_msats(2048), note)].into_iter().collect());
+
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let mut dbtx = db.begin_transaction().await;
+        let ModuleError::Other(error) = mint
+            .process_input(&mut dbtx.with_module_prefix(42), &input)
+            .await
+            .expect_err("Note with mismatched denomination is rejected");
+        assert_eq!(
+            error.downcast_ref::<MintError>(),
+            Some(&MintError::InvalidSignature)
+        );
+    }
 }
 
 #[cfg(test)]
@@ -1103,6 +1583,9 @@ mod fedimint_migration_tests {
                                 "validate_migrations was not able to read any MintAuditItems"
                             );
                         }
+                        // Denomination usage and the outstanding e-cash total were introduced
+                        // after the v0 snapshot was taken
+                        DbKeyPrefix::DenominationUsage | DbKeyPrefix::OutstandingEcash => {}
                         DbKeyPrefix::EcashBackup => {
                             let backups = dbtx
                                 .find_by_prefix(&EcashBackupKeyPrefix)
@@ -1,20 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use fedimint_client::sm::OperationId;
+use fedimint_client::transaction::{ClientOutput, TransactionBuilder};
+use fedimint_client::Client;
+use fedimint_core::api::{EventType, GlobalFederationApi};
+use fedimint_core::core::{DynInput, IntoDynInstance};
+use fedimint_core::encoding::Encodable;
+use fedimint_core::task::{sleep, timeout, TaskGroup};
 use fedimint_core::util::NextOrPending;
 use fedimint_core::{sats, Amount};
 use fedimint_dummy_client::{DummyClientExt, DummyClientGen};
 use fedimint_dummy_common::config::DummyGenParams;
 use fedimint_dummy_server::DummyGen;
+use fedimint_logging::LOG_TEST;
 use fedimint_mint_client::{
-    MintClientExt, MintClientGen, OOBNotes, ReissueExternalNotesState, SpendOOBState,
+    MintClientExt, MintClientGen, MintClientModule, MintClientStateMachines, OOBNotes,
+    ReissueExternalNotesState, RestoreStrategy, SpendOOBState,
 };
-use fedimint_mint_common::config::MintGenParams;
+use fedimint_mint_common::config::{DenominationSet, MintConfig, MintGenParams};
+use fedimint_mint_common::{BlindNonce, MintInput, MintOutput};
 use fedimint_mint_server::MintGen;
+use fedimint_testing::federation::FederationTest;
 use fedimint_testing::fixtures::{Fixtures, TIMEOUT};
+use tracing::info;
 
 fn fixtures() -> Fixtures {
-    let fixtures = Fixtures::new_primary(MintClientGen, MintGen, MintGenParams::default());
+    let fixtures = Fixtures::new_primary(MintClientGen, MintGen, MintGenParams::default())
+        .with_input_spend_keys(fedimint_mint_common::KIND, mint_input_spend_keys);
     fixtures.with_module(DummyClientGen, DummyGen, DummyGenParams::default())
 }
 
+/// Spending a mint input consumes the nonces of its notes
+fn mint_input_spend_keys(input: &DynInput) -> Vec<Vec<u8>> {
+    input
+        .as_any()
+        .downcast_ref::<MintInput>()
+        .expect("Inputs of the mint are mint inputs")
+        .iter_items()
+        .map(|(_, note)| {
+            note.nonce
+                .consensus_encode_to_vec()
+                .expect("Writing to a vector can't fail")
+        })
+        .collect()
+}
+
+/// Waits for the federation to complete `epoch_count` epochs and measures
+/// how long a new client takes to scan the epoch history when restoring
+/// notes with each of the `strategies`, keyed by their debug name
+///
+/// Every strategy restores into a fresh client so no checkpoint is
+/// reused, the epochs completed while measuring are scanned as well.
+async fn compare_recovery_time(
+    fed: &FederationTest,
+    strategies: Vec<RestoreStrategy>,
+    epoch_count: usize,
+) -> BTreeMap<String, Duration> {
+    if let Some(last_epoch) = (epoch_count as u64).checked_sub(1) {
+        for peer_id in fed.configs().keys() {
+            fed.consensus_api(*peer_id)
+                .await_signed_block(last_epoch)
+                .await;
+        }
+    }
+
+    let mut recovery_times = BTreeMap::new();
+    for strategy in strategies {
+        let client = fed.new_client().await;
+
+        let start = Instant::now();
+        client
+            .restore_from_raw_nonces_with_strategy(vec![], strategy)
+            .await
+            .expect("Failed to restore from the epoch history");
+        let recovery_time = start.elapsed();
+
+        info!(target: LOG_TEST, ?strategy, ?recovery_time, "Restored from the epoch history");
+        recovery_times.insert(format!("{strategy:?}"), recovery_time);
+    }
+
+    recovery_times
+}
+
+/// Asserts that every peer's mint module holds a secret key share for
+/// each of the `expected_denominations` and that the corresponding public
+/// key share known to all peers matches it
+fn assert_mint_key_coverage(fed: &FederationTest, expected_denominations: &[Amount]) {
+    for (peer_id, config) in fed.configs() {
+        let instance_id = config
+            .get_module_id_by_kind(fedimint_mint_common::KIND)
+            .expect("Federation has no mint module");
+        let mint_cfg: MintConfig = config
+            .get_module_config_typed(instance_id)
+            .expect("Invalid mint module config");
+
+        for amount in expected_denominations {
+            let sk = mint_cfg
+                .private
+                .tbs_sks
+                .get(*amount)
+                .unwrap_or_else(|| panic!("Peer {peer_id} has no secret key for {amount}"));
+
+            for (other_peer, pks) in &mint_cfg.consensus.peer_tbs_pks {
+                let pk = pks.get(*amount).unwrap_or_else(|| {
+                    panic!("Peer {peer_id} has no public key of {other_peer} for {amount}")
+                });
+
+                if other_peer == peer_id {
+                    assert_eq!(
+                        sk.to_pub_key_share(),
+                        *pk,
+                        "Peer {peer_id} has a mismatched keypair for {amount}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Asserts that the e-cash issued minus the e-cash redeemed according to
+/// the mint's audit equals the total balance of `clients`, so every client
+/// that may hold notes needs to be passed once all operations settled
+async fn assert_mint_module_balanced(fed: &FederationTest, clients: &[&Client]) {
+    let outstanding = -fed
+        .audit()
+        .await
+        .module_summaries
+        .values()
+        .filter(|summary| summary.kind == fedimint_mint_common::KIND.as_str())
+        .map(|summary| summary.net_assets)
+        .sum::<i64>();
+
+    let mut balances = Amount::ZERO;
+    for client in clients {
+        balances += client.get_balance().await;
+    }
+
+    assert_eq!(
+        outstanding, balances.msats as i64,
+        "Outstanding e-cash doesn't match the client balances"
+    );
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
     // Print notes for client1
@@ -38,6 +167,218 @@ async fn sends_ecash_out_of_band() -> anyhow::Result<()> {
 
     assert_eq!(client1.get_balance().await, sats(250));
     assert_eq!(client2.get_balance().await, sats(750));
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn offline_notes_are_refunded_after_their_deadline() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    // The merchant redeems the note before its deadline
+    let (_, offline_note) = client1.request_offline_note(sats(750), 10, ()).await?;
+    let op = client2.redeem_offline_note(offline_note, ()).await?;
+    let sub = &mut client2
+        .subscribe_reissue_external_notes(op)
+        .await?
+        .into_stream();
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Done);
+    assert_eq!(client2.get_balance().await, sats(750));
+
+    // A note past its deadline is rejected and refunded to the sender
+    let (op, offline_note) = client1.request_offline_note(sats(250), 1, ()).await?;
+    assert_eq!(client1.get_balance().await, sats(0));
+    client2
+        .api()
+        .await_block(offline_note.deadline_epoch, client2.decoders())
+        .await?;
+    assert!(client2.redeem_offline_note(offline_note, ()).await.is_err());
+    let sub = &mut client1.subscribe_spend_notes(op).await?.into_stream();
+    assert_eq!(sub.ok().await?, SpendOOBState::Created);
+    assert_eq!(sub.ok().await?, SpendOOBState::Refunded);
+    assert_eq!(client1.get_balance().await, sats(250));
+    assert_eq!(client2.get_balance().await, sats(750));
+
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn finds_epoch_notes_were_issued_in() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    // Let two epochs pass so the notes are issued in epoch 2 or later
+    client1.api().await_block(1, client1.decoders()).await?;
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let issuance_epoch = client1
+        .get_federation_events(0, &[EventType::NoteIssuance])
+        .await?
+        .into_iter()
+        .find(|event| event.txid == outpoint.txid)
+        .expect("Notes were issued")
+        .epoch;
+    assert!(issuance_epoch >= 2);
+
+    let (spend_op, notes) = client1.spend_notes(sats(1000), TIMEOUT, ()).await?;
+    for (_, note) in notes.notes.iter_items() {
+        assert_eq!(client1.get_note_issuance_epoch(note).await?, issuance_epoch);
+    }
+
+    // Only the client that requested the notes can find them
+    let (_, note) = notes.notes.iter_items().next().expect("Has notes");
+    assert!(client2.get_note_issuance_epoch(note).await.is_err());
+
+    let sub = &mut client1.subscribe_spend_notes(spend_op).await?.into_stream();
+    assert_eq!(sub.ok().await?, SpendOOBState::Created);
+    client1.try_cancel_spend_notes(spend_op).await;
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledProcessing);
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledSuccess);
+    assert_eq!(client1.get_balance().await, sats(1000));
+
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn note_creation_proof_is_verified_against_epoch_header() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    let (spend_op, notes) = client1.spend_notes(sats(1000), TIMEOUT, ()).await?;
+    let (_, note) = notes.notes.iter_items().next().expect("Has notes");
+    let proof = client1.get_note_creation_proof(note).await?;
+    assert_eq!(proof.epoch, client1.get_note_issuance_epoch(note).await?);
+    assert!(proof.verify());
+
+    // A third party compares the merkle root to the header of the epoch
+    let block = client2
+        .api()
+        .await_block(proof.epoch, client2.decoders())
+        .await?;
+    assert_eq!(&block.header(proof.epoch)[8..], &proof.merkle_root[..]);
+
+    let mut wrong_root = proof.clone();
+    wrong_root.merkle_root[0] ^= 1;
+    assert!(!wrong_root.verify());
+
+    // The output can't be passed off as another output of the transaction
+    let mut wrong_output = proof.clone();
+    wrong_output.out_idx ^= 1;
+    assert!(!wrong_output.verify());
+
+    let sub = &mut client1.subscribe_spend_notes(spend_op).await?.into_stream();
+    assert_eq!(sub.ok().await?, SpendOOBState::Created);
+    client1.try_cancel_spend_notes(spend_op).await;
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledProcessing);
+    assert_eq!(sub.ok().await?, SpendOOBState::UserCanceledSuccess);
+
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retried_reissue_receives_notes_once() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+    let (_, notes) = client1.spend_notes(sats(750), TIMEOUT, ()).await?;
+
+    // The first call reaches the federation, but its response never reaches the
+    // caller
+    let _lost_response = client2.reissue_external_notes(notes.clone(), ()).await;
+
+    // The caller retries, even twice concurrently, without learning the result
+    let (op, retried_op) = tokio::join!(
+        client2.reissue_external_notes(notes.clone(), ()),
+        client2.reissue_external_notes(notes, ())
+    );
+    let op = op?;
+    assert_eq!(op, retried_op?);
+    // only the first call created an operation
+    assert_eq!(
+        client2
+            .operation_log()
+            .list_operations(10, None)
+            .await
+            .len(),
+        1
+    );
+
+    let sub = client2.subscribe_reissue_external_notes(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Done);
+    assert_eq!(client2.get_balance().await, sats(750));
+
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sends_ecash_with_custom_denominations() -> anyhow::Result<()> {
+    let denominations = [1, 10, 100]
+        .into_iter()
+        .map(Amount::from_msats)
+        .chain([1, 5, 10, 50, 100, 500].into_iter().map(Amount::from_sats))
+        .collect::<BTreeSet<_>>();
+    let mut params = MintGenParams::default();
+    params.consensus = params
+        .consensus
+        .with_denominations(DenominationSet::new(denominations.clone())?);
+    let fixtures = Fixtures::new_primary(MintClientGen, MintGen, params)
+        .with_input_spend_keys(fedimint_mint_common::KIND, mint_input_spend_keys)
+        .with_module(DummyClientGen, DummyGen, DummyGenParams::default());
+
+    // Print notes for client1
+    let fed = fixtures.new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+
+    // Spend from client1 to client2
+    let (op, notes) = client1.spend_notes(sats(750), TIMEOUT, ()).await?;
+    let sub1 = &mut client1.subscribe_spend_notes(op).await?.into_stream();
+    assert_eq!(sub1.ok().await?, SpendOOBState::Created);
+    assert!(notes
+        .notes
+        .iter_items()
+        .all(|(amount, _)| denominations.contains(&amount)));
+
+    let op = client2.reissue_external_notes(notes, ()).await?;
+    let sub2 = client2.subscribe_reissue_external_notes(op).await?;
+    let mut sub2 = sub2.into_stream();
+    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(sub2.ok().await?, ReissueExternalNotesState::Done);
+    assert_eq!(sub1.ok().await?, SpendOOBState::Success);
+
+    assert_eq!(client1.get_balance().await, sats(250));
+    assert_eq!(client2.get_balance().await, sats(750));
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -57,6 +398,9 @@ async fn error_zero_value_oob_spend() -> anyhow::Result<()> {
         .to_string();
     assert!(err_msg.contains("zero-amount"));
 
+    assert_mint_module_balanced(&fed, &[&client1]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
@@ -82,5 +426,253 @@ async fn error_zero_value_oob_receive() -> anyhow::Result<()> {
         .to_string();
     assert!(err_msg.contains("zero-amount"));
 
+    assert_mint_module_balanced(&fed, &[&client1]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restores_unspent_notes_from_raw_nonces() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let (client1, client2) = fed.two_clients().await;
+
+    // Notes that are lost after being sent out of band but never reissued
+    let (op, outpoint) = client1.print_money(sats(1000)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+    let (_, lost_notes) = client1
+        .spend_notes(sats(1000), Duration::from_secs(3600), ())
+        .await?;
+
+    // Notes that were reissued by the recipient can't be recovered
+    let (op, outpoint) = client1.print_money(sats(500)).await?;
+    client1.await_primary_module_output(op, outpoint).await?;
+    let (_, spent_notes) = client1.spend_notes(sats(500), TIMEOUT, ()).await?;
+    let op = client2
+        .reissue_external_notes(spent_notes.clone(), ())
+        .await?;
+    let sub = client2.subscribe_reissue_external_notes(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Created);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Issuing);
+    assert_eq!(sub.ok().await?, ReissueExternalNotesState::Done);
+    assert_eq!(client1.get_balance().await, sats(0));
+
+    let nonces = lost_notes
+        .notes
+        .iter_items()
+        .chain(spent_notes.notes.iter_items())
+        .map(|(_, note)| note.nonce())
+        .collect();
+    assert_eq!(client1.restore_from_raw_nonces(nonces).await?, sats(1000));
+    assert_eq!(client1.get_balance().await, sats(1000));
+
+    assert_mint_module_balanced(&fed, &[&client1, &client2]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn raw_nonce_restore_resumes_from_checkpoint() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (op, outpoint) = client.print_money(sats(1000)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+    let (_, lost_notes) = client
+        .spend_notes(sats(1000), Duration::from_secs(3600), ())
+        .await?;
+    let nonces = lost_notes
+        .notes
+        .iter_items()
+        .map(|(_, note)| note.nonce())
+        .collect();
+    assert_eq!(client.restore_from_raw_nonces(nonces).await?, sats(1000));
+
+    // The second restore only scans the epochs after the checkpoint of the first
+    let (op, outpoint) = client.print_money(sats(500)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+    let (_, lost_notes) = client
+        .spend_notes(sats(500), Duration::from_secs(3600), ())
+        .await?;
+    let nonces = lost_notes
+        .notes
+        .iter_items()
+        .map(|(_, note)| note.nonce())
+        .collect();
+    assert_eq!(client.restore_from_raw_nonces(nonces).await?, sats(500));
+    assert_eq!(client.get_balance().await, sats(1500));
+
+    assert_mint_module_balanced(&fed, &[&client]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn restore_strategies_recover_the_same_notes() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    let (op, outpoint) = client.print_money(sats(1000)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+    let (_, lost_notes) = client
+        .spend_notes(sats(1000), Duration::from_secs(3600), ())
+        .await?;
+    let nonces = lost_notes
+        .notes
+        .iter_items()
+        .map(|(_, note)| note.nonce())
+        .collect();
+    assert_eq!(
+        client
+            .restore_from_raw_nonces_with_strategy(nonces, RestoreStrategy::Concurrent(8))
+            .await?,
+        sats(1000)
+    );
+
+    let strategies = vec![
+        RestoreStrategy::Sequential,
+        RestoreStrategy::Concurrent(4),
+        RestoreStrategy::Concurrent(8),
+    ];
+    let recovery_times = compare_recovery_time(&fed, strategies, 10).await;
+    assert_eq!(
+        recovery_times.keys().collect::<Vec<_>>(),
+        vec!["Concurrent(4)", "Concurrent(8)", "Sequential"]
+    );
+
+    assert_mint_module_balanced(&fed, &[&client]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn module_audit_proof_covers_issued_ecash() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let (op, outpoint) = client.print_money(sats(1000)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+
+    let (_, instance) = client.get_first_module::<MintClientModule>(&fedimint_mint_common::KIND);
+    let proof = client.api().get_module_audit_proof(instance.id).await?;
+    proof.verify(&client.get_config().epoch_pk)?;
+    assert_eq!(proof.module_audit.module_instance_id, instance.id);
+    assert_ne!(proof.module_audit.merkle_root, [0; 32]);
+
+    // the proven balance matches the independently signed balance sheet
+    let signed = client.api().fetch_balance_sheet().await?;
+    signed.verify(&client.get_config().epoch_pk)?;
+    assert_eq!(
+        signed.balance_sheet.modules[&instance.id],
+        proof.module_audit.balance
+    );
+    assert!(proof.module_audit.balance.liabilities_msat >= sats(1000).msats);
+
+    let mut tampered = proof.clone();
+    tampered.module_audit.balance.liabilities_msat = 0;
+    assert!(tampered.verify(&client.get_config().epoch_pk).is_err());
+
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn all_default_denominations_have_keys() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let denominations = MintGenParams::default().consensus.gen_denominations();
+    assert_mint_key_coverage(&fed, &denominations);
+    assert_mint_module_balanced(&fed, &[]).await;
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_denominations_are_rejected_before_submission() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let (op, outpoint) = client.print_money(sats(1000)).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+
+    // The mint only issues notes in powers of two
+    let blind_nonce = BlindNonce(tbs::blind_message(
+        tbs::Message::from_bytes(b"invalid denomination"),
+        tbs::BlindingKey::random(),
+    ));
+    let (_mint, instance) =
+        client.get_first_module::<MintClientModule>(&fedimint_mint_common::KIND);
+    let output = ClientOutput {
+        output: MintOutput(
+            vec![(Amount::from_msats(3), blind_nonce)]
+                .into_iter()
+                .collect(),
+        ),
+        state_machines: Arc::new(move |_, _| Vec::<MintClientStateMachines>::new()),
+    };
+    let tx = TransactionBuilder::new().with_output(output.into_dyn(instance.id));
+
+    let operation_id = OperationId::new_random();
+    let err_msg = client
+        .finalize_and_submit_transaction(operation_id, "invalid-denomination", |_, _| (), tx)
+        .await
+        .expect_err("Invalid denominations should be rejected")
+        .to_string();
+    assert!(err_msg.contains("invalid amount"));
+
+    // Nothing was submitted so no notes were spent
+    assert!(client
+        .operation_log()
+        .get_operation(operation_id)
+        .await
+        .is_none());
+    assert_eq!(client.get_balance().await, sats(1000));
+    assert_mint_module_balanced(&fed, &[&client]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consolidates_notes_in_background() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    // Every print creates a single 1 msat change note
+    let mut outputs = vec![];
+    for _ in 0..50 {
+        outputs.push(client.print_money(Amount::from_msats(1)).await?);
+    }
+    for (op, outpoint) in outputs {
+        client.await_primary_module_output(op, outpoint).await?;
+    }
+    assert_eq!(note_count(&client).await, 50);
+
+    let mut task_group = TaskGroup::new();
+    client
+        .set_auto_consolidation_threshold(10, &mut task_group)
+        .await;
+    timeout(TIMEOUT, async {
+        while note_count(&client).await >= 10 || !client.get_active_operations().await.is_empty() {
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await?;
+    task_group.shutdown_join_all(None).await?;
+
+    assert_eq!(client.get_balance().await, Amount::from_msats(50));
+    assert_mint_module_balanced(&fed, &[&client]).await;
+    fed.assert_guardians_have_identical_balancesheets().await;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+async fn note_count(client: &Client) -> usize {
+    let (mint, instance) = client.get_first_module::<MintClientModule>(&fedimint_mint_common::KIND);
+    let mut dbtx = client.db().begin_transaction().await;
+    mint.get_wallet_summary(&mut dbtx.with_module_prefix(instance.id))
+        .await
+        .count_items()
+}
@@ -1,10 +1,20 @@
+use std::collections::BTreeMap;
+
 use bitcoin::Address;
 use fedimint_core::api::{FederationApiExt, FederationResult, IModuleFederationApi};
-use fedimint_core::endpoint_constants::{BLOCK_COUNT_ENDPOINT, PEG_OUT_FEES_ENDPOINT};
+use fedimint_core::endpoint_constants::{
+    ADDRESS_PROOF_SIGNATURE_ENDPOINT, BLOCK_COUNT_ENDPOINT, COMPACT_FILTER_ENDPOINT,
+    FEE_RATE_VOTE_HISTORY_ENDPOINT, PEG_OUT_FEES_ENDPOINT, PENDING_PEG_OUTS_ENDPOINT,
+    RBF_CAPABLE_PEG_OUTS_ENDPOINT, WALLET_CONFIG_ENDPOINT,
+};
 use fedimint_core::module::ApiRequestErased;
+use fedimint_core::query::UnionResponsesSingle;
 use fedimint_core::task::{MaybeSend, MaybeSync};
-use fedimint_core::{apply, async_trait_maybe_send};
-use fedimint_wallet_common::PegOutFees;
+use fedimint_core::{apply, async_trait_maybe_send, Feerate, NumPeers, PeerId};
+use fedimint_wallet_common::address_proof::AddressProofSignature;
+use fedimint_wallet_common::compact_filter::CompactFilter;
+use fedimint_wallet_common::config::WalletClientConfig;
+use fedimint_wallet_common::{FeeTarget, PegOutFees, PendingPegOut, RbfCapablePegOut};
 
 #[apply(async_trait_maybe_send!)]
 pub trait WalletFederationApi {
@@ -13,7 +23,32 @@ pub trait WalletFederationApi {
         &self,
         address: &Address,
         amount: bitcoin::Amount,
+        target: Option<FeeTarget>,
     ) -> FederationResult<Option<PegOutFees>>;
+    /// Collects the signatures of the guardians over the peg-in descriptor
+    /// tweaked with `tweak`
+    async fn fetch_address_proof_signatures(
+        &self,
+        tweak: &secp256k1::XOnlyPublicKey,
+    ) -> FederationResult<Vec<AddressProofSignature>>;
+    /// Fetches the network, finality delay and fees of the wallet module the
+    /// guardians agree on, so peg-in parameters can be validated against them
+    async fn get_wallet_module_config(&self) -> FederationResult<WalletClientConfig>;
+    /// Lists the pending peg-outs whose fees can still be bumped with RBF
+    async fn get_rbf_capable_peg_outs(&self) -> FederationResult<Vec<RbfCapablePegOut>>;
+    /// Lists the peg-outs the guardians signed that did not confirm on-chain
+    /// yet, e.g. to show them as pending in a wallet or gateway UI
+    async fn get_pending_peg_outs(&self) -> FederationResult<Vec<PendingPegOut>>;
+    /// Fetches the compact filter of the Bitcoin block at `block_height`, only
+    /// blocks the guardians agreed on are served
+    async fn get_compact_filter(&self, block_height: u64) -> FederationResult<CompactFilter>;
+    /// Fetches the fee rate each guardian voted for at the end of each of the
+    /// last `epochs` sessions, oldest first. The federation uses the median
+    /// of the votes.
+    async fn fee_rate_vote_history(
+        &self,
+        epochs: usize,
+    ) -> FederationResult<Vec<BTreeMap<PeerId, Feerate>>>;
 }
 
 #[apply(async_trait_maybe_send!)]
@@ -33,10 +68,66 @@ where
         &self,
         address: &Address,
         amount: bitcoin::Amount,
+        target: Option<FeeTarget>,
     ) -> FederationResult<Option<PegOutFees>> {
         self.request_current_consensus(
             PEG_OUT_FEES_ENDPOINT.to_string(),
-            ApiRequestErased::new((address, amount.to_sat())),
+            ApiRequestErased::new((address, amount.to_sat(), target)),
+        )
+        .await
+    }
+
+    async fn fetch_address_proof_signatures(
+        &self,
+        tweak: &secp256k1::XOnlyPublicKey,
+    ) -> FederationResult<Vec<AddressProofSignature>> {
+        self.request_with_strategy(
+            UnionResponsesSingle::<AddressProofSignature>::new(self.all_peers().total()),
+            ADDRESS_PROOF_SIGNATURE_ENDPOINT.to_string(),
+            ApiRequestErased::new(tweak),
+        )
+        .await
+    }
+
+    async fn get_wallet_module_config(&self) -> FederationResult<WalletClientConfig> {
+        self.request_current_consensus(
+            WALLET_CONFIG_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn get_rbf_capable_peg_outs(&self) -> FederationResult<Vec<RbfCapablePegOut>> {
+        self.request_current_consensus(
+            RBF_CAPABLE_PEG_OUTS_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn get_pending_peg_outs(&self) -> FederationResult<Vec<PendingPegOut>> {
+        self.request_current_consensus(
+            PENDING_PEG_OUTS_ENDPOINT.to_string(),
+            ApiRequestErased::default(),
+        )
+        .await
+    }
+
+    async fn get_compact_filter(&self, block_height: u64) -> FederationResult<CompactFilter> {
+        self.request_current_consensus(
+            COMPACT_FILTER_ENDPOINT.to_string(),
+            ApiRequestErased::new(block_height),
+        )
+        .await
+    }
+
+    async fn fee_rate_vote_history(
+        &self,
+        epochs: usize,
+    ) -> FederationResult<Vec<BTreeMap<PeerId, Feerate>>> {
+        self.request_current_consensus(
+            FEE_RATE_VOTE_HISTORY_ENDPOINT.to_string(),
+            ApiRequestErased::new(epochs),
         )
         .await
     }
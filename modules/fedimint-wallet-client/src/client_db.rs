@@ -1,11 +1,14 @@
 use fedimint_core::encoding::{Decodable, Encodable};
-use fedimint_core::impl_db_record;
+use fedimint_core::{impl_db_lookup, impl_db_record};
 use serde::Serialize;
 use strum_macros::EnumIter;
 
+use crate::{RecurringPegOut, RecurringPegOutId};
+
 #[derive(Clone, EnumIter, Debug)]
 pub enum DbKeyPrefix {
     NextPegInTweakIndex = 0x2c,
+    RecurringPegOut = 0x2e,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -22,3 +25,19 @@ impl_db_record!(
     value = u64,
     db_prefix = DbKeyPrefix::NextPegInTweakIndex,
 );
+
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub(crate) struct RecurringPegOutKey(pub RecurringPegOutId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub(crate) struct RecurringPegOutKeyPrefix;
+
+impl_db_record!(
+    key = RecurringPegOutKey,
+    value = RecurringPegOut,
+    db_prefix = DbKeyPrefix::RecurringPegOut,
+);
+impl_db_lookup!(
+    key = RecurringPegOutKey,
+    query_prefix = RecurringPegOutKeyPrefix
+);
@@ -6,7 +6,7 @@ mod withdraw;
 
 use std::collections::BTreeMap;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, bail, ensure, Context as AnyhowContext};
 use async_stream::stream;
@@ -19,9 +19,9 @@ use fedimint_client::module::{ClientModule, IClientModule};
 use fedimint_client::oplog::UpdateStreamOrOutcome;
 use fedimint_client::sm::util::MapStateTransitions;
 use fedimint_client::sm::{Context, DynState, ModuleNotifier, OperationId, State, StateTransition};
-use fedimint_client::transaction::{ClientOutput, TransactionBuilder};
+use fedimint_client::transaction::{ClientInput, ClientOutput, TransactionBuilder};
 use fedimint_client::{sm_enum_variant_translation, Client, DynGlobalClientContext};
-use fedimint_core::api::DynModuleApi;
+use fedimint_core::api::{DynModuleApi, EventType, GlobalFederationApi};
 use fedimint_core::bitcoinrpc::BitcoinRpcConfig;
 use fedimint_core::core::{Decoder, IntoDynInstance, ModuleInstanceId};
 use fedimint_core::db::{AutocommitError, ModuleDatabaseTransaction};
@@ -30,25 +30,41 @@ use fedimint_core::module::{
     ApiVersion, CommonModuleInit, ExtendsCommonModuleInit, ModuleCommon, MultiApiVersion,
     TransactionItemAmount,
 };
-use fedimint_core::task::TaskGroup;
-use fedimint_core::{apply, async_trait_maybe_send, Amount, OutPoint};
+use fedimint_core::task::{sleep, TaskGroup};
+use fedimint_core::{apply, async_trait_maybe_send, push_db_pair_items, Amount, OutPoint};
+use fedimint_wallet_common::address_proof::AddressProof;
 use fedimint_wallet_common::config::WalletClientConfig;
 use fedimint_wallet_common::tweakable::Tweakable;
+use fedimint_wallet_common::txoproof::PegInProof;
 pub use fedimint_wallet_common::*;
 use futures::{Stream, StreamExt};
-use miniscript::ToPublicKey;
+use miniscript::descriptor::WshInner;
+use miniscript::{Descriptor, ToPublicKey};
 use rand::{thread_rng, Rng};
-use secp256k1::{All, Secp256k1};
+use secp256k1::{All, KeyPair, Secp256k1};
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
+use tracing::{info, warn};
 
 use crate::api::WalletFederationApi;
-use crate::client_db::NextPegInTweakIndexKey;
+use crate::client_db::{NextPegInTweakIndexKey, RecurringPegOutKey, RecurringPegOutKeyPrefix};
 use crate::deposit::{CreatedDepositState, DepositStateMachine, DepositStates};
 use crate::withdraw::{CreatedWithdrawState, WithdrawStateMachine, WithdrawStates};
 
 const WALLET_TWEAK_CHILD_ID: ChildId = ChildId(0);
 
+/// Expected time between two bitcoin blocks, the target of the difficulty
+/// adjustment. Used if the recent blocks don't tell us anything better.
+const BLOCK_INTERVAL_TARGET: Duration = Duration::from_secs(10 * 60);
+
+/// Number of recent blocks whose timestamps we average over to estimate the
+/// block interval, about one day worth of blocks
+const BLOCK_INTERVAL_SAMPLE_BLOCKS: u64 = 144;
+
+/// How often a recurring peg-out checks whether it is due or retries a failed
+/// peg-out, see [`WalletClientExt::create_reoccurring_peg_out`]
+const RECURRING_PEG_OUT_INTERVAL: Duration = Duration::from_secs(1);
+
 #[apply(async_trait_maybe_send!)]
 pub trait WalletClientExt {
     async fn get_deposit_address(
@@ -61,6 +77,38 @@ pub trait WalletClientExt {
         operation_id: OperationId,
     ) -> anyhow::Result<UpdateStreamOrOutcome<DepositState>>;
 
+    /// Creates a proof that a deposit `address` previously generated by this
+    /// client using [`WalletClientExt::get_deposit_address`] belongs to the
+    /// federation, which can be handed to third parties such as auditors.
+    async fn get_address_proof(&self, address: &Address) -> anyhow::Result<AddressProof>;
+
+    /// Checks that `address` is derived from the federation's peg-in
+    /// descriptor before bitcoin is sent to it.
+    ///
+    /// Peg-in addresses are tweaked with keys of the client that generated
+    /// them, so only addresses generated by this client using
+    /// [`WalletClientExt::get_deposit_address`] can be verified.
+    async fn verify_peg_in_address(&self, address: &Address)
+        -> anyhow::Result<AddressVerification>;
+
+    /// Claims several peg-ins in a single federation transaction instead of one
+    /// transaction per deposit, returning the outpoint of the e-cash issued
+    /// for them.
+    ///
+    /// Each peg-in is given as the key pair that tweaked its address (see
+    /// [`WalletClientModule::peg_in_address`]) and the bitcoin transaction
+    /// paying to it, which has to be final for the federation already. Nothing
+    /// is submitted if any of the peg-ins is invalid.
+    async fn submit_peg_in_batch(
+        &self,
+        peg_ins: Vec<(KeyPair, bitcoin::Transaction)>,
+    ) -> anyhow::Result<(OperationId, OutPoint)>;
+
+    /// Estimates how long it will take until a peg-in transaction with the
+    /// given number of `confirmations` is final for the federation and can be
+    /// claimed as ecash. Returns [`Duration::ZERO`] if it already is.
+    async fn estimate_peg_in_finality_time(&self, confirmations: u32) -> anyhow::Result<Duration>;
+
     /// Fetches the fees that would need to be paid to make the withdraw request
     /// using [`WalletClientExt::withdraw`] work *right now*.
     ///
@@ -73,6 +121,19 @@ pub trait WalletClientExt {
         amount: bitcoin::Amount,
     ) -> anyhow::Result<PegOutFees>;
 
+    /// Like [`WalletClientExt::get_withdraw_fee`] but quotes a fee rate that is
+    /// estimated to confirm within `target`.
+    ///
+    /// The quote is never below the fee rate returned by
+    /// [`WalletClientExt::get_withdraw_fee`] since that is the minimum the
+    /// federation accepts.
+    async fn get_withdraw_fee_for_target(
+        &self,
+        address: bitcoin::Address,
+        amount: bitcoin::Amount,
+        target: FeeTarget,
+    ) -> anyhow::Result<PegOutFees>;
+
     /// Attempt to withdraw a given `amount` of Bitcoin to a destination
     /// `address`. The caller has to supply the fee rate to be used which can be
     /// fetched using [`WalletClientExt::get_withdraw_fee`] and should be
@@ -84,16 +145,89 @@ pub trait WalletClientExt {
         fee: PegOutFees,
     ) -> anyhow::Result<OperationId>;
 
+    /// Withdraws to several addresses in a single transaction at the fees
+    /// quoted by [`WalletClientExt::get_withdraw_fee`] for each of them. Since
+    /// the federation batches the peg-outs of a session they are paid by the
+    /// same on-chain transaction, whose id is the outcome of each of the
+    /// returned out points.
+    async fn split_peg_out(
+        &self,
+        payments: Vec<(bitcoin::Amount, bitcoin::Address)>,
+    ) -> anyhow::Result<Vec<OutPoint>>;
+
     /// Attempt to increase the fee of a onchain withdraw transaction using
     /// replace by fee (RBF).
     /// This can prevent transactions from getting stuck
     /// in the mempool
     async fn rbf_withdraw(&self, rbf: Rbf) -> anyhow::Result<OperationId>;
 
+    /// Attempt to increase the fee of a onchain withdraw transaction using
+    /// child pays for parent (CPFP), spending its change in a child
+    /// transaction that pays for both.
+    /// Unlike RBF this leaves the original transaction untouched
+    async fn cpfp_withdraw(&self, cpfp: Cpfp) -> anyhow::Result<OperationId>;
+
     async fn subscribe_withdraw_updates(
         &self,
         operation_id: OperationId,
     ) -> anyhow::Result<UpdateStreamOrOutcome<WithdrawState>>;
+
+    /// Returns up to `limit` of our most recent successful withdrawals, newest
+    /// first.
+    ///
+    /// Only withdrawals whose outcome was cached in the operation log are
+    /// included, i.e. the ones for which the update stream of
+    /// [`WalletClientExt::subscribe_withdraw_updates`] ran to completion.
+    async fn get_peg_out_history(&self, limit: usize) -> Vec<PegOutRecord>;
+
+    /// Withdraws `amount` to `address` each time another `every_n_epochs`
+    /// epochs were completed, until
+    /// [`WalletClientExt::cancel_reoccurring_peg_out`] is called.
+    ///
+    /// Since only the client can spend its e-cash the peg-outs are submitted
+    /// by a task spawned on `task_group` at the fees quoted by
+    /// [`WalletClientExt::get_withdraw_fee`], shutting down `task_group` stops
+    /// them. A peg-out that fails, e.g. because the change of the previous one
+    /// isn't confirmed yet, is retried until it succeeds and the next one is
+    /// due `every_n_epochs` epochs after it.
+    async fn create_reoccurring_peg_out(
+        &self,
+        amount: bitcoin::Amount,
+        address: bitcoin::Address,
+        every_n_epochs: u64,
+        task_group: &mut TaskGroup,
+    ) -> anyhow::Result<RecurringPegOutId>;
+
+    /// Stops the recurring peg-out `id`, peg-outs that were already submitted
+    /// are not affected
+    async fn cancel_reoccurring_peg_out(&self, id: RecurringPegOutId) -> anyhow::Result<()>;
+}
+
+/// Identifies a [`RecurringPegOut`]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    Eq,
+    PartialEq,
+    Hash,
+    Ord,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    Encodable,
+    Decodable,
+)]
+pub struct RecurringPegOutId(pub [u8; 32]);
+
+/// Peg-out that is repeated every `every_n_epochs` epochs, see
+/// [`WalletClientExt::create_reoccurring_peg_out`]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct RecurringPegOut {
+    pub recipient: bitcoin::Address,
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub amount: bitcoin::Amount,
+    pub every_n_epochs: u64,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -124,6 +258,30 @@ pub enum WithdrawState {
     // RefundFailed(String),
 }
 
+/// Outcome of [`WalletClientExt::verify_peg_in_address`]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum AddressVerification {
+    /// The address is controlled by the federation
+    Verified,
+    /// The address is not one of the federation's peg-in addresses generated
+    /// by this client, bitcoin sent to it can't be claimed
+    NotFederationAddress,
+}
+
+/// A completed withdrawal, see [`WalletClientExt::get_peg_out_history`]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PegOutRecord {
+    pub operation_id: OperationId,
+    pub txid: bitcoin::Txid,
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub amount: bitcoin::Amount,
+    pub address: Address,
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub fee: bitcoin::Amount,
+    /// When the withdrawal was started
+    pub timestamp: SystemTime,
+}
+
 #[apply(async_trait_maybe_send!)]
 impl WalletClientExt for Client {
     async fn get_deposit_address(
@@ -263,6 +421,80 @@ impl WalletClientExt for Client {
         )
     }
 
+    async fn get_address_proof(&self, address: &Address) -> anyhow::Result<AddressProof> {
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let mut dbtx = self.db().begin_transaction().await;
+        wallet_client
+            .get_address_proof(address, &mut dbtx.with_module_prefix(instance.id))
+            .await
+    }
+
+    async fn verify_peg_in_address(
+        &self,
+        address: &Address,
+    ) -> anyhow::Result<AddressVerification> {
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let mut dbtx = self.db().begin_transaction().await;
+        let tweak = wallet_client
+            .find_peg_in_tweak(address, &mut dbtx.with_module_prefix(instance.id))
+            .await;
+
+        Ok(match tweak {
+            Some(_) => AddressVerification::Verified,
+            None => AddressVerification::NotFederationAddress,
+        })
+    }
+
+    async fn submit_peg_in_batch(
+        &self,
+        peg_ins: Vec<(KeyPair, bitcoin::Transaction)>,
+    ) -> anyhow::Result<(OperationId, OutPoint)> {
+        ensure!(!peg_ins.is_empty(), "No peg-ins to submit");
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let operation_id = OperationId(thread_rng().gen());
+
+        let mut tx_builder = TransactionBuilder::new();
+        let mut txids = vec![];
+        for (idx, (tweak_key, transaction)) in peg_ins.into_iter().enumerate() {
+            txids.push(transaction.txid());
+            let input = wallet_client
+                .create_peg_in_input(tweak_key, transaction)
+                .await
+                .with_context(|| format!("Peg-in {idx} is invalid"))?;
+            tx_builder = tx_builder.with_input(input.into_dyn(instance.id));
+        }
+
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                WalletCommonGen::KIND.as_str(),
+                move |_, change| WalletOperationMeta::PegInBatch {
+                    txids: txids.clone(),
+                    change,
+                },
+                tx_builder,
+            )
+            .await?;
+
+        // The primary module adds the e-cash for the peg-ins as the only output
+        Ok((operation_id, OutPoint { txid, out_idx: 0 }))
+    }
+
+    async fn estimate_peg_in_finality_time(&self, confirmations: u32) -> anyhow::Result<Duration> {
+        let (wallet_client, _) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        wallet_client
+            .estimate_peg_in_finality_time(confirmations)
+            .await
+    }
+
     async fn get_withdraw_fee(
         &self,
         address: Address,
@@ -271,7 +503,21 @@ impl WalletClientExt for Client {
         let (wallet_client, _) =
             self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
 
-        wallet_client.get_withdraw_fees(address, amount).await
+        wallet_client.get_withdraw_fees(address, amount, None).await
+    }
+
+    async fn get_withdraw_fee_for_target(
+        &self,
+        address: Address,
+        amount: bitcoin::Amount,
+        target: FeeTarget,
+    ) -> anyhow::Result<PegOutFees> {
+        let (wallet_client, _) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        wallet_client
+            .get_withdraw_fees(address, amount, Some(target))
+            .await
     }
 
     async fn withdraw(
@@ -307,6 +553,143 @@ impl WalletClientExt for Client {
         Ok(operation_id)
     }
 
+    async fn split_peg_out(
+        &self,
+        payments: Vec<(bitcoin::Amount, Address)>,
+    ) -> anyhow::Result<Vec<OutPoint>> {
+        ensure!(!payments.is_empty(), "No payments to withdraw");
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let operation_id = OperationId(thread_rng().gen());
+
+        let mut tx_builder = TransactionBuilder::new();
+        let mut peg_outs = vec![];
+        for (amount, address) in payments {
+            let fees = wallet_client
+                .get_withdraw_fees(address.clone(), amount, None)
+                .await?;
+            let withdraw_output = wallet_client
+                .create_withdraw_output(operation_id, address.clone(), amount, fees)
+                .await?;
+            tx_builder = tx_builder.with_output(withdraw_output.into_dyn(instance.id));
+            peg_outs.push(PegOut {
+                recipient: address,
+                amount,
+                fees,
+            });
+        }
+
+        // The change output is added after the peg-outs
+        let num_peg_outs = peg_outs.len() as u64;
+        let txid = self
+            .finalize_and_submit_transaction(
+                operation_id,
+                WalletCommonGen::KIND.as_str(),
+                move |_, change| WalletOperationMeta::SplitWithdraw {
+                    peg_outs: peg_outs.clone(),
+                    change,
+                },
+                tx_builder,
+            )
+            .await?;
+
+        Ok((0..num_peg_outs)
+            .map(|out_idx| OutPoint { txid, out_idx })
+            .collect())
+    }
+
+    async fn create_reoccurring_peg_out(
+        &self,
+        amount: bitcoin::Amount,
+        address: bitcoin::Address,
+        every_n_epochs: u64,
+        task_group: &mut TaskGroup,
+    ) -> anyhow::Result<RecurringPegOutId> {
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+        check_address(&address, wallet_client.cfg.network)?;
+        ensure!(
+            every_n_epochs > 0,
+            "Peg-outs have to be at least one epoch apart"
+        );
+
+        let id = RecurringPegOutId(thread_rng().gen());
+        let recurring = RecurringPegOut {
+            recipient: address,
+            amount,
+            every_n_epochs,
+        };
+
+        let mut dbtx = self.db().begin_transaction().await;
+        dbtx.with_module_prefix(instance.id)
+            .insert_new_entry(&RecurringPegOutKey(id), &recurring)
+            .await;
+        dbtx.commit_tx_result().await?;
+
+        let mut next_epoch = self.api().fetch_block_count().await? + every_n_epochs;
+        let client = self.clone();
+        task_group
+            .spawn("wallet recurring peg-out", move |handle| async move {
+                while !handle.is_shutting_down() {
+                    let is_scheduled = client
+                        .db()
+                        .begin_transaction()
+                        .await
+                        .with_module_prefix(instance.id)
+                        .get_value(&RecurringPegOutKey(id))
+                        .await
+                        .is_some();
+                    if !is_scheduled {
+                        break;
+                    }
+
+                    // If the federation can't be reached we simply check again
+                    let block_count = client.api().fetch_block_count().await.unwrap_or(0);
+                    if block_count < next_epoch {
+                        sleep(RECURRING_PEG_OUT_INTERVAL).await;
+                        continue;
+                    }
+
+                    let RecurringPegOut {
+                        recipient, amount, ..
+                    } = recurring.clone();
+                    let result = async {
+                        let fee = client.get_withdraw_fee(recipient.clone(), amount).await?;
+                        client.withdraw(recipient, amount, fee).await
+                    }
+                    .await;
+
+                    match result {
+                        Ok(operation_id) => {
+                            info!(?id, ?operation_id, "Submitted recurring peg-out");
+                            next_epoch = block_count + every_n_epochs;
+                        }
+                        Err(e) => {
+                            warn!(?id, "Recurring peg-out failed, retrying: {e:?}");
+                            sleep(RECURRING_PEG_OUT_INTERVAL).await;
+                        }
+                    }
+                }
+            })
+            .await;
+
+        Ok(id)
+    }
+
+    async fn cancel_reoccurring_peg_out(&self, id: RecurringPegOutId) -> anyhow::Result<()> {
+        let (_, instance) = self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let mut dbtx = self.db().begin_transaction().await;
+        dbtx.with_module_prefix(instance.id)
+            .remove_entry(&RecurringPegOutKey(id))
+            .await
+            .context("Unknown recurring peg-out")?;
+        dbtx.commit_tx_result().await?;
+
+        Ok(())
+    }
+
     async fn rbf_withdraw(&self, rbf: Rbf) -> anyhow::Result<OperationId> {
         let (wallet_client, instance) =
             self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
@@ -333,6 +716,78 @@ impl WalletClientExt for Client {
         Ok(operation_id)
     }
 
+    async fn cpfp_withdraw(&self, cpfp: Cpfp) -> anyhow::Result<OperationId> {
+        let (wallet_client, instance) =
+            self.get_first_module::<WalletClientModule>(&WalletCommonGen::KIND);
+
+        let operation_id = OperationId(thread_rng().gen());
+
+        let withdraw_output = wallet_client
+            .create_cpfp_withdraw_output(operation_id, cpfp.clone())
+            .await?;
+        let tx_builder =
+            TransactionBuilder::new().with_output(withdraw_output.into_dyn(instance.id));
+
+        self.finalize_and_submit_transaction(
+            operation_id,
+            WalletCommonGen::KIND.as_str(),
+            move |_, change| WalletOperationMeta::CpfpWithdraw {
+                cpfp: cpfp.clone(),
+                change,
+            },
+            tx_builder,
+        )
+        .await?;
+
+        Ok(operation_id)
+    }
+
+    async fn get_peg_out_history(&self, limit: usize) -> Vec<PegOutRecord> {
+        let mut history = Vec::new();
+        let mut start_after = None;
+
+        while history.len() < limit {
+            let page = self
+                .operation_log()
+                .list_operations(limit, start_after)
+                .await;
+            let Some((last_key, _)) = page.last() else {
+                break;
+            };
+            start_after = Some(*last_key);
+
+            for (key, entry) in page {
+                if entry.operation_module_kind() != WalletCommonGen::KIND.as_str() {
+                    continue;
+                }
+
+                let WalletOperationMeta::Withdraw {
+                    address,
+                    amount,
+                    fee,
+                    ..
+                } = entry.meta::<WalletOperationMeta>()
+                else {
+                    continue;
+                };
+
+                if let Some(WithdrawState::Succeeded(txid)) = entry.outcome::<WithdrawState>() {
+                    history.push(PegOutRecord {
+                        operation_id: key.operation_id,
+                        txid,
+                        amount,
+                        address,
+                        fee: fee.amount(),
+                        timestamp: key.creation_time,
+                    });
+                }
+            }
+        }
+
+        history.truncate(limit);
+        history
+    }
+
     async fn subscribe_withdraw_updates(
         &self,
         operation_id: OperationId,
@@ -353,7 +808,8 @@ impl WalletClientExt for Client {
         let operation_meta = operation.meta::<WalletOperationMeta>();
 
         let (WalletOperationMeta::Withdraw { change, .. }
-        | WalletOperationMeta::RbfWithdraw { change, .. }) = operation_meta
+        | WalletOperationMeta::RbfWithdraw { change, .. }
+        | WalletOperationMeta::CpfpWithdraw { change, .. }) = operation_meta
         else {
             bail!("Operation is not a withdraw operation");
         };
@@ -458,6 +914,16 @@ impl ExtendsCommonModuleInit for WalletClientGen {
                             .insert("NextPegInTweakIndex".to_string(), Box::new(index));
                     }
                 }
+                DbKeyPrefix::RecurringPegOut => {
+                    push_db_pair_items!(
+                        dbtx,
+                        RecurringPegOutKeyPrefix,
+                        RecurringPegOutKey,
+                        RecurringPegOut,
+                        wallet_client_items,
+                        "Recurring Peg-Outs"
+                    );
+                }
             }
         }
 
@@ -504,10 +970,25 @@ pub enum WalletOperationMeta {
         change: Option<OutPoint>,
     },
 
+    SplitWithdraw {
+        peg_outs: Vec<PegOut>,
+        change: Option<OutPoint>,
+    },
+
     RbfWithdraw {
         rbf: Rbf,
         change: Option<OutPoint>,
     },
+
+    CpfpWithdraw {
+        cpfp: Cpfp,
+        change: Option<OutPoint>,
+    },
+
+    PegInBatch {
+        txids: Vec<bitcoin::Txid>,
+        change: Option<OutPoint>,
+    },
 }
 
 #[derive(Debug)]
@@ -550,6 +1031,24 @@ impl ClientModule for WalletClientModule {
             fee: self.cfg.fee_consensus.peg_out_abs,
         }
     }
+
+    fn input_event_type(
+        &self,
+        _input: &<Self::Common as ModuleCommon>::Input,
+    ) -> Option<EventType> {
+        Some(EventType::PegIn)
+    }
+
+    fn output_event_type(
+        &self,
+        output: &<Self::Common as ModuleCommon>::Output,
+    ) -> Option<EventType> {
+        // RBF and CPFP outputs only bump the fees of existing peg-outs
+        match output {
+            WalletOutput::PegOut(_) => Some(EventType::PegOut),
+            WalletOutput::Rbf(_) | WalletOutput::Cpfp(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -581,26 +1080,69 @@ impl WalletClientModule {
         self.cfg.network
     }
 
+    /// The federation only considers blocks buried under `finality_delay`
+    /// blocks, so a transaction needs `finality_delay + 1` confirmations
+    /// before it can be claimed
+    pub async fn estimate_peg_in_finality_time(
+        &self,
+        confirmations: u32,
+    ) -> anyhow::Result<Duration> {
+        let missing_blocks = (self.cfg.finality_delay + 1).saturating_sub(confirmations);
+        if missing_blocks == 0 {
+            return Ok(Duration::ZERO);
+        }
+
+        Ok(self.estimate_block_interval().await? * missing_blocks)
+    }
+
+    /// Averages the time between the last [`BLOCK_INTERVAL_SAMPLE_BLOCKS`]
+    /// blocks according to their timestamps. Falls back to
+    /// [`BLOCK_INTERVAL_TARGET`] if the chain is too short or the timestamps
+    /// don't increase, e.g. on a test chain with fake timestamps.
+    pub async fn estimate_block_interval(&self) -> anyhow::Result<Duration> {
+        let block_count = self.rpc.get_block_count().await?;
+        let sample_blocks = BLOCK_INTERVAL_SAMPLE_BLOCKS.min(block_count.saturating_sub(1));
+        if sample_blocks == 0 {
+            return Ok(BLOCK_INTERVAL_TARGET);
+        }
+
+        let tip_time = self.block_time(block_count - 1).await?;
+        let first_time = self.block_time(block_count - 1 - sample_blocks).await?;
+        // block timestamps are only roughly ordered, so the span can be zero or
+        // even negative over short samples
+        let span = tip_time.saturating_sub(first_time);
+        if span == 0 {
+            return Ok(BLOCK_INTERVAL_TARGET);
+        }
+
+        let sample_blocks = u32::try_from(sample_blocks).expect("At most 144 blocks");
+        Ok(Duration::from_secs(u64::from(span)) / sample_blocks)
+    }
+
+    async fn block_time(&self, height: u64) -> anyhow::Result<u32> {
+        let block_hash = self.rpc.get_block_hash(height).await?;
+
+        Ok(self.rpc.get_block(&block_hash).await?.header.time)
+    }
+
+    fn peg_in_tweak_key(&self, child_id: ChildId) -> KeyPair {
+        self.module_root_secret
+            .child_key(WALLET_TWEAK_CHILD_ID)
+            .child_key(child_id)
+            .to_secp_key(&self.secp)
+    }
+
     pub async fn get_deposit_address(
         &self,
         valid_until: SystemTime,
         dbtx: &mut ModuleDatabaseTransaction<'_>,
     ) -> (OperationId, WalletClientStates, Address) {
-        let tweak_key = self
-            .module_root_secret
-            .child_key(WALLET_TWEAK_CHILD_ID)
-            .child_key(get_next_peg_in_tweak_child_id(dbtx).await)
-            .to_secp_key(&self.secp);
+        let tweak_key = self.peg_in_tweak_key(get_next_peg_in_tweak_child_id(dbtx).await);
 
         let x_only_pk = tweak_key.public_key().to_x_only_pubkey();
         let operation_id = OperationId(x_only_pk.serialize());
 
-        let address = self
-            .cfg
-            .peg_in_descriptor
-            .tweak(&x_only_pk, secp256k1::SECP256K1)
-            .address(self.cfg.network)
-            .unwrap();
+        let address = self.peg_in_address(&tweak_key);
 
         let deposit_sm = WalletClientStates::Deposit(DepositStateMachine {
             operation_id,
@@ -613,15 +1155,137 @@ impl WalletClientModule {
         (operation_id, deposit_sm, address)
     }
 
+    /// The address of the federation's peg-in descriptor tweaked with the
+    /// public key of `tweak_key`
+    pub fn peg_in_address(&self, tweak_key: &KeyPair) -> Address {
+        self.cfg
+            .peg_in_descriptor
+            .tweak(
+                &tweak_key.public_key().to_x_only_pubkey(),
+                secp256k1::SECP256K1,
+            )
+            .address(self.cfg.network)
+            .unwrap()
+    }
+
+    /// Creates an input claiming the output of `transaction` that pays to the
+    /// peg-in address of `tweak_key`
+    async fn create_peg_in_input(
+        &self,
+        tweak_key: KeyPair,
+        transaction: bitcoin::Transaction,
+    ) -> anyhow::Result<ClientInput<WalletInput, WalletClientStates>> {
+        let script_pubkey = self.peg_in_address(&tweak_key).script_pubkey();
+        let out_idx = transaction
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == script_pubkey)
+            .context("Transaction doesn't pay to the peg-in address of the key")?;
+
+        let txout_proof = self.rpc.get_txout_proof(transaction.txid()).await?;
+        let peg_in_proof = PegInProof::new(
+            txout_proof,
+            transaction,
+            out_idx as u32,
+            tweak_key.public_key().to_x_only_pubkey(),
+        )?;
+
+        Ok(ClientInput {
+            input: WalletInput(Box::new(peg_in_proof)),
+            keys: vec![tweak_key],
+            state_machines: Arc::new(|_, _| vec![]),
+        })
+    }
+
+    /// Finds the tweak this client derived the peg-in `address` with from the
+    /// federation's peg-in descriptor, along with the tweaked descriptor
+    async fn find_peg_in_tweak(
+        &self,
+        address: &Address,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> Option<(secp256k1::XOnlyPublicKey, PegInDescriptor)> {
+        let next_index = dbtx.get_value(&NextPegInTweakIndexKey).await.unwrap_or(0);
+        (0..next_index)
+            .map(|index| {
+                let tweak = self
+                    .peg_in_tweak_key(ChildId(index))
+                    .public_key()
+                    .to_x_only_pubkey();
+                let descriptor = self.cfg.peg_in_descriptor.tweak(&tweak, &self.secp);
+                (tweak, descriptor)
+            })
+            .find(|(_, descriptor)| {
+                descriptor
+                    .address(self.cfg.network)
+                    .map_or(false, |a| &a == address)
+            })
+    }
+
+    pub async fn get_address_proof(
+        &self,
+        address: &Address,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> anyhow::Result<AddressProof> {
+        let (tweak, descriptor) = self
+            .find_peg_in_tweak(address, dbtx)
+            .await
+            .context("Address wasn't generated by this client")?;
+
+        let threshold = match &self.cfg.peg_in_descriptor {
+            Descriptor::Wsh(wsh) => match wsh.as_inner() {
+                WshInner::SortedMulti(multi) => multi.k,
+                WshInner::Ms(_) => bail!("Unsupported peg-in descriptor"),
+            },
+            _ => bail!("Unsupported peg-in descriptor"),
+        };
+        let peer_peg_in_keys = self.cfg.peer_peg_in_keys.clone();
+        ensure!(
+            !peer_peg_in_keys.is_empty(),
+            "The client config doesn't contain the guardians' peg-in keys, so their signatures \
+             can't be attributed"
+        );
+
+        let message = AddressProof::message(&descriptor);
+        let signatures = self
+            .module_api
+            .fetch_address_proof_signatures(&tweak)
+            .await?
+            .into_iter()
+            .filter(|sig| {
+                peer_peg_in_keys.get(&sig.peer_id).map_or(false, |key| {
+                    self.secp
+                        .verify_ecdsa(&message, &sig.signature, &key.key)
+                        .is_ok()
+                })
+            })
+            .map(|sig| (sig.peer_id, sig.signature))
+            .collect::<BTreeMap<_, _>>();
+
+        ensure!(
+            signatures.len() >= threshold,
+            "Only received {} valid signatures, need {threshold}",
+            signatures.len()
+        );
+
+        Ok(AddressProof {
+            descriptor,
+            tweak,
+            peer_peg_in_keys,
+            threshold: threshold as u64,
+            signatures,
+        })
+    }
+
     pub async fn get_withdraw_fees(
         &self,
         address: bitcoin::Address,
         amount: bitcoin::Amount,
+        target: Option<FeeTarget>,
     ) -> anyhow::Result<PegOutFees> {
         check_address(&address, self.cfg.network)?;
 
         self.module_api
-            .fetch_peg_out_fees(&address, amount)
+            .fetch_peg_out_fees(&address, amount, target)
             .await?
             .context("Federation didn't return peg-out fees")
     }
@@ -677,6 +1341,28 @@ impl WalletClientModule {
             state_machines: Arc::new(sm_gen),
         })
     }
+
+    pub async fn create_cpfp_withdraw_output(
+        &self,
+        operation_id: OperationId,
+        cpfp: Cpfp,
+    ) -> anyhow::Result<ClientOutput<WalletOutput, WalletClientStates>> {
+        let output = WalletOutput::Cpfp(cpfp);
+
+        let sm_gen = move |txid, out_idx| {
+            vec![WalletClientStates::Withdraw(WithdrawStateMachine {
+                operation_id,
+                state: WithdrawStates::Created(CreatedWithdrawState {
+                    fm_outpoint: OutPoint { txid, out_idx },
+                }),
+            })]
+        };
+
+        Ok(ClientOutput::<WalletOutput, WalletClientStates> {
+            output,
+            state_machines: Arc::new(sm_gen),
+        })
+    }
 }
 
 fn check_address(address: &Address, network: Network) -> anyhow::Result<()> {
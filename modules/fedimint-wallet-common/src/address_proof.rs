@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::sha256;
+use bitcoin::{Address, Network};
+use fedimint_core::encoding::{Decodable, Encodable};
+use fedimint_core::PeerId;
+use miniscript::descriptor::Wsh;
+use secp256k1::{Message, Secp256k1, Signing, Verification};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::keys::CompressedPublicKey;
+use crate::tweakable::Tweakable;
+use crate::PegInDescriptor;
+
+/// Signature of a single guardian over a tweaked peg-in descriptor, attesting
+/// that the corresponding address is controlled by the federation
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct AddressProofSignature {
+    pub peer_id: PeerId,
+    pub signature: secp256k1::ecdsa::Signature,
+}
+
+/// A proof that can be handed to a third party (e.g. an auditor) showing that
+/// a peg-in address belongs to the federation.
+///
+/// It is valid if the untweaked descriptor built from `peer_peg_in_keys`
+/// matches the federation's peg-in descriptor, tweaking it with `tweak` yields
+/// the address and at least `threshold` guardians signed the tweaked
+/// descriptor.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct AddressProof {
+    /// The tweaked peg-in descriptor the address is derived from
+    pub descriptor: PegInDescriptor,
+    /// The tweak that was applied to the federation's peg-in descriptor
+    pub tweak: secp256k1::XOnlyPublicKey,
+    /// The untweaked public keys of the guardians
+    pub peer_peg_in_keys: BTreeMap<PeerId, CompressedPublicKey>,
+    /// Number of guardian signatures required to spend from the address
+    pub threshold: u64,
+    /// Signatures of the guardians over `descriptor`
+    pub signatures: BTreeMap<PeerId, secp256k1::ecdsa::Signature>,
+}
+
+impl AddressProof {
+    /// The message guardians sign to attest the ownership of `descriptor`.
+    ///
+    /// It is signed with the guardians' peg-in keys over a tweak chosen by the
+    /// client, so it carries a domain tag to never be a valid hash with
+    /// custody meaning.
+    pub fn message(descriptor: &PegInDescriptor) -> Message {
+        let hash: sha256::Hash = descriptor.consensus_hash_tagged(ADDRESS_PROOF_SIGNATURE_TAG);
+        Message::from_slice(&hash[..]).expect("Hash has the right length")
+    }
+
+    pub fn verify<C: Verification + Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        address: &Address,
+        network: Network,
+        untweaked_pegin_descriptor: &PegInDescriptor,
+    ) -> Result<(), AddressProofError> {
+        let threshold = self.threshold as usize;
+        let keys_descriptor = PegInDescriptor::Wsh(
+            Wsh::new_sortedmulti(threshold, self.peer_peg_in_keys.values().copied().collect())
+                .map_err(|_| AddressProofError::KeysDoNotMatchDescriptor)?,
+        );
+        if &keys_descriptor != untweaked_pegin_descriptor {
+            return Err(AddressProofError::KeysDoNotMatchDescriptor);
+        }
+
+        if untweaked_pegin_descriptor.tweak(&self.tweak, secp) != self.descriptor {
+            return Err(AddressProofError::TweakDoesNotMatchDescriptor);
+        }
+
+        let proof_address = self
+            .descriptor
+            .address(network)
+            .map_err(|_| AddressProofError::AddressDoesNotMatch)?;
+        if &proof_address != address {
+            return Err(AddressProofError::AddressDoesNotMatch);
+        }
+
+        let message = Self::message(&self.descriptor);
+        for (peer_id, signature) in &self.signatures {
+            let key = self
+                .peer_peg_in_keys
+                .get(peer_id)
+                .ok_or(AddressProofError::UnknownPeer(*peer_id))?;
+            secp.verify_ecdsa(&message, signature, &key.key)
+                .map_err(|_| AddressProofError::InvalidSignature(*peer_id))?;
+        }
+
+        if self.signatures.len() < threshold {
+            return Err(AddressProofError::NotEnoughSignatures(
+                self.signatures.len(),
+                threshold,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Domain tag of the signed [`AddressProof`] message
+const ADDRESS_PROOF_SIGNATURE_TAG: &[u8] = b"fedimint-address-proof";
+
+#[derive(Debug, Error, Eq, PartialEq)]
+pub enum AddressProofError {
+    #[error("Guardian keys don't match the federation's peg-in descriptor")]
+    KeysDoNotMatchDescriptor,
+    #[error("Tweaked descriptor doesn't match the tweak")]
+    TweakDoesNotMatchDescriptor,
+    #[error("Descriptor doesn't produce the given address")]
+    AddressDoesNotMatch,
+    #[error("Signature by unknown peer {0}")]
+    UnknownPeer(PeerId),
+    #[error("Invalid signature by peer {0}")]
+    InvalidSignature(PeerId),
+    #[error("Got {0} signatures, expected at least {1}")]
+    NotEnoughSignatures(usize, usize),
+}
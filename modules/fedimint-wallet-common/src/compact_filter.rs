@@ -0,0 +1,43 @@
+use bitcoin::util::bip158::{BlockFilter, BlockFilterWriter};
+use bitcoin::{Address, Block, BlockHash};
+use serde::{Deserialize, Serialize};
+
+/// A BIP158 compact filter over the output scripts of a Bitcoin block, letting
+/// clients check whether the block pays to one of their peg-in addresses
+/// without downloading it.
+///
+/// Unlike the BIP158 basic filter it doesn't contain the scripts spent by the
+/// inputs of the block since finding peg-ins only requires the outputs.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CompactFilter {
+    /// The hash of the filtered block, it keys the hashes of the filter
+    pub block_hash: BlockHash,
+    /// The golomb-coded set of the output scripts
+    pub content: Vec<u8>,
+}
+
+impl CompactFilter {
+    pub fn from_block(block: &Block) -> CompactFilter {
+        let mut content = vec![];
+        {
+            let mut writer = BlockFilterWriter::new(&mut content, block);
+            writer.add_output_scripts();
+            writer.finish().expect("Writing to a vec can't fail");
+        }
+
+        CompactFilter {
+            block_hash: block.block_hash(),
+            content,
+        }
+    }
+
+    /// Returns true if the block may contain an output to `address`, false
+    /// positives happen with a probability of 1 in 784931
+    pub fn matches(&self, address: &Address) -> bool {
+        let script = address.script_pubkey();
+        BlockFilter::new(&self.content)
+            .match_any(&self.block_hash, &mut std::iter::once(script.as_bytes()))
+            // A malformed filter matches everything so no peg-in is missed
+            .unwrap_or(true)
+    }
+}
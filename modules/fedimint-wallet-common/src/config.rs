@@ -1,6 +1,6 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use bitcoin::Network;
+use bitcoin::{Address, Network};
 use fedimint_core::bitcoinrpc::BitcoinRpcConfig;
 use fedimint_core::core::ModuleKind;
 use fedimint_core::encoding::{Decodable, Encodable};
@@ -12,7 +12,7 @@ use secp256k1::SecretKey;
 use serde::{Deserialize, Serialize};
 
 use crate::keys::CompressedPublicKey;
-use crate::{PegInDescriptor, WalletCommonGen};
+use crate::{PegInDescriptor, WalletCommonGen, WalletError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletGenParams {
@@ -26,7 +26,9 @@ impl WalletGenParams {
             local: WalletGenParamsLocal { bitcoin_rpc },
             consensus: WalletGenParamsConsensus {
                 network: Network::Regtest,
-                finality_delay: 10,
+                finality_delay: NetworkFinality::default(),
+                dust_change_policy: None,
+                peg_out_policy: PegOutPolicy::default(),
                 client_default_bitcoin_rpc: BitcoinRpcConfig {
                     kind: "esplora".to_string(),
                     url: SafeUrl::parse(&format!(
@@ -48,7 +50,14 @@ pub struct WalletGenParamsLocal {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletGenParamsConsensus {
     pub network: Network,
-    pub finality_delay: u32,
+    /// See [`WalletConfigConsensus::finality_delay`].
+    pub finality_delay: NetworkFinality,
+    /// See [`WalletConfigConsensus::dust_change_policy`].
+    #[serde(default)]
+    pub dust_change_policy: Option<DustChangePolicy>,
+    /// See [`WalletConfigConsensus::peg_out_policy`].
+    #[serde(default)]
+    pub peg_out_policy: PegOutPolicy,
     /// See [`WalletConfigConsensus::client_default_bitcoin_rpc`].
     pub client_default_bitcoin_rpc: BitcoinRpcConfig,
 }
@@ -81,13 +90,20 @@ pub struct WalletConfigConsensus {
     /// The public keys for the bitcoin multisig
     pub peer_peg_in_keys: BTreeMap<PeerId, CompressedPublicKey>,
     /// How many bitcoin blocks to wait before considering a transaction
-    /// confirmed
-    pub finality_delay: u32,
+    /// confirmed, only the value for `network` is used
+    pub finality_delay: NetworkFinality,
     /// If we cannot determine the feerate from our bitcoin node, default to
     /// this
     pub default_fee: Feerate,
     /// Fees for bitcoin transactions
     pub fee_consensus: FeeConsensus,
+    /// How to handle peg-outs whose change would be below the dust limit, if
+    /// `None` we only create peg-outs with enough change to be spendable
+    pub dust_change_policy: Option<DustChangePolicy>,
+    /// Restricts the addresses peg-outs may pay to, configs from before
+    /// policies existed get the default policy allowing all peg-outs
+    #[serde(default)]
+    pub peg_out_policy: PegOutPolicy,
     /// Points to a Bitcoin API that the client can use to interact with the
     /// Bitcoin blockchain (mostly for deposits). *Eventually the backend should
     /// become configurable locally and this should merely be a suggested
@@ -98,10 +114,191 @@ pub struct WalletConfigConsensus {
     pub client_default_bitcoin_rpc: BitcoinRpcConfig,
 }
 
+impl WalletConfigConsensus {
+    /// How many bitcoin blocks to wait on the network the federation runs on
+    pub fn finality_delay_for_network(&self) -> u32 {
+        self.finality_delay.for_network(self.network)
+    }
+
+    /// The part of the config the clients of the federation get to see
+    pub fn to_client_config(&self) -> WalletClientConfig {
+        WalletClientConfig {
+            peg_in_descriptor: self.peg_in_descriptor.clone(),
+            peer_peg_in_keys: self.peer_peg_in_keys.clone(),
+            network: self.network,
+            fee_consensus: self.fee_consensus.clone(),
+            finality_delay: self.finality_delay_for_network(),
+            default_bitcoin_rpc: self.client_default_bitcoin_rpc.clone(),
+        }
+    }
+}
+
+/// Finality delays for each bitcoin network, since e.g. regtest tests want to
+/// confirm transactions quickly while mainnet needs more confirmations to be
+/// safe against reorgs
+///
+/// Configs from before per-network delays stored a single integer, which is
+/// still accepted and used for all networks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+#[serde(from = "NetworkFinalityRepr")]
+pub struct NetworkFinality {
+    pub mainnet: u32,
+    pub testnet: u32,
+    pub signet: u32,
+    pub regtest: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NetworkFinalityRepr {
+    Uniform(u32),
+    PerNetwork {
+        mainnet: u32,
+        testnet: u32,
+        signet: u32,
+        regtest: u32,
+    },
+}
+
+impl From<NetworkFinalityRepr> for NetworkFinality {
+    fn from(repr: NetworkFinalityRepr) -> Self {
+        match repr {
+            NetworkFinalityRepr::Uniform(finality_delay) => {
+                NetworkFinality::uniform(finality_delay)
+            }
+            NetworkFinalityRepr::PerNetwork {
+                mainnet,
+                testnet,
+                signet,
+                regtest,
+            } => NetworkFinality {
+                mainnet,
+                testnet,
+                signet,
+                regtest,
+            },
+        }
+    }
+}
+
+impl NetworkFinality {
+    /// Uses the same finality delay on all networks
+    pub fn uniform(finality_delay: u32) -> NetworkFinality {
+        NetworkFinality {
+            mainnet: finality_delay,
+            testnet: finality_delay,
+            signet: finality_delay,
+            regtest: finality_delay,
+        }
+    }
+
+    pub fn for_network(&self, network: Network) -> u32 {
+        match network {
+            Network::Bitcoin => self.mainnet,
+            Network::Testnet => self.testnet,
+            Network::Signet => self.signet,
+            Network::Regtest => self.regtest,
+        }
+    }
+}
+
+impl Default for NetworkFinality {
+    fn default() -> Self {
+        Self {
+            mainnet: 10,
+            testnet: 6,
+            signet: 3,
+            regtest: 10,
+        }
+    }
+}
+
+/// What to do with the change of a peg-out if it is below the dust limit and
+/// thus cannot be spent economically. In all cases the federation absorbs the
+/// dust amount instead of paying itself change.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+pub enum DustChangePolicy {
+    /// Send the dust to a provably unspendable `OP_RETURN` output
+    Burn,
+    /// Leave out the change output so the dust goes to the miners
+    AddToFee,
+    /// Round up the peg-out amount so the recipient receives the dust
+    Round,
+}
+
+/// Restricts the destinations of peg-outs, e.g. to comply with regulations.
+/// The default policy with no allowed script types and no blocked addresses
+/// allows all peg-outs.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize, Encodable, Decodable)]
+pub struct PegOutPolicy {
+    /// Script types peg-outs may pay to, all are allowed if empty
+    pub allowed_script_types: Vec<ScriptType>,
+    /// Addresses peg-outs must never pay to
+    pub blocked_addresses: BTreeSet<Address>,
+}
+
+impl PegOutPolicy {
+    /// Checks that the policy allows a peg-out to `address`
+    pub fn check(&self, address: &Address) -> Result<(), WalletError> {
+        if self.blocked_addresses.contains(address) {
+            return Err(WalletError::PolicyViolation(format!(
+                "Address {address} is blocked"
+            )));
+        }
+
+        if self.allowed_script_types.is_empty() {
+            return Ok(());
+        }
+
+        match ScriptType::from_address(address) {
+            Some(script_type) if self.allowed_script_types.contains(&script_type) => Ok(()),
+            script_type => Err(WalletError::PolicyViolation(format!(
+                "Script type {script_type:?} of address {address} is not allowed"
+            ))),
+        }
+    }
+}
+
+/// Standard script types an address can pay to
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl ScriptType {
+    /// The script type of `address`, `None` for non-standard scripts
+    pub fn from_address(address: &Address) -> Option<ScriptType> {
+        let script = address.script_pubkey();
+        if script.is_p2pkh() {
+            Some(ScriptType::P2pkh)
+        } else if script.is_p2sh() {
+            Some(ScriptType::P2sh)
+        } else if script.is_v0_p2wpkh() {
+            Some(ScriptType::P2wpkh)
+        } else if script.is_v0_p2wsh() {
+            Some(ScriptType::P2wsh)
+        } else if script.is_v1_p2tr() {
+            Some(ScriptType::P2tr)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize, Encodable, Decodable)]
 pub struct WalletClientConfig {
     /// The federations public peg-in-descriptor
     pub peg_in_descriptor: PegInDescriptor,
+    /// The public keys for the bitcoin multisig, used to check which guardian
+    /// signed an address proof. Empty for configs stored before the keys were
+    /// added, then address proofs can't be attributed to the guardians.
+    #[serde(default)]
+    pub peer_peg_in_keys: BTreeMap<PeerId, CompressedPublicKey>,
     /// The bitcoin network the client will use
     pub network: Network,
     /// Confirmations required for a peg in to be accepted by federation
@@ -146,7 +343,9 @@ impl WalletConfig {
         sk: SecretKey,
         threshold: usize,
         network: Network,
-        finality_delay: u32,
+        finality_delay: NetworkFinality,
+        dust_change_policy: Option<DustChangePolicy>,
+        peg_out_policy: PegOutPolicy,
         bitcoin_rpc: BitcoinRpcConfig,
         client_default_bitcoin_rpc: BitcoinRpcConfig,
     ) -> Self {
@@ -164,6 +363,8 @@ impl WalletConfig {
                 finality_delay,
                 default_fee: Feerate { sats_per_kvb: 1000 },
                 fee_consensus: Default::default(),
+                dust_change_policy,
+                peg_out_policy,
                 client_default_bitcoin_rpc,
             },
         }
@@ -173,12 +374,14 @@ impl WalletConfig {
 impl WalletClientConfig {
     pub fn new(
         peg_in_descriptor: PegInDescriptor,
+        peer_peg_in_keys: BTreeMap<PeerId, CompressedPublicKey>,
         network: bitcoin::network::constants::Network,
         finality_delay: u32,
         default_bitcoin_rpc: BitcoinRpcConfig,
     ) -> Self {
         Self {
             peg_in_descriptor,
+            peer_peg_in_keys,
             network,
             finality_delay,
             fee_consensus: Default::default(),
@@ -198,3 +401,61 @@ plugin_types_trait_impl_config!(
     WalletConfigConsensus,
     WalletClientConfig
 );
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::bitcoinrpc::BitcoinRpcConfig;
+    use fedimint_core::module::__reexports::serde_json;
+    use fedimint_core::PeerId;
+    use miniscript::descriptor::Wsh;
+
+    use super::{NetworkFinality, WalletClientConfig};
+    use crate::keys::CompressedPublicKey;
+    use crate::PegInDescriptor;
+
+    #[test]
+    fn network_finality_accepts_legacy_integer() {
+        assert_eq!(
+            serde_json::from_str::<NetworkFinality>("10").unwrap(),
+            NetworkFinality::uniform(10)
+        );
+
+        let finality = NetworkFinality {
+            mainnet: 10,
+            testnet: 6,
+            signet: 3,
+            regtest: 1,
+        };
+        let json = serde_json::to_string(&finality).unwrap();
+        assert_eq!(
+            serde_json::from_str::<NetworkFinality>(&json).unwrap(),
+            finality
+        );
+    }
+
+    #[test]
+    fn client_config_without_peer_keys_deserializes() {
+        let secp = secp256k1::Secp256k1::new();
+        let (_, pk) = secp.generate_keypair(&mut rand::thread_rng());
+        let descriptor = PegInDescriptor::Wsh(
+            Wsh::new_sortedmulti(1, vec![CompressedPublicKey::new(pk)]).unwrap(),
+        );
+        let config = WalletClientConfig::new(
+            descriptor,
+            [(PeerId::from(0), CompressedPublicKey::new(pk))].into(),
+            bitcoin::Network::Regtest,
+            10,
+            BitcoinRpcConfig {
+                kind: "bitcoind".to_string(),
+                url: "http://localhost:18332".parse().unwrap(),
+            },
+        );
+
+        let mut json = serde_json::to_value(&config).unwrap();
+        json.as_object_mut().unwrap().remove("peer_peg_in_keys");
+        let stored = serde_json::from_value::<WalletClientConfig>(json).unwrap();
+
+        assert!(stored.peer_peg_in_keys.is_empty());
+        assert_eq!(stored.peg_in_descriptor, config.peg_in_descriptor);
+    }
+}
@@ -5,7 +5,9 @@ use secp256k1::ecdsa::Signature;
 use serde::Serialize;
 use strum_macros::EnumIter;
 
-use crate::{PendingTransaction, SpendableUTXO, UnsignedTransaction, WalletOutputOutcome};
+use crate::{
+    FeeTarget, PegOut, PendingTransaction, SpendableUTXO, UnsignedTransaction, WalletOutputOutcome,
+};
 
 #[repr(u8)]
 #[derive(Clone, EnumIter, Debug)]
@@ -19,6 +21,12 @@ pub enum DbKeyPrefix {
     PegOutTxSigCi = 0x36,
     PegOutBitcoinOutPoint = 0x37,
     PegOutNonce = 0x38,
+    PegOutQueue = 0x39,
+    Cpfp = 0x3a,
+    TargetFeeRateVote = 0x3b,
+    PegOutFees = 0x3c,
+    FeeRateVoteHistory = 0x3d,
+    PegOutSignedSession = 0x3e,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -146,6 +154,66 @@ impl_db_record!(
 
 impl_db_lookup!(key = FeeRateVoteKey, query_prefix = FeeRateVotePrefix);
 
+/// Fee rate a peer voted for to confirm within a [`FeeTarget`]
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct TargetFeeRateVoteKey(pub FeeTarget, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct TargetFeeRateVotePrefix;
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct TargetFeeRateVoteTargetPrefix(pub FeeTarget);
+
+impl_db_record!(
+    key = TargetFeeRateVoteKey,
+    value = fedimint_core::Feerate,
+    db_prefix = DbKeyPrefix::TargetFeeRateVote
+);
+
+impl_db_lookup!(
+    key = TargetFeeRateVoteKey,
+    query_prefix = TargetFeeRateVotePrefix,
+    query_prefix = TargetFeeRateVoteTargetPrefix
+);
+
+/// Fee rate a peer had voted for when the session with the given index was
+/// completed
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct FeeRateVoteHistoryKey(pub u64, pub PeerId);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct FeeRateVoteHistoryPrefix;
+
+impl_db_record!(
+    key = FeeRateVoteHistoryKey,
+    value = fedimint_core::Feerate,
+    db_prefix = DbKeyPrefix::FeeRateVoteHistory
+);
+
+impl_db_lookup!(
+    key = FeeRateVoteHistoryKey,
+    query_prefix = FeeRateVoteHistoryPrefix
+);
+
+/// Index of the session in which a `PendingTransaction` was fully signed,
+/// removed together with the transaction once it confirms
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegOutSignedSessionKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PegOutSignedSessionPrefix;
+
+impl_db_record!(
+    key = PegOutSignedSessionKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::PegOutSignedSession
+);
+
+impl_db_lookup!(
+    key = PegOutSignedSessionKey,
+    query_prefix = PegOutSignedSessionPrefix
+);
+
 #[derive(Clone, Debug, Encodable, Decodable)]
 pub struct PegOutNonceKey;
 
@@ -154,3 +222,43 @@ impl_db_record!(
     value = u64,
     db_prefix = DbKeyPrefix::PegOutNonce
 );
+
+/// Total bitcoin transaction fees paid by the accepted wallet outputs
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PegOutFeesKey;
+
+impl_db_record!(
+    key = PegOutFeesKey,
+    value = bitcoin::Amount,
+    db_prefix = DbKeyPrefix::PegOutFees
+);
+
+/// Peg-outs accepted in the current session that will be batched into one
+/// transaction once it completes
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct PegOutQueueKey(pub fedimint_core::OutPoint);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct PegOutQueuePrefix;
+
+impl_db_record!(
+    key = PegOutQueueKey,
+    value = PegOut,
+    db_prefix = DbKeyPrefix::PegOutQueue,
+);
+impl_db_lookup!(key = PegOutQueueKey, query_prefix = PegOutQueuePrefix);
+
+/// Change UTXO of a pending transaction that is spent by a CPFP child, keyed
+/// by the parent's txid
+#[derive(Clone, Debug, Encodable, Decodable, Serialize)]
+pub struct CpfpKey(pub Txid);
+
+#[derive(Clone, Debug, Encodable, Decodable)]
+pub struct CpfpPrefix;
+
+impl_db_record!(
+    key = CpfpKey,
+    value = SpendableUTXO,
+    db_prefix = DbKeyPrefix::Cpfp,
+);
+impl_db_lookup!(key = CpfpKey, query_prefix = CpfpPrefix);
@@ -19,6 +19,8 @@ use crate::db::UTXOKey;
 use crate::keys::CompressedPublicKey;
 use crate::txoproof::{PegInProof, PegInProofError};
 
+pub mod address_proof;
+pub mod compact_filter;
 pub mod config;
 pub mod db;
 pub mod keys;
@@ -30,6 +32,48 @@ const CONSENSUS_VERSION: ModuleConsensusVersion = ModuleConsensusVersion(0);
 
 pub const CONFIRMATION_TARGET: u16 = 10;
 
+/// How quickly a peg-out should confirm, used to quote fees above the
+/// federation's default fee rate
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Hash,
+    Ord,
+    PartialOrd,
+    Serialize,
+    Deserialize,
+    Encodable,
+    Decodable,
+)]
+pub enum FeeTarget {
+    NextBlock,
+    ThreeBlocks,
+    SixBlocks,
+    OneDayEconomy,
+}
+
+impl FeeTarget {
+    pub const ALL: [FeeTarget; 4] = [
+        FeeTarget::NextBlock,
+        FeeTarget::ThreeBlocks,
+        FeeTarget::SixBlocks,
+        FeeTarget::OneDayEconomy,
+    ];
+
+    /// Number of blocks bitcoind should target when estimating the fee rate
+    pub fn confirmation_target(&self) -> u16 {
+        match self {
+            FeeTarget::NextBlock => 1,
+            FeeTarget::ThreeBlocks => 3,
+            FeeTarget::SixBlocks => 6,
+            FeeTarget::OneDayEconomy => 144,
+        }
+    }
+}
+
 pub type PartialSig = Vec<u8>;
 
 pub type PegInDescriptor = Descriptor<CompressedPublicKey>;
@@ -42,6 +86,7 @@ pub enum WalletConsensusItem {
                       * * verification logic */
     Feerate(Feerate),
     PegOutSignature(PegOutSignatureItem),
+    TargetFeerate(FeeTarget, Feerate),
 }
 
 impl std::fmt::Display for WalletConsensusItem {
@@ -60,6 +105,13 @@ impl std::fmt::Display for WalletConsensusItem {
             WalletConsensusItem::PegOutSignature(sig) => {
                 write!(f, "Wallet PegOut signature for Bitcoin TxId {}", sig.txid)
             }
+            WalletConsensusItem::TargetFeerate(target, feerate) => {
+                write!(
+                    f,
+                    "Wallet Feerate for {target:?} with sats per kvb {}",
+                    feerate.sats_per_kvb
+                )
+            }
         }
     }
 }
@@ -79,6 +131,9 @@ pub struct SpendableUTXO {
 }
 
 /// A peg-out tx that is ready to be broadcast with a tweak for the change UTXO
+///
+/// If several peg-outs were batched into the tx `destination` is the first
+/// recipient and `peg_out_amount` the total amount paid to all of them.
 #[derive(Clone, Debug, Encodable, Decodable)]
 pub struct PendingTransaction {
     pub tx: Transaction,
@@ -109,6 +164,9 @@ impl Serialize for PendingTransaction {
 
 /// A PSBT that is awaiting enough signatures from the federation to becoming a
 /// `PendingTransaction`
+///
+/// See [`PendingTransaction`] for the meaning of `destination` and
+/// `peg_out_amount` for batched peg-outs.
 #[derive(Clone, Debug, Eq, PartialEq, Encodable, Decodable)]
 pub struct UnsignedTransaction {
     pub psbt: PartiallySignedTransaction,
@@ -207,6 +265,7 @@ impl std::fmt::Display for WalletInput {
 pub enum WalletOutput {
     PegOut(PegOut),
     Rbf(Rbf),
+    Cpfp(Cpfp),
 }
 
 /// Allows a user to bump the fees of a `PendingTransaction`
@@ -214,15 +273,82 @@ pub enum WalletOutput {
 pub struct Rbf {
     /// Fees expressed as an increase over existing peg-out fees
     pub fees: PegOutFees,
-    /// Bitcoin tx id to bump the fees for
+    /// Bitcoin tx id to bump the fees for, identifies the whole batch if
+    /// several peg-outs were combined into the tx
     pub txid: Txid,
 }
 
+/// A peg-out paid by a `PendingTransaction` whose fees can still be bumped
+/// with an [`Rbf`] output
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct RbfCapablePegOut {
+    /// Bitcoin tx id to pass as [`Rbf::txid`]
+    pub txid: Txid,
+    /// Fees the pending transaction currently pays
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub current_fee: bitcoin::Amount,
+    /// Upper bound for the additional fees, the change of the pending
+    /// transaction plus all UTXOs the federation could add to the replacement
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub max_bump_fee: bitcoin::Amount,
+    pub address: bitcoin::Address,
+    pub out_point: bitcoin::OutPoint,
+}
+
+/// A peg-out paid by a `PendingTransaction`, i.e. signed by the federation but
+/// not confirmed on-chain yet
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct PendingPegOut {
+    pub txid: Txid,
+    /// Amount paid to `address`
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub amount: bitcoin::Amount,
+    pub address: bitcoin::Address,
+    /// Fees of the whole transaction, shared by all peg-outs batched into it
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub fee: bitcoin::Amount,
+    /// Index of the session in which the guardians signed the transaction
+    pub epoch_signed: u64,
+}
+
+/// Allows a user to bump the fees of a `PendingTransaction` by spending its
+/// change in a child transaction that pays for the whole package
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize, Encodable, Decodable)]
+pub struct Cpfp {
+    /// Bitcoin tx id of the parent transaction whose change the child spends
+    pub txid: Txid,
+    /// Fee the parent transaction already pays
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub parent_fee: bitcoin::Amount,
+    /// Desired fee of parent and child combined
+    #[serde(with = "bitcoin::util::amount::serde::as_sat")]
+    pub package_fee: bitcoin::Amount,
+}
+
+impl Cpfp {
+    /// The fee the child transaction has to pay
+    pub fn child_fee(&self) -> bitcoin::Amount {
+        self.package_fee
+            .checked_sub(self.parent_fee)
+            .unwrap_or(bitcoin::Amount::ZERO)
+    }
+}
+
 impl WalletOutput {
     pub fn amount(&self) -> Amount {
         match self {
             WalletOutput::PegOut(pegout) => pegout.amount + pegout.fees.amount(),
             WalletOutput::Rbf(rbf) => rbf.fees.amount(),
+            WalletOutput::Cpfp(cpfp) => cpfp.child_fee(),
+        }
+    }
+
+    /// The bitcoin transaction fees the output pays for
+    pub fn fees(&self) -> Amount {
+        match self {
+            WalletOutput::PegOut(pegout) => pegout.fees.amount(),
+            WalletOutput::Rbf(rbf) => rbf.fees.amount(),
+            WalletOutput::Cpfp(cpfp) => cpfp.child_fee(),
         }
     }
 }
@@ -234,6 +360,9 @@ impl std::fmt::Display for WalletOutput {
                 write!(f, "Wallet PegOut {} to {}", pegout.amount, pegout.recipient)
             }
             WalletOutput::Rbf(rbf) => write!(f, "Wallet RBF {:?} to {}", rbf.fees, rbf.txid),
+            WalletOutput::Cpfp(cpfp) => {
+                write!(f, "Wallet CPFP {} for {}", cpfp.package_fee, cpfp.txid)
+            }
         }
     }
 }
@@ -294,12 +423,34 @@ pub enum WalletError {
     NotEnoughSpendableUTXO,
     #[error("Peg out amount was under the dust limit")]
     PegOutUnderDustLimit,
+    #[error("Peg out amount doesn't cover its fees")]
+    PegOutUneconomical,
     #[error("RBF transaction id not found")]
     RbfTransactionIdNotFound,
     #[error("Peg-out fee weight {0} doesn't match actual weight {1}")]
     TxWeightIncorrect(u64, u64),
     #[error("Peg-out fee rate is below min relay fee")]
     BelowMinRelayFee,
+    #[error("CPFP parent transaction id not found")]
+    CpfpParentNotFound,
+    #[error("CPFP parent transaction pays a fee of {0}, not {1}")]
+    CpfpParentFeeIncorrect(bitcoin::Amount, bitcoin::Amount),
+    #[error("CPFP package fee doesn't exceed the parent's fee")]
+    CpfpPackageFeeTooLow,
+    #[error("CPFP parent transaction has no change to spend")]
+    CpfpParentHasNoChange,
+    #[error("CPFP parent transaction change doesn't cover the child's fee")]
+    CpfpChangeTooSmall,
+    #[error("Transaction already has a CPFP child")]
+    CpfpChildExists,
+    #[error("CPFP parent transaction is being replaced by RBF")]
+    CpfpParentReplaced,
+    #[error("CPFP transactions can't be replaced by RBF")]
+    RbfOfCpfpTransaction,
+    #[error("CPFP outputs can't be validated as peg-out transactions")]
+    UnexpectedCpfpOutput,
+    #[error("Peg-out policy violation: {0}")]
+    PolicyViolation(String),
 }
 
 #[derive(Debug, Error)]
@@ -13,13 +13,17 @@ use bitcoin::{
     Address, BlockHash, EcdsaSig, EcdsaSighashType, Network, PackedLockTime, Script, Sequence,
     Transaction, TxIn, TxOut, Txid,
 };
+use common::address_proof::{AddressProof, AddressProofSignature};
 use common::config::WalletConfigConsensus;
 use common::db::{
-    BlockCountVoteKey, BlockCountVotePrefix, DbKeyPrefix, FeeRateVoteKey, FeeRateVotePrefix,
-    PegOutNonceKey,
+    BlockCountVoteKey, BlockCountVotePrefix, CpfpKey, CpfpPrefix, DbKeyPrefix,
+    FeeRateVoteHistoryKey, FeeRateVoteHistoryPrefix, FeeRateVoteKey, FeeRateVotePrefix,
+    PegOutFeesKey, PegOutNonceKey, PegOutQueueKey, PegOutQueuePrefix, PegOutSignedSessionKey,
+    PegOutSignedSessionPrefix, TargetFeeRateVoteKey, TargetFeeRateVotePrefix,
+    TargetFeeRateVoteTargetPrefix,
 };
 use common::{
-    proprietary_tweak_key, PegOutFees, PegOutSignatureItem, PendingTransaction,
+    proprietary_tweak_key, FeeTarget, PegOut, PegOutFees, PegOutSignatureItem, PendingTransaction,
     ProcessPegOutSigError, SpendableUTXO, UnsignedTransaction, WalletCommonGen,
     WalletConsensusItem, WalletError, WalletInput, WalletModuleTypes, WalletOutput,
     WalletOutputOutcome, CONFIRMATION_TARGET,
@@ -35,11 +39,13 @@ use fedimint_core::db::{
 };
 use fedimint_core::encoding::Encodable;
 use fedimint_core::endpoint_constants::{
-    BLOCK_COUNT_ENDPOINT, BLOCK_COUNT_LOCAL_ENDPOINT, PEG_OUT_FEES_ENDPOINT,
+    ADDRESS_PROOF_SIGNATURE_ENDPOINT, BLOCK_COUNT_ENDPOINT, BLOCK_COUNT_LOCAL_ENDPOINT,
+    COMPACT_FILTER_ENDPOINT, FEE_RATE_VOTE_HISTORY_ENDPOINT, PEG_OUT_FEES_ENDPOINT,
+    PENDING_PEG_OUTS_ENDPOINT, RBF_CAPABLE_PEG_OUTS_ENDPOINT, WALLET_CONFIG_ENDPOINT,
 };
 use fedimint_core::module::audit::Audit;
 use fedimint_core::module::{
-    api_endpoint, ApiEndpoint, CoreConsensusVersion, ExtendsCommonModuleInit, InputMeta,
+    api_endpoint, ApiEndpoint, ApiError, CoreConsensusVersion, ExtendsCommonModuleInit, InputMeta,
     IntoModuleError, ModuleConsensusVersion, ModuleError, PeerHandle, ServerModuleInit,
     ServerModuleInitArgs, SupportedModuleApiVersions, TransactionItemAmount,
 };
@@ -53,7 +59,10 @@ use fedimint_core::{
 };
 use fedimint_server::config::distributedgen::PeerHandleOps;
 pub use fedimint_wallet_common as common;
-use fedimint_wallet_common::config::{WalletClientConfig, WalletConfig, WalletGenParams};
+use fedimint_wallet_common::compact_filter::CompactFilter;
+use fedimint_wallet_common::config::{
+    DustChangePolicy, WalletClientConfig, WalletConfig, WalletGenParams,
+};
 use fedimint_wallet_common::db::{
     BlockHashKey, BlockHashKeyPrefix, PegOutBitcoinTransaction, PegOutBitcoinTransactionPrefix,
     PegOutTxSignatureCI, PegOutTxSignatureCIPrefix, PendingTransactionKey,
@@ -62,14 +71,14 @@ use fedimint_wallet_common::db::{
 };
 use fedimint_wallet_common::keys::CompressedPublicKey;
 use fedimint_wallet_common::tweakable::Tweakable;
-use fedimint_wallet_common::Rbf;
+use fedimint_wallet_common::{Cpfp, PendingPegOut, Rbf, RbfCapablePegOut};
 use futures::StreamExt;
 use miniscript::psbt::PsbtExt;
 use miniscript::{translate_hash_fail, Descriptor, TranslatePk};
 use rand::rngs::OsRng;
 use secp256k1::{Message, Scalar};
 use strum::IntoEnumIterator;
-use tracing::{debug, info, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
 
 #[derive(Debug, Clone)]
 pub struct WalletGen;
@@ -127,6 +136,31 @@ impl ExtendsCommonModuleInit for WalletGen {
                         wallet.insert("Peg Out Nonce".to_string(), Box::new(nonce));
                     }
                 }
+                DbKeyPrefix::PegOutFees => {
+                    if let Some(fees) = dbtx.get_value(&PegOutFeesKey).await {
+                        wallet.insert("Peg Out Fees".to_string(), Box::new(fees));
+                    }
+                }
+                DbKeyPrefix::PegOutQueue => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegOutQueuePrefix,
+                        PegOutQueueKey,
+                        PegOut,
+                        wallet,
+                        "Peg Out Queue"
+                    );
+                }
+                DbKeyPrefix::Cpfp => {
+                    push_db_pair_items!(
+                        dbtx,
+                        CpfpPrefix,
+                        CpfpKey,
+                        SpendableUTXO,
+                        wallet,
+                        "CPFP Spent Change"
+                    );
+                }
                 DbKeyPrefix::UnsignedTransaction => {
                     push_db_pair_items!(
                         dbtx,
@@ -169,6 +203,36 @@ impl ExtendsCommonModuleInit for WalletGen {
                         "Fee Rate Votes"
                     );
                 }
+                DbKeyPrefix::TargetFeeRateVote => {
+                    push_db_pair_items!(
+                        dbtx,
+                        TargetFeeRateVotePrefix,
+                        TargetFeeRateVoteKey,
+                        Feerate,
+                        wallet,
+                        "Target Fee Rate Votes"
+                    );
+                }
+                DbKeyPrefix::FeeRateVoteHistory => {
+                    push_db_pair_items!(
+                        dbtx,
+                        FeeRateVoteHistoryPrefix,
+                        FeeRateVoteHistoryKey,
+                        Feerate,
+                        wallet,
+                        "Fee Rate Vote History"
+                    );
+                }
+                DbKeyPrefix::PegOutSignedSession => {
+                    push_db_pair_items!(
+                        dbtx,
+                        PegOutSignedSessionPrefix,
+                        PegOutSignedSessionKey,
+                        u64,
+                        wallet,
+                        "Peg Out Signed Sessions"
+                    );
+                }
             }
         }
 
@@ -225,6 +289,8 @@ impl ServerModuleInit for WalletGen {
                     peers.threshold(),
                     params.consensus.network,
                     params.consensus.finality_delay,
+                    params.consensus.dust_change_policy,
+                    params.consensus.peg_out_policy.clone(),
                     params.local.bitcoin_rpc.clone(),
                     params.consensus.client_default_bitcoin_rpc.clone(),
                 );
@@ -260,6 +326,8 @@ impl ServerModuleInit for WalletGen {
             peers.peer_ids().threshold(),
             params.consensus.network,
             params.consensus.finality_delay,
+            params.consensus.dust_change_policy,
+            params.consensus.peg_out_policy.clone(),
             params.local.bitcoin_rpc.clone(),
             params.consensus.client_default_bitcoin_rpc.clone(),
         );
@@ -288,14 +356,7 @@ impl ServerModuleInit for WalletGen {
         &self,
         config: &ServerModuleConsensusConfig,
     ) -> anyhow::Result<WalletClientConfig> {
-        let config = WalletConfigConsensus::from_erased(config)?;
-        Ok(WalletClientConfig {
-            peg_in_descriptor: config.peg_in_descriptor,
-            network: config.network,
-            fee_consensus: config.fee_consensus,
-            finality_delay: config.finality_delay,
-            default_bitcoin_rpc: config.client_default_bitcoin_rpc,
-        })
+        Ok(WalletConfigConsensus::from_erased(config)?.to_client_config())
     }
 }
 
@@ -322,7 +383,8 @@ impl ServerModule for Wallet {
 
         // TODO: We should not be panicking
         let block_count = self.get_block_count().await.expect("bitcoind rpc failed");
-        let block_count_proposal = block_count.saturating_sub(self.cfg.consensus.finality_delay);
+        let block_count_proposal =
+            block_count.saturating_sub(self.cfg.consensus.finality_delay_for_network());
 
         debug!(
             ?block_count_proposal,
@@ -350,6 +412,34 @@ impl ServerModule for Wallet {
             items.push(WalletConsensusItem::Feerate(fee_rate_proposal));
         }
 
+        for target in FeeTarget::ALL {
+            let target_fee_rate_proposal = match self
+                .btc_rpc
+                .get_fee_rate(target.confirmation_target())
+                .await
+            {
+                Ok(Some(feerate)) => feerate,
+                // Without an estimate we keep our last vote, quotes for the target
+                // fall back to the default fee rate
+                Ok(None) => continue,
+                Err(error) => {
+                    warn!(?target, %error, "Failed to estimate fee rate for target");
+                    continue;
+                }
+            };
+
+            let current_target_vote = dbtx
+                .get_value(&TargetFeeRateVoteKey(target, self.our_peer_id))
+                .await;
+
+            if Some(target_fee_rate_proposal) != current_target_vote {
+                items.push(WalletConsensusItem::TargetFeerate(
+                    target,
+                    target_fee_rate_proposal,
+                ));
+            }
+        }
+
         items
     }
 
@@ -422,6 +512,15 @@ impl ServerModule for Wallet {
                     bail!("Fee rate vote is redundant");
                 }
             }
+            WalletConsensusItem::TargetFeerate(target, feerate) => {
+                if Some(feerate)
+                    == dbtx
+                        .insert_entry(&TargetFeeRateVoteKey(target, peer_id), &feerate)
+                        .await
+                {
+                    bail!("Target fee rate vote is redundant");
+                }
+            }
             WalletConsensusItem::PegOutSignature(peg_out_signature) => {
                 let txid = peg_out_signature.txid;
 
@@ -502,71 +601,62 @@ impl ServerModule for Wallet {
         output: &'a WalletOutput,
         out_point: OutPoint,
     ) -> Result<TransactionItemAmount, ModuleError> {
-        let change_tweak = self.consensus_nonce(dbtx).await;
-
-        let mut tx = self
-            .create_peg_out_tx(dbtx, output, &change_tweak)
-            .await
-            .into_module_error_other()?;
+        match output {
+            WalletOutput::PegOut(peg_out) => {
+                self.queue_peg_out(dbtx, peg_out, out_point)
+                    .await
+                    .into_module_error_other()?;
+            }
+            WalletOutput::Rbf(rbf) => {
+                let change_tweak = self.consensus_nonce(dbtx).await;
 
-        let fee_rate = self.consensus_fee_rate(dbtx).await;
+                let tx = self
+                    .create_rbf_tx(dbtx, rbf, &change_tweak)
+                    .await
+                    .into_module_error_other()?;
 
-        self.offline_wallet()
-            .validate_tx(&tx, output, fee_rate, self.cfg.consensus.network)
-            .into_module_error_other()?;
+                let fee_rate = self.consensus_fee_rate(dbtx).await;
 
-        self.offline_wallet().sign_psbt(&mut tx.psbt);
+                self.offline_wallet()
+                    .validate_tx(&tx, output, fee_rate, self.cfg.consensus.network)
+                    .into_module_error_other()?;
 
-        let txid = tx.psbt.unsigned_tx.txid();
+                let txid = self.sign_peg_out_tx(dbtx, tx).await;
 
-        info!(
-            %txid,
-            "Signing peg out",
-        );
+                dbtx.insert_new_entry(
+                    &PegOutBitcoinTransaction(out_point),
+                    &WalletOutputOutcome(txid),
+                )
+                .await;
+            }
+            WalletOutput::Cpfp(cpfp) => {
+                let change_tweak = self.consensus_nonce(dbtx).await;
 
-        let sigs = tx
-            .psbt
-            .inputs
-            .iter_mut()
-            .map(|input| {
-                assert_eq!(
-                    input.partial_sigs.len(),
-                    1,
-                    "There was already more than one (our) or no signatures in input"
-                );
+                let (tx, parent_change) = self
+                    .create_cpfp_tx(dbtx, cpfp, &change_tweak)
+                    .await
+                    .into_module_error_other()?;
 
-                // TODO: don't put sig into PSBT in the first place
-                // We actually take out our own signature so everyone finalizes the tx in the
-                // same epoch.
-                let sig = std::mem::take(&mut input.partial_sigs)
-                    .into_values()
-                    .next()
-                    .expect("asserted previously");
+                let txid = self.sign_peg_out_tx(dbtx, tx).await;
 
-                // We drop SIGHASH_ALL, because we always use that and it is only present in the
-                // PSBT for compatibility with other tools.
-                secp256k1::ecdsa::Signature::from_der(&sig.to_vec()[..sig.to_vec().len() - 1])
-                    .expect("we serialized it ourselves that way")
-            })
-            .collect::<Vec<_>>();
+                dbtx.insert_new_entry(&CpfpKey(cpfp.txid), &parent_change)
+                    .await;
 
-        // Delete used UTXOs
-        for input in tx.psbt.unsigned_tx.input.iter() {
-            dbtx.remove_entry(&UTXOKey(input.previous_output)).await;
+                dbtx.insert_new_entry(
+                    &PegOutBitcoinTransaction(out_point),
+                    &WalletOutputOutcome(txid),
+                )
+                .await;
+            }
         }
 
-        dbtx.insert_new_entry(&UnsignedTransactionKey(txid), &tx)
-            .await;
-
-        dbtx.insert_new_entry(&PegOutTxSignatureCI(txid), &sigs)
+        let fees = dbtx
+            .get_value(&PegOutFeesKey)
+            .await
+            .unwrap_or(bitcoin::Amount::ZERO);
+        dbtx.insert_entry(&PegOutFeesKey, &(fees + output.fees()))
             .await;
 
-        dbtx.insert_new_entry(
-            &PegOutBitcoinTransaction(out_point),
-            &WalletOutputOutcome(txid),
-        )
-        .await;
-
         Ok(TransactionItemAmount {
             amount: output.amount().into(),
             fee: self.cfg.consensus.fee_consensus.peg_out_abs,
@@ -592,6 +682,17 @@ impl ServerModule for Wallet {
                 v.amount.to_sat() as i64 * 1000
             })
             .await;
+        audit
+            .add_items(dbtx, module_instance_id, &PegOutQueuePrefix, |_, v| {
+                (v.amount + v.fees.amount()).to_sat() as i64 * -1000
+            })
+            .await;
+        // The change spent by a CPFP child is still counted by its parent
+        audit
+            .add_items(dbtx, module_instance_id, &CpfpPrefix, |_, v| {
+                v.amount.to_sat() as i64 * -1000
+            })
+            .await;
         audit
             .add_items(
                 dbtx,
@@ -616,6 +717,92 @@ impl ServerModule for Wallet {
             .await;
     }
 
+    async fn complete_session(&self, dbtx: &mut ModuleDatabaseTransaction<'_>, session_index: u64) {
+        let votes = dbtx
+            .find_by_prefix(&FeeRateVotePrefix)
+            .await
+            .collect::<Vec<(FeeRateVoteKey, Feerate)>>()
+            .await;
+        for (FeeRateVoteKey(peer_id), fee_rate) in votes {
+            dbtx.insert_new_entry(&FeeRateVoteHistoryKey(session_index, peer_id), &fee_rate)
+                .await;
+        }
+
+        let pending_txids = dbtx
+            .find_by_prefix(&PendingTransactionPrefixKey)
+            .await
+            .map(|(PendingTransactionKey(txid), _)| txid)
+            .collect::<Vec<_>>()
+            .await;
+        for txid in pending_txids {
+            if dbtx
+                .get_value(&PegOutSignedSessionKey(txid))
+                .await
+                .is_none()
+            {
+                dbtx.insert_new_entry(&PegOutSignedSessionKey(txid), &session_index)
+                    .await;
+            }
+        }
+
+        let queued = dbtx
+            .find_by_prefix(&PegOutQueuePrefix)
+            .await
+            .collect::<Vec<(PegOutQueueKey, PegOut)>>()
+            .await;
+
+        if queued.is_empty() {
+            return;
+        }
+
+        let batch = queued
+            .iter()
+            .map(|(_, peg_out)| peg_out.clone())
+            .collect::<Vec<_>>();
+        let change_tweak = self.consensus_nonce(dbtx).await;
+
+        // We checked that the batch can be funded when queueing its peg-outs, this can
+        // only fail if an RBF tx spent some of the UTXOs in the meantime. In that case
+        // we pay as many peg-outs as we can and the rest stays queued until the change
+        // of our other txs confirms.
+        let utxos = self.available_utxos(dbtx).await;
+        let (tx, paid) =
+            match self
+                .offline_wallet()
+                .create_largest_batch_tx(&batch, utxos, &change_tweak)
+            {
+                Ok(batch_tx) => batch_tx,
+                Err(error) => {
+                    error!(
+                        %error,
+                        session_index,
+                        queued = batch.len(),
+                        "Failed to batch any queued peg-out, retrying next session"
+                    );
+                    return;
+                }
+            };
+
+        if paid < batch.len() {
+            warn!(
+                session_index,
+                paid,
+                deferred = batch.len() - paid,
+                "Not enough funds to batch all queued peg-outs, retrying the rest next session"
+            );
+        }
+
+        let txid = self.sign_peg_out_tx(dbtx, tx).await;
+
+        info!(%txid, peg_outs = paid, session_index, "Batched peg-outs");
+
+        for (key, _) in queued.into_iter().take(paid) {
+            dbtx.remove_entry(&key).await;
+            dbtx.insert_new_entry(&PegOutBitcoinTransaction(key.0), &WalletOutputOutcome(txid))
+                .await;
+        }
+    }
+
     fn api_endpoints(&self) -> Vec<ApiEndpoint<Self>> {
         vec![
             api_endpoint! {
@@ -633,33 +820,122 @@ impl ServerModule for Wallet {
             },
             api_endpoint! {
                 PEG_OUT_FEES_ENDPOINT,
-                async |module: &Wallet, context, params: (Address, u64)| -> Option<PegOutFees> {
-                    let (address, sats) = params;
-                    let feerate = module.consensus_fee_rate(&mut context.dbtx()).await;
+                async |module: &Wallet, context, params: (Address, u64, Option<FeeTarget>)| -> Option<PegOutFees> {
+                    let (address, sats, target) = params;
+                    module
+                        .cfg
+                        .consensus
+                        .peg_out_policy
+                        .check(&address)
+                        .map_err(|error| ApiError::bad_request(error.to_string()))?;
+
+                    let mut feerate = module.consensus_fee_rate(&mut context.dbtx()).await;
+
+                    // Peg-outs are only required to pay the consensus fee rate, so targets
+                    // that are estimated to be cheaper than it are quoted at it
+                    if let Some(target) = target {
+                        feerate = feerate
+                            .max(module.consensus_target_fee_rate(&mut context.dbtx(), target).await);
+                    }
+
+                    let peg_out = PegOut {
+                        recipient: address,
+                        amount: bitcoin::Amount::from_sat(sats),
+                        fees: PegOutFees::new(feerate.sats_per_kvb, 0),
+                    };
+
+                    // The peg-out only has to pay for the weight it adds to the batch when it is
+                    // processed. We quote the weight of a tx paying it alone, which covers the
+                    // case that the queue is empty by then, so the quote doesn't go stale when
+                    // the session changes.
+                    let mut batch = module.queued_peg_outs(&mut context.dbtx()).await;
+                    let queued_weight = module.batch_weight(&mut context.dbtx(), &batch).await;
+                    batch.push(peg_out.clone());
 
                     // Since we are only calculating the tx size we can use an arbitrary dummy nonce.
                     let dummy_tweak = [0; 32];
 
-                    let tx = module.offline_wallet().create_tx(
-                        bitcoin::Amount::from_sat(sats),
-                        address.script_pubkey(),
-                        vec![],
-                        module.available_utxos(&mut context.dbtx()).await,
-                        feerate,
-                        &dummy_tweak,
-                        None
-                    );
+                    let alone_tx = module
+                        .create_batch_tx(&mut context.dbtx(), &[peg_out], &dummy_tweak)
+                        .await;
+                    let batch_tx = module
+                        .create_batch_tx(&mut context.dbtx(), &batch, &dummy_tweak)
+                        .await;
 
-                    match tx {
-                        Err(error) => {
+                    match (alone_tx, batch_tx) {
+                        (Err(error), _) | (_, Err(error)) => {
                             // Usually from not enough spendable UTXOs
                             warn!("Error returning peg-out fees {error}");
                             Ok(None)
                         }
-                        Ok(tx) => Ok(Some(tx.fees))
+                        (Ok(alone_tx), Ok(batch_tx)) => {
+                            let marginal_weight =
+                                batch_tx.fees.total_weight.saturating_sub(queued_weight);
+                            Ok(Some(PegOutFees::new(
+                                feerate.sats_per_kvb,
+                                alone_tx.fees.total_weight.max(marginal_weight),
+                            )))
+                        }
                     }
                 }
             },
+            api_endpoint! {
+                ADDRESS_PROOF_SIGNATURE_ENDPOINT,
+                async |module: &Wallet, _context, tweak: secp256k1::XOnlyPublicKey| -> AddressProofSignature {
+                    Ok(module.sign_address_proof(&tweak))
+                }
+            },
+            api_endpoint! {
+                RBF_CAPABLE_PEG_OUTS_ENDPOINT,
+                async |module: &Wallet, context, _params: ()| -> Vec<RbfCapablePegOut> {
+                    Ok(module.rbf_capable_peg_outs(&mut context.dbtx()).await)
+                }
+            },
+            api_endpoint! {
+                PENDING_PEG_OUTS_ENDPOINT,
+                async |module: &Wallet, context, _params: ()| -> Vec<PendingPegOut> {
+                    Ok(module.pending_peg_outs(&mut context.dbtx()).await)
+                }
+            },
+            api_endpoint! {
+                COMPACT_FILTER_ENDPOINT,
+                async |module: &Wallet, context, block_height: u64| -> CompactFilter {
+                    let block_count = module
+                        .consensus_block_count(&mut context.dbtx())
+                        .await
+                        .unwrap_or_default();
+                    if block_height >= u64::from(block_count) {
+                        return Err(ApiError::bad_request(format!(
+                            "Block {block_height} is not final yet"
+                        )));
+                    }
+
+                    let block_hash = module
+                        .btc_rpc
+                        .get_block_hash(block_height)
+                        .await
+                        .map_err(|error| ApiError::server_error(error.to_string()))?;
+                    let block = module
+                        .btc_rpc
+                        .get_block(&block_hash)
+                        .await
+                        .map_err(|error| ApiError::server_error(error.to_string()))?;
+
+                    Ok(CompactFilter::from_block(&block))
+                }
+            },
+            api_endpoint! {
+                FEE_RATE_VOTE_HISTORY_ENDPOINT,
+                async |module: &Wallet, context, epochs: usize| -> Vec<BTreeMap<PeerId, Feerate>> {
+                    Ok(module.fee_rate_vote_history(&mut context.dbtx(), epochs).await)
+                }
+            },
+            api_endpoint! {
+                WALLET_CONFIG_ENDPOINT,
+                async |module: &Wallet, _context, _params: ()| -> WalletClientConfig {
+                    Ok(module.cfg.consensus.to_client_config())
+                }
+            },
         ]
     }
 }
@@ -891,25 +1167,72 @@ impl Wallet {
         counts[peer_count / 2]
     }
 
+    /// The fee rate the federation agreed on.
+    ///
+    /// Every peer proposes the fee rate its bitcoind estimates as a
+    /// [`WalletConsensusItem::Feerate`] whenever it changes and we keep the
+    /// latest vote of each peer. The median of the votes is adopted, so it is
+    /// backed by a threshold of peers and a minority of faulty peers can
+    /// neither push it up nor down. Peers that haven't voted yet count as
+    /// voting for the default fee rate.
     pub async fn consensus_fee_rate(&self, dbtx: &mut ModuleDatabaseTransaction<'_>) -> Feerate {
-        let peer_count = self.cfg.consensus.peer_peg_in_keys.total();
-
-        let mut rates = dbtx
+        let rates = dbtx
             .find_by_prefix(&FeeRateVotePrefix)
             .await
             .map(|(.., rate)| rate)
             .collect::<Vec<_>>()
             .await;
 
-        assert!(rates.len() <= peer_count);
+        self.median_fee_rate(rates)
+    }
 
-        while rates.len() < peer_count {
-            rates.push(self.cfg.consensus.default_fee);
+    /// The fee rate votes of the peers at the end of each of the last `epochs`
+    /// completed sessions, oldest first
+    pub async fn fee_rate_vote_history(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        epochs: usize,
+    ) -> Vec<BTreeMap<PeerId, Feerate>> {
+        let mut history = BTreeMap::<u64, BTreeMap<PeerId, Feerate>>::new();
+        let votes = dbtx
+            .find_by_prefix(&FeeRateVoteHistoryPrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        for (FeeRateVoteHistoryKey(session_index, peer_id), rate) in votes {
+            history
+                .entry(session_index)
+                .or_default()
+                .insert(peer_id, rate);
         }
 
-        rates.sort_unstable();
+        let skipped = history.len().saturating_sub(epochs);
+        history.into_values().skip(skipped).collect()
+    }
+
+    /// Median of the fee rates the peers voted for to confirm within `target`,
+    /// missing votes count as the default fee rate
+    pub async fn consensus_target_fee_rate(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        target: FeeTarget,
+    ) -> Feerate {
+        let rates = dbtx
+            .find_by_prefix(&TargetFeeRateVoteTargetPrefix(target))
+            .await
+            .map(|(.., rate)| rate)
+            .collect::<Vec<_>>()
+            .await;
+
+        self.median_fee_rate(rates)
+    }
 
-        rates[peer_count / 2]
+    fn median_fee_rate(&self, rates: Vec<Feerate>) -> Feerate {
+        median_fee_rate(
+            rates,
+            self.cfg.consensus.peer_peg_in_keys.total(),
+            self.cfg.consensus.default_fee,
+        )
     }
 
     pub async fn consensus_nonce(&self, dbtx: &mut ModuleDatabaseTransaction<'_>) -> [u8; 32] {
@@ -997,6 +1320,12 @@ impl Wallet {
     ) {
         self.remove_rbf_transactions(dbtx, pending_tx).await;
 
+        // If a CPFP child spent the change we will recognize the child's change instead
+        let spent_by_cpfp = dbtx
+            .remove_entry(&CpfpKey(pending_tx.tx.txid()))
+            .await
+            .is_some();
+
         let script_pk = self
             .cfg
             .consensus
@@ -1004,7 +1333,7 @@ impl Wallet {
             .tweak(&pending_tx.tweak, &self.secp)
             .script_pubkey();
         for (idx, output) in pending_tx.tx.output.iter().enumerate() {
-            if output.script_pubkey == script_pk {
+            if output.script_pubkey == script_pk && !spent_by_cpfp {
                 dbtx.insert_entry(
                     &UTXOKey(bitcoin::OutPoint {
                         txid: pending_tx.tx.txid(),
@@ -1039,6 +1368,8 @@ impl Wallet {
             all_transactions.remove(&removed.tx.txid());
             dbtx.remove_entry(&PendingTransactionKey(removed.tx.txid()))
                 .await;
+            dbtx.remove_entry(&PegOutSignedSessionKey(removed.tx.txid()))
+                .await;
 
             // Search for tx that this `removed` has as RBF
             if let Some(rbf) = &removed.rbf {
@@ -1066,72 +1397,435 @@ impl Wallet {
         dbtx.get_value(&BlockHashKey(block_hash)).await.is_some()
     }
 
-    async fn create_peg_out_tx(
+    /// Checks that the peg-out can be funded together with the ones already
+    /// queued in this session and queues it to be batched into a single
+    /// transaction once the session completes
+    async fn queue_peg_out(
         &self,
         dbtx: &mut ModuleDatabaseTransaction<'_>,
-        output: &WalletOutput,
-        change_tweak: &[u8; 32],
-    ) -> Result<UnsignedTransaction, WalletError> {
-        match output {
-            WalletOutput::PegOut(peg_out) => self.offline_wallet().create_tx(
-                peg_out.amount,
-                peg_out.recipient.script_pubkey(),
-                vec![],
-                self.available_utxos(dbtx).await,
-                peg_out.fees.fee_rate,
-                change_tweak,
-                None,
-            ),
-            WalletOutput::Rbf(rbf) => {
-                let tx = dbtx
-                    .get_value(&PendingTransactionKey(rbf.txid))
-                    .await
-                    .ok_or(WalletError::RbfTransactionIdNotFound)?;
-
-                self.offline_wallet().create_tx(
-                    tx.peg_out_amount,
-                    tx.destination,
-                    tx.selected_utxos,
-                    self.available_utxos(dbtx).await,
-                    tx.fees.fee_rate,
-                    change_tweak,
-                    Some(rbf.clone()),
-                )
-            }
-        }
+        peg_out: &PegOut,
+        out_point: OutPoint,
+    ) -> Result<(), WalletError> {
+        self.cfg
+            .consensus
+            .peg_out_policy
+            .check(&peg_out.recipient)?;
+
+        let mut batch = self.queued_peg_outs(dbtx).await;
+        let queued_weight = self.batch_weight(dbtx, &batch).await;
+        batch.push(peg_out.clone());
+
+        // The change tweak doesn't influence the funding or weight of the tx
+        let tx = self.create_batch_tx(dbtx, &batch, &[0; 32]).await?;
+
+        let fee_rate = self.consensus_fee_rate(dbtx).await;
+
+        self.offline_wallet().validate_batch(
+            &tx,
+            peg_out,
+            queued_weight,
+            fee_rate,
+            self.cfg.consensus.network,
+        )?;
+
+        dbtx.insert_new_entry(&PegOutQueueKey(out_point), peg_out)
+            .await;
+
+        Ok(())
     }
 
-    async fn available_utxos(
-        &self,
-        dbtx: &mut ModuleDatabaseTransaction<'_>,
-    ) -> Vec<(UTXOKey, SpendableUTXO)> {
-        dbtx.find_by_prefix(&UTXOPrefixKey)
+    async fn queued_peg_outs(&self, dbtx: &mut ModuleDatabaseTransaction<'_>) -> Vec<PegOut> {
+        dbtx.find_by_prefix(&PegOutQueuePrefix)
             .await
-            .collect::<Vec<(UTXOKey, SpendableUTXO)>>()
+            .map(|(_, peg_out)| peg_out)
+            .collect::<Vec<_>>()
             .await
     }
 
-    pub async fn get_wallet_value(
+    /// Weight of the tx paying all peg-outs of `batch`, zero if the batch is
+    /// empty or can't be funded anymore
+    async fn batch_weight(
         &self,
         dbtx: &mut ModuleDatabaseTransaction<'_>,
-    ) -> bitcoin::Amount {
-        let sat_sum = self
-            .available_utxos(dbtx)
+        batch: &[PegOut],
+    ) -> u64 {
+        if batch.is_empty() {
+            return 0;
+        }
+
+        self.create_batch_tx(dbtx, batch, &[0; 32])
             .await
-            .into_iter()
-            .map(|(_, utxo)| utxo.amount.to_sat())
-            .sum();
-        bitcoin::Amount::from_sat(sat_sum)
+            .map_or(0, |tx| tx.fees.total_weight)
     }
 
-    fn offline_wallet(&self) -> StatelessWallet {
-        StatelessWallet {
-            descriptor: &self.cfg.consensus.peg_in_descriptor,
-            secret_key: &self.cfg.private.peg_in_key,
-            secp: &self.secp,
-        }
-    }
-}
+    /// Creates a single tx paying all peg-outs of `batch` from the available
+    /// UTXOs
+    async fn create_batch_tx(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        batch: &[PegOut],
+        change_tweak: &[u8; 32],
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let utxos = self.available_utxos(dbtx).await;
+
+        self.offline_wallet()
+            .create_batch_tx(batch, utxos, change_tweak)
+    }
+
+    async fn create_rbf_tx(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        rbf: &Rbf,
+        change_tweak: &[u8; 32],
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let tx = dbtx
+            .get_value(&PendingTransactionKey(rbf.txid))
+            .await
+            .ok_or(WalletError::RbfTransactionIdNotFound)?;
+
+        // Replacing the tx would invalidate the CPFP child spending its change
+        if dbtx.get_value(&CpfpKey(rbf.txid)).await.is_some() {
+            return Err(WalletError::CpfpChildExists);
+        }
+
+        // All outputs besides our change pay a peg-out of the batch, burned dust is
+        // recreated if still needed
+        let change_script = self
+            .cfg
+            .consensus
+            .peg_in_descriptor
+            .tweak(&tx.tweak, &self.secp)
+            .script_pubkey();
+        let peg_outs = tx
+            .tx
+            .output
+            .into_iter()
+            .filter(|output| output.script_pubkey != change_script)
+            .filter(|output| !output.script_pubkey.is_op_return())
+            .collect::<Vec<_>>();
+
+        // A CPFP child only pays ourselves change
+        if peg_outs.is_empty() {
+            return Err(WalletError::RbfOfCpfpTransaction);
+        }
+
+        self.offline_wallet().create_tx(
+            peg_outs,
+            tx.selected_utxos,
+            self.available_utxos(dbtx).await,
+            tx.fees.fee_rate,
+            change_tweak,
+            Some(rbf.clone()),
+        )
+    }
+
+    /// Lists the peg-outs of pending transactions that [`Self::create_rbf_tx`]
+    /// would replace, i.e. that were neither replaced already nor have a CPFP
+    /// child spending their change
+    async fn rbf_capable_peg_outs(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> Vec<RbfCapablePegOut> {
+        let pending = dbtx
+            .find_by_prefix(&PendingTransactionPrefixKey)
+            .await
+            .map(|(_, tx)| tx)
+            .collect::<Vec<_>>()
+            .await;
+        let replaced = pending
+            .iter()
+            .filter_map(|tx| tx.rbf.as_ref().map(|rbf| rbf.txid))
+            .collect::<BTreeSet<_>>();
+        let available = self
+            .available_utxos(dbtx)
+            .await
+            .into_iter()
+            .map(|(_, utxo)| utxo.amount)
+            .sum::<bitcoin::Amount>();
+
+        let mut peg_outs = vec![];
+        for tx in pending {
+            let txid = tx.tx.txid();
+            if replaced.contains(&txid) || dbtx.get_value(&CpfpKey(txid)).await.is_some() {
+                continue;
+            }
+
+            for (vout, _, address) in self.peg_out_outputs(&tx) {
+                peg_outs.push(RbfCapablePegOut {
+                    txid,
+                    current_fee: tx.fees.amount(),
+                    max_bump_fee: tx.change + available,
+                    address,
+                    out_point: bitcoin::OutPoint { txid, vout },
+                });
+            }
+        }
+
+        peg_outs
+    }
+
+    /// Lists the peg-outs of all transactions that were signed in a completed
+    /// session but did not confirm yet, including ones that were replaced
+    /// with RBF
+    async fn pending_peg_outs(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> Vec<PendingPegOut> {
+        let pending = dbtx
+            .find_by_prefix(&PendingTransactionPrefixKey)
+            .await
+            .map(|(_, tx)| tx)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut peg_outs = vec![];
+        for tx in pending {
+            let txid = tx.tx.txid();
+            let Some(epoch_signed) = dbtx.get_value(&PegOutSignedSessionKey(txid)).await else {
+                continue;
+            };
+
+            for (_, amount, address) in self.peg_out_outputs(&tx) {
+                peg_outs.push(PendingPegOut {
+                    txid,
+                    amount,
+                    address,
+                    fee: tx.fees.amount(),
+                    epoch_signed,
+                });
+            }
+        }
+
+        peg_outs
+    }
+
+    /// The outputs of `tx` paying to a peg-out address with their index and
+    /// amount, skipping the change and `OP_RETURN` outputs
+    fn peg_out_outputs(&self, tx: &PendingTransaction) -> Vec<(u32, bitcoin::Amount, Address)> {
+        let change_script = self
+            .cfg
+            .consensus
+            .peg_in_descriptor
+            .tweak(&tx.tweak, &self.secp)
+            .script_pubkey();
+
+        tx.tx
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| {
+                output.script_pubkey != change_script && !output.script_pubkey.is_op_return()
+            })
+            .filter_map(|(vout, output)| {
+                let address =
+                    Address::from_script(&output.script_pubkey, self.cfg.consensus.network).ok()?;
+                Some((
+                    vout as u32,
+                    bitcoin::Amount::from_sat(output.value),
+                    address,
+                ))
+            })
+            .collect()
+    }
+
+    /// Creates a child tx spending the change of the pending tx `cpfp.txid`
+    /// that bumps the fees of both to `cpfp.package_fee`, returns it together
+    /// with the spent change
+    async fn create_cpfp_tx(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        cpfp: &Cpfp,
+        change_tweak: &[u8; 32],
+    ) -> Result<(UnsignedTransaction, SpendableUTXO), WalletError> {
+        let parent = dbtx
+            .get_value(&PendingTransactionKey(cpfp.txid))
+            .await
+            .ok_or(WalletError::CpfpParentNotFound)?;
+
+        if dbtx.get_value(&CpfpKey(cpfp.txid)).await.is_some() {
+            return Err(WalletError::CpfpChildExists);
+        }
+
+        // Replacing the parent via RBF would invalidate the child
+        let pending_rbfs = dbtx
+            .find_by_prefix(&PendingTransactionPrefixKey)
+            .await
+            .map(|(_, tx)| tx.rbf)
+            .collect::<Vec<_>>()
+            .await;
+        let unsigned_rbfs = dbtx
+            .find_by_prefix(&UnsignedTransactionPrefixKey)
+            .await
+            .map(|(_, tx)| tx.rbf)
+            .collect::<Vec<_>>()
+            .await;
+        if pending_rbfs
+            .into_iter()
+            .chain(unsigned_rbfs)
+            .flatten()
+            .any(|rbf| rbf.txid == cpfp.txid)
+        {
+            return Err(WalletError::CpfpParentReplaced);
+        }
+
+        let input_sats: u64 = parent
+            .selected_utxos
+            .iter()
+            .map(|(_, utxo)| utxo.amount.to_sat())
+            .sum();
+        let output_sats: u64 = parent.tx.output.iter().map(|output| output.value).sum();
+        let parent_fee = bitcoin::Amount::from_sat(input_sats - output_sats);
+        if parent_fee != cpfp.parent_fee {
+            return Err(WalletError::CpfpParentFeeIncorrect(
+                parent_fee,
+                cpfp.parent_fee,
+            ));
+        }
+
+        let change_script = self
+            .cfg
+            .consensus
+            .peg_in_descriptor
+            .tweak(&parent.tweak, &self.secp)
+            .script_pubkey();
+        let (vout, change_output) = parent
+            .tx
+            .output
+            .iter()
+            .enumerate()
+            .find(|(_, output)| output.script_pubkey == change_script)
+            .ok_or(WalletError::CpfpParentHasNoChange)?;
+        let parent_change = (
+            UTXOKey(bitcoin::OutPoint {
+                txid: cpfp.txid,
+                vout: vout as u32,
+            }),
+            SpendableUTXO {
+                tweak: parent.tweak,
+                amount: bitcoin::Amount::from_sat(change_output.value),
+            },
+        );
+
+        let fee_rate = self.consensus_fee_rate(dbtx).await;
+
+        let tx = self.offline_wallet().create_cpfp_tx(
+            parent_change.clone(),
+            cpfp,
+            parent.fees.total_weight,
+            fee_rate,
+            change_tweak,
+        )?;
+
+        Ok((tx, parent_change.1))
+    }
+
+    /// Signs the peg-out tx and stores it together with our signatures until
+    /// we received a threshold of signatures from our peers, returns its txid
+    async fn sign_peg_out_tx(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+        mut tx: UnsignedTransaction,
+    ) -> Txid {
+        self.offline_wallet().sign_psbt(&mut tx.psbt);
+
+        let txid = tx.psbt.unsigned_tx.txid();
+
+        info!(
+            %txid,
+            "Signing peg out",
+        );
+
+        let sigs = tx
+            .psbt
+            .inputs
+            .iter_mut()
+            .map(|input| {
+                assert_eq!(
+                    input.partial_sigs.len(),
+                    1,
+                    "There was already more than one (our) or no signatures in input"
+                );
+
+                // TODO: don't put sig into PSBT in the first place
+                // We actually take out our own signature so everyone finalizes the tx in the
+                // same epoch.
+                let sig = std::mem::take(&mut input.partial_sigs)
+                    .into_values()
+                    .next()
+                    .expect("asserted previously");
+
+                // We drop SIGHASH_ALL, because we always use that and it is only present in the
+                // PSBT for compatibility with other tools.
+                secp256k1::ecdsa::Signature::from_der(&sig.to_vec()[..sig.to_vec().len() - 1])
+                    .expect("we serialized it ourselves that way")
+            })
+            .collect::<Vec<_>>();
+
+        // Delete used UTXOs
+        for input in tx.psbt.unsigned_tx.input.iter() {
+            dbtx.remove_entry(&UTXOKey(input.previous_output)).await;
+        }
+
+        dbtx.insert_new_entry(&UnsignedTransactionKey(txid), &tx)
+            .await;
+
+        dbtx.insert_new_entry(&PegOutTxSignatureCI(txid), &sigs)
+            .await;
+
+        txid
+    }
+
+    async fn available_utxos(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> Vec<(UTXOKey, SpendableUTXO)> {
+        dbtx.find_by_prefix(&UTXOPrefixKey)
+            .await
+            .collect::<Vec<(UTXOKey, SpendableUTXO)>>()
+            .await
+    }
+
+    pub async fn get_wallet_value(
+        &self,
+        dbtx: &mut ModuleDatabaseTransaction<'_>,
+    ) -> bitcoin::Amount {
+        let sat_sum = self
+            .available_utxos(dbtx)
+            .await
+            .into_iter()
+            .map(|(_, utxo)| utxo.amount.to_sat())
+            .sum();
+        bitcoin::Amount::from_sat(sat_sum)
+    }
+
+    /// Signs the peg-in descriptor tweaked with `tweak` to attest that the
+    /// resulting address belongs to the federation
+    fn sign_address_proof(&self, tweak: &secp256k1::XOnlyPublicKey) -> AddressProofSignature {
+        let descriptor = self
+            .cfg
+            .consensus
+            .peg_in_descriptor
+            .tweak(tweak, &self.secp);
+        let signature = self.secp.sign_ecdsa(
+            &AddressProof::message(&descriptor),
+            &self.cfg.private.peg_in_key,
+        );
+
+        AddressProofSignature {
+            peer_id: self.our_peer_id,
+            signature,
+        }
+    }
+
+    fn offline_wallet(&self) -> StatelessWallet {
+        StatelessWallet {
+            descriptor: &self.cfg.consensus.peg_in_descriptor,
+            secret_key: &self.cfg.private.peg_in_key,
+            secp: &self.secp,
+            dust_change_policy: self.cfg.consensus.dust_change_policy,
+        }
+    }
+}
 
 #[instrument(level = "debug", skip_all)]
 pub async fn run_broadcast_pending_tx(db: Database, rpc: DynBitcoindRpc, tg_handle: &TaskHandle) {
@@ -1171,6 +1865,7 @@ struct StatelessWallet<'a> {
     descriptor: &'a Descriptor<CompressedPublicKey>,
     secret_key: &'a secp256k1::SecretKey,
     secp: &'a secp256k1::Secp256k1<secp256k1::All>,
+    dust_change_policy: Option<DustChangePolicy>,
 }
 
 impl<'a> StatelessWallet<'a> {
@@ -1210,6 +1905,8 @@ impl<'a> StatelessWallet<'a> {
         let fees = match output {
             WalletOutput::PegOut(pegout) => pegout.fees,
             WalletOutput::Rbf(rbf) => rbf.fees,
+            // CPFP transactions are validated when they are created
+            WalletOutput::Cpfp(_) => return Err(WalletError::UnexpectedCpfpOutput),
         };
         if fees.fee_rate.sats_per_kvb < DEFAULT_MIN_RELAY_TX_FEE as u64 {
             return Err(WalletError::BelowMinRelayFee);
@@ -1226,10 +1923,109 @@ impl<'a> StatelessWallet<'a> {
         Ok(())
     }
 
+    /// Validates `peg_out` is economical to include in the batch `tx` and that
+    /// it pays for the weight it adds to the tx of the already queued
+    /// peg-outs, which weighs `queued_weight`
+    fn validate_batch(
+        &self,
+        tx: &UnsignedTransaction,
+        peg_out: &PegOut,
+        queued_weight: u64,
+        consensus_fee_rate: Feerate,
+        network: Network,
+    ) -> Result<(), WalletError> {
+        if !peg_out.recipient.is_valid_for_network(network) {
+            return Err(WalletError::WrongNetwork(
+                network,
+                peg_out.recipient.network,
+            ));
+        }
+
+        if peg_out.amount < peg_out.recipient.script_pubkey().dust_value() {
+            return Err(WalletError::PegOutUnderDustLimit);
+        }
+
+        // Including a peg-out that is worth less than the fees it pays only burns
+        // the user's funds
+        if peg_out.amount <= peg_out.fees.amount() {
+            return Err(WalletError::PegOutUneconomical);
+        }
+
+        if peg_out.fees.fee_rate < consensus_fee_rate {
+            return Err(WalletError::PegOutFeeBelowConsensus(
+                peg_out.fees.fee_rate,
+                consensus_fee_rate,
+            ));
+        }
+
+        if peg_out.fees.fee_rate.sats_per_kvb < DEFAULT_MIN_RELAY_TX_FEE as u64 {
+            return Err(WalletError::BelowMinRelayFee);
+        }
+
+        // Every queued peg-out paid for the weight it added at a fee rate at least as
+        // high as the one of the tx, so together they pay for the whole tx and the
+        // federation doesn't pay for any fees
+        let marginal_weight = tx.fees.total_weight.saturating_sub(queued_weight);
+        if peg_out.fees.total_weight < marginal_weight {
+            return Err(WalletError::TxWeightIncorrect(
+                peg_out.fees.total_weight,
+                marginal_weight,
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Creates a single tx paying all peg-outs of `batch` at the lowest fee
+    /// rate any of them pays
+    fn create_batch_tx(
+        &self,
+        batch: &[PegOut],
+        utxos: Vec<(UTXOKey, SpendableUTXO)>,
+        change_tweak: &[u8; 32],
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let fee_rate = batch
+            .iter()
+            .map(|peg_out| peg_out.fees.fee_rate)
+            .min()
+            .expect("Batch contains at least one peg-out");
+
+        let peg_outs = batch
+            .iter()
+            .map(|peg_out| TxOut {
+                value: peg_out.amount.to_sat(),
+                script_pubkey: peg_out.recipient.script_pubkey(),
+            })
+            .collect();
+
+        self.create_tx(peg_outs, vec![], utxos, fee_rate, change_tweak, None)
+    }
+
+    /// Creates a tx paying the longest prefix of `batch` that `utxos` can fund
+    /// and returns it together with the number of peg-outs it pays. Fails with
+    /// the error of the first peg-out if not even that one can be paid.
+    fn create_largest_batch_tx(
+        &self,
+        batch: &[PegOut],
+        utxos: Vec<(UTXOKey, SpendableUTXO)>,
+        change_tweak: &[u8; 32],
+    ) -> Result<(UnsignedTransaction, usize), WalletError> {
+        let mut last_error = None;
+
+        for paid in (1..=batch.len()).rev() {
+            match self.create_batch_tx(&batch[..paid], utxos.clone(), change_tweak) {
+                Ok(tx) => return Ok((tx, paid)),
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.expect("Batch contains at least one peg-out"))
+    }
+
     /// Attempts to create a tx ready to be signed from available UTXOs.
     //
-    // * `peg_out_amount`: How much the peg-out should be
-    // * `destination`: The address the user is pegging-out to
+    // * `peg_outs`: The outputs paying the users pegging-out, several peg-outs
+    //   can be batched into one tx
     // * `included_utxos`: UXTOs that must be included (for RBF)
     // * `remaining_utxos`: All other spendable UXTOs
     // * `fee_rate`: How much needs to be spent on fees
@@ -1238,8 +2034,7 @@ impl<'a> StatelessWallet<'a> {
     #[allow(clippy::too_many_arguments)]
     fn create_tx(
         &self,
-        peg_out_amount: bitcoin::Amount,
-        destination: Script,
+        mut peg_outs: Vec<TxOut>,
         mut included_utxos: Vec<(UTXOKey, SpendableUTXO)>,
         mut remaining_utxos: Vec<(UTXOKey, SpendableUTXO)>,
         mut fee_rate: Feerate,
@@ -1259,7 +2054,17 @@ impl<'a> StatelessWallet<'a> {
         // and the maximum weight per added input which we will add every time
         // we select an input.
         let change_script = self.derive_script(change_tweak);
-        let out_weight = (destination.len() * 4 + 1 + 32
+        let destination = peg_outs
+            .first()
+            .expect("Tx pays at least one peg-out")
+            .script_pubkey
+            .clone();
+        let mut peg_out_amount =
+            bitcoin::Amount::from_sat(peg_outs.iter().map(|out| out.value).sum());
+        let out_weight = (peg_outs
+            .iter()
+            .map(|out| out.script_pubkey.len() * 4 + 1 + 32)
+            .sum::<usize>()
             // Add change script weight, it's very likely to be needed if not we just overpay in fees
             + 1 // script len varint, 1 byte for all addresses we accept
             + change_script.len() * 4 // script len
@@ -1269,13 +2074,7 @@ impl<'a> StatelessWallet<'a> {
             12 + // up to 2**16-1 outputs
             out_weight + // weight of all outputs
             16; // lock time
-        let max_input_weight = (self
-            .descriptor
-            .max_satisfaction_weight()
-            .expect("is satisfyable") +
-            128 + // TxOutHash
-            16 + // TxOutIndex
-            16) as u64; // sequence
+        let max_input_weight = self.max_input_weight();
 
         // Ensure deterministic ordering of UTXOs for all peers
         included_utxos.sort_by_key(|(_, utxo)| utxo.amount);
@@ -1295,25 +2094,61 @@ impl<'a> StatelessWallet<'a> {
                     fees = fee_rate.calculate_fee(total_weight);
                     selected_utxos.push((utxo_key, utxo));
                 }
+                // If the federation agreed on a dust change policy we can still fund the
+                // peg-out, otherwise we would lose the dust
+                None if self.dust_change_policy.is_some()
+                    && total_selected_value >= peg_out_amount + fees =>
+                {
+                    break
+                }
                 _ => return Err(WalletError::NotEnoughSpendableUTXO), // Not enough UTXOs
             }
         }
 
-        // We always pay ourselves change back to ensure that we don't lose anything due
-        // to dust
-        let change = total_selected_value - fees - peg_out_amount;
-        let output: Vec<TxOut> = vec![
-            TxOut {
-                value: peg_out_amount.to_sat(),
-                script_pubkey: destination.clone(),
-            },
-            TxOut {
-                value: change.to_sat(),
-                script_pubkey: change_script,
-            },
-        ];
-        let mut change_out = bitcoin::util::psbt::Output::default();
-        change_out
+        // We pay ourselves change back to ensure that we don't lose anything due to
+        // dust, unless the change is dust itself
+        let mut change = total_selected_value - fees - peg_out_amount;
+        let mut change_output = Some(TxOut {
+            value: change.to_sat(),
+            script_pubkey: change_script.clone(),
+        });
+        if change < change_script.dust_value() {
+            let dust = change;
+            change = bitcoin::Amount::ZERO;
+            change_output = match self
+                .dust_change_policy
+                .expect("Change is only dust if we have a policy")
+            {
+                DustChangePolicy::Burn => Some(TxOut {
+                    value: dust.to_sat(),
+                    script_pubkey: Script::new_op_return(&[]),
+                }),
+                DustChangePolicy::AddToFee => None,
+                DustChangePolicy::Round => {
+                    peg_out_amount += dust;
+                    peg_outs
+                        .last_mut()
+                        .expect("Tx pays at least one peg-out")
+                        .value += dust.to_sat();
+                    None
+                }
+            };
+            info!(
+                dust_sats = dust.to_sat(),
+                policy = ?self.dust_change_policy,
+                "Peg-out has dust change",
+            );
+        }
+
+        let mut output = peg_outs;
+        output.extend(change_output);
+
+        // Finalizing the peg-out requires the change tweak, so we store it even if we
+        // don't pay ourselves change
+        let mut psbt_outputs = vec![bitcoin::util::psbt::Output::default(); output.len()];
+        psbt_outputs
+            .last_mut()
+            .expect("Has peg-out output")
             .proprietary
             .insert(proprietary_tweak_key(), change_tweak.to_vec());
 
@@ -1353,47 +2188,9 @@ impl<'a> StatelessWallet<'a> {
             unknown: Default::default(),
             inputs: selected_utxos
                 .iter()
-                .map(|(_utxo_key, utxo)| {
-                    let script_pubkey = self
-                        .descriptor
-                        .tweak(&utxo.tweak, self.secp)
-                        .script_pubkey();
-                    Input {
-                        non_witness_utxo: None,
-                        witness_utxo: Some(TxOut {
-                            value: utxo.amount.to_sat(),
-                            script_pubkey,
-                        }),
-                        partial_sigs: Default::default(),
-                        sighash_type: None,
-                        redeem_script: None,
-                        witness_script: Some(
-                            self.descriptor
-                                .tweak(&utxo.tweak, self.secp)
-                                .script_code()
-                                .expect("Failed to tweak descriptor"),
-                        ),
-                        bip32_derivation: Default::default(),
-                        final_script_sig: None,
-                        final_script_witness: None,
-                        ripemd160_preimages: Default::default(),
-                        sha256_preimages: Default::default(),
-                        hash160_preimages: Default::default(),
-                        hash256_preimages: Default::default(),
-                        proprietary: vec![(proprietary_tweak_key(), utxo.tweak.to_vec())]
-                            .into_iter()
-                            .collect(),
-                        tap_key_sig: Default::default(),
-                        tap_script_sigs: Default::default(),
-                        tap_scripts: Default::default(),
-                        tap_key_origins: Default::default(),
-                        tap_internal_key: Default::default(),
-                        tap_merkle_root: Default::default(),
-                        unknown: Default::default(),
-                    }
-                })
+                .map(|(_utxo_key, utxo)| self.psbt_input(utxo))
                 .collect(),
-            outputs: vec![Default::default(), change_out],
+            outputs: psbt_outputs,
         };
 
         Ok(UnsignedTransaction {
@@ -1411,6 +2208,158 @@ impl<'a> StatelessWallet<'a> {
         })
     }
 
+    /// Creates a tx that only spends the `parent_change` of a pending tx to a
+    /// new change output, paying enough fees for the parent and child to
+    /// reach `cpfp.package_fee` together (CPFP)
+    fn create_cpfp_tx(
+        &self,
+        parent_change: (UTXOKey, SpendableUTXO),
+        cpfp: &Cpfp,
+        parent_weight: u64,
+        consensus_fee_rate: Feerate,
+        change_tweak: &[u8],
+    ) -> Result<UnsignedTransaction, WalletError> {
+        let child_fee = cpfp.child_fee();
+        if child_fee == bitcoin::Amount::ZERO {
+            return Err(WalletError::CpfpPackageFeeTooLow);
+        }
+
+        let change_script = self.derive_script(change_tweak);
+        let total_weight = 16 + // version
+            12 + // up to 2**16-1 inputs
+            12 + // up to 2**16-1 outputs
+            (change_script.len() * 4 + 1 + 32) as u64 + // change output
+            16 + // lock time
+            self.max_input_weight();
+
+        // Miners consider the fee rate of parent and child combined
+        let package_fee_rate = Feerate {
+            sats_per_kvb: cpfp.package_fee.to_sat() * 1000 / (parent_weight + total_weight),
+        };
+        if package_fee_rate < consensus_fee_rate {
+            return Err(WalletError::PegOutFeeBelowConsensus(
+                package_fee_rate,
+                consensus_fee_rate,
+            ));
+        }
+
+        let fee_rate = Feerate {
+            sats_per_kvb: child_fee.to_sat() * 1000 / total_weight,
+        };
+        if fee_rate.sats_per_kvb < DEFAULT_MIN_RELAY_TX_FEE as u64 {
+            return Err(WalletError::BelowMinRelayFee);
+        }
+
+        let (utxo_key, utxo) = parent_change;
+        let change = utxo
+            .amount
+            .checked_sub(child_fee)
+            .filter(|change| *change >= change_script.dust_value())
+            .ok_or(WalletError::CpfpChangeTooSmall)?;
+
+        info!(
+            parent = %cpfp.txid,
+            input_sats = utxo.amount.to_sat(),
+            fees_sats = child_fee.to_sat(),
+            change_sats = change.to_sat(),
+            "Creating CPFP tx",
+        );
+
+        let transaction = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: utxo_key.0,
+                script_sig: Default::default(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: change.to_sat(),
+                script_pubkey: change_script.clone(),
+            }],
+        };
+
+        let mut psbt_output = bitcoin::util::psbt::Output::default();
+        psbt_output
+            .proprietary
+            .insert(proprietary_tweak_key(), change_tweak.to_vec());
+
+        let psbt = PartiallySignedTransaction {
+            unsigned_tx: transaction,
+            version: 0,
+            xpub: Default::default(),
+            proprietary: Default::default(),
+            unknown: Default::default(),
+            inputs: vec![self.psbt_input(&utxo)],
+            outputs: vec![psbt_output],
+        };
+
+        Ok(UnsignedTransaction {
+            psbt,
+            signatures: vec![],
+            change,
+            fees: PegOutFees {
+                fee_rate,
+                total_weight,
+            },
+            destination: change_script,
+            selected_utxos: vec![(utxo_key, utxo)],
+            peg_out_amount: bitcoin::Amount::ZERO,
+            rbf: None,
+        })
+    }
+
+    fn max_input_weight(&self) -> u64 {
+        (self
+            .descriptor
+            .max_satisfaction_weight()
+            .expect("is satisfyable") +
+            128 + // TxOutHash
+            16 + // TxOutIndex
+            16) as u64 // sequence
+    }
+
+    fn psbt_input(&self, utxo: &SpendableUTXO) -> Input {
+        let script_pubkey = self
+            .descriptor
+            .tweak(&utxo.tweak, self.secp)
+            .script_pubkey();
+        Input {
+            non_witness_utxo: None,
+            witness_utxo: Some(TxOut {
+                value: utxo.amount.to_sat(),
+                script_pubkey,
+            }),
+            partial_sigs: Default::default(),
+            sighash_type: None,
+            redeem_script: None,
+            witness_script: Some(
+                self.descriptor
+                    .tweak(&utxo.tweak, self.secp)
+                    .script_code()
+                    .expect("Failed to tweak descriptor"),
+            ),
+            bip32_derivation: Default::default(),
+            final_script_sig: None,
+            final_script_witness: None,
+            ripemd160_preimages: Default::default(),
+            sha256_preimages: Default::default(),
+            hash160_preimages: Default::default(),
+            hash256_preimages: Default::default(),
+            proprietary: vec![(proprietary_tweak_key(), utxo.tweak.to_vec())]
+                .into_iter()
+                .collect(),
+            tap_key_sig: Default::default(),
+            tap_script_sigs: Default::default(),
+            tap_scripts: Default::default(),
+            tap_key_origins: Default::default(),
+            tap_internal_key: Default::default(),
+            tap_merkle_root: Default::default(),
+            unknown: Default::default(),
+        }
+    }
+
     fn sign_psbt(&self, psbt: &mut PartiallySignedTransaction) {
         let mut tx_hasher = SighashCache::new(&psbt.unsigned_tx);
 
@@ -1501,19 +2450,72 @@ impl<'a> StatelessWallet<'a> {
     }
 }
 
+/// Median of the fee rate votes of `peer_count` peers, peers that haven't voted
+/// count as voting for `default_fee`
+fn median_fee_rate(mut rates: Vec<Feerate>, peer_count: usize, default_fee: Feerate) -> Feerate {
+    assert!(rates.len() <= peer_count);
+
+    while rates.len() < peer_count {
+        rates.push(default_fee);
+    }
+
+    rates.sort_unstable();
+
+    rates[peer_count / 2]
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::str::FromStr;
 
     use bitcoin::Network::{Bitcoin, Testnet};
-    use bitcoin::{Address, Amount, Network, OutPoint, Txid};
+    use bitcoin::{Address, Amount, Network, OutPoint, TxOut, Txid};
     use fedimint_core::{BitcoinHash, Feerate};
-    use fedimint_wallet_common::{PegOut, PegOutFees, Rbf, WalletOutput};
+    use fedimint_wallet_common::config::DustChangePolicy;
+    use fedimint_wallet_common::{
+        Cpfp, PegOut, PegOutFees, Rbf, UnsignedTransaction, WalletOutput,
+    };
     use miniscript::descriptor::Wsh;
 
     use crate::common::PegInDescriptor;
-    use crate::{CompressedPublicKey, OsRng, SpendableUTXO, StatelessWallet, UTXOKey, WalletError};
+    use crate::{
+        median_fee_rate, CompressedPublicKey, OsRng, SpendableUTXO, StatelessWallet, UTXOKey,
+        WalletError,
+    };
+
+    #[test]
+    fn median_of_divergent_fee_rate_votes_is_adopted() {
+        let default_fee = Feerate { sats_per_kvb: 1000 };
+        let votes = |rates: &[u64]| {
+            rates
+                .iter()
+                .map(|sats_per_kvb| Feerate {
+                    sats_per_kvb: *sats_per_kvb,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            median_fee_rate(votes(&[2000, 50_000, 3000, 1]), 4, default_fee),
+            Feerate { sats_per_kvb: 3000 }
+        );
+        // a single peer can't move the fee rate, no matter how far it deviates
+        assert_eq!(
+            median_fee_rate(votes(&[2000, 2000, 2000, u64::MAX]), 4, default_fee),
+            Feerate { sats_per_kvb: 2000 }
+        );
+        assert_eq!(
+            median_fee_rate(votes(&[0, 2000, 2000, 2000]), 4, default_fee),
+            Feerate { sats_per_kvb: 2000 }
+        );
+        // peers that haven't voted count as voting for the default fee rate
+        assert_eq!(
+            median_fee_rate(votes(&[5000, 7000]), 4, default_fee),
+            Feerate { sats_per_kvb: 5000 }
+        );
+        assert_eq!(median_fee_rate(votes(&[]), 4, default_fee), default_fee);
+    }
 
     #[test]
     fn create_tx_should_validate_amounts() {
@@ -1536,6 +2538,7 @@ mod tests {
             descriptor: &descriptor,
             secret_key: &secret_key,
             secp: &secp,
+            dust_change_policy: None,
         };
 
         let spendable = SpendableUTXO {
@@ -1550,8 +2553,10 @@ mod tests {
 
         // not enough SpendableUTXO
         let tx = wallet.create_tx(
-            Amount::from_sat(2000),
-            recipient.script_pubkey(),
+            vec![TxOut {
+                value: 2000,
+                script_pubkey: recipient.script_pubkey(),
+            }],
             vec![],
             vec![(UTXOKey(OutPoint::null()), spendable.clone())],
             fee,
@@ -1563,8 +2568,10 @@ mod tests {
         // successful tx creation
         let mut tx = wallet
             .create_tx(
-                Amount::from_sat(1000),
-                recipient.script_pubkey(),
+                vec![TxOut {
+                    value: 1000,
+                    script_pubkey: recipient.script_pubkey(),
+                }],
                 vec![],
                 vec![(UTXOKey(OutPoint::null()), spendable)],
                 fee,
@@ -1609,6 +2616,236 @@ mod tests {
         });
         let res = wallet.validate_tx(&tx, &output, fee, Testnet);
         assert_eq!(res, Err(WalletError::WrongNetwork(Testnet, Bitcoin)));
+
+        // CPFP outputs are not peg-outs
+        tx.peg_out_amount = Amount::from_sat(1000);
+        tx.fees = PegOutFees::new(fee.sats_per_kvb, weight);
+        let output = WalletOutput::Cpfp(Cpfp {
+            txid: Txid::all_zeros(),
+            parent_fee: Amount::from_sat(100),
+            package_fee: Amount::from_sat(1000),
+        });
+        let res = wallet.validate_tx(&tx, &output, fee, Bitcoin);
+        assert_eq!(res, Err(WalletError::UnexpectedCpfpOutput));
+    }
+
+    #[test]
+    fn largest_fundable_prefix_of_queued_peg_outs_is_batched() {
+        let secp = secp256k1::Secp256k1::new();
+
+        let descriptor = PegInDescriptor::Wsh(
+            Wsh::new_sortedmulti(
+                3,
+                (0..4)
+                    .map(|_| secp.generate_keypair(&mut OsRng))
+                    .map(|(_, key)| CompressedPublicKey { key })
+                    .collect(),
+            )
+            .unwrap(),
+        );
+
+        let (secret_key, _) = secp.generate_keypair(&mut OsRng);
+
+        let wallet = StatelessWallet {
+            descriptor: &descriptor,
+            secret_key: &secret_key,
+            secp: &secp,
+            dust_change_policy: None,
+        };
+
+        let recipient = Address::from_str("32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf").unwrap();
+        let peg_out = PegOut {
+            recipient,
+            amount: Amount::from_sat(1500),
+            fees: PegOutFees::new(1000, 0),
+        };
+        let batch = vec![peg_out.clone(), peg_out];
+        let utxos = |sats| {
+            vec![(
+                UTXOKey(OutPoint::null()),
+                SpendableUTXO {
+                    tweak: [0; 32],
+                    amount: Amount::from_sat(sats),
+                },
+            )]
+        };
+
+        // enough funds for the whole batch
+        let (tx, paid) = wallet
+            .create_largest_batch_tx(&batch, utxos(10_000), &[0; 32])
+            .expect("is ok");
+        assert_eq!(paid, 2);
+        assert_eq!(tx.peg_out_amount, Amount::from_sat(3000));
+
+        // only the first peg-out can be funded, the second one stays queued
+        let (tx, paid) = wallet
+            .create_largest_batch_tx(&batch, utxos(3000), &[0; 32])
+            .expect("is ok");
+        assert_eq!(paid, 1);
+        assert_eq!(tx.peg_out_amount, Amount::from_sat(1500));
+
+        // not even the first peg-out can be funded
+        let res = wallet.create_largest_batch_tx(&batch, vec![], &[0; 32]);
+        assert_eq!(res, Err(WalletError::NotEnoughSpendableUTXO));
+    }
+
+    #[test]
+    fn queued_peg_outs_pay_for_the_weight_they_add() {
+        let secp = secp256k1::Secp256k1::new();
+
+        let descriptor = PegInDescriptor::Wsh(
+            Wsh::new_sortedmulti(
+                3,
+                (0..4)
+                    .map(|_| secp.generate_keypair(&mut OsRng))
+                    .map(|(_, key)| CompressedPublicKey { key })
+                    .collect(),
+            )
+            .unwrap(),
+        );
+
+        let (secret_key, _) = secp.generate_keypair(&mut OsRng);
+
+        let wallet = StatelessWallet {
+            descriptor: &descriptor,
+            secret_key: &secret_key,
+            secp: &secp,
+            dust_change_policy: None,
+        };
+
+        let recipient = Address::from_str("32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf").unwrap();
+        let fee = Feerate { sats_per_kvb: 1000 };
+        let peg_out = PegOut {
+            recipient,
+            amount: Amount::from_sat(1500),
+            fees: PegOutFees::new(fee.sats_per_kvb, 0),
+        };
+        let utxos = vec![(
+            UTXOKey(OutPoint::null()),
+            SpendableUTXO {
+                tweak: [0; 32],
+                amount: Amount::from_sat(10_000),
+            },
+        )];
+
+        let alone = wallet
+            .create_batch_tx(&[peg_out.clone()], utxos.clone(), &[0; 32])
+            .expect("is ok");
+        let batched = wallet
+            .create_batch_tx(&[peg_out.clone(), peg_out.clone()], utxos, &[0; 32])
+            .expect("is ok");
+        let alone_weight = alone.fees.total_weight;
+        let marginal_weight = batched.fees.total_weight - alone_weight;
+        assert!(marginal_weight < alone_weight);
+
+        // the weight of the tx paying the peg-out alone is enough to join a batch
+        let quoted = PegOut {
+            fees: PegOutFees::new(fee.sats_per_kvb, alone_weight),
+            ..peg_out.clone()
+        };
+        let res = wallet.validate_batch(&batched, &quoted, alone_weight, fee, Bitcoin);
+        assert_eq!(res, Ok(()));
+        let res = wallet.validate_batch(&alone, &quoted, 0, fee, Bitcoin);
+        assert_eq!(res, Ok(()));
+
+        // paying for the added weight is enough, but not for less
+        let marginal = PegOut {
+            fees: PegOutFees::new(fee.sats_per_kvb, marginal_weight),
+            ..peg_out.clone()
+        };
+        let res = wallet.validate_batch(&batched, &marginal, alone_weight, fee, Bitcoin);
+        assert_eq!(res, Ok(()));
+        let res = wallet.validate_batch(&alone, &marginal, 0, fee, Bitcoin);
+        assert_eq!(
+            res,
+            Err(WalletError::TxWeightIncorrect(
+                marginal_weight,
+                alone_weight
+            ))
+        );
+    }
+
+    /// Creates a peg-out of 2000 sats from a 3000 sats UTXO, leaving 125 sats of
+    /// change after paying 875 sats in fees
+    fn create_tx_with_dust_change(policy: DustChangePolicy) -> UnsignedTransaction {
+        let secp = secp256k1::Secp256k1::new();
+
+        let descriptor = PegInDescriptor::Wsh(
+            Wsh::new_sortedmulti(
+                3,
+                (0..4)
+                    .map(|_| secp.generate_keypair(&mut OsRng))
+                    .map(|(_, key)| CompressedPublicKey { key })
+                    .collect(),
+            )
+            .unwrap(),
+        );
+
+        let (secret_key, _) = secp.generate_keypair(&mut OsRng);
+
+        let wallet = StatelessWallet {
+            descriptor: &descriptor,
+            secret_key: &secret_key,
+            secp: &secp,
+            dust_change_policy: Some(policy),
+        };
+
+        let spendable = SpendableUTXO {
+            tweak: [0; 32],
+            amount: Amount::from_sat(3000),
+        };
+
+        let recipient = Address::from_str("32iVBEu4dxkUQk9dJbZUiBiQdmypcEyJRf").unwrap();
+
+        let tx = wallet
+            .create_tx(
+                vec![TxOut {
+                    value: 2000,
+                    script_pubkey: recipient.script_pubkey(),
+                }],
+                vec![],
+                vec![(UTXOKey(OutPoint::null()), spendable)],
+                Feerate { sats_per_kvb: 1000 },
+                &[],
+                None,
+            )
+            .expect("is ok");
+
+        assert_eq!(tx.change, Amount::ZERO);
+        assert_eq!(tx.fees.amount(), Amount::from_sat(875));
+        tx
+    }
+
+    #[test]
+    fn dust_change_can_be_burned() {
+        let tx = create_tx_with_dust_change(DustChangePolicy::Burn);
+        let outputs = &tx.psbt.unsigned_tx.output;
+
+        assert_eq!(tx.peg_out_amount, Amount::from_sat(2000));
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].value, 2000);
+        assert!(outputs[1].script_pubkey.is_op_return());
+        assert_eq!(outputs[1].value, 125);
+    }
+
+    #[test]
+    fn dust_change_can_be_added_to_fee() {
+        let tx = create_tx_with_dust_change(DustChangePolicy::AddToFee);
+        let outputs = &tx.psbt.unsigned_tx.output;
+
+        assert_eq!(tx.peg_out_amount, Amount::from_sat(2000));
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].value, 2000);
+    }
+
+    #[test]
+    fn dust_change_can_be_rounded_into_peg_out() {
+        let tx = create_tx_with_dust_change(DustChangePolicy::Round);
+        let outputs = &tx.psbt.unsigned_tx.output;
+
+        assert_eq!(tx.peg_out_amount, Amount::from_sat(2125));
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].value, 2125);
     }
 
     fn rbf(sats_per_kvb: u64, total_weight: u64) -> WalletOutput {
@@ -1902,6 +3139,18 @@ mod fedimint_migration_tests {
                                 .await
                                 .is_some());
                         }
+                        // Peg-outs are only batched since after the v0 snapshot was taken
+                        DbKeyPrefix::PegOutQueue => {}
+                        // CPFP was introduced after the v0 snapshot was taken
+                        DbKeyPrefix::Cpfp => {}
+                        // Fee targets were introduced after the v0 snapshot was taken
+                        DbKeyPrefix::TargetFeeRateVote => {}
+                        // Fees are only accumulated since after the v0 snapshot was taken
+                        DbKeyPrefix::PegOutFees => {}
+                        // Fee rate votes are only recorded since after the v0 snapshot was taken
+                        DbKeyPrefix::FeeRateVoteHistory => {}
+                        // Signing sessions are only recorded since after the v0 snapshot was taken
+                        DbKeyPrefix::PegOutSignedSession => {}
                         DbKeyPrefix::UnsignedTransaction => {
                             let unsigned_txs = dbtx
                                 .find_by_prefix(&UnsignedTransactionPrefixKey)
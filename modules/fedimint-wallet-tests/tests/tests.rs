@@ -1,47 +1,406 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::str::FromStr;
 use std::time::{Duration, SystemTime};
 
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, ensure, Context};
 use assert_matches::assert_matches;
+use bitcoin::hashes::Hash;
 use bitcoin::secp256k1::rand::rngs::OsRng;
 use bitcoin::secp256k1::{self, Secp256k1};
+use bitcoin::BlockHash;
 use fedimint_bitcoind::DynBitcoindRpc;
+use fedimint_client::module::ClientModule;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
 use fedimint_client::Client;
+use fedimint_core::api::{EventType, GlobalFederationApi, IFederationApi};
 use fedimint_core::bitcoinrpc::BitcoinRpcConfig;
+use fedimint_core::core::{DynInput, IntoDynInstance};
 use fedimint_core::db::mem_impl::MemDatabase;
 use fedimint_core::db::{Database, ModuleDatabaseTransaction};
-use fedimint_core::task::sleep;
+use fedimint_core::encoding::Encodable;
+use fedimint_core::endpoint_constants::TRANSACTION_ENDPOINT;
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::task::{sleep, timeout, TaskGroup};
+use fedimint_core::txoproof::TxOutProof;
 use fedimint_core::util::{BoxStream, NextOrPending};
-use fedimint_core::{sats, Amount, Feerate, PeerId, ServerModule};
+use fedimint_core::{sats, Amount, Feerate, NumPeers, PeerId, ServerModule};
 use fedimint_dummy_client::DummyClientGen;
 use fedimint_dummy_common::config::DummyGenParams;
 use fedimint_dummy_server::DummyGen;
-use fedimint_testing::btc::BitcoinTest;
+use fedimint_mint_client::MintClientGen;
+use fedimint_mint_common::config::MintGenParams;
+use fedimint_mint_server::MintGen;
+use fedimint_testing::btc::{AddressType, BitcoinTest};
+use fedimint_testing::federation::FederationTest;
 use fedimint_testing::fixtures::Fixtures;
 use fedimint_wallet_client::api::WalletFederationApi;
 use fedimint_wallet_client::{
-    DepositState, WalletClientExt, WalletClientGen, WalletClientModule, WithdrawState,
+    AddressVerification, DepositState, WalletClientExt, WalletClientGen, WalletClientModule,
+    WithdrawState,
+};
+use fedimint_wallet_common::address_proof::AddressProofError;
+use fedimint_wallet_common::config::{
+    NetworkFinality, PegOutPolicy, ScriptType, WalletClientConfig, WalletConfig, WalletGenParams,
+};
+use fedimint_wallet_common::db::{
+    BlockHashKey, PegOutFeesKey, PendingTransactionKey, UTXOPrefixKey,
 };
-use fedimint_wallet_common::config::{WalletConfig, WalletGenParams};
 use fedimint_wallet_common::tweakable::Tweakable;
 use fedimint_wallet_common::txoproof::PegInProof;
-use fedimint_wallet_common::{PegOutFees, Rbf};
+use fedimint_wallet_common::{
+    Cpfp, FeeTarget, PegInDescriptor, PegOutFees, Rbf, WalletConsensusItem, WalletInput,
+    WalletOutputOutcome,
+};
 use fedimint_wallet_server::WalletGen;
 use futures::stream::StreamExt;
+use miniscript::descriptor::{Descriptor, WshInner};
 use miniscript::ToPublicKey;
 use tracing::info;
 
+/// Regtest blocks are mined on demand, so we only wait for a few of them
+const FINALITY_DELAY: NetworkFinality = NetworkFinality {
+    mainnet: 6,
+    testnet: 6,
+    signet: 3,
+    regtest: 2,
+};
+
+fn wallet_params(bitcoin_rpc: BitcoinRpcConfig) -> WalletGenParams {
+    let mut wallet_params = WalletGenParams::regtest(bitcoin_rpc);
+    wallet_params.consensus.finality_delay = FINALITY_DELAY;
+    wallet_params
+}
+
 fn fixtures() -> Fixtures {
     let fixtures = Fixtures::new_primary(DummyClientGen, DummyGen, DummyGenParams::default());
-    let wallet_params = WalletGenParams::regtest(fixtures.bitcoin_server());
+    let wallet_params = wallet_params(fixtures.bitcoin_server());
     let wallet_client = WalletClientGen::new(fixtures.bitcoin_client());
-    fixtures.with_module(wallet_client, WalletGen, wallet_params)
+    fixtures
+        .with_module(wallet_client, WalletGen, wallet_params)
+        .with_input_spend_keys(fedimint_wallet_common::KIND, wallet_input_spend_keys)
+}
+
+/// Spending a wallet input consumes the outpoint of its peg-in
+fn wallet_input_spend_keys(input: &DynInput) -> Vec<Vec<u8>> {
+    let outpoint = input
+        .as_any()
+        .downcast_ref::<WalletInput>()
+        .expect("Inputs of the wallet are wallet inputs")
+        .outpoint();
+    vec![outpoint
+        .consensus_encode_to_vec()
+        .expect("Writing to a vector can't fail")]
 }
 
 fn bsats(satoshi: u64) -> bitcoin::Amount {
     bitcoin::Amount::from_sat(satoshi)
 }
 
+/// Checks that `proof` shows the inclusion of `txid` in the block
+/// `block_hash` and that every guardian has already synced that block
+async fn verify_compact_block_proof(
+    fed: &FederationTest,
+    txid: bitcoin::Txid,
+    proof: &TxOutProof,
+    block_hash: BlockHash,
+) -> anyhow::Result<()> {
+    ensure!(
+        proof.block() == block_hash,
+        "Proof is for block {} instead of {block_hash}",
+        proof.block()
+    );
+
+    let mut matches = vec![];
+    let mut indices = vec![];
+    let root = proof
+        .merkle_proof
+        .extract_matches(&mut matches, &mut indices)
+        .map_err(|e| anyhow!("Invalid merkle proof: {e:?}"))?;
+    ensure!(
+        root == proof.block_header.merkle_root,
+        "Merkle proof does not belong to the block header"
+    );
+    ensure!(
+        matches.contains(&txid),
+        "Transaction {txid} is not included in the proof"
+    );
+
+    for (peer_id, config) in fed.configs() {
+        let instance_id = config.get_module_id_by_kind(fedimint_wallet_common::KIND)?;
+        let mut dbtx = fed.consensus_api(*peer_id).db.begin_transaction().await;
+        ensure!(
+            dbtx.with_module_prefix(instance_id)
+                .get_value(&BlockHashKey(block_hash))
+                .await
+                .is_some(),
+            "Peer {peer_id} has not synced block {block_hash}"
+        );
+    }
+
+    Ok(())
+}
+
+/// Asserts that `proof` shows the inclusion of `txid` in the block
+/// `block_hash` known to all guardians
+async fn assert_compact_block_proof_valid(
+    fed: &FederationTest,
+    txid: bitcoin::Txid,
+    proof: TxOutProof,
+    block_hash: BlockHash,
+) {
+    if let Err(e) = verify_compact_block_proof(fed, txid, &proof, block_hash).await {
+        panic!("Invalid proof for transaction {txid}: {e}");
+    }
+}
+
+/// Time the guardians get to sync the UTXOs of their wallet with bitcoin
+const WALLET_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Asserts that the peg-in descriptor of every peer's wallet module parses
+/// from its string representation, is a `t-of-n` multisig over the keys
+/// of all guardians and derives addresses valid on the configured network
+fn assert_wallet_descriptor_valid(fed: &FederationTest) {
+    let secp = secp256k1::Secp256k1::new();
+
+    for (peer_id, config) in fed.configs() {
+        let instance_id = config
+            .get_module_id_by_kind(fedimint_wallet_common::KIND)
+            .expect("Federation has no wallet module");
+        let wallet_cfg: WalletConfig = config
+            .get_module_config_typed(instance_id)
+            .expect("Invalid wallet module config");
+        let consensus = &wallet_cfg.consensus;
+
+        let descriptor = PegInDescriptor::from_str(&consensus.peg_in_descriptor.to_string())
+            .unwrap_or_else(|e| panic!("Peer {peer_id} has an invalid descriptor: {e}"));
+        assert_eq!(descriptor, consensus.peg_in_descriptor);
+
+        let Descriptor::Wsh(wsh) = &descriptor else {
+            panic!("Peer {peer_id} has a non-wsh descriptor {descriptor}");
+        };
+        let WshInner::SortedMulti(multi) = wsh.as_inner() else {
+            panic!("Peer {peer_id} has a descriptor that is not a sorted multisig");
+        };
+        assert_eq!(multi.k, fed.configs().threshold());
+        assert_eq!(
+            multi.pks.iter().collect::<BTreeSet<_>>(),
+            consensus.peer_peg_in_keys.values().collect::<BTreeSet<_>>(),
+        );
+
+        for i in 1..=10u8 {
+            let tweak = secp256k1::SecretKey::from_slice(&[i; 32])
+                .expect("Valid secret key")
+                .x_only_public_key(&secp)
+                .0;
+            let address = descriptor
+                .tweak(&tweak, &secp)
+                .address(consensus.network)
+                .unwrap_or_else(|e| panic!("Peer {peer_id} derived no address: {e}"));
+            assert!(address.is_valid_for_network(consensus.network));
+        }
+    }
+}
+
+/// Generates `n` peg-in addresses with a new client and panics if any of
+/// them was returned more than once
+async fn assert_peg_in_address_uniqueness(fed: &FederationTest, n: usize) {
+    let client = fed.new_client().await;
+    let valid_until = SystemTime::now() + Duration::from_secs(60 * 60);
+
+    let mut addresses = HashSet::new();
+    for _ in 0..n {
+        let (_, address) = client
+            .get_deposit_address(valid_until)
+            .await
+            .expect("Failed to generate peg-in address");
+        assert!(
+            addresses.insert(address.clone()),
+            "Peg-in address {address} was returned more than once"
+        );
+    }
+}
+
+/// Waits until the guardians signed the CPFP child `txid` and submits it
+/// together with its parent to bitcoind, so both can be mined in the same
+/// block without waiting for the guardians to broadcast them
+async fn broadcast_cpfp_transaction(
+    fed: &FederationTest,
+    bitcoin_rpc: &DynBitcoindRpc,
+    txid: bitcoin::Txid,
+) -> anyhow::Result<()> {
+    let peer_id = PeerId::from(0);
+    let instance_id =
+        fed.configs()[&peer_id].get_module_id_by_kind(fedimint_wallet_common::KIND)?;
+    let db = &fed.consensus_api(peer_id).db;
+
+    let child = timeout(Duration::from_secs(60), async {
+        loop {
+            let mut dbtx = db.begin_transaction().await;
+            if let Some(child) = dbtx
+                .with_module_prefix(instance_id)
+                .get_value(&PendingTransactionKey(txid))
+                .await
+            {
+                return child;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("CPFP transaction {txid} was never signed"))?;
+
+    let parent_txid = child
+        .tx
+        .input
+        .first()
+        .ok_or_else(|| anyhow!("CPFP transaction {txid} has no inputs"))?
+        .previous_output
+        .txid;
+    let mut dbtx = db.begin_transaction().await;
+    let parent = dbtx
+        .with_module_prefix(instance_id)
+        .get_value(&PendingTransactionKey(parent_txid))
+        .await;
+
+    // The parent is unknown if it already confirmed
+    if let Some(parent) = parent {
+        bitcoin_rpc.submit_transaction(parent.tx).await;
+    }
+    bitcoin_rpc.submit_transaction(child.tx).await;
+
+    Ok(())
+}
+
+/// Panics unless the UTXOs of every peer's wallet module and the unspent
+/// outputs `bitcoin` knows for their addresses match within
+/// [`WALLET_SYNC_TIMEOUT`].
+///
+/// The wallet forgets the UTXOs a peg-out spends as soon as it is signed
+/// but only learns about its change once it is confirmed, so the wallet's
+/// transactions have to be mined first.
+async fn assert_wallet_module_state_matches_bitcoin(
+    fed: &FederationTest,
+    bitcoin: &dyn BitcoinTest,
+) {
+    let synced = timeout(WALLET_SYNC_TIMEOUT, async {
+        while !wallet_utxo_mismatches(fed, bitcoin).await.is_empty() {
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+
+    assert!(
+        synced.is_ok(),
+        "Wallet UTXOs (left) don't match bitcoin (right): {:?}",
+        wallet_utxo_mismatches(fed, bitcoin).await
+    );
+}
+
+/// Asserts that every peer's wallet module accumulated `expected` bitcoin
+/// transaction fees from the accepted peg-outs within
+/// [`WALLET_SYNC_TIMEOUT`], giving lagging peers time to catch up
+async fn assert_total_fees_collected(fed: &FederationTest, expected: bitcoin::Amount) {
+    let collected = timeout(WALLET_SYNC_TIMEOUT, async {
+        loop {
+            let fees = wallet_fees_collected(fed).await;
+            if fees.values().all(|fees| *fees == expected) {
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await;
+
+    assert!(
+        collected.is_ok(),
+        "Peers collected {:?} in fees, expected {expected}",
+        wallet_fees_collected(fed).await
+    );
+}
+
+async fn wallet_fees_collected(fed: &FederationTest) -> BTreeMap<PeerId, bitcoin::Amount> {
+    let mut fees = BTreeMap::new();
+    for peer_id in fed.configs().keys() {
+        let api = fed.consensus_api(*peer_id);
+        let instance_id = fed.configs()[peer_id]
+            .get_module_id_by_kind(fedimint_wallet_common::KIND)
+            .expect("Federation has no wallet module");
+        let peer_fees = api
+            .db
+            .begin_transaction()
+            .await
+            .with_module_prefix(instance_id)
+            .get_value(&PegOutFeesKey)
+            .await
+            .unwrap_or(bitcoin::Amount::ZERO);
+        fees.insert(*peer_id, peer_fees);
+    }
+
+    fees
+}
+
+/// The UTXOs of the peers whose wallet doesn't match the unspent outputs
+/// on chain, together with these outputs
+#[allow(clippy::type_complexity)]
+async fn wallet_utxo_mismatches(
+    fed: &FederationTest,
+    bitcoin: &dyn BitcoinTest,
+) -> BTreeMap<
+    PeerId,
+    (
+        BTreeMap<bitcoin::OutPoint, bitcoin::Amount>,
+        BTreeMap<bitcoin::OutPoint, bitcoin::Amount>,
+    ),
+> {
+    let secp = secp256k1::Secp256k1::new();
+    let mut mismatches = BTreeMap::new();
+
+    for peer_id in fed.configs().keys() {
+        let api = fed.consensus_api(*peer_id);
+        let instance_id = fed.configs()[peer_id]
+            .get_module_id_by_kind(fedimint_wallet_common::KIND)
+            .expect("Federation has no wallet module");
+        let consensus = fed.configs()[peer_id]
+            .get_module_config_typed::<WalletConfig>(instance_id)
+            .expect("Invalid wallet module config")
+            .consensus;
+
+        let utxos = api
+            .db
+            .begin_transaction()
+            .await
+            .with_module_prefix(instance_id)
+            .find_by_prefix(&UTXOPrefixKey)
+            .await
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut wallet = BTreeMap::new();
+        let mut addresses = BTreeSet::new();
+        for (key, utxo) in utxos {
+            wallet.insert(key.0, utxo.amount);
+            addresses.insert(
+                consensus
+                    .peg_in_descriptor
+                    .tweak(&utxo.tweak, &secp)
+                    .address(consensus.network)
+                    .expect("Peg-in descriptor derives addresses"),
+            );
+        }
+
+        let mut chain = BTreeMap::new();
+        for address in &addresses {
+            chain.extend(bitcoin.list_unspent_for_address(address).await);
+        }
+
+        if wallet != chain {
+            mismatches.insert(*peer_id, (wallet, chain));
+        }
+    }
+
+    mismatches
+}
+
 const PEG_IN_AMOUNT_SATS: u64 = 5000;
 const PEG_OUT_AMOUNT_SATS: u64 = 1000;
 const PEG_IN_TIMEOUT: Duration = Duration::from_secs(60);
@@ -101,11 +460,31 @@ async fn await_consensus_to_catch_up(client: &Client, block_count: u64) -> anyho
     }
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn wallet_module_config_matches_server_config() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+
+    let config = client
+        .api()
+        .with_module(instance.id)
+        .get_wallet_module_config()
+        .await?;
+    assert_eq!(config.network, bitcoin::Network::Regtest);
+    assert_eq!(config.finality_delay, FINALITY_DELAY.regtest);
+    assert_eq!(config.fee_consensus, Default::default());
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 //#[ignore]
 async fn sanity_check_bitcoin_blocks() -> anyhow::Result<()> {
     let fixtures = fixtures();
     let fed = fixtures.new_fed().await;
+    assert_wallet_descriptor_valid(&fed);
     let client = fed.new_client().await;
     let bitcoin = fixtures.bitcoin();
     // Avoid other tests from interfering here
@@ -113,7 +492,7 @@ async fn sanity_check_bitcoin_blocks() -> anyhow::Result<()> {
     let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
     info!("Starting test sanity_check_bitcoin_blocks");
 
-    let finality_delay = 10; // TODO: get from config
+    let finality_delay = FINALITY_DELAY.regtest as u64;
     let initial_block_count = dyn_bitcoin_rpc.get_block_count().await?;
     info!("Initial block count is {initial_block_count}");
     bitcoin.mine_blocks(finality_delay).await;
@@ -149,13 +528,15 @@ async fn sanity_check_bitcoin_blocks() -> anyhow::Result<()> {
 async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
     let fixtures = fixtures();
     let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
     let client = fed.new_client().await;
     let bitcoin = fixtures.bitcoin();
     let bitcoin = bitcoin.lock_exclusive().await;
     let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
     info!("Starting test on_chain_peg_in_and_peg_out_happy_case");
 
-    let finality_delay = 10;
+    let finality_delay = FINALITY_DELAY.regtest as u64;
     bitcoin.mine_blocks(finality_delay).await;
     await_consensus_to_catch_up(&client, 1).await?;
 
@@ -163,6 +544,8 @@ async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
         peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
 
     info!("Peg-in finished for test on_chain_peg_in_and_peg_out_happy_case");
+    assert_wallet_module_state_matches_bitcoin(&fed, bitcoin.as_ref()).await;
+
     // Peg-out test, requires block to recognize change UTXOs
     let address = bitcoin.get_new_address().await;
     let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
@@ -185,161 +568,216 @@ async fn on_chain_peg_in_and_peg_out_happy_case() -> anyhow::Result<()> {
 
     let received = bitcoin.mine_block_and_get_received(&address).await;
     assert_eq!(received, peg_out.into());
+
+    // The change is only recognized once the peg-out is final
+    bitcoin.mine_blocks(finality_delay).await;
+    assert_wallet_module_state_matches_bitcoin(&fed, bitcoin.as_ref()).await;
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
-#[tokio::test(flavor = "multi_thread")]
-//#[ignore]
-async fn peg_out_fail_refund() -> anyhow::Result<()> {
+/// Pegs in and then out to an address of `address_type`, checking that the
+/// federation stays balanced
+async fn peg_in_and_peg_out_to_address_type(address_type: AddressType) -> anyhow::Result<()> {
     let fixtures = fixtures();
     let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
     let client = fed.new_client().await;
     let bitcoin = fixtures.bitcoin();
     let bitcoin = bitcoin.lock_exclusive().await;
     let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
-    info!("Starting test peg_out_fail_refund");
+    info!(?address_type, "Starting peg-in and peg-out to address type");
 
-    let finality_delay = 10;
+    let finality_delay = FINALITY_DELAY.regtest as u64;
     bitcoin.mine_blocks(finality_delay).await;
     await_consensus_to_catch_up(&client, 1).await?;
 
     let mut balance_sub =
         peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
 
-    info!("Peg-in finished for test peg_out_fail_refund");
-    // Peg-out test, requires block to recognize change UTXOs
-    let address = bitcoin.get_new_address().await;
+    let address = bitcoin.get_new_address_of_type(address_type).await;
     let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
 
-    // Set invalid fees
-    let fees = PegOutFees {
-        fee_rate: Feerate { sats_per_kvb: 0 },
-        total_weight: 0,
+    let balance_after_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
+    assert_eq!(client.get_balance().await, balance_after_peg_out);
+    assert_eq!(balance_sub.ok().await?, balance_after_peg_out);
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
     };
-    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    // The fee estimate covers the weight of the destination output
     assert_eq!(
-        balance_sub.next().await.unwrap(),
-        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS)
+        bitcoin.get_mempool_tx_fee(&txid).await,
+        fees.amount().into()
     );
 
-    let sub = client.subscribe_withdraw_updates(op).await?;
-    let mut sub = sub.into_stream();
+    let received = bitcoin.mine_block_and_get_received(&address).await;
+    assert_eq!(received, peg_out.into());
+    assert_eq!(fed.audit().await.net_assets, 0);
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_to_taproot_address() -> anyhow::Result<()> {
+    peg_in_and_peg_out_to_address_type(AddressType::Taproot).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_to_legacy_address() -> anyhow::Result<()> {
+    peg_in_and_peg_out_to_address_type(AddressType::Legacy).await
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_outs_are_rejected_if_policy_forbids_script_type() -> anyhow::Result<()> {
+    let fixtures = Fixtures::new_primary(DummyClientGen, DummyGen, DummyGenParams::default());
+    let mut wallet_params = wallet_params(fixtures.bitcoin_server());
+    wallet_params.consensus.peg_out_policy = PegOutPolicy {
+        allowed_script_types: vec![ScriptType::P2wpkh],
+        blocked_addresses: BTreeSet::new(),
+    };
+    let wallet_client = WalletClientGen::new(fixtures.bitcoin_client());
+    let fixtures = fixtures
+        .with_module(wallet_client, WalletGen, wallet_params)
+        .with_input_spend_keys(fedimint_wallet_common::KIND, wallet_input_spend_keys);
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_outs_are_rejected_if_policy_forbids_script_type");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let legacy_address = bitcoin.get_new_address_of_type(AddressType::Legacy).await;
+    let segwit_address = bitcoin.get_new_address_of_type(AddressType::SegwitV0).await;
+
+    // Clients learn about the violation before building a transaction
+    let error = client
+        .get_withdraw_fee(legacy_address.clone(), peg_out)
+        .await
+        .expect_err("P2PKH peg-outs are not allowed");
+    info!(?error, "Fee quote for P2PKH peg-out was rejected");
+
+    // Consensus rejects the peg-out even if the client skips the fee quote
+    let fees = client
+        .get_withdraw_fee(segwit_address.clone(), peg_out)
+        .await?;
+    let op = client.withdraw(legacy_address, peg_out, fees).await?;
+    let mut sub = client.subscribe_withdraw_updates(op).await?.into_stream();
     assert_eq!(sub.ok().await?, WithdrawState::Created);
-    assert!(matches!(sub.ok().await?, WithdrawState::Failed(_)));
+    assert_matches!(sub.ok().await?, WithdrawState::Failed(_));
 
-    // Check that we get our money back if the peg-out fails
-    assert_eq!(balance_sub.next().await.unwrap(), sats(PEG_IN_AMOUNT_SATS));
-    assert_eq!(client.get_balance().await, sats(PEG_IN_AMOUNT_SATS));
+    let op = client
+        .withdraw(segwit_address.clone(), peg_out, fees)
+        .await?;
+    let mut sub = client.subscribe_withdraw_updates(op).await?.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    assert_matches!(sub.ok().await?, WithdrawState::Succeeded(_));
 
+    let received = bitcoin.mine_block_and_get_received(&segwit_address).await;
+    assert_eq!(received, peg_out.into());
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-//#[ignore]
-async fn peg_outs_support_rbf() -> anyhow::Result<()> {
+async fn split_peg_out_pays_all_recipients_in_one_transaction() -> anyhow::Result<()> {
     let fixtures = fixtures();
     let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
     let client = fed.new_client().await;
     let bitcoin = fixtures.bitcoin();
-    // Need lock to keep tx in mempool from getting mined
     let bitcoin = bitcoin.lock_exclusive().await;
     let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
-    info!("Starting test peg_outs_support_rbf");
+    info!("Starting test split_peg_out_pays_all_recipients_in_one_transaction");
 
-    let finality_delay = 10;
+    let finality_delay = FINALITY_DELAY.regtest as u64;
     bitcoin.mine_blocks(finality_delay).await;
     await_consensus_to_catch_up(&client, 1).await?;
 
     let mut balance_sub =
         peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
 
-    info!("Peg-in finished for test peg_outs_support_rbf");
-    let address = bitcoin.get_new_address().await;
-    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
-    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
-    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    let mut payments = vec![];
+    for amount in [1000, 1100, 1200] {
+        payments.push((bsats(amount), bitcoin.get_new_address().await));
+    }
+    let out_points = client.split_peg_out(payments.clone()).await?;
+    assert_eq!(out_points.len(), 3);
+    assert!(balance_sub.ok().await? < sats(PEG_IN_AMOUNT_SATS - 3300));
 
-    let sub = client.subscribe_withdraw_updates(op).await?;
-    let mut sub = sub.into_stream();
-    assert_eq!(sub.ok().await?, WithdrawState::Created);
-    let state = sub.ok().await?;
-    let WithdrawState::Succeeded(txid) = state else {
-        bail!("Unexpected state: {state:?}")
-    };
-    assert_eq!(
-        bitcoin.get_mempool_tx_fee(&txid).await,
-        fees.amount().into()
-    );
-    let balance_after_normal_peg_out =
-        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
-    assert_eq!(client.get_balance().await, balance_after_normal_peg_out);
-    assert_eq!(balance_sub.ok().await?, balance_after_normal_peg_out);
+    let decoder = WalletClientModule::decoder();
+    let mut txids = BTreeSet::new();
+    for out_point in out_points {
+        let WalletOutputOutcome(txid) = client
+            .api()
+            .await_output_outcome(out_point, Duration::from_secs(60), &decoder)
+            .await?;
+        txids.insert(txid);
+    }
+    assert_eq!(txids.len(), 1);
+    bitcoin
+        .get_mempool_tx_fee(txids.first().expect("Contains a txid"))
+        .await;
 
-    // RBF by increasing sats per kvb by 1000
-    let rbf = Rbf {
-        fees: PegOutFees::new(1000, fees.total_weight),
-        txid,
-    };
-    let op = client.rbf_withdraw(rbf.clone()).await?;
-    let sub = client.subscribe_withdraw_updates(op).await?;
-    let mut sub = sub.into_stream();
-    assert_eq!(sub.ok().await?, WithdrawState::Created);
-    let txid = match sub.ok().await? {
-        WithdrawState::Succeeded(txid) => txid,
-        other => panic!("Unexpected state: {other:?}"),
-    };
-    let total_fees = fees.amount() + rbf.fees.amount();
-    assert_eq!(bitcoin.get_mempool_tx_fee(&txid).await, total_fees.into());
-    assert_eq!(
-        bitcoin.mine_block_and_get_received(&address).await,
-        sats(PEG_OUT_AMOUNT_SATS)
-    );
-    let balance_after_rbf_peg_out =
-        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - total_fees.to_sat());
-    let current_balance = client.get_balance().await;
-    assert_eq!(balance_sub.ok().await?, current_balance);
-    // So we don't know which transaction will get mined first, it could be
-    // any one of the two, so we accept both
-    if current_balance != balance_after_rbf_peg_out
-        && current_balance != balance_after_normal_peg_out
-    {
-        bail!(
-            "Balance is {current_balance}, expected {balance_after_rbf_peg_out} or {balance_after_normal_peg_out}"
-        )
+    for (amount, address) in payments {
+        let received = bitcoin.mine_block_and_get_received(&address).await;
+        assert_eq!(received, amount.into());
     }
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
     Ok(())
 }
 
 #[tokio::test(flavor = "multi_thread")]
-async fn peg_outs_must_wait_for_available_utxos() -> anyhow::Result<()> {
+async fn peg_out_with_fees_for_next_block() -> anyhow::Result<()> {
     let fixtures = fixtures();
     let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
     let client = fed.new_client().await;
     let bitcoin = fixtures.bitcoin();
-    // This test has many assumptions about bitcoin L1 blocks
-    // and FM epochs, so we just lock the node
     let bitcoin = bitcoin.lock_exclusive().await;
     let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
-    info!("Starting test peg_outs_must_wait_for_available_utxos");
+    info!("Starting test peg_out_with_fees_for_next_block");
 
-    let finality_delay = 10;
+    let finality_delay = FINALITY_DELAY.regtest as u64;
     bitcoin.mine_blocks(finality_delay).await;
     await_consensus_to_catch_up(&client, 1).await?;
 
     let mut balance_sub =
         peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
 
-    info!("Peg-in finished for test peg_outs_must_wait_for_available_utxos");
     let address = bitcoin.get_new_address().await;
-    let peg_out1 = PEG_OUT_AMOUNT_SATS;
-    let fees1 = client
-        .get_withdraw_fee(address.clone(), bsats(peg_out1))
-        .await?;
-    let op = client
-        .withdraw(address.clone(), bsats(peg_out1), fees1)
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let default_fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let fees = client
+        .get_withdraw_fee_for_target(address.clone(), peg_out, FeeTarget::NextBlock)
         .await?;
+    // Targets are never quoted below the fee rate the federation requires
+    assert!(fees.fee_rate >= default_fees.fee_rate);
+    assert_eq!(fees.total_weight, default_fees.total_weight);
+
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
     let balance_after_peg_out =
-        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees1.amount().to_sat());
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
     assert_eq!(client.get_balance().await, balance_after_peg_out);
     assert_eq!(balance_sub.ok().await?, balance_after_peg_out);
 
@@ -350,17 +788,1053 @@ async fn peg_outs_must_wait_for_available_utxos() -> anyhow::Result<()> {
         WithdrawState::Succeeded(txid) => txid,
         other => panic!("Unexpected state: {other:?}"),
     };
-    bitcoin.get_mempool_tx_fee(&txid).await;
+    assert_eq!(
+        bitcoin.get_mempool_tx_fee(&txid).await,
+        fees.amount().into()
+    );
 
-    // Do another peg-out
-    let peg_out2 = PEG_OUT_AMOUNT_SATS;
-    let fees2 = client
-        .get_withdraw_fee(address.clone(), bsats(peg_out2))
-        .await;
-    // Must fail because change UTXOs are still being confirmed
-    assert!(fees2.is_err());
+    let received = bitcoin.mine_block_and_get_received(&address).await;
+    assert_eq!(received, peg_out.into());
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
 
-    let current_block = dyn_bitcoin_rpc.get_block_count().await?;
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_fees_are_accumulated() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_out_fees_are_accumulated");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+    assert_total_fees_collected(&fed, bsats(0)).await;
+
+    let mut total_fees = bsats(0);
+    for target in [
+        FeeTarget::OneDayEconomy,
+        FeeTarget::ThreeBlocks,
+        FeeTarget::NextBlock,
+    ] {
+        let address = bitcoin.get_new_address().await;
+        let peg_out = bsats(PEG_OUT_AMOUNT_SATS / 2);
+        let fees = client
+            .get_withdraw_fee_for_target(address.clone(), peg_out, target)
+            .await?;
+        let op = client.withdraw(address, peg_out, fees).await?;
+        let sub = client.subscribe_withdraw_updates(op).await?;
+        let mut sub = sub.into_stream();
+        assert_eq!(sub.ok().await?, WithdrawState::Created);
+        let txid = match sub.ok().await? {
+            WithdrawState::Succeeded(txid) => txid,
+            other => panic!("Unexpected state: {other:?}"),
+        };
+        assert_eq!(
+            bitcoin.get_mempool_tx_fee(&txid).await,
+            fees.amount().into()
+        );
+
+        total_fees += fees.amount();
+        assert_total_fees_collected(&fed, total_fees).await;
+
+        // The next peg-out spends the change once it is confirmed
+        let current_block = dyn_bitcoin_rpc.get_block_count().await?;
+        bitcoin.mine_blocks(finality_delay + 1).await;
+        await_consensus_to_catch_up(&client, current_block + 1).await?;
+    }
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn recurring_peg_out_withdraws_every_n_epochs() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test recurring_peg_out_withdraws_every_n_epochs");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS / 2);
+    let mut task_group = TaskGroup::new();
+    let start_epoch = client.api().fetch_block_count().await?;
+    let id = client
+        .create_reoccurring_peg_out(peg_out, address.clone(), 2, &mut task_group)
+        .await?;
+
+    // Mining confirms the change of each peg-out so the next one can spend it
+    let mut received = sats(0);
+    while received < (peg_out * 3).into() {
+        received = bitcoin.mine_block_and_get_received(&address).await;
+        sleep(Duration::from_millis(500)).await;
+    }
+    assert_eq!(received, (peg_out * 3).into());
+    assert!(client.api().fetch_block_count().await? >= start_epoch + 6);
+
+    client.cancel_reoccurring_peg_out(id).await?;
+    assert!(client.cancel_reoccurring_peg_out(id).await.is_err());
+    task_group.shutdown_join_all(None).await?;
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compact_filter_matches_peg_in_address() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test compact_filter_matches_peg_in_address");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    let valid_until = SystemTime::now() + PEG_IN_TIMEOUT;
+    let (_, address) = client.get_deposit_address(valid_until).await?;
+    let (_proof, tx) = bitcoin
+        .send_and_mine_block(&address, bsats(PEG_IN_AMOUNT_SATS))
+        .await;
+    let height = dyn_bitcoin_rpc
+        .get_tx_block_height(&tx.txid())
+        .await?
+        .context("expected tx to be mined")?;
+
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, height + 1).await?;
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let filter = client
+        .api()
+        .with_module(instance.id)
+        .get_compact_filter(height)
+        .await?;
+    assert_eq!(
+        filter.block_hash,
+        dyn_bitcoin_rpc.get_block_hash(height).await?
+    );
+    assert!(filter.matches(&address));
+    assert!(!filter.matches(&bitcoin.get_new_address().await));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fee_rate_votes_are_recorded_per_epoch() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let client = fed.new_client().await;
+
+    fed.run_n_epochs_and_verify_all_invariants(3).await?;
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let api = client.api().with_module(instance.id);
+    let history = api.fee_rate_vote_history(2).await?;
+    assert_eq!(history.len(), 2);
+    for votes in history {
+        assert_eq!(votes.len(), api.all_peers().total());
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn genesis_config_stays_immutable() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+
+    // New blocks make the guardians vote on the block count in the next epochs
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    bitcoin.mine_blocks(10).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    fed.assert_config_immutable_after_genesis(&["epoch_pk_set", "wallet.network"], 3)
+        .await;
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn balance_sheet_is_signed_by_threshold() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test balance_sheet_is_signed_by_threshold");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    let balance_after_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
+    assert_eq!(balance_sub.ok().await?, balance_after_peg_out);
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    assert_matches!(sub.ok().await?, WithdrawState::Succeeded(_));
+    let received = bitcoin.mine_block_and_get_received(&address).await;
+    assert_eq!(received, peg_out.into());
+
+    let signed = client.api().fetch_balance_sheet().await?;
+    signed.verify(&client.get_config().epoch_pk)?;
+
+    // The bitcoin held by the wallet backs the funds of the client
+    let balance_sheet = &signed.balance_sheet;
+    let module_balance = |kind: &str| {
+        balance_sheet
+            .modules
+            .values()
+            .find(|module| module.kind == kind)
+            .expect("Module is part of the balance sheet")
+            .clone()
+    };
+    let client_msats = balance_after_peg_out.msats as i64;
+    assert_eq!(
+        module_balance(fedimint_wallet_common::KIND.as_str()).net_assets(),
+        client_msats
+    );
+    assert_eq!(
+        module_balance(fedimint_dummy_common::KIND.as_str()).net_assets(),
+        -client_msats
+    );
+    assert_eq!(balance_sheet.net_assets(), 0);
+
+    // A tampered balance sheet doesn't verify
+    let mut tampered = signed.clone();
+    for module in tampered.balance_sheet.modules.values_mut() {
+        module.liabilities_msat = 0;
+    }
+    assert!(tampered.verify(&client.get_config().epoch_pk).is_err());
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn module_audit_proof_covers_pegged_in_bitcoin() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test module_audit_proof_covers_pegged_in_bitcoin");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let proof = client.api().get_module_audit_proof(instance.id).await?;
+    proof.verify(&client.get_config().epoch_pk)?;
+    assert_eq!(proof.module_audit.module_instance_id, instance.id);
+    assert_ne!(proof.module_audit.merkle_root, [0; 32]);
+
+    // the proven balance matches the independently signed balance sheet
+    let signed = client.api().fetch_balance_sheet().await?;
+    signed.verify(&client.get_config().epoch_pk)?;
+    assert_eq!(
+        signed.balance_sheet.modules[&instance.id],
+        proof.module_audit.balance
+    );
+    assert_eq!(
+        proof.module_audit.balance.net_assets(),
+        sats(PEG_IN_AMOUNT_SATS).msats as i64
+    );
+
+    let mut tampered = proof.clone();
+    tampered.module_audit.merkle_root = [0; 32];
+    assert!(tampered.verify(&client.get_config().epoch_pk).is_err());
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_signatures_are_pending_for_next_epoch() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_out_signatures_are_pending_for_next_epoch");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+
+    // Every guardian signs the peg-out tx with its own key, so we look for a
+    // signature of the same tx among a threshold of proposals
+    let signed_txid = loop {
+        let proposals = fed.pending_proposals().await;
+        let mut signers = BTreeMap::<_, usize>::new();
+        for items in proposals.values() {
+            for item in items {
+                let ConsensusItem::Module(item) = item else {
+                    continue;
+                };
+                if let Some(WalletConsensusItem::PegOutSignature(sig)) =
+                    item.as_any().downcast_ref::<WalletConsensusItem>()
+                {
+                    *signers.entry(sig.txid).or_default() += 1;
+                }
+            }
+        }
+
+        if let Some((txid, _)) = signers
+            .into_iter()
+            .find(|(_, count)| *count >= proposals.threshold())
+        {
+            break txid;
+        }
+        sleep(Duration::from_millis(10)).await;
+    };
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    assert_eq!(sub.ok().await?, WithdrawState::Succeeded(signed_txid));
+
+    // All guardians vote for the new block count once the peg-out confirms
+    let received = bitcoin.mine_block_and_get_received(&address).await;
+    assert_eq!(received, peg_out.into());
+    let block_count = (dyn_bitcoin_rpc.get_block_count().await? - finality_delay) as u32;
+    let wallet_instance = client
+        .get_first_instance(&fedimint_wallet_common::KIND)
+        .context("Wallet module is registered")?;
+    fed.assert_pending_epoch_contains(&[ConsensusItem::Module(
+        WalletConsensusItem::BlockCount(block_count).into_dyn(wallet_instance),
+    )])
+    .await;
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_out_history_lists_newest_first() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_out_history_lists_newest_first");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let mut peg_outs = vec![];
+    for i in 1..=3 {
+        // Different amounts so we can tell the peg-outs apart
+        let peg_out = bsats(PEG_OUT_AMOUNT_SATS / 2 + i);
+        let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+        let op = client.withdraw(address.clone(), peg_out, fees).await?;
+
+        let sub = client.subscribe_withdraw_updates(op).await?;
+        let mut sub = sub.into_stream();
+        assert_eq!(sub.ok().await?, WithdrawState::Created);
+        let txid = match sub.ok().await? {
+            WithdrawState::Succeeded(txid) => txid,
+            other => panic!("Unexpected state: {other:?}"),
+        };
+        // The outcome is cached once the stream ended
+        assert!(sub.next().await.is_none());
+        peg_outs.push((op, txid, peg_out, fees.amount()));
+
+        // Our next peg-out needs the change of this one
+        let current_block = dyn_bitcoin_rpc.get_block_count().await?;
+        bitcoin.mine_blocks(finality_delay + 1).await;
+        await_consensus_to_catch_up(&client, current_block + 1).await?;
+    }
+
+    let history = client.get_peg_out_history(10).await;
+    assert_eq!(history.len(), 3);
+    for (record, (op, txid, amount, fee)) in history.iter().zip(peg_outs.iter().rev()) {
+        assert_eq!(record.operation_id, *op);
+        assert_eq!(record.txid, *txid);
+        assert_eq!(record.amount, *amount);
+        assert_eq!(record.fee, *fee);
+        assert_eq!(record.address, address);
+    }
+    assert!(history
+        .windows(2)
+        .all(|records| records[0].timestamp >= records[1].timestamp));
+
+    let latest = client.get_peg_out_history(2).await;
+    assert_eq!(latest, history[..2]);
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_batch_claims_all_utxos_in_one_transaction() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_in_batch_claims_all_utxos_in_one_transaction");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let (wallet_module, _) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let secp = Secp256k1::new();
+    let mut peg_ins = vec![];
+    for _ in 0..3 {
+        let tweak_key = secp256k1::KeyPair::new(&secp, &mut OsRng);
+        let address = wallet_module.peg_in_address(&tweak_key);
+        let (_proof, tx) = bitcoin
+            .send_and_mine_block(&address, bsats(PEG_IN_AMOUNT_SATS))
+            .await;
+        peg_ins.push((tweak_key, tx));
+    }
+    bitcoin.mine_blocks(finality_delay).await;
+    let block_count = dyn_bitcoin_rpc.get_block_count().await?;
+    await_consensus_to_catch_up(&client, block_count - finality_delay).await?;
+
+    // A single invalid peg-in rejects the whole batch
+    let mut invalid = peg_ins.clone();
+    invalid[1].0 = secp256k1::KeyPair::new(&secp, &mut OsRng);
+    let error = client
+        .submit_peg_in_batch(invalid)
+        .await
+        .expect_err("Peg-in 1 doesn't pay to its key");
+    assert!(error.to_string().contains("Peg-in 1 is invalid"));
+    assert_eq!(client.get_balance().await, sats(0));
+
+    let (op, outpoint) = client.submit_peg_in_batch(peg_ins).await?;
+    client.await_primary_module_output(op, outpoint).await?;
+    assert_eq!(client.get_balance().await, sats(3 * PEG_IN_AMOUNT_SATS));
+
+    let signed = client.api().fetch_balance_sheet().await?;
+    assert_eq!(signed.balance_sheet.net_assets(), 0);
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_is_reported_as_federation_events() -> anyhow::Result<()> {
+    // Notes are only issued by the mint, so we use it as the primary module
+    let fixtures = Fixtures::new_primary(MintClientGen, MintGen, MintGenParams::default());
+    let wallet_params = wallet_params(fixtures.bitcoin_server());
+    let wallet_client = WalletClientGen::new(fixtures.bitcoin_client());
+    let fixtures = fixtures
+        .with_module(wallet_client, WalletGen, wallet_params)
+        .with_input_spend_keys(fedimint_wallet_common::KIND, wallet_input_spend_keys);
+
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_in_is_reported_as_federation_events");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    // The claim may not be part of a completed epoch yet
+    let all_types = vec![
+        EventType::PegIn,
+        EventType::PegOut,
+        EventType::NoteIssuance,
+        EventType::NoteRedemption,
+    ];
+    let events = loop {
+        let events = client.get_federation_events(0, &all_types).await?;
+        if !events.is_empty() {
+            break events;
+        }
+        sleep(Duration::from_millis(100)).await;
+    };
+
+    let types = events
+        .iter()
+        .map(|event| event.event_type)
+        .collect::<Vec<_>>();
+    assert_eq!(types, vec![EventType::PegIn, EventType::NoteIssuance]);
+    assert_eq!(events[0].txid, events[1].txid);
+
+    let peg_ins = client.get_federation_events(0, &[EventType::PegIn]).await?;
+    assert_eq!(peg_ins, events[..1]);
+    let later = client
+        .get_federation_events(events[0].epoch + 1, &all_types)
+        .await?;
+    assert!(later.is_empty());
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_bandwidth_is_recorded_per_method() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_in_bandwidth_is_recorded_per_method");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let recorder = fed.start_bandwidth_recording();
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+    let report = fed.stop_bandwidth_recording(recorder);
+
+    let most_expensive = report.most_expensive();
+    for (method, bytes) in &most_expensive {
+        info!(method, bytes, "Peg-in API bandwidth");
+    }
+    assert!(!most_expensive.is_empty());
+    assert!(most_expensive
+        .windows(2)
+        .all(|methods| methods[0].1 >= methods[1].1));
+    // Claiming the peg-in submits a transaction
+    assert!(report.per_method[TRANSACTION_ENDPOINT] > 0);
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+//#[ignore]
+async fn peg_out_fail_refund() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_out_fail_refund");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    info!("Peg-in finished for test peg_out_fail_refund");
+    // Peg-out test, requires block to recognize change UTXOs
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+
+    // Set invalid fees
+    let fees = PegOutFees {
+        fee_rate: Feerate { sats_per_kvb: 0 },
+        total_weight: 0,
+    };
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    assert_eq!(
+        balance_sub.next().await.unwrap(),
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS)
+    );
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    assert!(matches!(sub.ok().await?, WithdrawState::Failed(_)));
+
+    // Check that we get our money back if the peg-out fails
+    assert_eq!(balance_sub.next().await.unwrap(), sats(PEG_IN_AMOUNT_SATS));
+    assert_eq!(client.get_balance().await, sats(PEG_IN_AMOUNT_SATS));
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+//#[ignore]
+async fn peg_outs_support_rbf() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // Need lock to keep tx in mempool from getting mined
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_outs_support_rbf");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    info!("Peg-in finished for test peg_outs_support_rbf");
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let state = sub.ok().await?;
+    let WithdrawState::Succeeded(txid) = state else {
+        bail!("Unexpected state: {state:?}")
+    };
+    assert_eq!(
+        bitcoin.get_mempool_tx_fee(&txid).await,
+        fees.amount().into()
+    );
+    let balance_after_normal_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
+    assert_eq!(client.get_balance().await, balance_after_normal_peg_out);
+    assert_eq!(balance_sub.ok().await?, balance_after_normal_peg_out);
+
+    // RBF by increasing sats per kvb by 1000
+    let rbf = Rbf {
+        fees: PegOutFees::new(1000, fees.total_weight),
+        txid,
+    };
+    let op = client.rbf_withdraw(rbf.clone()).await?;
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+    let total_fees = fees.amount() + rbf.fees.amount();
+    assert_eq!(bitcoin.get_mempool_tx_fee(&txid).await, total_fees.into());
+    assert_eq!(
+        bitcoin.mine_block_and_get_received(&address).await,
+        sats(PEG_OUT_AMOUNT_SATS)
+    );
+    let balance_after_rbf_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - total_fees.to_sat());
+    let current_balance = client.get_balance().await;
+    assert_eq!(balance_sub.ok().await?, current_balance);
+    // So we don't know which transaction will get mined first, it could be
+    // any one of the two, so we accept both
+    if current_balance != balance_after_rbf_peg_out
+        && current_balance != balance_after_normal_peg_out
+    {
+        bail!(
+            "Balance is {current_balance}, expected {balance_after_rbf_peg_out} or {balance_after_normal_peg_out}"
+        )
+    }
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pending_peg_outs_are_listed_as_rbf_capable() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // Need lock to keep tx in mempool from getting mined
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test pending_peg_outs_are_listed_as_rbf_capable");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let wallet_api = client.api().with_module(instance.id);
+    let rbf_capable = wallet_api.get_rbf_capable_peg_outs().await?;
+    assert_eq!(rbf_capable.len(), 1);
+    assert_eq!(rbf_capable[0].txid, txid);
+    assert_eq!(rbf_capable[0].out_point.txid, txid);
+    assert_eq!(rbf_capable[0].address, address);
+    assert_eq!(rbf_capable[0].current_fee, fees.amount());
+    assert!(rbf_capable[0].max_bump_fee > bitcoin::Amount::ZERO);
+
+    // Once the peg-out is final its transaction can't be replaced anymore
+    bitcoin.get_mempool_tx_fee(&txid).await;
+    let current_block = dyn_bitcoin_rpc.get_block_count().await?;
+    bitcoin.mine_blocks(finality_delay + 1).await;
+    await_consensus_to_catch_up(&client, current_block + 1).await?;
+    assert!(wallet_api.get_rbf_capable_peg_outs().await?.is_empty());
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn signed_peg_outs_are_listed_as_pending_until_confirmed() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // Need lock to keep tx in mempool from getting mined
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test signed_peg_outs_are_listed_as_pending_until_confirmed");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let wallet_api = client.api().with_module(instance.id);
+    assert!(wallet_api.get_pending_peg_outs().await?.is_empty());
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+
+    // The tx is only broadcast once it is fully signed, it is listed once the
+    // session it was signed in completed
+    bitcoin.get_mempool_tx_fee(&txid).await;
+    fed.run_n_epochs_and_verify_all_invariants(1).await?;
+
+    let pending = wallet_api.get_pending_peg_outs().await?;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].txid, txid);
+    assert_eq!(pending[0].amount, peg_out);
+    assert_eq!(pending[0].address, address);
+    assert_eq!(pending[0].fee, fees.amount());
+    assert!(pending[0].epoch_signed < client.api().fetch_block_count().await?);
+
+    let current_block = dyn_bitcoin_rpc.get_block_count().await?;
+    bitcoin.mine_blocks(finality_delay + 1).await;
+    await_consensus_to_catch_up(&client, current_block + 1).await?;
+    assert!(wallet_api.get_pending_peg_outs().await?.is_empty());
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn epoch_metrics_count_accepted_wallet_items() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test epoch_metrics_count_accepted_wallet_items");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address, peg_out, fees).await?;
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    assert_matches!(sub.ok().await?, WithdrawState::Succeeded(_));
+
+    // The epoch the peg-out was accepted in has to be completed to be found
+    let wallet_instance = client
+        .get_first_instance(&fedimint_wallet_common::KIND)
+        .context("Wallet module is registered")?;
+    let block_count = client.api().fetch_block_count().await?;
+    client
+        .api()
+        .await_block(block_count, client.decoders())
+        .await?;
+
+    let mut peg_out_epoch = None;
+    for epoch in (0..=block_count).rev() {
+        let block = client.api().await_block(epoch, client.decoders()).await?;
+        let is_peg_out = |item: &ConsensusItem| match item {
+            ConsensusItem::Transaction(tx) => tx
+                .outputs
+                .iter()
+                .any(|output| output.module_instance_id() == wallet_instance),
+            _ => false,
+        };
+        if block
+            .items
+            .iter()
+            .any(|accepted| is_peg_out(&accepted.item))
+        {
+            peg_out_epoch = Some((epoch, block));
+            break;
+        }
+    }
+    let (epoch, block) = peg_out_epoch.context("Peg-out was accepted")?;
+
+    // Every guardian accepted the same items, among them the peg-out
+    let wallet_items = block
+        .items
+        .iter()
+        .filter(|accepted| match &accepted.item {
+            ConsensusItem::Module(item) => item.module_instance_id() == wallet_instance,
+            ConsensusItem::Transaction(tx) => tx
+                .outputs
+                .iter()
+                .any(|output| output.module_instance_id() == wallet_instance),
+            ConsensusItem::ClientConfigSignatureShare(_) => false,
+        })
+        .count();
+    let metrics = client.api().get_epoch_metrics(epoch).await?;
+    assert_eq!(
+        metrics.len(),
+        client.get_config().global.api_endpoints.len()
+    );
+    for peer_metrics in metrics.values() {
+        let wallet_metrics = peer_metrics[&wallet_instance];
+        assert_eq!(wallet_metrics.items_accepted, wallet_items);
+        assert!(wallet_metrics.items_proposed >= wallet_metrics.items_accepted);
+    }
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_outs_support_cpfp() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // Need lock to keep tx in mempool from getting mined
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_outs_support_cpfp");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    info!("Peg-in finished for test peg_outs_support_cpfp");
+    let address = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let fees = client.get_withdraw_fee(address.clone(), peg_out).await?;
+    let op = client.withdraw(address.clone(), peg_out, fees).await?;
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let parent_txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+    assert_eq!(
+        bitcoin.get_mempool_tx_fee(&parent_txid).await,
+        fees.amount().into()
+    );
+    let balance_after_normal_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees.amount().to_sat());
+    assert_eq!(client.get_balance().await, balance_after_normal_peg_out);
+    assert_eq!(balance_sub.ok().await?, balance_after_normal_peg_out);
+
+    // CPFP by paying another 1000 sats for the package
+    let cpfp = Cpfp {
+        txid: parent_txid,
+        parent_fee: fees.amount(),
+        package_fee: fees.amount() + bsats(1000),
+    };
+    let op = client.cpfp_withdraw(cpfp.clone()).await?;
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let child_txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+    assert_ne!(child_txid, parent_txid);
+
+    broadcast_cpfp_transaction(&fed, &dyn_bitcoin_rpc, child_txid).await?;
+    assert_eq!(
+        bitcoin.mine_block_and_get_received(&address).await,
+        sats(PEG_OUT_AMOUNT_SATS)
+    );
+
+    // Both transactions confirm together
+    let parent_height = dyn_bitcoin_rpc.get_tx_block_height(&parent_txid).await?;
+    assert!(parent_height.is_some());
+    assert_eq!(
+        dyn_bitcoin_rpc.get_tx_block_height(&child_txid).await?,
+        parent_height
+    );
+
+    let balance_after_cpfp_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - cpfp.package_fee.to_sat());
+    assert_eq!(client.get_balance().await, balance_after_cpfp_peg_out);
+    assert_eq!(balance_sub.ok().await?, balance_after_cpfp_peg_out);
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_outs_must_wait_for_available_utxos() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // This test has many assumptions about bitcoin L1 blocks
+    // and FM epochs, so we just lock the node
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_outs_must_wait_for_available_utxos");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    info!("Peg-in finished for test peg_outs_must_wait_for_available_utxos");
+    let address = bitcoin.get_new_address().await;
+    let peg_out1 = PEG_OUT_AMOUNT_SATS;
+    let fees1 = client
+        .get_withdraw_fee(address.clone(), bsats(peg_out1))
+        .await?;
+    let op = client
+        .withdraw(address.clone(), bsats(peg_out1), fees1)
+        .await?;
+    let balance_after_peg_out =
+        sats(PEG_IN_AMOUNT_SATS - PEG_OUT_AMOUNT_SATS - fees1.amount().to_sat());
+    assert_eq!(client.get_balance().await, balance_after_peg_out);
+    assert_eq!(balance_sub.ok().await?, balance_after_peg_out);
+
+    let sub = client.subscribe_withdraw_updates(op).await?;
+    let mut sub = sub.into_stream();
+    assert_eq!(sub.ok().await?, WithdrawState::Created);
+    let txid = match sub.ok().await? {
+        WithdrawState::Succeeded(txid) => txid,
+        other => panic!("Unexpected state: {other:?}"),
+    };
+    bitcoin.get_mempool_tx_fee(&txid).await;
+
+    // Do another peg-out
+    let peg_out2 = PEG_OUT_AMOUNT_SATS;
+    let fees2 = client
+        .get_withdraw_fee(address.clone(), bsats(peg_out2))
+        .await;
+    // Must fail because change UTXOs are still being confirmed
+    assert!(fees2.is_err());
+
+    let current_block = dyn_bitcoin_rpc.get_block_count().await?;
     bitcoin.mine_blocks(finality_delay + 1).await;
     await_consensus_to_catch_up(&client, current_block + 1).await?;
     // Now change UTXOs are available and we can peg-out again
@@ -388,6 +1862,278 @@ async fn peg_outs_must_wait_for_available_utxos() -> anyhow::Result<()> {
     );
     assert_eq!(client.get_balance().await, balance_after_second_peg_out);
     assert_eq!(balance_sub.ok().await?, balance_after_second_peg_out);
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_outs_in_one_session_are_batched() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    // Need lock to keep tx in mempool from getting mined
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    info!("Starting test peg_outs_in_one_session_are_batched");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let mut balance_sub =
+        peg_in(&client, bitcoin.as_ref(), &dyn_bitcoin_rpc, finality_delay).await?;
+
+    info!("Peg-in finished for test peg_outs_in_one_session_are_batched");
+    // Submitting both peg-outs in one transaction guarantees they are queued in the
+    // same session, without batching each would be paid by its own bitcoin tx
+    let address1 = bitcoin.get_new_address().await;
+    let address2 = bitcoin.get_new_address().await;
+    let peg_out = bsats(PEG_OUT_AMOUNT_SATS);
+    let out_points = client
+        .split_peg_out(vec![
+            (peg_out, address1.clone()),
+            (peg_out, address2.clone()),
+        ])
+        .await?;
+
+    let decoder = WalletClientModule::decoder();
+    let mut txids = vec![];
+    for out_point in out_points {
+        let WalletOutputOutcome(txid) = client
+            .api()
+            .await_output_outcome(out_point, Duration::from_secs(60), &decoder)
+            .await?;
+        txids.push(txid);
+    }
+    assert_eq!(txids[0], txids[1], "Peg-outs were not batched");
+
+    let balance_after_peg_outs = balance_sub.ok().await?;
+    assert_eq!(client.get_balance().await, balance_after_peg_outs);
+
+    // Each peg-out paid for a tx of its own, sharing one tx is cheaper
+    let paid_fees = sats(PEG_IN_AMOUNT_SATS - 2 * PEG_OUT_AMOUNT_SATS) - balance_after_peg_outs;
+    assert!(bitcoin.get_mempool_tx_fee(&txids[0]).await < paid_fees);
+
+    assert_eq!(
+        bitcoin.mine_block_and_get_received(&address1).await,
+        sats(PEG_OUT_AMOUNT_SATS)
+    );
+    assert_eq!(
+        bitcoin.mine_block_and_get_received(&address2).await,
+        sats(PEG_OUT_AMOUNT_SATS)
+    );
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_finality_estimate_reaches_zero_once_final() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+
+    let address = bitcoin.get_new_address().await;
+    let (_proof, tx) = bitcoin
+        .send_and_mine_block(&address, bsats(PEG_IN_AMOUNT_SATS))
+        .await;
+    let height = dyn_bitcoin_rpc
+        .get_tx_block_height(&tx.txid())
+        .await?
+        .context("expected tx to be mined")?;
+
+    assert!(client.estimate_peg_in_finality_time(0).await? > Duration::ZERO);
+
+    // the block interval is re-estimated from the chain on every call, so we only
+    // check that the estimate reaches zero once the peg-in is final
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    loop {
+        let block_count = dyn_bitcoin_rpc.get_block_count().await?;
+        let confirmations = block_count - height;
+        let estimate = client
+            .estimate_peg_in_finality_time(u32::try_from(confirmations)?)
+            .await?;
+        assert_eq!(
+            estimate == Duration::ZERO,
+            finality_delay < confirmations,
+            "{estimate:?} for {confirmations} confirmations"
+        );
+        if estimate == Duration::ZERO {
+            break;
+        }
+        bitcoin.mine_blocks(1).await;
+    }
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_addresses_are_not_reused() -> anyhow::Result<()> {
+    let fed = fixtures().new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_peg_in_address_uniqueness(&fed, 100).await;
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_address_proof_is_valid() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    info!("Starting test peg_in_address_proof_is_valid");
+
+    let valid_until = SystemTime::now() + PEG_IN_TIMEOUT;
+    let (_, address) = client.get_deposit_address(valid_until).await?;
+    let proof = client.get_address_proof(&address).await?;
+
+    let (_, instance) =
+        client.get_first_module::<WalletClientModule>(&fedimint_wallet_client::KIND);
+    let wallet_config: &WalletClientConfig = client.get_config().modules[&instance.id].cast()?;
+    let secp = Secp256k1::new();
+    assert_eq!(proof.peer_peg_in_keys, wallet_config.peer_peg_in_keys);
+    proof.verify(
+        &secp,
+        &address,
+        wallet_config.network,
+        &wallet_config.peg_in_descriptor,
+    )?;
+
+    // The proof must not be valid for any other address
+    let (_, other_address) = client.get_deposit_address(valid_until).await?;
+    assert_eq!(
+        proof.verify(
+            &secp,
+            &other_address,
+            wallet_config.network,
+            &wallet_config.peg_in_descriptor,
+        ),
+        Err(AddressProofError::AddressDoesNotMatch)
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_in_address_is_verified_against_federation_descriptor() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    info!("Starting test peg_in_address_is_verified_against_federation_descriptor");
+
+    let valid_until = SystemTime::now() + PEG_IN_TIMEOUT;
+    let (_, address) = client.get_deposit_address(valid_until).await?;
+    assert_eq!(
+        client.verify_peg_in_address(&address).await?,
+        AddressVerification::Verified
+    );
+
+    let external_address = bitcoin.get_new_address().await;
+    assert_eq!(
+        client.verify_peg_in_address(&external_address).await?,
+        AddressVerification::NotFederationAddress
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compact_block_proofs_are_verified_against_synced_blocks() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let fed = fixtures.new_fed().await;
+    let monitor = fed.start_balance_sheet_monitoring().await;
+    assert_wallet_descriptor_valid(&fed);
+    let client = fed.new_client().await;
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    info!("Starting test compact_block_proofs_are_verified_against_synced_blocks");
+
+    let finality_delay = FINALITY_DELAY.regtest as u64;
+    bitcoin.mine_blocks(finality_delay).await;
+    await_consensus_to_catch_up(&client, 1).await?;
+
+    let valid_until = SystemTime::now() + PEG_IN_TIMEOUT;
+    let (op, address) = client.get_deposit_address(valid_until).await?;
+    let (proof, tx) = bitcoin
+        .send_and_mine_block(&address, bsats(PEG_IN_AMOUNT_SATS))
+        .await;
+    let txid = tx.txid();
+    let block_hash = proof.block();
+
+    // Once the deposit is confirmed the federation has synced the block
+    let mut sub = client.subscribe_deposit_updates(op).await?.into_stream();
+    assert_eq!(sub.ok().await?, DepositState::WaitingForTransaction);
+    assert_matches!(sub.ok().await?, DepositState::WaitingForConfirmation { .. });
+    bitcoin.mine_blocks(finality_delay).await;
+    assert_matches!(sub.ok().await?, DepositState::Confirmed(_));
+
+    assert_compact_block_proof_valid(&fed, txid, proof.clone(), block_hash).await;
+
+    // The proof doesn't show the inclusion of other transactions …
+    let other_txid = bitcoin::Txid::from_inner([42; 32]);
+    assert!(
+        verify_compact_block_proof(&fed, other_txid, &proof, block_hash)
+            .await
+            .is_err()
+    );
+
+    // … nor does it belong to any other block …
+    let mut tampered_header = proof.clone();
+    tampered_header.block_header.nonce = tampered_header.block_header.nonce.wrapping_add(1);
+    assert!(
+        verify_compact_block_proof(&fed, txid, &tampered_header, block_hash)
+            .await
+            .is_err()
+    );
+    assert!(
+        verify_compact_block_proof(&fed, txid, &tampered_header, tampered_header.block())
+            .await
+            .is_err()
+    );
+
+    // … and its merkle tree has to match the header
+    let mut tampered_root = proof.clone();
+    tampered_root.block_header.merkle_root = bitcoin::TxMerkleNode::from_inner([42; 32]);
+    assert!(
+        verify_compact_block_proof(&fed, txid, &tampered_root, tampered_root.block())
+            .await
+            .is_err()
+    );
+
+    fed.stop_monitoring(monitor).await?;
+    fed.assert_no_stuck_transactions().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn node_info_reports_software_and_module_versions() -> anyhow::Result<()> {
+    let fed = fixtures()
+        .with_module(MintClientGen, MintGen, MintGenParams::default())
+        .new_fed()
+        .await;
+    assert_wallet_descriptor_valid(&fed);
+
+    let node_info = fed.get_node_info().await;
+    assert!(!node_info.software_version.is_empty());
+    assert!(node_info
+        .module_versions
+        .contains_key(fedimint_wallet_common::KIND.as_str()));
+    assert!(node_info
+        .module_versions
+        .contains_key(fedimint_mint_common::KIND.as_str()));
+
     Ok(())
 }
 
@@ -430,7 +2176,7 @@ async fn peg_ins_that_are_unconfirmed_are_rejected() -> anyhow::Result<()> {
 
     // Generate a minimum number of blocks before sending transactions
     bitcoin
-        .mine_blocks(wallet_config.consensus.finality_delay.into())
+        .mine_blocks(wallet_config.consensus.finality_delay_for_network().into())
         .await;
 
     let block_count = dyn_bitcoin_rpc.get_block_count().await?;
@@ -476,7 +2222,7 @@ async fn peg_ins_that_are_unconfirmed_are_rejected() -> anyhow::Result<()> {
 
     // For this transaction to be confirmed, we need to mine at least finality_delay
     bitcoin
-        .mine_blocks((wallet_config.consensus.finality_delay).into())
+        .mine_blocks(wallet_config.consensus.finality_delay_for_network().into())
         .await;
     let block_count = dyn_bitcoin_rpc.get_block_count().await?;
     sync_wallet_to_block(
@@ -496,6 +2242,115 @@ async fn peg_ins_that_are_unconfirmed_are_rejected() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn peg_ins_wait_for_finality_delay_of_network() -> anyhow::Result<()> {
+    let fixtures = fixtures();
+    let bitcoin = fixtures.bitcoin();
+    let bitcoin = bitcoin.lock_exclusive().await;
+    let dyn_bitcoin_rpc = fixtures.dyn_bitcoin_rpc();
+    let db = Database::new(MemDatabase::new(), Default::default());
+    let mut task_group = fedimint_core::task::TaskGroup::new();
+
+    let (wallet_server_cfg, _) = build_wallet_server_configs(fixtures.bitcoin_server())?;
+    let wallet_config: WalletConfig = wallet_server_cfg[0].to_typed()?;
+    assert_eq!(wallet_config.consensus.finality_delay.mainnet, 6);
+    let finality_delay = wallet_config.consensus.finality_delay_for_network();
+    assert_eq!(finality_delay, FINALITY_DELAY.regtest);
+
+    let module_instance_id = 1;
+    let root_secret =
+        PlainRootSecretStrategy::to_root_secret(&PlainRootSecretStrategy::random(&mut OsRng));
+    let secp = Secp256k1::new();
+    let x_only_pk = root_secret
+        .to_secp_key(&secp)
+        .public_key()
+        .to_x_only_pubkey();
+    let peg_in_address = wallet_config
+        .consensus
+        .peg_in_descriptor
+        .tweak(&x_only_pk, secp256k1::SECP256K1)
+        .address(wallet_config.consensus.network)?;
+
+    let mut wallet = fedimint_wallet_server::Wallet::new_with_bitcoind(
+        wallet_config.clone(),
+        db.clone(),
+        dyn_bitcoin_rpc.clone(),
+        &mut task_group,
+        PeerId::from(0),
+    )
+    .await?;
+    let mut dbtx = db.begin_transaction().await;
+
+    let (proof, transaction) = bitcoin
+        .send_and_mine_block(&peg_in_address, bsats(PEG_IN_AMOUNT_SATS))
+        .await;
+    let output_index = transaction
+        .output
+        .iter()
+        .position(|o| o.script_pubkey == peg_in_address.script_pubkey())
+        .context("expected to find peg-in output")?;
+    let input = fedimint_wallet_common::WalletInput(Box::new(PegInProof::new(
+        proof,
+        transaction,
+        output_index.try_into()?,
+        x_only_pk,
+    )?));
+
+    // One block short of the regtest finality delay
+    bitcoin.mine_blocks((finality_delay - 1).into()).await;
+    sync_wallet_to_proposal(
+        &mut dbtx.with_module_prefix(module_instance_id),
+        &mut wallet,
+    )
+    .await?;
+    match wallet
+        .process_input(&mut dbtx.with_module_prefix(module_instance_id), &input)
+        .await
+    {
+        Ok(_) => bail!("Expected peg-in to fail"),
+        Err(e) => {
+            assert!(e.to_string().contains("Unknown block hash in peg-in proof"));
+        }
+    }
+
+    // The mainnet finality delay would still reject the peg-in here
+    bitcoin.mine_blocks(1).await;
+    sync_wallet_to_proposal(
+        &mut dbtx.with_module_prefix(module_instance_id),
+        &mut wallet,
+    )
+    .await?;
+    assert_matches!(
+        wallet
+            .process_input(&mut dbtx.with_module_prefix(module_instance_id), &input)
+            .await,
+        Ok(_)
+    );
+    dbtx.commit_tx().await;
+    Ok(())
+}
+
+/// Lets all peers vote for the block count the wallet proposes, which already
+/// excludes the blocks within the finality delay
+async fn sync_wallet_to_proposal(
+    dbtx: &mut ModuleDatabaseTransaction<'_>,
+    wallet: &mut fedimint_wallet_server::Wallet,
+) -> anyhow::Result<()> {
+    let block_count =
+        wallet
+            .consensus_proposal(dbtx)
+            .await
+            .into_iter()
+            .find_map(|item| match item {
+                WalletConsensusItem::BlockCount(block_count) => Some(block_count),
+                _ => None,
+            });
+    if let Some(block_count) = block_count {
+        sync_wallet_to_block(dbtx, wallet, block_count).await?;
+    }
+    Ok(())
+}
+
 async fn sync_wallet_to_block(
     dbtx: &mut ModuleDatabaseTransaction<'_>,
     wallet: &mut fedimint_wallet_server::Wallet,
@@ -531,7 +2386,9 @@ fn build_wallet_server_configs(
             },
             consensus: fedimint_wallet_common::config::WalletGenParamsConsensus {
                 network: bitcoin::Network::Regtest,
-                finality_delay: 10,
+                finality_delay: FINALITY_DELAY,
+                dust_change_policy: None,
+                peg_out_policy: Default::default(),
                 client_default_bitcoin_rpc: bitcoin_rpc.clone(),
             },
         })?,